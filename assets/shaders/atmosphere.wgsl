@@ -0,0 +1,327 @@
+// Rayleigh + Mie atmosphere with precomputed lookup tables, after "A Scalable
+// and Production Ready Sky and Atmosphere Rendering Technique" (Hillaire 2020).
+// All positions are relative to the planet's center, in the planet's frame.
+//
+// Mirrored on the CPU by `src/atmosphere.rs`, keep the two in sync.
+
+const PI = 3.14159265359;
+const TRANSMITTANCE_STEPS = 40u;
+const MULTISCATTERING_STEPS = 20u;
+const MULTISCATTERING_SQRT_DIRECTIONS = 8u;
+const SCATTERING_STEPS = 32u;
+const SKY_IRRADIANCE_SQRT_DIRECTIONS = 8u;
+
+struct Atmosphere {
+    planet_radius: f32,
+    atmosphere_radius: f32,
+    // Rayleigh scattering coefficients at the surface, per unit length
+    rayleigh_scattering: vec3<f32>,
+    // Altitude over which the Rayleigh density falls by a factor e
+    rayleigh_scale_height: f32,
+    // Mie scattering coefficient at the surface, per unit length
+    mie_scattering: f32,
+    // Mie extinction coefficient at the surface: scattering plus absorption
+    mie_extinction: f32,
+    mie_scale_height: f32,
+    // Asymmetry of the Mie phase function, towards forward scattering when positive
+    mie_anisotropy: f32,
+}
+
+// Where the sky-view LUT is seen from
+struct SkyView {
+    // Distance of the camera from the planet's center, inside the atmosphere
+    camera_radius: f32,
+    // Cosine of the sun's zenith angle seen from the camera
+    sun_cos_zenith: f32,
+}
+
+// Scattering and extinction at a point in the atmosphere
+struct Medium {
+    rayleigh_scattering: vec3<f32>,
+    mie_scattering: f32,
+    extinction: vec3<f32>,
+}
+
+// Light scattered towards the viewer along a ray, for a sun of unit
+// illuminance, and the light let through from behind
+struct Scattering {
+    radiance: vec3<f32>,
+    transmittance: vec3<f32>,
+}
+
+// Distances along `dir` where the ray from `p` enters and leaves the sphere of
+// radius `r`. When it misses, the entry is past the exit.
+fn ray_sphere_intersection(p: vec3<f32>, dir: vec3<f32>, r: f32) -> vec2<f32> {
+    let b = dot(p, dir);
+    let c = dot(p, p) - r * r;
+    let d = b * b - c;
+    if d < 0.0 {
+        return vec2(1e30, -1e30);
+    }
+    let s = sqrt(d);
+    return vec2(-b - s, -b + s);
+}
+
+// Cornette-Shanks phase function, `c` is the cosine of the scattering angle
+fn phase_mie(g: f32, c: f32) -> f32 {
+    let gg = g * g;
+    let a = (1.0 - gg) * (1.0 + c * c);
+    var b = 1.0 + gg - 2.0 * g * c;
+    b *= sqrt(b);
+    b *= 2.0 + gg;
+    return (3.0 / 8.0 / PI) * a / b;
+}
+
+fn phase_rayleigh(c: f32) -> f32 {
+    return (3.0 / 16.0 / PI) * (1.0 + c * c);
+}
+
+// Distance from a point at `r` from the center to the sphere of `radius`,
+// along a ray whose zenith angle has the cosine `mu`. Assumes the point is inside.
+fn distance_to_sphere(r: f32, mu: f32, radius: f32) -> f32 {
+    let discriminant = r * r * (mu * mu - 1.0) + radius * radius;
+    return -r * mu + sqrt(max(discriminant, 0.0));
+}
+
+// Distance from the center after travelling `t` from a point at `r`, at a zenith cosine `mu`
+fn radius_along_ray(r: f32, mu: f32, t: f32) -> f32 {
+    return sqrt(max(r * r + 2.0 * r * mu * t + t * t, 0.0));
+}
+
+fn atmosphere_medium(atmosphere: Atmosphere, r: f32) -> Medium {
+    let altitude = max(r - atmosphere.planet_radius, 0.0);
+    let rayleigh = exp(-altitude / atmosphere.rayleigh_scale_height);
+    let mie = exp(-altitude / atmosphere.mie_scale_height);
+    var medium: Medium;
+    medium.rayleigh_scattering = atmosphere.rayleigh_scattering * rayleigh;
+    medium.mie_scattering = atmosphere.mie_scattering * mie;
+    medium.extinction = atmosphere.rayleigh_scattering * rayleigh + atmosphere.mie_extinction * mie;
+    return medium;
+}
+
+// Whether a ray from `r` at the zenith cosine `mu` hits the planet
+fn ray_hits_ground(atmosphere: Atmosphere, r: f32, mu: f32) -> bool {
+    return mu < 0.0 && r * r * (mu * mu - 1.0) + atmosphere.planet_radius * atmosphere.planet_radius >= 0.0;
+}
+
+// Distance to the ground or to the top of the atmosphere, whichever the ray from `r` hits first
+fn distance_to_boundary(atmosphere: Atmosphere, r: f32, mu: f32) -> f32 {
+    if ray_hits_ground(atmosphere, r, mu) {
+        let bottom = atmosphere.planet_radius;
+        return -r * mu - sqrt(max(r * r * (mu * mu - 1.0) + bottom * bottom, 0.0));
+    }
+    return distance_to_sphere(r, mu, atmosphere.atmosphere_radius);
+}
+
+// UV in the transmittance LUT of the ray from `r` at the zenith cosine `mu`
+fn transmittance_lut_uv(atmosphere: Atmosphere, r: f32, mu: f32) -> vec2<f32> {
+    let bottom = atmosphere.planet_radius;
+    let top = atmosphere.atmosphere_radius;
+    // Distance to the horizon from the top of the atmosphere, and from `r`
+    let h = sqrt(top * top - bottom * bottom);
+    let rho = sqrt(max(r * r - bottom * bottom, 0.0));
+    let d = distance_to_sphere(r, mu, top);
+    let d_min = top - r;
+    let d_max = rho + h;
+    return vec2((d - d_min) / (d_max - d_min), rho / h);
+}
+
+// Inverse of `transmittance_lut_uv`, returns (r, mu)
+fn transmittance_lut_r_mu(atmosphere: Atmosphere, uv: vec2<f32>) -> vec2<f32> {
+    let bottom = atmosphere.planet_radius;
+    let top = atmosphere.atmosphere_radius;
+    let h = sqrt(top * top - bottom * bottom);
+    let rho = h * uv.y;
+    let r = sqrt(rho * rho + bottom * bottom);
+    let d_min = top - r;
+    let d_max = rho + h;
+    let d = d_min + uv.x * (d_max - d_min);
+    var mu = 1.0;
+    if d != 0.0 {
+        mu = (h * h - rho * rho - d * d) / (2.0 * r * d);
+    }
+    return vec2(r, clamp(mu, -1.0, 1.0));
+}
+
+// Transmittance from `r` to the top of the atmosphere at the zenith cosine `mu`, ignoring the ground
+fn transmittance_to_top(atmosphere: Atmosphere, r: f32, mu: f32) -> vec3<f32> {
+    let step_size = distance_to_sphere(r, mu, atmosphere.atmosphere_radius) / f32(TRANSMITTANCE_STEPS);
+    var optical_depth = vec3(0.0);
+    for (var i = 0u; i < TRANSMITTANCE_STEPS; i++) {
+        let t = (f32(i) + 0.5) * step_size;
+        optical_depth += atmosphere_medium(atmosphere, radius_along_ray(r, mu, t)).extinction;
+    }
+    return exp(-optical_depth * step_size);
+}
+
+// Transmittance of the sunlight reaching `r` at the sun zenith cosine `mu`, zero in the planet's shadow
+fn sun_transmittance(atmosphere: Atmosphere, transmittance_lut: texture_2d<f32>, lut_sampler: sampler, r: f32, mu: f32) -> vec3<f32> {
+    if ray_hits_ground(atmosphere, r, mu) {
+        return vec3(0.0);
+    }
+    return textureSampleLevel(transmittance_lut, lut_sampler, transmittance_lut_uv(atmosphere, r, mu), 0.0).rgb;
+}
+
+// UV in the multiple scattering LUT for a point at `r` with the sun at the zenith cosine `mu`
+fn multiscattering_lut_uv(atmosphere: Atmosphere, r: f32, mu: f32) -> vec2<f32> {
+    let altitude = (r - atmosphere.planet_radius) / (atmosphere.atmosphere_radius - atmosphere.planet_radius);
+    return vec2(mu * 0.5 + 0.5, clamp(altitude, 0.0, 1.0));
+}
+
+// The isotropic light scattered more than once, per unit of scattering
+// coefficient and of sun illuminance
+fn multiscattering(atmosphere: Atmosphere, transmittance_lut: texture_2d<f32>, lut_sampler: sampler, r: f32, sun_mu: f32) -> vec3<f32> {
+    let position = vec3(0.0, r, 0.0);
+    let to_sun = vec3(sqrt(max(1.0 - sun_mu * sun_mu, 0.0)), sun_mu, 0.0);
+    let isotropic_phase = 1.0 / (4.0 * PI);
+    var second_order = vec3(0.0);
+    var transfer = vec3(0.0);
+
+    // Uniformly distributed directions over the sphere
+    for (var i = 0u; i < MULTISCATTERING_SQRT_DIRECTIONS; i++) {
+        for (var j = 0u; j < MULTISCATTERING_SQRT_DIRECTIONS; j++) {
+            let theta = 2.0 * PI * (f32(i) + 0.5) / f32(MULTISCATTERING_SQRT_DIRECTIONS);
+            let cos_phi = 1.0 - 2.0 * (f32(j) + 0.5) / f32(MULTISCATTERING_SQRT_DIRECTIONS);
+            let sin_phi = sqrt(1.0 - cos_phi * cos_phi);
+            let direction = vec3(cos(theta) * sin_phi, cos_phi, sin(theta) * sin_phi);
+
+            let step_size = distance_to_boundary(atmosphere, r, direction.y) / f32(MULTISCATTERING_STEPS);
+            var throughput = vec3(1.0);
+            for (var k = 0u; k < MULTISCATTERING_STEPS; k++) {
+                let point = position + direction * (f32(k) + 0.5) * step_size;
+                let point_r = length(point);
+                let medium = atmosphere_medium(atmosphere, point_r);
+                let scattering = medium.rayleigh_scattering + medium.mie_scattering;
+                let extinction = max(medium.extinction, vec3(1e-9));
+                let step_transmittance = exp(-medium.extinction * step_size);
+
+                let sun = sun_transmittance(atmosphere, transmittance_lut, lut_sampler, point_r, dot(point, to_sun) / point_r);
+                let source = scattering * sun * isotropic_phase;
+                // Analytic integration of the source over the step
+                second_order += throughput * (source - source * step_transmittance) / extinction;
+                transfer += throughput * (scattering - scattering * step_transmittance) / extinction;
+                throughput *= step_transmittance;
+            }
+        }
+    }
+
+    let directions = f32(MULTISCATTERING_SQRT_DIRECTIONS * MULTISCATTERING_SQRT_DIRECTIONS);
+    // Sum of the geometric series of ever higher scattering orders
+    return (second_order / directions) / (vec3(1.0) - transfer / directions);
+}
+
+// Single and multiple scattering along a ray from `origin` in the unit
+// `direction`, over the distance `t_max`, for a sun of unit illuminance
+fn integrate_scattering(
+    atmosphere: Atmosphere,
+    transmittance_lut: texture_2d<f32>,
+    multiscattering_lut: texture_2d<f32>,
+    lut_sampler: sampler,
+    origin: vec3<f32>,
+    direction: vec3<f32>,
+    t_max: f32,
+    to_sun: vec3<f32>,
+) -> Scattering {
+    let c = dot(direction, to_sun);
+    let phase_r = phase_rayleigh(c);
+    let phase_m = phase_mie(atmosphere.mie_anisotropy, c);
+    let step_size = t_max / f32(SCATTERING_STEPS);
+
+    var radiance = vec3(0.0);
+    var throughput = vec3(1.0);
+    for (var i = 0u; i < SCATTERING_STEPS; i++) {
+        let point = origin + direction * (f32(i) + 0.5) * step_size;
+        let r = length(point);
+        let medium = atmosphere_medium(atmosphere, r);
+        let step_transmittance = exp(-medium.extinction * step_size);
+
+        let sun_mu = dot(point, to_sun) / r;
+        let sun = sun_transmittance(atmosphere, transmittance_lut, lut_sampler, r, sun_mu);
+        let multiple = textureSampleLevel(multiscattering_lut, lut_sampler, multiscattering_lut_uv(atmosphere, r, sun_mu), 0.0).rgb;
+        let source = (medium.rayleigh_scattering * phase_r + medium.mie_scattering * phase_m) * sun
+            + (medium.rayleigh_scattering + medium.mie_scattering) * multiple;
+        radiance += throughput * (source - source * step_transmittance) / max(medium.extinction, vec3(1e-9));
+        throughput *= step_transmittance;
+    }
+
+    var scattering: Scattering;
+    scattering.radiance = radiance;
+    scattering.transmittance = throughput;
+    return scattering;
+}
+
+// The light from the sky on a surface facing the zenith, over pi, for a sun of unit illuminance
+fn sky_irradiance(
+    atmosphere: Atmosphere,
+    transmittance_lut: texture_2d<f32>,
+    multiscattering_lut: texture_2d<f32>,
+    lut_sampler: sampler,
+    r: f32,
+    sun_mu: f32,
+) -> vec3<f32> {
+    let position = vec3(0.0, r, 0.0);
+    let to_sun = vec3(sqrt(max(1.0 - sun_mu * sun_mu, 0.0)), sun_mu, 0.0);
+    var irradiance = vec3(0.0);
+
+    // Uniformly distributed directions over the upper hemisphere, which never meet the ground
+    for (var i = 0u; i < SKY_IRRADIANCE_SQRT_DIRECTIONS; i++) {
+        for (var j = 0u; j < SKY_IRRADIANCE_SQRT_DIRECTIONS; j++) {
+            let theta = 2.0 * PI * (f32(i) + 0.5) / f32(SKY_IRRADIANCE_SQRT_DIRECTIONS);
+            let cos_phi = (f32(j) + 0.5) / f32(SKY_IRRADIANCE_SQRT_DIRECTIONS);
+            let sin_phi = sqrt(1.0 - cos_phi * cos_phi);
+            let direction = vec3(cos(theta) * sin_phi, cos_phi, sin(theta) * sin_phi);
+            let t_max = distance_to_boundary(atmosphere, r, cos_phi);
+            let scattering = integrate_scattering(
+                atmosphere, transmittance_lut, multiscattering_lut, lut_sampler,
+                position, direction, t_max, to_sun,
+            );
+            irradiance += scattering.radiance * cos_phi;
+        }
+    }
+
+    // Each direction covers 2 pi / n steradians, and the irradiance is divided by pi
+    return irradiance * 2.0 / f32(SKY_IRRADIANCE_SQRT_DIRECTIONS * SKY_IRRADIANCE_SQRT_DIRECTIONS);
+}
+
+// Zenith angle of the horizon (x) and the angle between the horizon and the nadir (y) at `r`
+fn horizon_angles(atmosphere: Atmosphere, r: f32) -> vec2<f32> {
+    let horizon = sqrt(max(r * r - atmosphere.planet_radius * atmosphere.planet_radius, 0.0));
+    let beta = acos(clamp(horizon / r, -1.0, 1.0));
+    return vec2(PI - beta, beta);
+}
+
+// View zenith angle (x) and azimuth from the sun (y) of a texel in the sky-view LUT.
+// Half the LUT height covers the sky and half the ground, squeezed towards the horizon.
+fn sky_view_lut_angles(atmosphere: Atmosphere, r: f32, uv: vec2<f32>) -> vec2<f32> {
+    let angles = horizon_angles(atmosphere, r);
+    var view_zenith: f32;
+    if uv.y < 0.5 {
+        let coord = 1.0 - 2.0 * uv.y;
+        view_zenith = angles.x * (1.0 - coord * coord);
+    } else {
+        let coord = uv.y * 2.0 - 1.0;
+        view_zenith = angles.x + angles.y * coord * coord;
+    }
+    // More resolution around the sun
+    return vec2(view_zenith, PI * uv.x * uv.x);
+}
+
+// Inverse of `sky_view_lut_angles`
+fn sky_view_lut_uv(atmosphere: Atmosphere, r: f32, view_zenith: f32, azimuth: f32) -> vec2<f32> {
+    let angles = horizon_angles(atmosphere, r);
+    var v: f32;
+    if view_zenith < angles.x {
+        let coord = sqrt(max(1.0 - view_zenith / angles.x, 0.0));
+        v = (1.0 - coord) * 0.5;
+    } else {
+        let coord = sqrt(max((view_zenith - angles.x) / angles.y, 0.0));
+        v = coord * 0.5 + 0.5;
+    }
+    return vec2(sqrt(min(abs(azimuth) / PI, 1.0)), v);
+}
+
+// Keeps the sky-view camera inside the atmosphere, where the LUT is defined
+fn sky_view_radius(atmosphere: Atmosphere, camera_radius: f32) -> f32 {
+    let thickness = atmosphere.atmosphere_radius - atmosphere.planet_radius;
+    return clamp(camera_radius, atmosphere.planet_radius + thickness * 1e-3, atmosphere.atmosphere_radius - thickness * 1e-3);
+}
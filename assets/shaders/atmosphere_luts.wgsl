@@ -0,0 +1,76 @@
+// Bakes the atmosphere lookup tables, one entry point per table.
+// Run in order: transmittance, then multiple scattering, then the sky
+// irradiance and the sky view.
+
+#import "shaders/atmosphere.wgsl"::{
+    Atmosphere, SkyView, transmittance_lut_r_mu, transmittance_to_top, multiscattering,
+    integrate_scattering, distance_to_boundary, sky_view_lut_angles, sky_view_radius,
+    sky_irradiance,
+}
+
+@group(0) @binding(0) var<uniform> atmosphere: Atmosphere;
+@group(0) @binding(1) var<uniform> sky_view: SkyView;
+@group(0) @binding(2) var transmittance_lut: texture_2d<f32>;
+@group(0) @binding(3) var multiscattering_lut: texture_2d<f32>;
+@group(0) @binding(4) var lut_sampler: sampler;
+@group(0) @binding(5) var output: texture_storage_2d<rgba16float, write>;
+
+// UV of the center of the output texel
+fn texel_uv(id: vec3<u32>) -> vec2<f32> {
+    return (vec2<f32>(id.xy) + 0.5) / vec2<f32>(textureDimensions(output));
+}
+
+// Workgroups overhang the edges of outputs whose size is not a multiple of 8
+fn outside_output(id: vec3<u32>) -> bool {
+    return any(id.xy >= textureDimensions(output));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn bake_transmittance(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let r_mu = transmittance_lut_r_mu(atmosphere, texel_uv(id));
+    textureStore(output, id.xy, vec4(transmittance_to_top(atmosphere, r_mu.x, r_mu.y), 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn bake_multiscattering(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let uv = texel_uv(id);
+    let r = atmosphere.planet_radius + uv.y * (atmosphere.atmosphere_radius - atmosphere.planet_radius);
+    let sun_mu = uv.x * 2.0 - 1.0;
+    textureStore(output, id.xy, vec4(multiscattering(atmosphere, transmittance_lut, lut_sampler, r, sun_mu), 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn bake_sky_irradiance(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let uv = texel_uv(id);
+    let r = atmosphere.planet_radius + uv.y * (atmosphere.atmosphere_radius - atmosphere.planet_radius);
+    let sun_mu = uv.x * 2.0 - 1.0;
+    let irradiance = sky_irradiance(atmosphere, transmittance_lut, multiscattering_lut, lut_sampler, r, sun_mu);
+    textureStore(output, id.xy, vec4(irradiance, 1.0));
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn bake_sky_view(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let r = sky_view_radius(atmosphere, sky_view.camera_radius);
+    let sun_mu = sky_view.sun_cos_zenith;
+    let to_sun = vec3(sqrt(max(1.0 - sun_mu * sun_mu, 0.0)), sun_mu, 0.0);
+    let angles = sky_view_lut_angles(atmosphere, r, texel_uv(id));
+    let direction = vec3(sin(angles.x) * cos(angles.y), cos(angles.x), sin(angles.x) * sin(angles.y));
+    let t_max = distance_to_boundary(atmosphere, r, direction.y);
+    let scattering = integrate_scattering(
+        atmosphere, transmittance_lut, multiscattering_lut, lut_sampler,
+        vec3(0.0, r, 0.0), direction, t_max, to_sun,
+    );
+    textureStore(output, id.xy, vec4(scattering.radiance, 1.0));
+}
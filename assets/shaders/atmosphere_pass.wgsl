@@ -0,0 +1,102 @@
+// Fullscreen pass drawing one atmosphere over the main pass: what is behind is
+// dimmed by the transmittance, per channel, and the scattered light is added.
+// The depth prepass bounds the rays, so the atmosphere is in front of the
+// terrain and the other bodies, and still works from inside.
+
+#import bevy_render::view::View
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+#import "shaders/atmosphere.wgsl"::{
+    Atmosphere, Scattering, ray_sphere_intersection, ray_hits_ground, integrate_scattering,
+    transmittance_lut_uv, sky_view_lut_uv, sky_view_radius,
+}
+
+struct Body {
+    // Center of the planet, in world space
+    center: vec3<f32>,
+    // Illuminance of the sunlight at the top of the atmosphere, in lux
+    sun_illuminance: f32,
+    // Direction towards the sun, in world space
+    sun_direction: vec3<f32>,
+}
+
+@group(0) @binding(0) var<uniform> view: View;
+#ifdef MULTISAMPLED
+@group(0) @binding(1) var depth_texture: texture_depth_multisampled_2d;
+#else
+@group(0) @binding(1) var depth_texture: texture_depth_2d;
+#endif
+
+@group(1) @binding(0) var<uniform> atmosphere: Atmosphere;
+@group(1) @binding(1) var<uniform> body: Body;
+@group(1) @binding(2) var transmittance_lut: texture_2d<f32>;
+@group(1) @binding(3) var lut_sampler: sampler;
+@group(1) @binding(4) var multiscattering_lut: texture_2d<f32>;
+@group(1) @binding(5) var sky_view_lut: texture_2d<f32>;
+
+// The main pass and the atmospheres drawn so far
+@group(2) @binding(0) var scene_texture: texture_2d<f32>;
+
+// Angle between the projections of `a` and `b` on the plane normal to `up`
+fn azimuth_between(a: vec3<f32>, b: vec3<f32>, up: vec3<f32>) -> f32 {
+    let a_horizontal = a - up * dot(a, up);
+    let b_horizontal = b - up * dot(b, up);
+    let lengths = length(a_horizontal) * length(b_horizontal);
+    if lengths < 1e-6 {
+        return 0.0;
+    }
+    return acos(clamp(dot(a_horizontal, b_horizontal) / lengths, -1.0, 1.0));
+}
+
+fn world_position_at(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let world = view.world_from_clip * vec4(ndc, depth, 1.0);
+    return world.xyz / world.w;
+}
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let ndc = vec2(in.uv.x * 2.0 - 1.0, 1.0 - in.uv.y * 2.0);
+    let depth = textureLoad(depth_texture, vec2<i32>(in.position.xy), 0);
+    let scene = textureLoad(scene_texture, vec2<i32>(in.position.xy), 0);
+
+    // March from the camera, relative to the planet's center
+    let origin = view.world_position - body.center;
+    let dir = normalize(world_position_at(ndc, 1.0) - view.world_position);
+    // Reversed Z, nothing was drawn where the depth is zero
+    var t_max = 1e30;
+    if depth > 0.0 {
+        t_max = distance(world_position_at(ndc, depth), view.world_position);
+    }
+
+    let shell = ray_sphere_intersection(origin, dir, atmosphere.atmosphere_radius);
+    let ground = ray_sphere_intersection(origin, dir, atmosphere.planet_radius);
+    let t_start = max(shell.x, 0.0);
+    var t_end = min(shell.y, t_max);
+    if ground.x > 0.0 && ground.x < ground.y {
+        t_end = min(t_end, ground.x);
+    }
+    if t_end <= t_start {
+        return scene;
+    }
+
+    var scattering: Scattering;
+    let r = length(origin);
+    let mu = dot(dir, origin) / r;
+    if r <= atmosphere.atmosphere_radius && !ray_hits_ground(atmosphere, r, mu) && t_max >= shell.y {
+        // Looking up at the open sky from inside: the sky-view LUT has it all
+        let up = origin / r;
+        let view_zenith = acos(clamp(mu, -1.0, 1.0));
+        let azimuth = azimuth_between(dir, body.sun_direction, up);
+        let sky_view_uv = sky_view_lut_uv(atmosphere, sky_view_radius(atmosphere, r), view_zenith, azimuth);
+        scattering.radiance = textureSampleLevel(sky_view_lut, lut_sampler, sky_view_uv, 0.0).rgb;
+        scattering.transmittance = textureSampleLevel(transmittance_lut, lut_sampler, transmittance_lut_uv(atmosphere, r, mu), 0.0).rgb;
+    } else {
+        scattering = integrate_scattering(
+            atmosphere, transmittance_lut, multiscattering_lut, lut_sampler,
+            origin + dir * t_start, dir, t_end - t_start, body.sun_direction,
+        );
+    }
+
+    let inscattering = scattering.radiance * body.sun_illuminance * view.exposure;
+    return vec4(scene.rgb * scattering.transmittance + inscattering, scene.a);
+}
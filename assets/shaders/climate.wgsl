@@ -0,0 +1,123 @@
+// Climate model: temperature, precipitation and Whittaker biome classification.
+// Mirrors `src/climate.rs`, whose tests hold golden outputs of this file.
+
+struct ClimateModel {
+    equator_temperature: f32,
+    pole_temperature: f32,
+    lapse_rate: f32,
+    sea_level: f32,
+    summit_level: f32,
+    max_precipitation: f32,
+    coastal_falloff: f32,
+}
+
+struct ClimateSample {
+    // Mean annual temperature in °C
+    temperature: f32,
+    // Annual precipitation in cm
+    precipitation: f32,
+    biome: u32,
+}
+
+const BIOME_OCEAN: u32 = 0u;
+const BIOME_ICE: u32 = 1u;
+const BIOME_TUNDRA: u32 = 2u;
+const BIOME_BOREAL_FOREST: u32 = 3u;
+const BIOME_COLD_DESERT: u32 = 4u;
+const BIOME_TEMPERATE_GRASSLAND: u32 = 5u;
+const BIOME_WOODLAND: u32 = 6u;
+const BIOME_TEMPERATE_FOREST: u32 = 7u;
+const BIOME_TEMPERATE_RAINFOREST: u32 = 8u;
+const BIOME_DESERT: u32 = 9u;
+const BIOME_SAVANNA: u32 = 10u;
+const BIOME_TROPICAL_SEASONAL_FOREST: u32 = 11u;
+const BIOME_TROPICAL_RAINFOREST: u32 = 12u;
+
+// Height above the shoreline, normalized so the summit level is 1.0
+fn land_height(model: ClimateModel, elevation: f32) -> f32 {
+    return clamp((elevation - model.sea_level) / (model.summit_level - model.sea_level), 0.0, 1.0);
+}
+
+// Mean annual temperature in °C. Latitude is in radians.
+fn climate_temperature(model: ClimateModel, latitude: f32, elevation: f32) -> f32 {
+    // cos() dips just below zero at the poles, where pow() would return NaN
+    let insolation = cos(latitude) * cos(latitude);
+    let sea_level_temperature = model.pole_temperature + (model.equator_temperature - model.pole_temperature) * insolation;
+    return sea_level_temperature - model.lapse_rate * land_height(model, elevation);
+}
+
+// Annual precipitation in cm. Latitude and the distance from the sea are in
+// radians.
+fn climate_precipitation(model: ClimateModel, latitude: f32, sea_distance: f32, temperature: f32) -> f32 {
+    // Three circulation cells per hemisphere: rising air (wet) at the
+    // equator and at 60°, sinking air (dry) at 30° and at the poles.
+    let wind_bands = 0.5 + 0.5 * cos(latitude * 6.0);
+    // The air dries out as it moves inland
+    let coastal = exp(-sea_distance * model.coastal_falloff);
+    // Cold air holds less water
+    let capacity = clamp((temperature + 15.0) / 45.0, 0.05, 1.0);
+    return model.max_precipitation * (0.15 + 0.85 * wind_bands) * coastal * capacity;
+}
+
+// Whittaker classification from mean annual temperature (°C) and precipitation (cm)
+fn classify_biome(temperature: f32, precipitation: f32) -> u32 {
+    if temperature < -12.0 {
+        return BIOME_ICE;
+    } else if temperature < -5.0 {
+        return BIOME_TUNDRA;
+    } else if temperature < 3.0 {
+        return select(BIOME_BOREAL_FOREST, BIOME_TUNDRA, precipitation < 30.0);
+    } else if temperature < 20.0 {
+        if precipitation < 25.0 {
+            return BIOME_COLD_DESERT;
+        } else if precipitation < 80.0 {
+            return BIOME_TEMPERATE_GRASSLAND;
+        } else if precipitation < 120.0 {
+            return BIOME_WOODLAND;
+        } else if precipitation < 220.0 {
+            return BIOME_TEMPERATE_FOREST;
+        }
+        return BIOME_TEMPERATE_RAINFOREST;
+    } else if precipitation < 50.0 {
+        return BIOME_DESERT;
+    } else if precipitation < 150.0 {
+        return BIOME_SAVANNA;
+    } else if precipitation < 250.0 {
+        return BIOME_TROPICAL_SEASONAL_FOREST;
+    }
+    return BIOME_TROPICAL_RAINFOREST;
+}
+
+// Classifies the climate at a latitude (in radians), normalized elevation
+// and distance from the sea (in radians)
+fn sample_climate(model: ClimateModel, latitude: f32, elevation: f32, sea_distance: f32) -> ClimateSample {
+    var sample: ClimateSample;
+    sample.temperature = climate_temperature(model, latitude, elevation);
+    sample.precipitation = climate_precipitation(model, latitude, sea_distance, sample.temperature);
+    if elevation < model.sea_level {
+        sample.biome = BIOME_OCEAN;
+    } else {
+        sample.biome = classify_biome(sample.temperature, sample.precipitation);
+    }
+    return sample;
+}
+
+// Ground color of a biome
+fn biome_color(biome: u32) -> vec3<f32> {
+    switch biome {
+        case 0u: { return vec3(0.254, 0.647, 0.705); }  // Ocean
+        case 1u: { return vec3(0.92, 0.94, 0.96); }     // Ice
+        case 2u: { return vec3(0.608, 0.620, 0.529); }  // Tundra
+        case 3u: { return vec3(0.306, 0.447, 0.329); }  // Boreal forest
+        case 4u: { return vec3(0.741, 0.690, 0.584); }  // Cold desert
+        case 5u: { return vec3(0.745, 0.773, 0.447); }  // Temperate grassland
+        case 6u: { return vec3(0.588, 0.655, 0.400); }  // Woodland / shrubland
+        case 7u: { return vec3(0.361, 0.600, 0.329); }  // Temperate forest
+        case 8u: { return vec3(0.227, 0.490, 0.333); }  // Temperate rainforest
+        case 9u: { return vec3(0.886, 0.800, 0.576); }  // Desert
+        case 10u: { return vec3(0.780, 0.737, 0.420); } // Savanna
+        case 11u: { return vec3(0.463, 0.639, 0.302); } // Tropical seasonal forest
+        case 12u: { return vec3(0.200, 0.541, 0.255); } // Tropical rainforest
+        default: { return vec3(1.0, 0.0, 1.0); }
+    }
+}
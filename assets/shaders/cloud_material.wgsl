@@ -0,0 +1,29 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::apply_pbr_lighting,
+    mesh_view_bindings::globals,
+}
+
+#import "shaders/local_space.wgsl"::planet_local_position;
+#import "shaders/clouds.wgsl"::{CloudLayer, cloud_coverage};
+
+@group(2) @binding(100)
+var<uniform> clouds: CloudLayer;
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    let local_position = planet_local_position(in.world_position.xyz, in.instance_index);
+    let coverage = cloud_coverage(local_position, globals.time, clouds);
+    pbr_input.material.base_color = vec4(pbr_input.material.base_color.rgb, pbr_input.material.base_color.a * coverage);
+    pbr_input.material.perceptual_roughness = 1.0;
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    return out;
+}
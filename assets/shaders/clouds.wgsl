@@ -0,0 +1,78 @@
+// Cloud coverage on the unit sphere: domain-warped FBM advected by the
+// latitude wind bands. Mirrors `CloudLayer` in `src/clouds.rs`.
+
+#import bevy_shader_utils::fbm::{Fbm, fbm_3d, NOISE_SIMPLEX}
+#import "shaders/gas_giant.wgsl"::{rotate_around, zonal_wind};
+
+struct CloudLayer {
+    seed: u32,
+    // Fraction of the sky covered by clouds, 0.0 to 1.0
+    coverage: f32,
+    // Height of the cloud shell above the surface, as a fraction of the planet's radius
+    altitude: f32,
+    // Number of wind band pairs from pole to pole
+    band_count: f32,
+    // Speed of the zonal winds, in radians per second at the fastest jet
+    wind_speed: f32,
+    // Rotation of the whole layer relative to the surface, in radians per second
+    drift_speed: f32,
+    // How much sunlight a full cover blocks from the surface, 0.0 to 1.0
+    shadow_strength: f32,
+}
+
+// Octaves of simplex noise at slightly off-doubling frequencies, so the
+// octaves don't line up
+fn cloud_octaves(octaves: u32) -> Fbm {
+    return Fbm(NOISE_SIMPLEX, octaves, 2.03, 0.5);
+}
+
+// Cloud density around 0.5, before the coverage threshold
+fn cloud_density(p: vec3<f32>, offset: vec3<f32>) -> f32 {
+    let q = p * 3.0 + offset;
+    // Warp the domain so the clouds curl into fronts and swirls
+    let warp = vec3(
+        fbm_3d(q, cloud_octaves(3u)),
+        fbm_3d(q + vec3(5.2, 1.3, 2.8), cloud_octaves(3u)),
+        fbm_3d(q + vec3(1.7, 9.2, 4.1), cloud_octaves(3u)),
+    );
+    // Stretch along the bands, the way the winds shear the clouds
+    return fbm_3d((q + warp * 1.3) * vec3(1.0, 2.0, 1.0), cloud_octaves(5u)) * 0.5 + 0.5;
+}
+
+// Cloud coverage, 0.0 to 1.0, in the direction `p_in` from the planet's center
+fn cloud_coverage(p_in: vec3<f32>, time: f32, layer: CloudLayer) -> f32 {
+    let axis = vec3(0.0, 1.0, 0.0);
+    // The whole layer drifts relative to the surface
+    let p = rotate_around(normalize(p_in), axis, -layer.drift_speed * time);
+    let offset = vec3(f32(layer.seed % 1024u), f32((layer.seed >> 10u) % 1024u), f32(layer.seed >> 20u)) * 0.731;
+
+    // Flow noise: advect along the zonal winds in two phases offset by half a
+    // cycle and cross-fade, so the shear never accumulates
+    let latitude = asin(clamp(p.y, -1.0, 1.0));
+    let flow = zonal_wind(latitude, layer.band_count) * layer.wind_speed;
+    let cycle = 30.0;
+    let phase_a = fract(time / cycle);
+    let phase_b = fract(time / cycle + 0.5);
+    let density_a = cloud_density(rotate_around(p, axis, flow * phase_a * cycle), offset);
+    let density_b = cloud_density(rotate_around(p, axis, flow * phase_b * cycle), offset + 17.0);
+    let density = mix(density_a, density_b, abs(1.0 - 2.0 * phase_a));
+
+    let threshold = mix(0.8, 0.2, layer.coverage);
+    return smoothstep(threshold - 0.05, threshold + 0.15, density) * step(0.001, layer.coverage);
+}
+
+// Fraction of the sunlight blocked by the clouds above `local_position`.
+// `to_sun` is the planet-local direction towards the sun.
+fn cloud_shadow(local_position: vec3<f32>, to_sun: vec3<f32>, time: f32, layer: CloudLayer) -> f32 {
+    if layer.coverage <= 0.0 {
+        return 0.0;
+    }
+    // The point is always inside the shell, so the ray always leaves through it
+    let shell_radius = length(local_position) * (1.0 + layer.altitude);
+    let b = dot(local_position, to_sun);
+    let c = dot(local_position, local_position) - shell_radius * shell_radius;
+    let t = -b + sqrt(b * b - c);
+    // Fade out past the terminator, where there is no direct sunlight to block
+    let daylight = smoothstep(-0.05, 0.1, dot(normalize(local_position), to_sun));
+    return cloud_coverage(local_position + to_sun * t, time, layer) * layer.shadow_strength * daylight;
+}
@@ -0,0 +1,101 @@
+// Banded gas giant atmosphere with flow-noise turbulence and vortex storms.
+// Everything is evaluated on the unit sphere, so there are no UV seams.
+
+#import bevy_shader_utils::simplex_noise_3d::simplex_noise_3d
+#import bevy_shader_utils::hash::{seed_hash, seed_float};
+
+const PI = 3.14159265359;
+const TAU = 6.28318530718;
+const MAX_STORMS = 8u;
+
+// Filled in on the CPU, from the seed or by hand, see `src/gas_giant.rs`
+struct GasGiant {
+    zone_color: vec4<f32>,
+    belt_color: vec4<f32>,
+    accent_color: vec4<f32>,
+    storm_color: vec4<f32>,
+    seed: u32,
+    storm_count: u32,
+    // Number of light/dark band pairs from pole to pole
+    band_count: f32,
+    // Strength of the turbulence along the band edges
+    turbulence: f32,
+    // Speed of the zonal winds, in radians per second at the fastest jet
+    wind_speed: f32,
+}
+
+// Rotates `p` around the unit `axis` by `angle` radians
+fn rotate_around(p: vec3<f32>, axis: vec3<f32>, angle: f32) -> vec3<f32> {
+    return p * cos(angle) + cross(axis, p) * sin(angle) + axis * dot(axis, p) * (1.0 - cos(angle));
+}
+
+// Zonal wind at a latitude, alternating east and west between neighbouring bands
+fn zonal_wind(latitude: f32, band_count: f32) -> f32 {
+    return sin(latitude * band_count * 2.0) * cos(latitude);
+}
+
+// Turbulent streaks, stretched along the bands
+fn band_turbulence(p: vec3<f32>, seed: f32) -> f32 {
+    let stretch = vec3(2.0, 10.0, 2.0);
+    // Domain warp so the streaks curl instead of running straight
+    let warp = vec3(
+        simplex_noise_3d(p * stretch + seed * 0.013),
+        simplex_noise_3d(p * stretch + 17.0 + seed * 0.007),
+        simplex_noise_3d(p * stretch + 31.0 + seed * 0.011),
+    ) * 0.15;
+    let q = p + warp;
+    return simplex_noise_3d(q * stretch * 1.5 + seed * 0.01) * 0.6
+        + simplex_noise_3d(q * stretch * 4.0) * 0.3
+        + simplex_noise_3d(q * stretch * 9.0) * 0.1;
+}
+
+// Center, radius and spin of a storm. xyz is the center, w the signed radius.
+fn storm(params: GasGiant, index: u32, time: f32) -> vec4<f32> {
+    let seed = seed_hash(params.seed, index);
+    // Big storms sit in the bands between the jets
+    let latitude = (floor(seed_float(seed, 1u) * params.band_count * 0.8) + 0.5) / params.band_count * PI * 0.5 * sign(seed_float(seed, 2u) - 0.5);
+    // and drift with the wind at their latitude
+    let longitude = seed_float(seed, 3u) * TAU + zonal_wind(latitude, params.band_count) * params.wind_speed * time;
+    let radius = mix(0.04, 0.16, pow(seed_float(seed, 4u), 3.0));
+    let spin = sign(seed_float(seed, 5u) - 0.5);
+    let center = vec3(cos(latitude) * cos(longitude), sin(latitude), cos(latitude) * sin(longitude));
+    return vec4(center, radius * spin);
+}
+
+// Color of the cloud tops at the direction `p` (planet-local, unit length)
+fn gas_giant_color(p_in: vec3<f32>, time: f32, params: GasGiant) -> vec3<f32> {
+    var p = normalize(p_in);
+    // Small enough for the noise offsets to stay precise
+    let seed = seed_float(params.seed, 100u) * 1000.0;
+    var storm_mask = 0.0;
+
+    // Vortices twist the sampling position around their centers
+    for (var i = 0u; i < min(params.storm_count, MAX_STORMS); i++) {
+        let s = storm(params, i, time);
+        let radius = abs(s.w);
+        let distance = acos(clamp(dot(p, s.xyz), -1.0, 1.0));
+        let falloff = max(1.0 - distance / (radius * 1.6), 0.0);
+        let angle = sign(s.w) * (falloff * falloff * 6.0 + falloff * time * 0.3);
+        p = rotate_around(p, s.xyz, angle);
+        storm_mask = max(storm_mask, smoothstep(radius, radius * 0.6, distance));
+    }
+
+    // Flow noise: advect along the zonal winds in two phases offset by half a
+    // cycle and cross-fade, so the shear never accumulates
+    let latitude = asin(clamp(p.y, -1.0, 1.0));
+    let flow = zonal_wind(latitude, params.band_count) * params.wind_speed;
+    let cycle = 20.0;
+    let phase_a = fract(time / cycle);
+    let phase_b = fract(time / cycle + 0.5);
+    let axis = vec3(0.0, 1.0, 0.0);
+    let turbulence_a = band_turbulence(rotate_around(p, axis, flow * phase_a * cycle), seed);
+    let turbulence_b = band_turbulence(rotate_around(p, axis, flow * phase_b * cycle), seed + 101.0);
+    let turbulence = mix(turbulence_a, turbulence_b, abs(1.0 - 2.0 * phase_a));
+
+    let bands = 0.5 + 0.5 * sin((p.y + turbulence * params.turbulence * 0.1) * params.band_count * PI);
+    var color = mix(params.belt_color.rgb, params.zone_color.rgb, bands);
+    // Bright streaks where the turbulence peaks
+    color = mix(color, params.accent_color.rgb, smoothstep(0.35, 0.8, turbulence) * params.turbulence);
+    color = mix(color, params.storm_color.rgb, storm_mask);
+    return color;
+}
@@ -0,0 +1,28 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::apply_pbr_lighting,
+    mesh_view_bindings::globals,
+}
+
+#import "shaders/local_space.wgsl"::planet_local_position;
+#import "shaders/gas_giant.wgsl"::{GasGiant, gas_giant_color};
+
+@group(2) @binding(100)
+var<uniform> gas_giant: GasGiant;
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    let local_position = planet_local_position(in.world_position.xyz, in.instance_index);
+    pbr_input.material.base_color = vec4(gas_giant_color(local_position, globals.time, gas_giant), 1.0);
+    pbr_input.material.perceptual_roughness = 1.0;
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    return out;
+}
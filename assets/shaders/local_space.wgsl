@@ -0,0 +1,17 @@
+#import bevy_pbr::mesh_functions::get_world_from_local
+
+// Position relative to the mesh origin, in the mesh's own (rotating) frame.
+// For a planet this is the position relative to its center, turning with it.
+fn planet_local_position(world_position: vec3<f32>, instance_index: u32) -> vec3<f32> {
+    let world_from_local = get_world_from_local(instance_index);
+    let basis = mat3x3<f32>(world_from_local[0].xyz, world_from_local[1].xyz, world_from_local[2].xyz);
+    // Multiplying from the left applies the transpose, which undoes the rotation
+    return ((world_position - world_from_local[3].xyz) * basis) / dot(basis[0], basis[0]);
+}
+
+// A world space direction in the mesh's own (rotating) frame
+fn planet_local_direction(world_direction: vec3<f32>, instance_index: u32) -> vec3<f32> {
+    let world_from_local = get_world_from_local(instance_index);
+    let basis = mat3x3<f32>(world_from_local[0].xyz, world_from_local[1].xyz, world_from_local[2].xyz);
+    return normalize(world_direction * basis);
+}
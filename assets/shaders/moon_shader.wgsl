@@ -0,0 +1,51 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::apply_pbr_lighting,
+    mesh_view_bindings::view,
+}
+
+#import bevy_shader_utils::{
+    simplex_noise_3d::simplex_noise_3d,
+    craters::{CraterField, crater_field},
+    fresnel::{FresnelRim, fresnel_rim},
+}
+
+#import "shaders/local_space.wgsl"::planet_local_position;
+
+@group(2) @binding(100)
+var<uniform> craters: CraterField;
+
+@group(2) @binding(101)
+var<uniform> rim: FresnelRim;
+
+// Regolith darkens with age, so fresh ejecta is brighter than the plains
+const color_regolith = vec3(0.42, 0.41, 0.40);
+const color_crater_floor = vec3(0.30, 0.29, 0.28);
+const color_ejecta = vec3(0.72, 0.71, 0.69);
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+    // The relief is displaced into the mesh, only the albedo is shaded here
+    let p = normalize(planet_local_position(in.world_position.xyz, in.instance_index));
+    let sample = crater_field(p, craters);
+    let speckle = simplex_noise_3d(p * 40.0) * 0.03 + simplex_noise_3d(p * 160.0) * 0.015;
+
+    var color = mix(color_regolith, color_crater_floor, sample.floor * 0.6);
+    color = mix(color, color_ejecta, sample.ejecta);
+    pbr_input.material.base_color = vec4(color + speckle, 1.0);
+    pbr_input.material.perceptual_roughness = 0.95;
+#ifdef FRESNEL_RIM
+    let rim_emissive = fresnel_rim(rim, view.world_position, in.world_position.xyz, pbr_input.world_normal);
+    pbr_input.material.emissive = vec4(pbr_input.material.emissive.rgb + rim_emissive, pbr_input.material.emissive.a);
+#endif
+
+    var out: FragmentOutput;
+    out.color = apply_pbr_lighting(pbr_input);
+    return out;
+}
@@ -1,5 +1,7 @@
 #import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
     pbr_functions::alpha_discard,
+    mesh_view_bindings::{globals, lights, view},
 }
 
 #ifdef PREPASS_PIPELINE
@@ -9,38 +11,59 @@
 }
 #else
 #import bevy_pbr::{
-    pbr_fragment::pbr_input_from_standard_material,
     forward_io::{VertexOutput, FragmentOutput},
-    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing, calculate_diffuse_color, calculate_F0},
+    lighting::{LightingInput, LAYER_BASE, directional_light, perceptualRoughnessToRoughness, F_AB},
+    shadows::fetch_directional_shadow,
+    mesh_types::MESH_FLAGS_SHADOW_RECEIVER_BIT,
+    mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT,
+    pbr_types::PbrInput,
 }
 #endif
 
+#import bevy_render::maths::{PI, PI_2}
 #import bevy_shader_utils::{
     simplex_noise_3d::simplex_noise_3d,
     perlin_noise_2d::perlin_noise_2d,
     perlin_noise_3d::perlin_noise_3d,
-    voronoise::voronoise
+    craters::{CraterField, crater_field},
+    blackbody::blackbody_color_lookup,
+    hash::seed_float,
+    fresnel::{FresnelRim, fresnel_rim},
 }
 
-#import "shaders/noise.wgsl"::rand11;
+#import "shaders/local_space.wgsl"::{planet_local_position, planet_local_direction};
+#import "shaders/clouds.wgsl"::{CloudLayer, cloud_shadow};
+#import "shaders/gas_giant.wgsl"::{GasGiant, gas_giant_color};
+#import "shaders/atmosphere.wgsl"::{
+    Atmosphere, transmittance_lut_uv, multiscattering_lut_uv,
+}
+#import "shaders/climate.wgsl"::{ClimateModel, sample_climate, biome_color, BIOME_ICE, BIOME_DESERT, BIOME_COLD_DESERT};
+#import "shaders/terrain.wgsl"::{terrain_elevation, sea_ice, terrestrial_color, terrestrial_roughness};
 
-// Color Palette
+// Color Palette, water and land are in `shaders/terrain.wgsl`
 const color_black = vec3(0.0, 0.0, 0.0);
-const color_orange = vec3(1.0, 0.0, 1.0);
-// Water
-const color_water_deep_deep_ocean = vec3(0.16, 0.50, 0.61);
-const color_water_deep_ocean = vec3(0.235, 0.592, 0.666);
-const color_water_ocean = vec3(0.254, 0.647, 0.705);
-const color_water_shallow = vec3(0.360, 0.682, 0.725);
-
-// Land
-const color_vegetation_lush = vec3(0.282, 0.690, 0.396);
-const color_vegetation = vec3(0.588, 0.784, 0.411);
-const color_vegetation_plains = vec3(0.886, 0.847, 0.568);
-const color_dirt = vec3(0.8, 0.721, 0.576);
-const color_highland = vec3(0.650, 0.568, 0.462);
-const color_mountain = vec3(0.486, 0.411, 0.352);
-const color_mountain_peaks = vec3(0.8, 0.8, 0.8);
+
+// Desert
+const color_sand_light = vec3(0.925, 0.824, 0.612);
+const color_sand_dark = vec3(0.710, 0.561, 0.376);
+
+// Ice
+const color_ice_crevasse = vec3(0.392, 0.596, 0.749);
+
+// Lava
+const color_basalt = vec3(0.102, 0.090, 0.086);
+const color_basalt_cooled = vec3(0.235, 0.204, 0.188);
+// Temperatures of the molten rock, in kelvin: crusting over, and freshly erupted
+const lava_temperature = 1100.0;
+const lava_temperature_hot = 1500.0;
+// Luminance of molten rock, in nits
+const lava_emissive_intensity = 1500.0;
+
+// Rocky
+const color_regolith = vec3(0.573, 0.565, 0.549);
+const color_regolith_highland = vec3(0.725, 0.718, 0.702);
+const color_mare = vec3(0.267, 0.263, 0.259);
 
 struct PlanetMaterial {
     // planet_radius: f32,
@@ -50,16 +73,50 @@ struct PlanetMaterial {
 @group(2) @binding(100)
 var<uniform> planet_material: PlanetMaterial;
 
-fn fmod(a: f32, b: f32) -> f32 {
-    return a - b * floor(a / b);
-}
+@group(2) @binding(101)
+var<uniform> climate: ClimateModel;
+
+@group(2) @binding(102)
+var<uniform> clouds: CloudLayer;
+
+#ifdef PLANET_ATMOSPHERE
+@group(2) @binding(103)
+var<uniform> atmosphere: Atmosphere;
+
+@group(2) @binding(104)
+var transmittance_lut: texture_2d<f32>;
+
+@group(2) @binding(105)
+var lut_sampler: sampler;
+
+@group(2) @binding(106)
+var sky_irradiance_lut: texture_2d<f32>;
+#endif
 
-fn lerp(a: f32, b: f32, t: f32) -> f32 {
-    return a + t * (b - a);
+@group(2) @binding(107)
+var<uniform> rim: FresnelRim;
+
+// Great circle distance from the sea in radians, on a latitude/longitude grid
+@group(2) @binding(108)
+var sea_distance_map: texture_2d<f32>;
+
+@group(2) @binding(109)
+var sea_distance_sampler: sampler;
+
+@group(2) @binding(110)
+var<uniform> gas_giant: GasGiant;
+
+// The shading inputs produced by each planet kind
+struct Surface {
+    base_color: vec3<f32>,
+    emissive: vec3<f32>,
+    roughness: f32,
+    // Relief below the mesh resolution, in local units. Zero unless set.
+    height: f32,
 }
 
-fn norm(min: f32, max: f32, value: f32) -> f32 {
-    return (value - min) / (max - min);
+fn fmod(a: f32, b: f32) -> f32 {
+    return a - b * floor(a / b);
 }
 
 fn offset(uv: vec2<f32>, offset: vec2<f32>) -> vec2<f32> {
@@ -76,7 +133,183 @@ fn swirl(uv: vec2<f32>, center: vec2<f32>, strength: f32) -> vec2<f32> {
     return center + swirled;
 }
 
+// Oceans, continents and biomes driven by the climate model.
+// Ocean, desert and ice worlds share this path with their own climate parameters.
+fn terrestrial_surface(local_position: vec3<f32>, latitude: f32, elevation: f32, seed: u32) -> Surface {
+    let direction = normalize(local_position);
+    let longitude = atan2(direction.z, direction.x);
+    let sea_distance_uv = vec2(longitude / PI_2 + 0.5, 0.5 - latitude / PI);
+    // The longitude jumps at the -X axis, so no derivatives
+    let sea_distance = textureSampleLevel(sea_distance_map, sea_distance_sampler, sea_distance_uv, 0.0).r;
+    let climate_sample = sample_climate(climate, latitude, elevation, sea_distance);
+
+    var ground = biome_color(climate_sample.biome);
+#ifdef PLANET_KIND_DESERT
+    // Dune fields on the sandy biomes
+    if climate_sample.biome == BIOME_DESERT || climate_sample.biome == BIOME_COLD_DESERT {
+        ground = mix(color_sand_dark, color_sand_light, dunes(local_position, seed));
+    }
+#endif
+
+    var surface: Surface;
+    surface.base_color = terrestrial_color(climate, elevation, climate_sample.temperature, ground);
+#ifdef PLANET_KIND_ICE
+    // Crevasses cut through the ice sheets and the frozen sea
+    if climate_sample.biome == BIOME_ICE || sea_ice(climate_sample.temperature) > 0.5 {
+        surface.base_color = mix(surface.base_color, color_ice_crevasse, crevasses(local_position, seed));
+    }
+#endif
+    surface.emissive = vec3(0.0);
+    surface.roughness = terrestrial_roughness(climate, elevation, climate_sample.temperature);
+    return surface;
+}
+
+// Wind-aligned dune crests, 0.0 in the troughs and 1.0 on the crests
+fn dunes(local_position: vec3<f32>, seed: u32) -> f32 {
+    let p = normalize(local_position);
+    let wind = normalize(vec3(seed_float(seed, 30u) - 0.5, 0.2, seed_float(seed, 31u) - 0.5));
+    let warp = simplex_noise_3d(p * 6.0 + seed_float(seed, 33u) * 10.0) * 2.0;
+    let phase = dot(p, wind) * 180.0 + warp;
+    // Asymmetric profile: gentle windward slope, steep slip face
+    let crest = fract(phase / (2.0 * 3.14159265));
+    return pow(crest, 2.0) * smoothstep(1.0, 0.9, crest);
+}
+
+// Thin crack lines, 1.0 inside a crevasse
+fn crevasses(local_position: vec3<f32>, seed: u32) -> f32 {
+    let p = normalize(local_position) * 40.0 + seed_float(seed, 32u) * 10.0;
+    let ridge = abs(simplex_noise_3d(p));
+    return 1.0 - smoothstep(0.0, 0.06, ridge);
+}
+
+fn lava_surface(local_position: vec3<f32>, elevation: f32, seed: u32) -> Surface {
+    let p = normalize(local_position);
+    // Cracks along the zero crossings of the noise, widening at lower elevations
+    let crack_noise = abs(simplex_noise_3d(p * 12.0 + seed_float(seed, 40u) * 10.0))
+        + abs(simplex_noise_3d(p * 37.0 + seed_float(seed, 41u) * 10.0)) * 0.35;
+    let crack_width = mix(0.12, 0.02, smoothstep(0.1, 0.35, elevation));
+    let cracks = 1.0 - smoothstep(0.0, crack_width, crack_noise);
+    // Low basins fill with molten lakes
+    let lakes = smoothstep(0.08, 0.03, elevation);
+    let molten = max(cracks, lakes);
+
+    // Glows like a blackbody, yellower where it is hottest
+    let temperature = mix(lava_temperature, lava_temperature_hot, pow(molten, 3.0));
+    let heat = blackbody_color_lookup(temperature);
+
+    var surface: Surface;
+    surface.base_color = mix(mix(color_basalt, color_basalt_cooled, smoothstep(0.1, 0.4, elevation)), heat, molten);
+    surface.emissive = heat * molten * lava_emissive_intensity;
+    surface.roughness = mix(0.95, 0.5, molten);
+    return surface;
+}
+
+fn rocky_surface(local_position: vec3<f32>, elevation: f32, seed: u32) -> Surface {
+    let p = normalize(local_position);
+    var field: CraterField;
+    field.seed = planet_material.planet_seed;
+    field.octaves = 6u;
+    field.max_radius = 0.12;
+    field.density = 0.6;
+    let craters = crater_field(p, field);
+    // Dark basalt plains (maria) flood the lowlands
+    let mare = smoothstep(0.16, 0.1, elevation + simplex_noise_3d(p * 3.0 + seed_float(seed, 42u) * 10.0) * 0.04);
+    let highland = smoothstep(0.15, 0.4, elevation);
+    let speckle = simplex_noise_3d(p * 60.0) * 0.04;
+
+    var surface: Surface;
+    surface.base_color = mix(mix(color_regolith, color_regolith_highland, highland), color_mare, mare) + speckle;
+    surface.base_color = mix(surface.base_color * mix(1.0, 0.8, craters.floor), color_regolith_highland, craters.ejecta);
+    surface.emissive = vec3(0.0);
+    surface.roughness = 0.95;
+    surface.height = craters.height * length(local_position);
+    return surface;
+}
+
+#ifdef PLANET_ATMOSPHERE
+// Color of the sunlight after crossing the atmosphere down to `local_position`,
+// red at sunset. Fades to white on the night side, where the ground is lit
+// by the sky alone.
+fn atmosphere_sun_tint(local_position: vec3<f32>, to_sun: vec3<f32>) -> vec3<f32> {
+    let r = max(length(local_position), atmosphere.planet_radius);
+    let mu = dot(local_position, to_sun) / length(local_position);
+    // Below the horizon the sunlight grazes the ground, clamp to the horizon
+    let transmittance = textureSampleLevel(transmittance_lut, lut_sampler, transmittance_lut_uv(atmosphere, r, max(mu, 0.0)), 0.0).rgb;
+    return mix(vec3(1.0), transmittance, smoothstep(-0.1, 0.0, mu));
+}
+
+// Irradiance from the whole sky over `local_position` on the ground facing
+// up, over pi, for a sun of unit illuminance
+fn atmosphere_sky_irradiance(local_position: vec3<f32>, to_sun: vec3<f32>) -> vec3<f32> {
+    let r = max(length(local_position), atmosphere.planet_radius);
+    let mu = dot(local_position, to_sun) / length(local_position);
+    return textureSampleLevel(sky_irradiance_lut, lut_sampler, multiscattering_lut_uv(atmosphere, r, mu), 0.0).rgb;
+}
+
+#ifndef PREPASS_PIPELINE
+// What the directional light `light_id` adds to `apply_pbr_lighting`, shadow
+// included, so it can be tinted without tinting the ambient and
+// environment light
+fn directional_lighting(in: PbrInput, light_id: u32) -> vec3<f32> {
+    let light = lights.directional_lights[light_id];
+    if light.skip != 0u {
+        return vec3(0.0);
+    }
+
+    let base_color = in.material.base_color.rgb;
+    let metallic = in.material.metallic;
+    let perceptual_roughness = in.material.perceptual_roughness;
+    let NdotV = max(dot(in.N, in.V), 0.0001);
+
+    var input: LightingInput;
+    input.layers[LAYER_BASE].NdotV = NdotV;
+    input.layers[LAYER_BASE].N = in.N;
+    input.layers[LAYER_BASE].R = reflect(-in.V, in.N);
+    input.layers[LAYER_BASE].perceptual_roughness = perceptual_roughness;
+    input.layers[LAYER_BASE].roughness = perceptualRoughnessToRoughness(perceptual_roughness);
+    input.P = in.world_position.xyz;
+    input.V = in.V;
+    input.diffuse_color = calculate_diffuse_color(
+        base_color, metallic, in.material.specular_transmission, in.material.diffuse_transmission,
+    );
+    input.F0_ = calculate_F0(base_color, metallic, in.material.reflectance);
+    input.F_ab = F_AB(perceptual_roughness, NdotV);
+
+    var shadow = 1.0;
+    if (in.flags & MESH_FLAGS_SHADOW_RECEIVER_BIT) != 0u
+            && (light.flags & DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u {
+        let view_z = dot(vec4(
+            view.view_from_world[0].z,
+            view.view_from_world[1].z,
+            view.view_from_world[2].z,
+            view.view_from_world[3].z,
+        ), in.world_position);
+        shadow = fetch_directional_shadow(light_id, in.world_position, in.world_normal, view_z);
+    }
+    return directional_light(light_id, &input) * shadow * view.exposure;
+}
+#endif
+#endif
+
+// Bumps the normal `N` by the screen-space slope of `height`, see
+// "Bump Mapping Unparametrized Surfaces on the GPU" by Morten Mikkelsen
+fn perturb_normal(N: vec3<f32>, position: vec3<f32>, height: f32) -> vec3<f32> {
+    let dpdx_ = dpdx(position);
+    let dpdy_ = dpdy(position);
+    let r1 = cross(dpdy_, N);
+    let r2 = cross(N, dpdx_);
+    let det = dot(dpdx_, r1);
+    let gradient = sign(det) * (dpdx(height) * r1 + dpdy(height) * r2);
+    return normalize(abs(det) * N - gradient);
+}
 
+fn gas_giant_surface(local_position: vec3<f32>) -> Surface {
+    var surface: Surface;
+    surface.base_color = gas_giant_color(local_position, globals.time, gas_giant);
+    surface.emissive = vec3(0.0);
+    surface.roughness = 1.0;
+    return surface;
+}
 
 @fragment
 fn fragment(
@@ -87,106 +320,80 @@ fn fragment(
     // generate a PbrInput struct from the StandardMaterial bindings
     var pbr_input = pbr_input_from_standard_material(in, is_front);
 
-    let seed = f32(planet_material.planet_seed);
-    let random = rand11(seed);
-
-    let oct_01_A: f32 = simplex_noise_3d(in.world_position.xyz * lerp(0.003, 0.01, rand11(seed + 1)) + lerp(1.0, 2.0, rand11(seed + 1)));
-    let oct_01_B: f32 = simplex_noise_3d(in.world_position.xyz * lerp(0.003, 0.01, rand11(seed + 2)) + lerp(1.0, 2.0, rand11(seed + 2)));
-    let oct_02_A: f32 = simplex_noise_3d(in.world_position.xyz * lerp(0.014, 0.025, rand11(seed + 3)) + lerp(0.5, 5.0, rand11(seed + 3)));
-    let oct_02_B: f32 = simplex_noise_3d(in.world_position.xyz * lerp(0.014, 0.025, rand11(seed + 4)) + lerp(0.5, 5.0, rand11(seed + 4)));
-    let oct_03_A: f32 = simplex_noise_3d(in.world_position.xyz * lerp(0.08, 0.10, rand11(seed + 5)) + lerp(0.5, 7.0, rand11(seed + 5)));
-    let oct_03_B: f32 = simplex_noise_3d(in.world_position.xyz * lerp(0.08, 0.10, rand11(seed + 6)) + lerp(0.5, 7.0, rand11(seed + 6)));
-    let oct_04_A: f32 = simplex_noise_3d(in.world_position.xyz * lerp(1.5, 0.3, rand11(seed + 7)) + lerp(0.5, 7.0, rand11(seed + 7)));
-    let oct_04_B: f32 = simplex_noise_3d(in.world_position.xyz * lerp(1.5, 0.3, rand11(seed + 8)) + lerp(0.5, 7.0, rand11(seed + 8)));
-
-    
-    var voronoi_01 = voronoise(vec2(in.uv.x * lerp(5.0, 20.0, rand11(seed + 9)), in.uv.y * lerp(5.0, 20.0, rand11(seed + 10))), 1., 1.0);
-    var voronoi_02 = voronoise(vec2(in.uv.x * lerp(1.0, 3.0, rand11(seed + 11)), in.uv.y * lerp(1.0, 3.0, rand11(seed + 12))), 1., 1.0);
-    var voronoi_03 = voronoise(vec2(in.uv.x * lerp(0.1, 0.3, rand11(seed + 13)), in.uv.y * lerp(0.1, 0.3, rand11(seed + 14))), 1., 1.0);
-    var voronoi_04 = voronoise(vec2(in.uv.x * lerp(0.01, 0.03, rand11(seed + 15)), in.uv.y * lerp(0.01, 0.3, rand11(seed + 16))), 1., 1.0);
-    
-    let oct_01 = oct_01_A + (oct_01_B * 0.5) * voronoi_01 + 0.4;
-    let oct_02 = oct_02_A + (oct_02_B * 0.5) * voronoi_02 + 0.2;
-    let oct_03 = oct_03_A + (oct_03_B * 0.5) * voronoi_03 + 0.1;
-    let oct_04 = oct_04_A + (oct_04_B * 0.5) * voronoi_04;
-
-    var elevation = clamp(0.0, 1.0, (oct_01 * 1.0 + oct_02 * 0.5 + oct_03 * 0.15 + oct_04 * 0.02) / 4.0);
-
-    let elevation_map = vec3(elevation, elevation, elevation);
-
-    let wind_bands = 6.0;
-    let distance_from_poles = 1.0 - abs(in.uv.y - 0.5) * 2.0;
-    let wind_pattern = abs(fmod(distance_from_poles * (wind_bands / 2.0), 2.0) - 1.0);
-    
-    let polar_region = smoothstep(0.45, 0.2, distance_from_poles);
-    let equator_region = smoothstep(0.15, 0.0, 1.0 - distance_from_poles);
-
-    let humidity_map = polar_region + equator_region;
-
-    let poles = smoothstep(0.3, 0.4, distance_from_poles);
-
-    let water_threshold = 0.15;
-    let water_area_map = 1.0 - step(water_threshold, elevation);
-    let water_normalized_elevation = norm(0.0, water_threshold, elevation * water_area_map);
-
-    var water_topographic_map = vec3(water_normalized_elevation, water_normalized_elevation, water_normalized_elevation);
-    water_topographic_map = mix(color_water_deep_deep_ocean, color_water_deep_ocean, step(0.3, water_normalized_elevation));
-    water_topographic_map = mix(water_topographic_map, color_water_ocean, step(0.4, water_normalized_elevation));
-    water_topographic_map = mix(water_topographic_map, color_water_shallow, step(0.8, water_normalized_elevation));
-    water_topographic_map *= water_area_map; // Restrict water to water areas
-
-    let land_area_map = 1.0 - water_area_map;
-
-    // Mountain Ranges
-    let perlin_a = perlin_noise_2d(in.uv * 10.0 + 5.0);
-    let perlin_b = perlin_noise_2d(in.uv * 11.0);
-    let perlin_c = perlin_noise_2d(in.uv * 50.0);
-    let perlin_d = perlin_noise_2d(in.uv * 100.0 + 15.0);
-
-    var final_noise = (abs((perlin_a + perlin_b * 0.1 + perlin_c * 0.2 + perlin_d * 0.1) / 4.0) * -1) + 0.1;
-    var land_normalized_elevation = norm(water_threshold, 0.5, elevation * land_area_map);
-    // land_normalized_elevation += perlin_a * 1.0 - perlin_b * 0.6 + perlin_c * 0.45;
-    // land_normalized_elevation *= land_area_map;
-
-    var land_topographic_map = vec3(land_normalized_elevation, land_normalized_elevation, land_normalized_elevation);
-
-    land_topographic_map = mix(color_dirt, color_vegetation, step(0.15, land_normalized_elevation));
-    land_topographic_map = mix(color_vegetation, color_vegetation_lush, step(0.15, land_normalized_elevation));
-    land_topographic_map = mix(land_topographic_map, color_vegetation_plains, step(0.30, land_normalized_elevation));
-    land_topographic_map = mix(land_topographic_map, color_dirt, step(0.65, land_normalized_elevation));
-    land_topographic_map = mix(land_topographic_map, color_highland, step(0.68, land_normalized_elevation));
-    land_topographic_map = mix(land_topographic_map, color_mountain, step(0.85, land_normalized_elevation));
-    land_topographic_map = mix(land_topographic_map, color_mountain_peaks, step(0.91, land_normalized_elevation));
-
-    let topographic_map = mix(water_topographic_map, land_topographic_map, land_area_map);
-
-    // Mountain ranges
-    // let scale_a = 20.0;
-    // let scale_b = 40.0;
-    // let a = voronoise(vec2(in.uv.x * 4.0, in.uv.y) * scale_a, 1.0, 0.6);
-    // let b = voronoise(vec2(in.uv.x, in.uv.y * 2.0) * scale_b, 1.0, 0.4);
-    // let result = (a * b); // - 1.0;
-    // let res2 = fmod(result, 0.2);
-    // // let b = voronoise(swirl(in.uv * 10.0, vec2(0.1, 0.5), 2.0), 1.0, 0.5);
-    // // let c = voronoise(offset(in.uv, vec2(2.4, -3.3)) * 30.0, 1.0, 0.5);
-    // var mountain_range = clamp(0.0, 1.0, (a + b) - 1.0);
-    // let c = fmod(mountain_range, 0.1); // step(0.4, mountain_range);
-    // let d = step(0.5, c);
-
-    // mountain_range = res2;
-
-    final_noise = land_normalized_elevation;
-
-    let final_color = vec3(final_noise, final_noise, final_noise);
-
-    // pbr_input.material.base_color = vec4(final_color, 1.0);
-    pbr_input.material.base_color = vec4(topographic_map, 1.0);
-    // pbr_input.material.reflectance = water_area_map;
-
-    let overlay = mix(color_black, color_orange, wind_pattern);
+    let seed = planet_material.planet_seed;
+
+    // Sample the terrain in the planet's frame so it turns and orbits with the planet
+    let local_position = planet_local_position(in.world_position.xyz, in.instance_index);
+    let latitude = asin(clamp(normalize(local_position).y, -1.0, 1.0));
+
+#ifdef PLANET_KIND_GAS_GIANT
+    let surface = gas_giant_surface(local_position);
+#else
+    let elevation = terrain_elevation(local_position, in.uv, seed);
+#ifdef PLANET_KIND_LAVA
+    let surface = lava_surface(local_position, elevation, seed);
+#else ifdef PLANET_KIND_ROCKY
+    let surface = rocky_surface(local_position, elevation, seed);
+#else
+    let surface = terrestrial_surface(local_position, latitude, elevation, seed);
+#endif
+#endif
 
+    pbr_input.material.base_color = vec4(surface.base_color, 1.0);
+    pbr_input.material.emissive = vec4(surface.emissive, 1.0);
+#ifdef FRESNEL_RIM
+    // The rim glows like the surface emission, in the forward and deferred paths
+    let rim_emissive = fresnel_rim(rim, view.world_position, in.world_position.xyz, pbr_input.world_normal);
+    pbr_input.material.emissive += vec4(rim_emissive, 0.0);
+#endif
+    pbr_input.material.perceptual_roughness = surface.roughness;
+    pbr_input.N = perturb_normal(pbr_input.N, in.world_position.xyz, surface.height);
+
+#ifdef PLANET_ATMOSPHERE
+    // Sunlight through the atmosphere, and the sky in place of the ambient light.
+    // The haze between the camera and the ground is drawn by the atmosphere pass.
+    var sky_light = vec3(0.0);
+    var sun_tint = vec3(1.0);
+    if lights.n_directional_lights > 0u {
+        let sun = lights.directional_lights[0];
+        let to_sun = planet_local_direction(sun.direction_to_light, in.instance_index);
+        sun_tint = atmosphere_sun_tint(local_position, to_sun);
+        let diffuse = surface.base_color * (1.0 - pbr_input.material.metallic) * pbr_input.diffuse_occlusion;
+        sky_light = diffuse * atmosphere_sky_irradiance(local_position, to_sun) * sun.color.rgb * view.exposure;
+    }
+#endif
+
+#ifdef PREPASS_PIPELINE
+    // The deferred lighting pass lights the G-buffer later, so the sky light,
+    // the sun's tint and the cloud shadows are only added in the forward path
+    let out = deferred_output(in, pbr_input);
+#else
     var out: FragmentOutput;
+#ifdef PLANET_ATMOSPHERE
+    // The sky light stands for the ambient and environment light, which would
+    // count the sky twice. Bevy scales both by the occlusion.
+    pbr_input.diffuse_occlusion = vec3(0.0);
+    pbr_input.specular_occlusion = 0.0;
+#endif
     out.color = apply_pbr_lighting(pbr_input);
-    
+#ifdef PLANET_ATMOSPHERE
+    // Only the sunlight is filtered by the air above
+    var sunlight = vec3(0.0);
+    if lights.n_directional_lights > 0u {
+        sunlight = directional_lighting(pbr_input, 0u);
+    }
+    out.color = vec4(out.color.rgb - sunlight * (1.0 - sun_tint) + sky_light, out.color.a);
+#endif
+
+#ifndef PLANET_KIND_GAS_GIANT
+    // Shadows of the cloud layer, cast by the main directional light
+    if lights.n_directional_lights > 0u {
+        let to_sun = planet_local_direction(lights.directional_lights[0].direction_to_light, in.instance_index);
+        let shadow = cloud_shadow(local_position, to_sun, globals.time, clouds);
+        out.color = vec4(out.color.rgb * (1.0 - shadow), out.color.a);
+    }
+#endif
+#endif
+
     return out;
-}
\ No newline at end of file
+}
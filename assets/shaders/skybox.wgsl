@@ -1,64 +1,163 @@
-#import bevy_pbr::forward_io::VertexOutput
+// The procedural sky: stars with a galactic band and nebulae, as radiance
+// along a direction. Baked into the skybox cubemap by `skybox_bake.wgsl`.
 
 #import bevy_shader_utils::{
-    simplex_noise_3d::simplex_noise_3d
-    // simplex_noise_2d::simplex_noise_2d,
+    fbm::{Fbm, fbm_3d, NOISE_SIMPLEX},
+    blackbody::blackbody_color,
+    hash::{pcg, pcg3d},
 }
-#import "shaders/noise.wgsl"::rand11;
 
-@group(2) @binding(100) var<uniform> seed: u32;
+struct Sky {
+    seed: u32,
+    // Opacity of the nebulae, 0.0 for none
+    nebula_density: f32,
+    // Size of the nebula features, larger is bigger
+    nebula_scale: f32,
+    // Index of the colors of the nebulae, see `nebula_palette`
+    nebula_palette: u32,
+}
+
+const PI = 3.14159265359;
+// Star cells per unit along each axis of the grid the unit sphere is cut from
+const STAR_CELLS = 40.0;
+// Fraction of the cells holding a star, away from and in the galactic plane
+const STAR_DENSITY = 0.25;
+const STAR_DENSITY_GALACTIC_PLANE = 0.9;
+// Half thickness of the galactic band, as the sine of the galactic latitude
+const GALACTIC_BAND_WIDTH = 0.2;
+// Apparent magnitudes of the brightest and the faintest stars
+const BRIGHTEST_MAGNITUDE = -1.0;
+const FAINTEST_MAGNITUDE = 7.0;
+// Growth of the star count per magnitude, log10 of the ratio, about 0.5 in our sky
+const MAGNITUDE_SLOPE = 0.5;
+// Brightness of a magnitude 0 star, spread over one pixel
+const STAR_BRIGHTNESS = 20.0;
+const NEBULA_PALETTES = 4u;
+const NEBULA_BRIGHTNESS = 0.25;
+
+// Uniform random numbers in [0, 1) for a cell of the star grid, different for each `salt`
+fn cell_random(cell: vec3<f32>, salt: u32, seed: u32) -> vec3<f32> {
+    let key = pcg(seed ^ pcg(salt));
+    let hash = pcg3d(bitcast<vec3<u32>>(vec3<i32>(cell)) ^ vec3(key, key * 3u, key * 7u));
+    return vec3<f32>(hash >> vec3(8u)) / 16777216.0;
+}
 
-fn rand_range(min: f32, max: f32) -> f32 {
-    return min + rand11(f32(seed)) * (max - min);
+// Pole of the galaxy's disc, picked by the seed
+fn galactic_pole(seed: u32) -> vec3<f32> {
+    let hash = pcg3d(vec3(seed, pcg(seed), 17u));
+    let r = vec3<f32>(hash >> vec3(8u)) / 16777216.0;
+    let z = r.x * 2.0 - 1.0;
+    let angle = r.y * 2.0 * PI;
+    let s = sqrt(1.0 - z * z);
+    return vec3(s * cos(angle), z, s * sin(angle));
 }
 
-@fragment
-fn fragment(
-    in: VertexOutput,
-) -> @location(0) vec4<f32> {
-
-    // Layered noise for star density and placement
-    // let base_noise = simplex_noise_2d(in.uv.xy * 1.0);
-    let world_position = in.world_position.xyz;
-    // Project to sphere
-    let sphere_position = normalize(world_position);
-
-    let oct1 = simplex_noise_3d(vec3<f32>(sphere_position * 0.7));
-    let oct2 = simplex_noise_3d(vec3<f32>(sphere_position * 0.3) + vec3<f32>(rand_range(1.0, 2.0), 3.0, .3));
-    let oct3 = simplex_noise_3d(vec3<f32>(sphere_position * 150.0) + vec3<f32>(rand_range(4.0, 28.0), 3.0, .3));
-
-    let nebula_mask = (oct2 + oct1) - 0.2;
-    var ramp = smoothstep(0.3, 0.4, nebula_mask);
-    ramp = mix(0.0, oct3, ramp);
-    
-
-    return vec4<f32>(ramp, ramp, ramp, 1.0);
-    
-    // let detail_noise = simplex_noise_2d(in.uv.xy * 50.0);
-    // let quantized_noise = floor(detail_noise * 20.0) / 20.0; // Quantize space
-
-    // let star_presence = base_noise * quantized_noise; // Combine noises for star density and placement
-    // let star_threshold = 0.6; // Adjust for overall star density
-
-    // // Determine if the current fragment is a star based on the noise value
-    // if (base_noise > star_threshold) {
-    //     // Generate a color based on the position to vary star colors
-    //     // This is a simple example; you can use more complex logic for color variation
-    //     let color = vec3<f32>(
-    //         fract(sin(dot(in.world_position.xyz, vec3<f32>(12.9898, 78.233, 54.53))) * 43758.5453),
-    //         fract(sin(dot(in.world_position.xyz, vec3<f32>(4.898, 7.233, 9.53))) * 43758.5453),
-    //         fract(sin(dot(in.world_position.xyz, vec3<f32>(2.989, 3.233, 6.53))) * 43758.5453)
-    //     );
-
-    //     // Vary the brightness of the star to simulate different sizes
-    //     let size_factor = fract(sin(dot(in.world_position.xyz, vec3<f32>(1.989, 2.233, 3.53))) * 43758.5453);
-    //     let brightness = mix(0.5, 1.0, size_factor); // Mix between min and max brightness based on size_factor
-
-    //     return vec4<f32>(color * brightness, 1.0); // Star with varying color and size (brightness)
-
-    // } else {
-    //     // Sky background color
-    //     let final_color = vec4<f32>(0.0, 0.0, 0.0, 1.0);
-    //     return final_color;
-    // }
-}
\ No newline at end of file
+// Probability of a cell holding a star in direction `dir`, highest along the galactic plane
+fn star_density(dir: vec3<f32>, seed: u32) -> f32 {
+    let galactic_latitude = dot(dir, galactic_pole(seed));
+    let band = exp(-pow(galactic_latitude / GALACTIC_BAND_WIDTH, 2.0));
+    return mix(STAR_DENSITY, STAR_DENSITY_GALACTIC_PLANE, band);
+}
+
+// Point stars around the unit direction `dir`. `pixel` is the angular size of
+// a pixel: each star is a gaussian at least a pixel wide, normalized to carry
+// the same light at any resolution, so stars don't shimmer as they move
+// across pixels or change size with the window.
+fn starfield(dir: vec3<f32>, pixel: f32, sky: Sky) -> vec3<f32> {
+    let p = dir * STAR_CELLS;
+    let cell = floor(p);
+    let sigma = max(pixel * 0.6, 1e-5);
+    var radiance = vec3(0.0);
+
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let neighbour = cell + vec3(f32(x), f32(y), f32(z));
+                let position = cell_random(neighbour, 0u, sky.seed);
+                let star = normalize(neighbour + position);
+                // Only the cells the sphere passes through hold stars
+                let center_radius = length(neighbour + 0.5) / STAR_CELLS;
+                if abs(center_radius - 1.0) > 0.5 / STAR_CELLS {
+                    continue;
+                }
+
+                let angle = length(cross(dir, star));
+                if dot(dir, star) < 0.0 || angle > sigma * 4.0 {
+                    continue;
+                }
+
+                let traits = cell_random(neighbour, 1u, sky.seed);
+                if traits.x > star_density(star, sky.seed) {
+                    continue;
+                }
+                // Each magnitude holds 10^MAGNITUDE_SLOPE times more stars than the previous one
+                let magnitude = max(FAINTEST_MAGNITUDE + log(max(traits.y, 1e-6)) / (MAGNITUDE_SLOPE * log(10.0)), BRIGHTEST_MAGNITUDE);
+                let flux = pow(10.0, -0.4 * magnitude);
+                // Mostly cool stars, few hot ones
+                let temperature = pow(10.0, mix(3.45, 4.4, traits.z * traits.z));
+
+                let splat = exp(-0.5 * angle * angle / (sigma * sigma)) * pixel * pixel / (2.0 * PI * sigma * sigma);
+                radiance += blackbody_color(temperature) * flux * splat * STAR_BRIGHTNESS;
+            }
+        }
+    }
+    return radiance;
+}
+
+// Emission colors of the ionized gas: two lines mixed across the cloud
+fn nebula_palette(index: u32) -> array<vec3<f32>, 2> {
+    switch index % NEBULA_PALETTES {
+        // Hydrogen alpha and oxygen III
+        case 0u: { return array<vec3<f32>, 2>(vec3(0.9, 0.12, 0.2), vec3(0.1, 0.6, 0.7)); }
+        // Sulfur, hydrogen and oxygen mapped to gold and blue
+        case 1u: { return array<vec3<f32>, 2>(vec3(0.9, 0.55, 0.15), vec3(0.15, 0.35, 0.9)); }
+        // Blue reflection nebula around young stars
+        case 2u: { return array<vec3<f32>, 2>(vec3(0.3, 0.45, 1.0), vec3(0.55, 0.3, 0.9)); }
+        // Supernova remnant
+        default: { return array<vec3<f32>, 2>(vec3(0.9, 0.2, 0.6), vec3(0.3, 0.9, 0.5)); }
+    }
+}
+
+// Octaves of simplex noise at slightly off-doubling frequencies, so the
+// octaves don't line up
+fn nebula_octaves() -> Fbm {
+    return Fbm(NOISE_SIMPLEX, 5u, 2.03, 0.5);
+}
+
+struct Nebula {
+    emission: vec3<f32>,
+    // Light let through the dust, from the stars and the gas behind it
+    transmittance: f32,
+}
+
+// Gas glowing in the palette's colors and dark dust lanes, both domain warped
+// so they billow and stretch into filaments. Thickest along the galactic plane.
+fn nebula(dir: vec3<f32>, sky: Sky) -> Nebula {
+    let offset = vec3<f32>(pcg3d(vec3(sky.seed, 5u, 9u)) >> vec3(16u)) / 65536.0 * 100.0;
+    let p = dir * 2.0 / max(sky.nebula_scale, 1e-3) + offset;
+
+    let octaves = nebula_octaves();
+    let warp = vec3(fbm_3d(p, octaves), fbm_3d(p + vec3(5.2, 1.3, 2.8), octaves), fbm_3d(p + vec3(1.7, 9.2, 4.1), octaves));
+    let gas = fbm_3d(p + warp * 4.0, octaves);
+    let dust = fbm_3d(p * 2.0 + warp * 3.0 + 31.0, octaves);
+
+    let galactic_latitude = dot(dir, galactic_pole(sky.seed));
+    let band = mix(0.3, 1.0, exp(-pow(galactic_latitude / (GALACTIC_BAND_WIDTH * 2.0), 2.0)));
+    let density = sky.nebula_density * band;
+
+    let palette = nebula_palette(sky.nebula_palette);
+    let color = mix(palette[0], palette[1], smoothstep(-0.3, 0.3, warp.x - warp.z));
+
+    var out: Nebula;
+    out.emission = color * smoothstep(0.0, 0.6, gas) * density * NEBULA_BRIGHTNESS;
+    out.transmittance = exp(-smoothstep(0.05, 0.45, dust) * density * 3.0);
+    return out;
+}
+
+// Radiance of the sky along the unit direction `dir`, for a pixel of angular size `pixel`
+fn sky_radiance(dir: vec3<f32>, pixel: f32, sky: Sky) -> vec3<f32> {
+    // The dust dims the stars behind it, and partly the glowing gas it is mixed with
+    let clouds = nebula(dir, sky);
+    return starfield(dir, pixel, sky) * clouds.transmittance
+        + clouds.emission * mix(1.0, clouds.transmittance, 0.6);
+}
@@ -0,0 +1,174 @@
+// Bakes the procedural sky into cubemaps: the skybox itself and its mips, then
+// the diffuse irradiance map and the prefiltered specular map lighting the
+// scene from it. The outputs are the six faces as an array, in wgpu's order:
+// +X, -X, +Y, -Y, +Z, -Z.
+
+#import "shaders/skybox.wgsl"::{Sky, sky_radiance}
+
+@group(0) @binding(0) var<uniform> sky: Sky;
+@group(0) @binding(1) var skybox: texture_cube<f32>;
+@group(0) @binding(2) var skybox_sampler: sampler;
+@group(0) @binding(3) var output: texture_storage_2d_array<rgba16float, write>;
+// Of the specular mip being baked
+@group(0) @binding(4) var<uniform> perceptual_roughness: f32;
+// The faces of the skybox mip above the one being downsampled
+@group(0) @binding(5) var source: texture_2d_array<f32>;
+
+const PI = 3.14159265359;
+// Directions the sky is sampled in for each texel of the diffuse map
+const DIFFUSE_SAMPLES = 1024u;
+// Directions the sky is sampled in for each texel of the specular map
+const SPECULAR_SAMPLES = 512u;
+
+// Direction through the texel `id` of the output cube, as a cubemap lookup
+fn cube_direction(id: vec3<u32>) -> vec3<f32> {
+    let st = (vec2<f32>(id.xy) + 0.5) / vec2<f32>(textureDimensions(output)) * 2.0 - 1.0;
+    let s = st.x;
+    let t = st.y;
+    switch id.z {
+        case 0u: { return vec3(1.0, -t, -s); }
+        case 1u: { return vec3(-1.0, -t, s); }
+        case 2u: { return vec3(s, 1.0, t); }
+        case 3u: { return vec3(s, -1.0, -t); }
+        case 4u: { return vec3(s, -t, 1.0); }
+        default: { return vec3(-s, -t, -1.0); }
+    }
+}
+
+// Bevy looks cubemaps up with z flipped, so the texel at `lookup` shows the sky
+// in the returned world direction
+fn world_direction(lookup: vec3<f32>) -> vec3<f32> {
+    return normalize(lookup * vec3(1.0, 1.0, -1.0));
+}
+
+// Workgroups overhang the edges of outputs whose size is not a multiple of 8
+fn outside_output(id: vec3<u32>) -> bool {
+    return any(id.xy >= textureDimensions(output)) || id.z >= 6u;
+}
+
+@compute @workgroup_size(8, 8, 1)
+fn bake_skybox(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let lookup = cube_direction(id);
+    // A texel spans 2 / size on the face, seen at a slant away from the center
+    let pixel = 2.0 / f32(textureDimensions(output).x) / pow(dot(lookup, lookup), 0.75);
+    let radiance = sky_radiance(world_direction(lookup), pixel, sky);
+    textureStore(output, id.xy, id.z, vec4(radiance, 1.0));
+}
+
+// Averages the 2x2 texels of the mip above into each texel of the output mip
+@compute @workgroup_size(8, 8, 1)
+fn downsample_skybox(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let last = textureDimensions(source) - 1u;
+    var radiance = vec4(0.0);
+    for (var i = 0u; i < 4u; i++) {
+        let texel = min(id.xy * 2u + vec2(i & 1u, i >> 1u), last);
+        radiance += textureLoad(source, texel, id.z, 0);
+    }
+    textureStore(output, id.xy, id.z, radiance * 0.25);
+}
+
+// Mip of the skybox to read a sample from, so that its texels cover the solid
+// angle of the sample, which is one over the count and the pdf of its
+// direction. Filtered importance sampling, "Real-time Shading with Filtered
+// Importance Sampling" (Křivánek and Colbert 2008), with their bias of one mip.
+fn sample_lod(count: u32, pdf: f32) -> f32 {
+    let size = f32(textureDimensions(skybox).x);
+    let sample_solid_angle = 1.0 / (f32(count) * pdf);
+    let texel_solid_angle = 4.0 * PI / (6.0 * size * size);
+    return max(0.5 * log2(sample_solid_angle / texel_solid_angle) + 1.0, 0.0);
+}
+
+// Evenly spread directions on the unit sphere
+fn fibonacci_sphere(i: u32, count: u32) -> vec3<f32> {
+    let z = 1.0 - (f32(i) + 0.5) / f32(count) * 2.0;
+    let angle = f32(i) * PI * (3.0 - sqrt(5.0));
+    let r = sqrt(1.0 - z * z);
+    return vec3(r * cos(angle), r * sin(angle), z);
+}
+
+// Irradiance divided by pi, as Bevy's diffuse environment maps expect, for a
+// surface facing the cubemap direction of the texel
+@compute @workgroup_size(8, 8, 1)
+fn bake_diffuse(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let normal = normalize(cube_direction(id));
+    // The samples are uniform over the sphere
+    let lod = sample_lod(DIFFUSE_SAMPLES, 1.0 / (4.0 * PI));
+    var irradiance = vec3(0.0);
+    for (var i = 0u; i < DIFFUSE_SAMPLES; i++) {
+        let dir = fibonacci_sphere(i, DIFFUSE_SAMPLES);
+        let cos_theta = dot(dir, normal);
+        if cos_theta > 0.0 {
+            irradiance += textureSampleLevel(skybox, skybox_sampler, dir, lod).rgb * cos_theta;
+        }
+    }
+    // Each sample covers 4 pi / n steradians
+    irradiance *= 4.0 / f32(DIFFUSE_SAMPLES);
+    textureStore(output, id.xy, id.z, vec4(irradiance, 1.0));
+}
+
+// The `i`th of `count` points of the Hammersley set on the unit square
+fn hammersley(i: u32, count: u32) -> vec2<f32> {
+    return vec2(f32(i) / f32(count), f32(reverseBits(i)) * 2.3283064365386963e-10);
+}
+
+// A half vector around `normal`, distributed like the GGX normal distribution
+// of roughness `alpha`, from the point `xi` of the unit square
+fn importance_sample_ggx(xi: vec2<f32>, alpha: f32, normal: vec3<f32>) -> vec3<f32> {
+    let a2 = alpha * alpha;
+    let phi = 2.0 * PI * xi.x;
+    let cos_theta = sqrt((1.0 - xi.y) / (1.0 + (a2 - 1.0) * xi.y));
+    let sin_theta = sqrt(1.0 - cos_theta * cos_theta);
+
+    let up = select(vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), abs(normal.z) < 0.999);
+    let tangent = normalize(cross(up, normal));
+    let bitangent = cross(normal, tangent);
+    return normalize(tangent * cos(phi) * sin_theta + bitangent * sin(phi) * sin_theta + normal * cos_theta);
+}
+
+// The GGX normal distribution of roughness `alpha`
+fn d_ggx(n_dot_h: f32, alpha: f32) -> f32 {
+    let a2 = alpha * alpha;
+    let f = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+    return a2 / (PI * f * f);
+}
+
+// Radiance reflected towards the cubemap direction of the texel by a surface
+// of `perceptual_roughness` facing it, the GGX prefilter of "Real Shading in
+// Unreal Engine 4" (Karis 2013). Bevy looks the mip up by roughness.
+@compute @workgroup_size(8, 8, 1)
+fn bake_specular(@builtin(global_invocation_id) id: vec3<u32>) {
+    if outside_output(id) {
+        return;
+    }
+    let normal = normalize(cube_direction(id));
+    if perceptual_roughness == 0.0 {
+        textureStore(output, id.xy, id.z, textureSampleLevel(skybox, skybox_sampler, normal, 0.0));
+        return;
+    }
+
+    let alpha = perceptual_roughness * perceptual_roughness;
+    var radiance = vec3(0.0);
+    var weight = 0.0;
+    for (var i = 0u; i < SPECULAR_SAMPLES; i++) {
+        let half_vector = importance_sample_ggx(hammersley(i, SPECULAR_SAMPLES), alpha, normal);
+        let dir = reflect(-normal, half_vector);
+        let cos_theta = dot(dir, normal);
+        if cos_theta > 0.0 {
+            // With the view along the normal, the pdf of the reflected
+            // direction is D(h) (n.h) / (4 v.h) = D(h) / 4
+            let lod = sample_lod(SPECULAR_SAMPLES, d_ggx(dot(normal, half_vector), alpha) / 4.0);
+            radiance += textureSampleLevel(skybox, skybox_sampler, dir, lod).rgb * cos_theta;
+            weight += cos_theta;
+        }
+    }
+    textureStore(output, id.xy, id.z, vec4(radiance / weight, 1.0));
+}
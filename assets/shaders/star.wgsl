@@ -0,0 +1,92 @@
+// Photosphere of a star: blackbody color, limb darkening, convection cells
+// and sunspots. Everything is evaluated on the unit sphere.
+
+#import bevy_shader_utils::{
+    simplex_noise_3d::simplex_noise_3d,
+    hash::{pcg4d, unit_float_3d, seed_float},
+}
+
+struct Star {
+    // Blackbody color of the effective temperature, brightest channel 1.0
+    color: vec3<f32>,
+    // Luminance at the center of the disc, in nits
+    luminance: f32,
+    // Blackbody color of the cooler spots
+    spot_color: vec3<f32>,
+    seed: u32,
+    // Linear limb darkening coefficient, 0.0 for a uniform disc
+    limb_darkening: f32,
+    // Number of granules across the equator
+    granulation_scale: f32,
+    // Brightness variation between the granule centers and the lanes
+    granulation_contrast: f32,
+    // Fraction of the active latitudes covered by spots
+    spot_coverage: f32,
+    // Rotation of the surface, in radians per second
+    rotation_speed: f32,
+}
+
+// Distance to the nearest cell boundary of a 3D Voronoi pattern, 0.0 in the lanes
+fn cell_edge(p: vec3<f32>, seed: u32) -> f32 {
+    let cell = floor(p);
+    var f1 = 8.0;
+    var f2 = 8.0;
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let neighbour = cell + vec3(f32(x), f32(y), f32(z));
+                // The seed is hashed with the cell, not added to it, so stars
+                // with nearby seeds don't share shifted patterns
+                let hash = pcg4d(vec4u(bitcast<vec3<u32>>(vec3<i32>(neighbour)), seed));
+                let feature = neighbour + unit_float_3d(hash.xyz);
+                let d = distance(p, feature);
+                if d < f1 {
+                    f2 = f1;
+                    f1 = d;
+                } else if d < f2 {
+                    f2 = d;
+                }
+            }
+        }
+    }
+    return f2 - f1;
+}
+
+// Convection cells: bright upwelling centers, dark sinking lanes. Between 0.0 and 1.0.
+fn granulation(p: vec3<f32>, time: f32, star: Star) -> f32 {
+    // The cells boil: drift the pattern through the third dimension of the noise
+    let boil = vec3(0.0, 0.0, time * 0.05);
+    let warp = simplex_noise_3d(p * star.granulation_scale * 0.25 + boil) * 0.3;
+    let scaled = p * star.granulation_scale / 3.14159265 + warp + boil;
+    let granules = smoothstep(0.0, 0.35, cell_edge(scaled, star.seed));
+    // Supergranulation modulates the brightness on a larger scale
+    let supergranules = simplex_noise_3d(p * star.granulation_scale * 0.1 + boil * 0.3) * 0.5 + 0.5;
+    return granules * mix(0.8, 1.0, supergranules);
+}
+
+// Darkening by the spots, 0.0 outside and 1.0 in the umbra. Spots gather in
+// two bands around the equator, like on the sun.
+fn sunspots(p: vec3<f32>, time: f32, star: Star) -> f32 {
+    // Small enough for the noise offsets to stay precise
+    let seed = seed_float(star.seed, 51u) * 1000.0;
+    let latitude = asin(clamp(p.y, -1.0, 1.0));
+    let band_latitude = mix(0.15, 0.55, seed_float(star.seed, 50u));
+    let activity = exp(-pow((abs(latitude) - band_latitude) / 0.15, 2.0));
+    // Spots slowly grow and decay
+    let field = simplex_noise_3d(p * 6.0 + vec3(seed * 0.01, 0.0, time * 0.01)) * 0.5 + 0.5
+        + simplex_noise_3d(p * 15.0 + seed * 0.02) * 0.15;
+    let threshold = 1.0 - star.spot_coverage * activity;
+    let penumbra = smoothstep(threshold, threshold + 0.05, field);
+    let umbra = smoothstep(threshold + 0.06, threshold + 0.1, field);
+    return penumbra * 0.6 + umbra * 0.4;
+}
+
+// Radiance of the photosphere at `p` on the unit sphere, seen at `mu`, the
+// cosine between the surface normal and the direction to the viewer
+fn star_radiance(p: vec3<f32>, mu: f32, time: f32, star: Star) -> vec3<f32> {
+    let limb = 1.0 - star.limb_darkening * (1.0 - max(mu, 0.0));
+    let cells = mix(1.0 - star.granulation_contrast, 1.0, granulation(p, time, star));
+    let spots = 1.0 - sunspots(p, time, star) * 0.85;
+    let color = mix(star.color, star.spot_color, 1.0 - spots);
+    return color * star.luminance * limb * cells * spots;
+}
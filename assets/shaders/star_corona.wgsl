@@ -0,0 +1,69 @@
+// Corona and glare of a star, on a quad turned towards the camera in the
+// vertex shader. The star's own mesh hides the middle of the quad.
+
+#import bevy_pbr::{
+    mesh_functions::get_world_from_local,
+    mesh_view_bindings::{globals, view},
+}
+#import bevy_shader_utils::simplex_noise_3d::simplex_noise_3d
+
+struct Corona {
+    // Blackbody color of the star, brightest channel 1.0
+    color: vec3<f32>,
+    // Luminance of the corona at the limb, in nits
+    luminance: f32,
+    // Radius of the star, in world units
+    star_radius: f32,
+    // Half the size of the quad, in star radii
+    extent: f32,
+    seed: u32,
+}
+
+@group(2) @binding(100)
+var<uniform> corona: Corona;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+}
+
+struct CoronaVertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Position on the quad, in star radii from its center
+    @location(0) offset: vec2<f32>,
+}
+
+@vertex
+fn vertex(vertex: Vertex) -> CoronaVertexOutput {
+    let center = get_world_from_local(vertex.instance_index)[3].xyz;
+    // Corners of a unit quad, spread along the camera's right and up
+    let offset = vertex.position.xy * 2.0 * corona.extent;
+    let right = view.world_from_view[0].xyz;
+    let up = view.world_from_view[1].xyz;
+    let world_position = center + (right * offset.x + up * offset.y) * corona.star_radius;
+
+    var out: CoronaVertexOutput;
+    out.position = view.clip_from_world * vec4(world_position, 1.0);
+    out.offset = offset;
+    return out;
+}
+
+@fragment
+fn fragment(in: CoronaVertexOutput) -> @location(0) vec4<f32> {
+    let d = length(in.offset);
+    // The noise is sampled on circles around the center rather than along the
+    // angle, which would jump from -PI to PI
+    let around = in.offset / max(d, 1e-4);
+    let seed = f32(corona.seed % 1000u);
+
+    // Streamers: the glow reaches further at some angles, and they slowly shift
+    let streamers = simplex_noise_3d(vec3(around * 3.0, globals.time * 0.02 + seed)) * 0.5
+        + simplex_noise_3d(vec3(around * 9.0, globals.time * 0.05 - seed)) * 0.25;
+    let reach = max(d - 1.0, 0.0) / (1.0 + streamers * 0.6);
+    let glow = exp(-reach * 4.0) + exp(-reach * 0.8) * 0.15;
+    // Fade out before the edge of the quad
+    let fade = 1.0 - smoothstep(corona.extent * 0.6, corona.extent, d);
+
+    let radiance = corona.color * corona.luminance * glow * fade;
+    return vec4(radiance * view.exposure, 0.0);
+}
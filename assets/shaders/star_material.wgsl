@@ -0,0 +1,38 @@
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    mesh_view_bindings::{globals, view},
+}
+
+#import bevy_shader_utils::fresnel::{FresnelRim, fresnel_rim}
+
+#import "shaders/local_space.wgsl"::planet_local_position;
+#import "shaders/gas_giant.wgsl"::rotate_around;
+#import "shaders/star.wgsl"::{Star, star_radiance};
+
+@group(2) @binding(100)
+var<uniform> star: Star;
+
+@group(2) @binding(101)
+var<uniform> rim: FresnelRim;
+
+// The star emits its own light, nothing lights it. The output is in nits
+// times the exposure, like the emissive of the standard material, so it
+// blooms on HDR cameras.
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    let local_position = planet_local_position(in.world_position.xyz, in.instance_index);
+    let p = rotate_around(normalize(local_position), vec3(0.0, 1.0, 0.0), globals.time * star.rotation_speed);
+    let normal = normalize(in.world_normal);
+    let mu = dot(normal, normalize(view.world_position - in.world_position.xyz));
+    var radiance = star_radiance(p, mu, globals.time, star);
+#ifdef FRESNEL_RIM
+    radiance += fresnel_rim(rim, view.world_position, in.world_position.xyz, normal);
+#endif
+
+    var out: FragmentOutput;
+    out.color = vec4(radiance * view.exposure, 1.0);
+    return out;
+}
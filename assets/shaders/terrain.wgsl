@@ -0,0 +1,88 @@
+// Terrain of the solid planets, and the colors of the kinds with seas and
+// biomes. Mirrored by `src/terrain.rs` and `src/planet_maps.rs`, whose tests
+// hold golden outputs of this file.
+
+#import bevy_shader_utils::{
+    simplex_noise_3d::simplex_noise_3d,
+    voronoise::voronoise,
+    hash::seed_float,
+}
+#import "shaders/climate.wgsl"::{ClimateModel, biome_color, BIOME_ICE}
+
+// Water
+const color_water_deep_deep_ocean = vec3(0.16, 0.50, 0.61);
+const color_water_deep_ocean = vec3(0.235, 0.592, 0.666);
+const color_water_ocean = vec3(0.254, 0.647, 0.705);
+const color_water_shallow = vec3(0.360, 0.682, 0.725);
+
+// Land
+const color_highland = vec3(0.650, 0.568, 0.462);
+const color_mountain = vec3(0.486, 0.411, 0.352);
+const color_mountain_peaks = vec3(0.8, 0.8, 0.8);
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    return a + t * (b - a);
+}
+
+fn norm(min: f32, max: f32, value: f32) -> f32 {
+    return (value - min) / (max - min);
+}
+
+// Normalized terrain elevation, shared by all solid planet kinds
+fn terrain_elevation(local_position: vec3<f32>, uv: vec2<f32>, seed: u32) -> f32 {
+    let oct_01_A: f32 = simplex_noise_3d(local_position * lerp(0.003, 0.01, seed_float(seed, 1u)) + lerp(1.0, 2.0, seed_float(seed, 1u)));
+    let oct_01_B: f32 = simplex_noise_3d(local_position * lerp(0.003, 0.01, seed_float(seed, 2u)) + lerp(1.0, 2.0, seed_float(seed, 2u)));
+    let oct_02_A: f32 = simplex_noise_3d(local_position * lerp(0.014, 0.025, seed_float(seed, 3u)) + lerp(0.5, 5.0, seed_float(seed, 3u)));
+    let oct_02_B: f32 = simplex_noise_3d(local_position * lerp(0.014, 0.025, seed_float(seed, 4u)) + lerp(0.5, 5.0, seed_float(seed, 4u)));
+    let oct_03_A: f32 = simplex_noise_3d(local_position * lerp(0.08, 0.10, seed_float(seed, 5u)) + lerp(0.5, 7.0, seed_float(seed, 5u)));
+    let oct_03_B: f32 = simplex_noise_3d(local_position * lerp(0.08, 0.10, seed_float(seed, 6u)) + lerp(0.5, 7.0, seed_float(seed, 6u)));
+    let oct_04_A: f32 = simplex_noise_3d(local_position * lerp(1.5, 0.3, seed_float(seed, 7u)) + lerp(0.5, 7.0, seed_float(seed, 7u)));
+    let oct_04_B: f32 = simplex_noise_3d(local_position * lerp(1.5, 0.3, seed_float(seed, 8u)) + lerp(0.5, 7.0, seed_float(seed, 8u)));
+
+    var voronoi_01 = voronoise(vec2(uv.x * lerp(5.0, 20.0, seed_float(seed, 9u)), uv.y * lerp(5.0, 20.0, seed_float(seed, 10u))), 1., 1.0);
+    var voronoi_02 = voronoise(vec2(uv.x * lerp(1.0, 3.0, seed_float(seed, 11u)), uv.y * lerp(1.0, 3.0, seed_float(seed, 12u))), 1., 1.0);
+    var voronoi_03 = voronoise(vec2(uv.x * lerp(0.1, 0.3, seed_float(seed, 13u)), uv.y * lerp(0.1, 0.3, seed_float(seed, 14u))), 1., 1.0);
+    var voronoi_04 = voronoise(vec2(uv.x * lerp(0.01, 0.03, seed_float(seed, 15u)), uv.y * lerp(0.01, 0.3, seed_float(seed, 16u))), 1., 1.0);
+
+    let oct_01 = oct_01_A + (oct_01_B * 0.5) * voronoi_01 + 0.4;
+    let oct_02 = oct_02_A + (oct_02_B * 0.5) * voronoi_02 + 0.2;
+    let oct_03 = oct_03_A + (oct_03_B * 0.5) * voronoi_03 + 0.1;
+    let oct_04 = oct_04_A + (oct_04_B * 0.5) * voronoi_04;
+
+    return clamp((oct_01 * 1.0 + oct_02 * 0.5 + oct_03 * 0.15 + oct_04 * 0.02) / 4.0, 0.0, 1.0);
+}
+
+// How much of the sea is frozen over, at a temperature in °C
+fn sea_ice(temperature: f32) -> f32 {
+    return smoothstep(-8.0, -14.0, temperature);
+}
+
+// The sea shaded by depth and frozen over where it is cold enough, and the
+// `ground` color of the land under the highlands, mountains and peaks
+fn terrestrial_color(model: ClimateModel, elevation: f32, temperature: f32, ground: vec3<f32>) -> vec3<f32> {
+    let water_threshold = model.sea_level;
+    let water_area_map = 1.0 - step(water_threshold, elevation);
+    let water_normalized_elevation = norm(0.0, water_threshold, elevation * water_area_map);
+
+    var water_topographic_map = mix(color_water_deep_deep_ocean, color_water_deep_ocean, step(0.3, water_normalized_elevation));
+    water_topographic_map = mix(water_topographic_map, color_water_ocean, step(0.4, water_normalized_elevation));
+    water_topographic_map = mix(water_topographic_map, color_water_shallow, step(0.8, water_normalized_elevation));
+    water_topographic_map = mix(water_topographic_map, biome_color(BIOME_ICE), sea_ice(temperature));
+    water_topographic_map *= water_area_map; // Restrict water to water areas
+
+    let land_area_map = 1.0 - water_area_map;
+    let land_normalized_elevation = norm(water_threshold, model.summit_level, elevation * land_area_map);
+
+    var land_topographic_map = ground;
+    land_topographic_map = mix(land_topographic_map, color_highland, step(0.68, land_normalized_elevation));
+    land_topographic_map = mix(land_topographic_map, color_mountain, step(0.85, land_normalized_elevation));
+    land_topographic_map = mix(land_topographic_map, color_mountain_peaks, step(0.91, land_normalized_elevation));
+
+    return mix(water_topographic_map, land_topographic_map, land_area_map);
+}
+
+// Open water is glossy, sea ice and land are not
+fn terrestrial_roughness(model: ClimateModel, elevation: f32, temperature: f32) -> f32 {
+    let land_area_map = step(model.sea_level, elevation);
+    return mix(mix(0.15, 0.6, sea_ice(temperature)), 0.9, land_area_map);
+}
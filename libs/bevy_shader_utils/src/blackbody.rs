@@ -0,0 +1,230 @@
+//! CPU mirror of `bevy_shader_utils::blackbody`, for light colors and material
+//! parameters that need to match what the shaders draw.
+//!
+//! Planck's law is integrated against an analytic fit of the CIE 1931
+//! colour-matching functions, "Simple Analytic Approximations to the CIE XYZ
+//! Color Matching Functions" (Wyman, Sloan, Shirley 2013).
+
+use bevy_math::{Mat3, Vec3};
+
+/// Second radiation constant `h * c / k`, in nanometer kelvins
+const SECOND_RADIATION_CONSTANT: f32 = 1.4388e7;
+const FIRST_WAVELENGTH: f32 = 380.0;
+const WAVELENGTH_STEP: f32 = 10.0;
+const WAVELENGTH_SAMPLES: u32 = 41;
+
+/// Temperatures are clamped to this range, in kelvin
+pub const MIN_TEMPERATURE: f32 = 500.0;
+pub const MAX_TEMPERATURE: f32 = 100_000.0;
+
+/// Entries of the table of `blackbody_color_lookup` in the shader, spaced evenly in log
+/// temperature from [`MIN_TEMPERATURE`] to [`MAX_TEMPERATURE`]
+pub const TABLE_SIZE: u32 = 64;
+
+/// CIE XYZ to linear sRGB, D65 white point
+const XYZ_TO_LINEAR_SRGB: Mat3 = Mat3::from_cols_array(&[
+    3.2404542, -0.969266, 0.0556434, //
+    -1.5371385, 1.8760108, -0.2040259, //
+    -0.4985314, 0.041556, 1.0572252,
+]);
+
+/// A lobe of the colour-matching function fit, with a different width on each side
+fn lobe(wavelength: f32, center: f32, width_below: f32, width_above: f32) -> f32 {
+    let width = if wavelength < center {
+        width_below
+    } else {
+        width_above
+    };
+    let t = (wavelength - center) / width;
+    (-0.5 * t * t).exp()
+}
+
+/// The CIE 1931 2° colour-matching functions at `wavelength`, in nanometers
+pub fn color_matching(wavelength: f32) -> Vec3 {
+    let l = wavelength;
+    Vec3::new(
+        1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7)
+            - 0.065 * lobe(l, 501.1, 20.4, 26.2),
+        0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1),
+        1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8),
+    )
+}
+
+/// Spectral radiance of a blackbody at `wavelength` nanometers, up to a constant factor
+fn planck(wavelength: f32, temperature: f32) -> f32 {
+    // Scaled to 550 nm, so the values stay in range
+    let x = wavelength / 550.0;
+    1.0 / (x.powi(5) * ((SECOND_RADIATION_CONSTANT / (wavelength * temperature)).exp() - 1.0))
+}
+
+/// CIE XYZ of a blackbody at `temperature` kelvin, scaled to `Y = 1.0`
+pub fn blackbody_xyz(temperature: f32) -> Vec3 {
+    let temperature = temperature.clamp(MIN_TEMPERATURE, MAX_TEMPERATURE);
+    let xyz = (0..WAVELENGTH_SAMPLES)
+        .map(|i| {
+            let wavelength = FIRST_WAVELENGTH + i as f32 * WAVELENGTH_STEP;
+            color_matching(wavelength) * planck(wavelength, temperature)
+        })
+        .sum::<Vec3>();
+    xyz / xyz.y
+}
+
+/// Linear sRGB of a blackbody at `temperature` kelvin, scaled so the brightest
+/// channel is 1.0. Colors outside the sRGB gamut are clamped.
+pub fn blackbody_color(temperature: f32) -> Vec3 {
+    let rgb = (XYZ_TO_LINEAR_SRGB * blackbody_xyz(temperature)).max(Vec3::ZERO);
+    rgb / rgb.max_element()
+}
+
+/// Temperature of the entry `index` of the table, in kelvin
+fn table_temperature(index: u32) -> f32 {
+    let t = index as f32 / (TABLE_SIZE - 1) as f32;
+    MIN_TEMPERATURE * (MAX_TEMPERATURE / MIN_TEMPERATURE).powf(t)
+}
+
+/// [`blackbody_color`] interpolated between the entries of the shader's
+/// table, cheap enough to run per fragment
+pub fn blackbody_color_lookup(temperature: f32) -> Vec3 {
+    let temperature = temperature.clamp(MIN_TEMPERATURE, MAX_TEMPERATURE);
+    let x = (temperature / MIN_TEMPERATURE).ln() / (MAX_TEMPERATURE / MIN_TEMPERATURE).ln()
+        * (TABLE_SIZE - 1) as f32;
+    let index = (x as u32).min(TABLE_SIZE - 2);
+    let rgb = blackbody_color(table_temperature(index)).lerp(
+        blackbody_color(table_temperature(index + 1)),
+        x - index as f32,
+    );
+    // Around 6500 K the brightest channel changes between the entries
+    rgb / rgb.max_element()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_within;
+
+    /// CIE 1931 chromaticity of the Planckian locus
+    const LOCUS: [(f32, f32, f32); 6] = [
+        (2000.0, 0.5267, 0.4133),
+        (2856.0, 0.4476, 0.4074),
+        (4000.0, 0.3805, 0.3768),
+        (5000.0, 0.3451, 0.3516),
+        (6500.0, 0.3135, 0.3236),
+        (10000.0, 0.2807, 0.2884),
+    ];
+
+    fn chromaticity(xyz: Vec3) -> (f32, f32) {
+        let sum = xyz.x + xyz.y + xyz.z;
+        (xyz.x / sum, xyz.y / sum)
+    }
+
+    #[test]
+    fn follows_the_planckian_locus() {
+        for (temperature, x, y) in LOCUS {
+            let (fit_x, fit_y) = chromaticity(blackbody_xyz(temperature));
+            assert!(
+                (fit_x - x).abs() < 0.004 && (fit_y - y).abs() < 0.004,
+                "{temperature} K: ({fit_x}, {fit_y}), expected ({x}, {y})"
+            );
+        }
+    }
+
+    #[test]
+    fn fit_matches_the_cie_table() {
+        // CIE 1931 2° observer at a few wavelengths, from the 5 nm table
+        let table = [
+            (450.0, Vec3::new(0.3362, 0.038, 1.7721)),
+            (500.0, Vec3::new(0.0049, 0.323, 0.272)),
+            (550.0, Vec3::new(0.4334, 0.995, 0.0087)),
+            (600.0, Vec3::new(1.0622, 0.631, 0.0008)),
+            (650.0, Vec3::new(0.2835, 0.107, 0.0)),
+        ];
+        for (wavelength, expected) in table {
+            let fit = color_matching(wavelength);
+            assert!(
+                fit.abs_diff_eq(expected, 0.05),
+                "{wavelength} nm: {fit}, expected {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn daylight_is_close_to_white() {
+        let color = blackbody_color(6500.0);
+        assert!(color.min_element() > 0.85, "{color}");
+    }
+
+    #[test]
+    fn warms_from_blue_to_red() {
+        let hot = blackbody_color(20_000.0);
+        let sun = blackbody_color(5772.0);
+        let ember = blackbody_color(1000.0);
+        assert_eq!(hot.z, 1.0, "{hot}");
+        assert_eq!(sun.x, 1.0, "{sun}");
+        assert!(sun.z > 0.7, "{sun}");
+        assert_eq!(ember.x, 1.0, "{ember}");
+        assert!(ember.y < 0.1 && ember.z < 0.01, "{ember}");
+    }
+
+    #[test]
+    fn clamps_the_temperature() {
+        assert_eq!(blackbody_color(0.0), blackbody_color(MIN_TEMPERATURE));
+        assert_eq!(blackbody_color(1e9), blackbody_color(MAX_TEMPERATURE));
+        assert!(blackbody_color(0.0).is_finite());
+        assert_eq!(
+            blackbody_color_lookup(0.0),
+            blackbody_color_lookup(MIN_TEMPERATURE)
+        );
+        assert_eq!(
+            blackbody_color_lookup(1e9),
+            blackbody_color_lookup(MAX_TEMPERATURE)
+        );
+    }
+
+    #[test]
+    fn lookup_is_close_to_the_integral() {
+        for i in 0..=1000 {
+            let temperature =
+                MIN_TEMPERATURE * (MAX_TEMPERATURE / MIN_TEMPERATURE).powf(i as f32 / 1000.0);
+            let lookup = blackbody_color_lookup(temperature);
+            let color = blackbody_color(temperature);
+            assert!(
+                lookup.abs_diff_eq(color, 0.01),
+                "{temperature} K: {lookup}, expected {color}"
+            );
+        }
+    }
+
+    /// Outputs of `blackbody_color_lookup` at a temperature, see [`crate::golden`]
+    const LOOKUP_GOLDEN: [([f32; 1], [f32; 3]); 10] = [
+        ([0.0], [1.0, 0.0, 0.0]),
+        ([500.0], [1.0, 0.0, 0.0]),
+        ([800.0], [1.0, 0.0, 0.0]),
+        ([1100.0], [1.0, 0.050552897, 0.0]),
+        ([1337.0], [1.0, 0.105863616, 0.0]),
+        ([1500.0], [1.0, 0.14523353, 0.0]),
+        ([5772.0], [1.0, 0.8787609, 0.8210484]),
+        ([6500.0], [1.0, 0.9430626, 0.9906272]),
+        ([25000.0], [0.38040987, 0.51215714, 1.0]),
+        ([1000000.0], [0.31621683, 0.45309454, 1.0]),
+    ];
+
+    #[test]
+    fn lookup_matches_the_shader() {
+        for (p, expected) in LOOKUP_GOLDEN {
+            let color = blackbody_color_lookup(p[0]);
+            assert_within(&p, &color.to_array(), &expected, 1e-5);
+        }
+    }
+
+    /// Prints the table of `blackbody_color_lookup` in `shaders/blackbody.wgsl`
+    #[test]
+    #[ignore = "prints the table of the shader"]
+    fn print_table() {
+        println!("    var table = array<vec3<f32>, BLACKBODY_TABLE_SIZE>(");
+        for index in 0..TABLE_SIZE {
+            let [r, g, b] = blackbody_color(table_temperature(index)).to_array();
+            println!("        vec3({r:?}, {g:?}, {b:?}),");
+        }
+        println!("    );");
+    }
+}
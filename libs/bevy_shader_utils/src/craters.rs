@@ -0,0 +1,251 @@
+//! CPU mirror of `bevy_shader_utils::craters`, for displacing meshes and for
+//! gameplay queries that need to agree with what the shader draws.
+
+use std::f32::consts::TAU;
+
+use bevy_math::{IVec3, UVec3, Vec3};
+
+use crate::hash::{pcg3d, unit_float_3d};
+
+pub use uniform::CraterField;
+
+/// Craters extend this many radii from their center, counting the ejecta rays
+const CRATER_EXTENT: f32 = 4.0;
+/// Craters larger than this fraction of `max_radius` grow a central peak
+const CENTRAL_PEAK_THRESHOLD: f32 = 0.3;
+
+// Scopes the derive's lint allow, see `uniform` in the app's `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy_render::render_resource::ShaderType;
+
+    /// Parameters of a crater field, matching `CraterField` in `craters.wgsl`
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct CraterField {
+        pub seed: u32,
+        /// Number of size octaves, each with half the radius of the previous one
+        pub octaves: u32,
+        /// Radius of the largest craters, as a fraction of the body's radius
+        pub max_radius: f32,
+        /// Probability of a grid cell holding a crater
+        pub density: f32,
+    }
+}
+
+impl Default for CraterField {
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            octaves: 5,
+            max_radius: 0.15,
+            density: 0.5,
+        }
+    }
+}
+
+/// The crater field at a point, matching `CraterSample` in `craters.wgsl`
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct CraterSample {
+    /// Height offset as a fraction of the body's radius
+    pub height: f32,
+    /// Brightness of fresh ejecta: rays and blankets, 0.0 to 1.0
+    pub ejecta: f32,
+    /// 1.0 on crater floors, 0.0 outside the rims
+    pub floor: f32,
+}
+
+/// Uniform floats in [0, 1) from the top 24 bits of each hash lane
+fn crater_random(p: UVec3) -> Vec3 {
+    unit_float_3d(pcg3d(p))
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
+
+/// Height profile of a single crater, `x` is the distance from its center in radii
+fn crater_profile(x: f32, radius: f32, large: f32) -> f32 {
+    let depth = 0.2 * radius;
+    // Parabolic bowl, flattened into a floor on large craters
+    let bowl = (x * x - 1.0).max(mix(-1.0, -0.6, large));
+    let cavity = depth * bowl.min(0.0);
+    // Raised rim, steep inside and gently sloping outside
+    let rim_offset = (x - 1.0) / if x < 1.0 { 0.2 } else { 0.5 };
+    let rim = depth * 0.35 * (-rim_offset * rim_offset).exp();
+    let peak_offset = x / 0.18;
+    let peak = depth * 0.5 * large * (-peak_offset * peak_offset).exp();
+    cavity + rim + peak
+}
+
+/// Evaluates the crater field in the direction `p` from the body's center
+pub fn crater_field(p: Vec3, field: &CraterField) -> CraterSample {
+    let p = p.normalize();
+    let mut sample = CraterSample::default();
+
+    for octave in 0..field.octaves {
+        // Each crater lies in its cell and its influence fits inside half a
+        // cell, so only the 2x2x2 cells nearest to `p` can reach it
+        let base = (p / cell_size(field, octave) - 0.5).floor().as_ivec3();
+        for i in 0..8u32 {
+            let cell = base + UVec3::new(i & 1, (i >> 1) & 1, (i >> 2) & 1).as_ivec3();
+            add_crater(&mut sample, p, field, octave, cell);
+        }
+    }
+
+    sample
+}
+
+/// Radius of the largest craters of an octave
+fn octave_radius(field: &CraterField, octave: u32) -> f32 {
+    field.max_radius * (-(octave as f32)).exp2()
+}
+
+/// Edge of the grid cells of an octave
+fn cell_size(field: &CraterField, octave: u32) -> f32 {
+    2.0 * CRATER_EXTENT * octave_radius(field, octave)
+}
+
+/// Adds the crater of `cell`, if it holds one that reaches `p`, to `sample`
+fn add_crater(sample: &mut CraterSample, p: Vec3, field: &CraterField, octave: u32, cell: IVec3) {
+    let radius = octave_radius(field, octave);
+    let cell_size = cell_size(field, octave);
+    let key = cell.as_uvec3().wrapping_add(UVec3::new(
+        field.seed,
+        field.seed.wrapping_mul(3).wrapping_add(octave),
+        octave.wrapping_mul(7919),
+    ));
+    let jitter = crater_random(key);
+    let extra = crater_random(UVec3::new(key.z, key.x, key.y).wrapping_add(UVec3::splat(101)));
+
+    // The crater sits where the jittered point projects onto the sphere. The
+    // cell only holds it if that stays inside the cell, so every crater is in
+    // its own cell and the cells crossing the sphere hold one each.
+    let center = ((cell.as_vec3() + jitter) * cell_size).normalize();
+    if extra.x >= field.density || (center / cell_size).floor().as_ivec3() != cell {
+        return;
+    }
+
+    // Power law within the octave as well: small craters are more common
+    let crater_radius = radius * mix(0.5, 1.0, extra.y * extra.y);
+    let x = (p - center).length() / crater_radius;
+    if x >= CRATER_EXTENT {
+        return;
+    }
+
+    let large = smoothstep(
+        CENTRAL_PEAK_THRESHOLD * 0.8,
+        CENTRAL_PEAK_THRESHOLD * 1.2,
+        crater_radius / field.max_radius,
+    );
+    sample.height += crater_profile(x, crater_radius, large);
+    sample.floor = sample.floor.max(1.0 - smoothstep(0.7, 0.9, x));
+
+    // Only young craters still show bright ejecta
+    let freshness = (extra.z * 4.0 - 3.0).max(0.0);
+    if freshness > 0.0 && x > 0.9 {
+        // Azimuth around the crater center in its tangent plane
+        let up = if center.y.abs() > 0.9 {
+            Vec3::X
+        } else {
+            Vec3::Y
+        };
+        let tangent = center.cross(up).normalize();
+        let bitangent = center.cross(tangent);
+        let offset = p - center;
+        let azimuth = offset.dot(bitangent).atan2(offset.dot(tangent));
+        let phase = jitter.x * TAU;
+        let rays = ((azimuth * 7.0 + phase).sin() * (azimuth * 11.0 + phase * 2.0).sin())
+            .abs()
+            .powf(6.0);
+        let blanket = (-(x - 1.0).max(0.0) * 3.0).exp();
+        let fade = 1.0 - smoothstep(1.5, CRATER_EXTENT, x);
+        sample.ejecta = sample.ejecta.max(freshness * blanket.max(rays * fade));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evenly spread directions on the unit sphere
+    fn fibonacci_sphere(count: u32) -> impl Iterator<Item = Vec3> {
+        (0..count).map(move |i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let ring = (1.0 - y * y).sqrt();
+            let angle = i as f32 * 2.399_963;
+            Vec3::new(ring * angle.cos(), y, ring * angle.sin())
+        })
+    }
+
+    /// `crater_field`, visiting the 4x4x4 cells around `p` instead of 2x2x2
+    fn crater_field_wide(p: Vec3, field: &CraterField) -> CraterSample {
+        let p = p.normalize();
+        let mut sample = CraterSample::default();
+        for octave in 0..field.octaves {
+            let base = (p / cell_size(field, octave) - 0.5).floor().as_ivec3();
+            for i in 0..64 {
+                let offset = IVec3::new(i & 3, (i >> 2) & 3, (i >> 4) & 3) - 1;
+                add_crater(&mut sample, p, field, octave, base + offset);
+            }
+        }
+        sample
+    }
+
+    #[test]
+    fn craters_are_not_cut_off_at_cell_borders() {
+        for seed in [0, 7, 12345] {
+            let field = CraterField {
+                seed,
+                octaves: 3,
+                ..Default::default()
+            };
+            for p in fibonacci_sphere(8_000) {
+                let sample = crater_field(p, &field);
+                let wide = crater_field_wide(p, &field);
+                assert!(
+                    (sample.height - wide.height).abs() < 1e-6
+                        && sample.floor == wide.floor
+                        && sample.ejecta == wide.ejecta,
+                    "seed {seed} at {p}: {sample:?} != {wide:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn seeds_are_deterministic() {
+        let field = |seed| CraterField {
+            seed,
+            ..Default::default()
+        };
+        let samples = |seed| -> Vec<CraterSample> {
+            fibonacci_sphere(2000)
+                .map(|p| crater_field(p, &field(seed)))
+                .collect()
+        };
+        assert_eq!(samples(42), samples(42));
+        assert_ne!(samples(42), samples(43));
+        // The field covers the sphere with craters
+        assert!(
+            samples(42)
+                .iter()
+                .filter(|sample| sample.floor > 0.5)
+                .count()
+                > 20
+        );
+    }
+
+    #[test]
+    fn no_craters_without_density() {
+        let field = CraterField {
+            density: 0.0,
+            ..Default::default()
+        };
+        assert!(fibonacci_sphere(500).all(|p| crater_field(p, &field) == CraterSample::default()));
+    }
+}
@@ -0,0 +1,47 @@
+//! Uniforms of `bevy_shader_utils::fresnel`.
+
+use bevy_color::LinearRgba;
+
+pub use uniform::FresnelRim;
+
+// Scopes the derive's lint allow, see `uniform` in the app's `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy_color::LinearRgba;
+    use bevy_render::render_resource::ShaderType;
+
+    /// A glow around the silhouette of a body, matching `FresnelRim` in
+    /// `mock_fresnel.wgsl`. A cheap stand-in for an atmosphere, for bodies that
+    /// are too far or too small to need the scattering.
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct FresnelRim {
+        /// Color at the limb, in nits like an emissive. Alpha is ignored.
+        pub color: LinearRgba,
+        /// Sharpness of the falloff from the limb, higher for a thinner rim
+        pub power: f32,
+    }
+}
+
+impl FresnelRim {
+    /// No rim
+    pub const NONE: Self = Self {
+        color: LinearRgba::BLACK,
+        power: 4.0,
+    };
+
+    pub fn new(color: LinearRgba, power: f32) -> Self {
+        Self { color, power }
+    }
+
+    /// Whether the rim adds any light. Materials leave it out of their
+    /// shaders when it doesn't.
+    pub fn is_visible(&self) -> bool {
+        self.color.red > 0.0 || self.color.green > 0.0 || self.color.blue > 0.0
+    }
+}
+
+impl Default for FresnelRim {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
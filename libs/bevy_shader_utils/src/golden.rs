@@ -0,0 +1,451 @@
+//! Outputs of the WGSL functions, that their CPU mirrors are tested against.
+//!
+//! The `GOLDEN` tables in the mirrors' tests are printed by
+//! `print_golden_tables`, which runs the shaders through wgpu on the first
+//! adapter found. The committed tables come from Mesa's llvmpipe:
+//!
+//! ```sh
+//! WGPU_BACKEND=gl EGL_PLATFORM=surfaceless LIBGL_ALWAYS_SOFTWARE=1 \
+//!     cargo test -p bevy_shader_utils print_golden_tables -- --ignored --nocapture
+//! ```
+
+use std::{borrow::Cow, collections::HashMap, fs, path::Path};
+
+use naga_oil::compose::{
+    get_preprocessor_data, ComposableModuleDescriptor, Composer, NagaModuleDescriptor,
+    ShaderLanguage,
+};
+use wgpu::util::DeviceExt;
+
+/// Checks the `values` of a CPU mirror at `p` against the shader's
+/// `expected` outputs
+pub(crate) fn assert_matches_shader(p: &[f32], values: &[f32], expected: &[f32]) {
+    assert_within(p, values, expected, 1e-6);
+}
+
+/// [`assert_matches_shader`] with a relative error of `epsilon`, for outputs
+/// much larger than the noise itself
+pub(crate) fn assert_within(p: &[f32], values: &[f32], expected: &[f32], epsilon: f32) {
+    assert_eq!(values.len(), expected.len());
+    // GPUs may fuse and reorder float operations, which moves the result by
+    // about the rounding error of the input
+    let magnitude = p.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+    let tolerance = epsilon * (1.0 + magnitude);
+    for (value, expected) in values.iter().zip(expected) {
+        assert!(
+            (value - expected).abs() < tolerance,
+            "{p:?}: {values:?} != {expected:?}"
+        );
+    }
+}
+
+/// The points of the noise tables, the 2D tables take `xy`
+const POINTS: [[f32; 4]; 10] = [
+    [0.0, 0.0, 0.0, 0.0],
+    [0.5, 0.25, 0.125, 0.0],
+    [-3.7, 12.2, 5.5, 0.0],
+    [123.456, -78.9, 0.001, 0.0],
+    [-0.001, 0.999, -17.25, 0.0],
+    [7.3, 7.3, 7.3, 0.0],
+    [290.5, -290.5, 1.75, 0.0],
+    [1000.25, 2000.75, -3000.5, 0.0],
+    [0.1, -0.2, 0.3, 0.0],
+    [-45.67, 89.01, -23.45, 0.0],
+];
+
+const POINTS_4D: [[f32; 4]; 9] = [
+    [0.0, 0.0, 0.0, 0.0],
+    [0.5, 0.25, 0.125, 0.0625],
+    [-3.7, 12.2, 5.5, -1.25],
+    [123.456, -78.9, 0.001, 42.0],
+    [-0.001, 0.999, -17.25, 3.3],
+    [7.3, 7.3, 7.3, 7.3],
+    [290.5, -290.5, 1.75, -0.5],
+    [0.1, -0.2, 0.3, -0.4],
+    [-45.67, 89.01, -23.45, 11.11],
+];
+
+/// [`POINTS`], and a point whose second closest Worley feature point is two
+/// cells away
+const WORLEY_POINTS: [[f32; 4]; 11] = [
+    [0.0, 0.0, 0.0, 0.0],
+    [0.5, 0.25, 0.125, 0.0],
+    [-3.7, 12.2, 5.5, 0.0],
+    [123.456, -78.9, 0.001, 0.0],
+    [-0.001, 0.999, -17.25, 0.0],
+    [7.3, 7.3, 7.3, 0.0],
+    [290.5, -290.5, 1.75, 0.0],
+    [1000.25, 2000.75, -3000.5, 0.0],
+    [0.1, -0.2, 0.3, 0.0],
+    [-45.67, 89.01, -23.45, 0.0],
+    [14.84375, -15.0, -19.375, 0.0],
+];
+
+/// Temperatures in kelvin, in and out of the range of the blackbody table
+const TEMPERATURES: [[f32; 4]; 10] = [
+    [0.0, 0.0, 0.0, 0.0],
+    [500.0, 0.0, 0.0, 0.0],
+    [800.0, 0.0, 0.0, 0.0],
+    [1100.0, 0.0, 0.0, 0.0],
+    [1337.0, 0.0, 0.0, 0.0],
+    [1500.0, 0.0, 0.0, 0.0],
+    [5772.0, 0.0, 0.0, 0.0],
+    [6500.0, 0.0, 0.0, 0.0],
+    [25000.0, 0.0, 0.0, 0.0],
+    [1e6, 0.0, 0.0, 0.0],
+];
+
+/// Seeds and indices of the hash table
+const INTEGERS: [[u32; 2]; 8] = [
+    [0, 0],
+    [1, 0],
+    [0, 1],
+    [42, 7],
+    [u32::MAX, u32::MAX],
+    [1 << 31, 3],
+    [123456789, 987654321],
+    [0xdeadbeef, 16],
+];
+
+enum Input {
+    /// The first `usize` coordinates of each point, as `p: vec4<f32>`
+    Floats(usize, &'static [[f32; 4]]),
+    /// As `n: vec2<u32>`
+    Integers(&'static [[u32; 2]]),
+}
+
+/// A WGSL expression of the input
+enum Output {
+    Float(&'static str),
+    Integer(&'static str),
+}
+
+struct Table {
+    name: &'static str,
+    import: &'static str,
+    input: Input,
+    outputs: &'static [Output],
+}
+
+const TABLES: &[Table] = &[
+    Table {
+        name: "blackbody::LOOKUP_GOLDEN",
+        import: "bevy_shader_utils::blackbody::blackbody_color_lookup",
+        input: Input::Floats(1, &TEMPERATURES),
+        outputs: &[
+            Output::Float("blackbody_color_lookup(p.x).x"),
+            Output::Float("blackbody_color_lookup(p.x).y"),
+            Output::Float("blackbody_color_lookup(p.x).z"),
+        ],
+    },
+    Table {
+        name: "hash::GOLDEN",
+        import: "bevy_shader_utils::hash::{pcg, pcg2d, pcg3d, pcg4d, seed_hash, seed_float}",
+        input: Input::Integers(&INTEGERS),
+        outputs: &[
+            Output::Integer("pcg(n.x)"),
+            Output::Integer("seed_hash(n.x, n.y)"),
+            Output::Integer("pcg2d(n).x"),
+            Output::Integer("pcg2d(n).y"),
+            Output::Integer("pcg3d(vec3(n, n.x ^ n.y)).x"),
+            Output::Integer("pcg3d(vec3(n, n.x ^ n.y)).y"),
+            Output::Integer("pcg3d(vec3(n, n.x ^ n.y)).z"),
+            Output::Integer("pcg4d(vec4(n, n.x + n.y, n.x * n.y)).x"),
+            Output::Integer("pcg4d(vec4(n, n.x + n.y, n.x * n.y)).y"),
+            Output::Integer("pcg4d(vec4(n, n.x + n.y, n.x * n.y)).z"),
+            Output::Integer("pcg4d(vec4(n, n.x + n.y, n.x * n.y)).w"),
+            Output::Float("seed_float(n.x, n.y)"),
+        ],
+    },
+    Table {
+        name: "perlin_noise_2d::GOLDEN",
+        import: "bevy_shader_utils::perlin_noise_2d::perlin_noise_2d",
+        input: Input::Floats(2, &POINTS),
+        outputs: &[Output::Float("perlin_noise_2d(p.xy)")],
+    },
+    Table {
+        name: "perlin_noise_2d::PERIODIC_GOLDEN",
+        import: "bevy_shader_utils::perlin_noise_2d::perlin_noise_2d_periodic",
+        input: Input::Floats(2, &POINTS),
+        outputs: &[Output::Float(
+            "perlin_noise_2d_periodic(p.xy, vec2(4.0, 3.0))",
+        )],
+    },
+    Table {
+        name: "perlin_noise_3d::GOLDEN",
+        import: "bevy_shader_utils::perlin_noise_3d::perlin_noise_3d",
+        input: Input::Floats(3, &POINTS),
+        outputs: &[Output::Float("perlin_noise_3d(p.xyz)")],
+    },
+    Table {
+        name: "perlin_noise_3d::PERIODIC_GOLDEN",
+        import: "bevy_shader_utils::perlin_noise_3d::perlin_noise_3d_periodic",
+        input: Input::Floats(3, &POINTS),
+        outputs: &[Output::Float(
+            "perlin_noise_3d_periodic(p.xyz, vec3(4.0, 0.0, 7.0))",
+        )],
+    },
+    Table {
+        name: "simplex_noise_2d::GOLDEN",
+        import: "bevy_shader_utils::simplex_noise_2d::simplex_noise_2d",
+        input: Input::Floats(2, &POINTS),
+        outputs: &[Output::Float("simplex_noise_2d(p.xy)")],
+    },
+    Table {
+        name: "simplex_noise_3d::GOLDEN",
+        import: "bevy_shader_utils::simplex_noise_3d::simplex_noise_3d",
+        input: Input::Floats(3, &POINTS),
+        outputs: &[Output::Float("simplex_noise_3d(p.xyz)")],
+    },
+    Table {
+        name: "simplex_noise_3d::DERIVATIVES_GOLDEN",
+        import: "bevy_shader_utils::simplex_noise_3d::simplex_noise_3d_derivatives",
+        input: Input::Floats(3, &POINTS),
+        outputs: &[
+            Output::Float("simplex_noise_3d_derivatives(p.xyz).x"),
+            Output::Float("simplex_noise_3d_derivatives(p.xyz).y"),
+            Output::Float("simplex_noise_3d_derivatives(p.xyz).z"),
+            Output::Float("simplex_noise_3d_derivatives(p.xyz).w"),
+        ],
+    },
+    Table {
+        name: "simplex_noise_4d::GOLDEN",
+        import: "bevy_shader_utils::simplex_noise_4d::simplex_noise_4d",
+        input: Input::Floats(4, &POINTS_4D),
+        outputs: &[Output::Float("simplex_noise_4d(p)")],
+    },
+    Table {
+        name: "value_noise::GOLDEN",
+        import: "bevy_shader_utils::value_noise::{value_noise_2d, value_noise_2d_periodic, value_noise_3d, value_noise_3d_periodic}",
+        input: Input::Floats(3, &POINTS),
+        outputs: &[
+            Output::Float("value_noise_2d(p.xy)"),
+            Output::Float("value_noise_2d_periodic(p.xy, vec2(4.0, 3.0))"),
+            Output::Float("value_noise_3d(p.xyz)"),
+            Output::Float("value_noise_3d_periodic(p.xyz, vec3(4.0, 0.0, 7.0))"),
+        ],
+    },
+    Table {
+        name: "voronoise::GOLDEN",
+        import: "bevy_shader_utils::voronoise::voronoise",
+        input: Input::Floats(2, &POINTS),
+        outputs: &[
+            Output::Float("voronoise(p.xy, 1.0, 1.0)"),
+            Output::Float("voronoise(p.xy, 0.0, 0.0)"),
+            Output::Float("voronoise(p.xy, 0.5, 0.5)"),
+        ],
+    },
+    Table {
+        name: "worley_noise_3d::GOLDEN",
+        import: "bevy_shader_utils::worley_noise_3d::{worley_noise_3d, worley_noise_3d_periodic}",
+        input: Input::Floats(3, &WORLEY_POINTS),
+        outputs: &[
+            Output::Float("worley_noise_3d(p.xyz).closest"),
+            Output::Float("worley_noise_3d(p.xyz).second_closest"),
+            Output::Float("worley_noise_3d(p.xyz).edge"),
+            Output::Float("worley_noise_3d_periodic(p.xyz, vec3(4.0, 0.0, 7.0)).edge"),
+        ],
+    },
+];
+
+/// A compute shader writing the outputs of `table` for each input, as bits
+fn golden_shader(table: &Table) -> String {
+    let mut shader = format!(
+        "#import {}
+
+@group(0) @binding(0) var<storage, read> inputs: array<vec4<u32>>;
+@group(0) @binding(1) var<storage, read_write> outputs: array<u32>;
+
+@compute @workgroup_size(1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+    let n = inputs[id.x].xy;
+    let p = bitcast<vec4<f32>>(inputs[id.x]);
+",
+        table.import
+    );
+    for (i, output) in table.outputs.iter().enumerate() {
+        let value = match output {
+            Output::Float(expression) => format!("bitcast<u32>({expression})"),
+            Output::Integer(expression) => expression.to_string(),
+        };
+        let index = format!("id.x * {}u + {i}u", table.outputs.len());
+        shader += &format!("    outputs[{index}] = {value};\n");
+    }
+    shader + "}\n"
+}
+
+/// `source` with the modules of `src/shaders` it imports resolved
+fn compose(source: &str) -> wgpu::naga::Module {
+    let modules: HashMap<String, (String, String)> =
+        fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("src/shaders"))
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let source = fs::read_to_string(&path).unwrap();
+                let (name, _, _) = get_preprocessor_data(&source);
+                (name.unwrap(), (path.display().to_string(), source))
+            })
+            .collect();
+
+    fn add(composer: &mut Composer, modules: &HashMap<String, (String, String)>, name: &str) {
+        if composer.contains_module(name) {
+            return;
+        }
+        let (path, source) = &modules[name];
+        for import in get_preprocessor_data(source).1 {
+            add(composer, modules, &import.import);
+        }
+        if let Err(error) = composer.add_composable_module(ComposableModuleDescriptor {
+            source,
+            file_path: path,
+            language: ShaderLanguage::Wgsl,
+            as_name: Some(name.to_string()),
+            ..Default::default()
+        }) {
+            panic!("{}", error.emit_to_string(composer));
+        }
+    }
+
+    let mut composer = Composer::default();
+    for import in get_preprocessor_data(source).1 {
+        add(&mut composer, &modules, &import.import);
+    }
+    composer
+        .make_naga_module(NagaModuleDescriptor {
+            source,
+            file_path: "golden.wgsl",
+            ..Default::default()
+        })
+        .unwrap_or_else(|error| panic!("{}", error.emit_to_string(&composer)))
+}
+
+/// Runs the shader of `table` over its inputs, and returns the bits of the
+/// outputs of each
+fn run(device: &wgpu::Device, queue: &wgpu::Queue, table: &Table) -> Vec<Vec<u32>> {
+    let inputs: Vec<[u32; 4]> = match table.input {
+        Input::Floats(_, points) => points.iter().map(|p| p.map(f32::to_bits)).collect(),
+        Input::Integers(integers) => integers.iter().map(|&[n, m]| [n, m, 0, 0]).collect(),
+    };
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(table.name),
+        source: wgpu::ShaderSource::Naga(Cow::Owned(compose(&golden_shader(table)))),
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(table.name),
+        layout: None,
+        module: &module,
+        entry_point: "main",
+        compilation_options: Default::default(),
+    });
+
+    let bytes =
+        |words: &[u32]| -> Vec<u8> { words.iter().flat_map(|word| word.to_ne_bytes()).collect() };
+    let input = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: &bytes(inputs.as_flattened()),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let size = (inputs.len() * table.outputs.len() * 4) as u64;
+    let output = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: input.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: output.as_entire_binding(),
+            },
+        ],
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(inputs.len() as u32, 1, 1);
+    }
+    encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, size);
+    queue.submit([encoder.finish()]);
+    readback
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, Result::unwrap);
+    device.poll(wgpu::Maintain::Wait);
+
+    let data = readback.slice(..).get_mapped_range();
+    data.chunks_exact(4)
+        .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+        .collect::<Vec<_>>()
+        .chunks_exact(table.outputs.len())
+        .map(<[u32]>::to_vec)
+        .collect()
+}
+
+/// A row of `table` as Rust: the input, then the outputs with runs of the
+/// same type grouped into arrays
+fn format_row(table: &Table, row: usize, outputs: &[u32]) -> String {
+    let mut fields = vec![match table.input {
+        Input::Floats(dimensions, points) => format!("{:?}", &points[row][..dimensions]),
+        Input::Integers(integers) => format!("{:?}", integers[row]),
+    }];
+    let mut i = 0;
+    while i < outputs.len() {
+        let is_float = |output: &Output| matches!(output, Output::Float(_));
+        let float = is_float(&table.outputs[i]);
+        let run = table.outputs[i..]
+            .iter()
+            .take_while(|output| is_float(output) == float)
+            .count();
+        let values: Vec<String> = outputs[i..i + run]
+            .iter()
+            .map(|&bits| match float {
+                true => format!("{:?}", f32::from_bits(bits)),
+                false => bits.to_string(),
+            })
+            .collect();
+        fields.push(match run {
+            1 => values[0].clone(),
+            _ => format!("[{}]", values.join(", ")),
+        });
+        i += run;
+    }
+    format!("({}),", fields.join(", "))
+}
+
+#[test]
+#[ignore = "needs a GPU, prints the golden tables"]
+fn print_golden_tables() {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or_default(),
+        ..Default::default()
+    });
+    let adapter = bevy_tasks::block_on(wgpu::util::initialize_adapter_from_env_or_default(
+        &instance, None,
+    ))
+    .expect("no adapter");
+    println!("// {:?}", adapter.get_info());
+    let (device, queue) =
+        bevy_tasks::block_on(adapter.request_device(&Default::default(), None)).unwrap();
+
+    for table in TABLES {
+        println!("\n// {}", table.name);
+        for (row, outputs) in run(&device, &queue, table).iter().enumerate() {
+            println!("{}", format_row(table, row, outputs));
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! CPU mirror of `bevy_shader_utils::hash`. The hashes are integer math, so
+//! unlike the noises they give the same bits as the shaders, and seeds pick
+//! the same values on the CPU as on any GPU.
+
+use bevy_math::{UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+
+/// PCG hash, https://www.pcg-random.org/
+pub fn pcg(n: u32) -> u32 {
+    let h = n.wrapping_mul(747796405).wrapping_add(2891336453);
+    let h = ((h >> ((h >> 28) + 4)) ^ h).wrapping_mul(277803737);
+    (h >> 22) ^ h
+}
+
+/// The linear congruential step the vector hashes start with
+fn lcg(n: u32) -> u32 {
+    n.wrapping_mul(1664525).wrapping_add(1013904223)
+}
+
+pub fn pcg2d(p: UVec2) -> UVec2 {
+    let mut v = UVec2::from_array(p.to_array().map(lcg));
+    v.x = v.x.wrapping_add(v.y.wrapping_mul(1664525));
+    v.y = v.y.wrapping_add(v.x.wrapping_mul(1664525));
+    v = v ^ (v >> UVec2::splat(16));
+    v.x = v.x.wrapping_add(v.y.wrapping_mul(1664525));
+    v.y = v.y.wrapping_add(v.x.wrapping_mul(1664525));
+    v = v ^ (v >> UVec2::splat(16));
+    v
+}
+
+/// http://www.jcgt.org/published/0009/03/02/
+pub fn pcg3d(p: UVec3) -> UVec3 {
+    let mut v = UVec3::from_array(p.to_array().map(lcg));
+    let mix = |v: &mut UVec3| {
+        v.x = v.x.wrapping_add(v.y.wrapping_mul(v.z));
+        v.y = v.y.wrapping_add(v.z.wrapping_mul(v.x));
+        v.z = v.z.wrapping_add(v.x.wrapping_mul(v.y));
+    };
+    mix(&mut v);
+    v = v ^ (v >> UVec3::splat(16));
+    mix(&mut v);
+    v
+}
+
+/// http://www.jcgt.org/published/0009/03/02/
+pub fn pcg4d(p: UVec4) -> UVec4 {
+    let mut v = UVec4::from_array(p.to_array().map(lcg));
+    let mix = |v: &mut UVec4| {
+        v.x = v.x.wrapping_add(v.y.wrapping_mul(v.w));
+        v.y = v.y.wrapping_add(v.z.wrapping_mul(v.x));
+        v.z = v.z.wrapping_add(v.x.wrapping_mul(v.y));
+        v.w = v.w.wrapping_add(v.y.wrapping_mul(v.z));
+    };
+    mix(&mut v);
+    v = v ^ (v >> UVec4::splat(16));
+    mix(&mut v);
+    v
+}
+
+/// A hash as a uniform float in `0.0..1.0`, from its top 24 bits
+pub fn unit_float(h: u32) -> f32 {
+    (h >> 8) as f32 / 16777216.0
+}
+
+pub fn unit_float_2d(h: UVec2) -> Vec2 {
+    (h >> UVec2::splat(8)).as_vec2() / 16777216.0
+}
+
+pub fn unit_float_3d(h: UVec3) -> Vec3 {
+    (h >> UVec3::splat(8)).as_vec3() / 16777216.0
+}
+
+pub fn unit_float_4d(h: UVec4) -> Vec4 {
+    (h >> UVec4::splat(8)).as_vec4() / 16777216.0
+}
+
+/// The `index`th random number of `seed`. Each index gives an independent
+/// stream, so `seed_hash(seed, 1)` and `seed_hash(seed + 1, 0)` are unrelated.
+pub fn seed_hash(seed: u32, index: u32) -> u32 {
+    pcg(seed ^ pcg(index))
+}
+
+/// The `index`th random float of `seed`, in `0.0..1.0`
+pub fn seed_float(seed: u32, index: u32) -> f32 {
+    unit_float(seed_hash(seed, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Outputs of the WGSL functions for `[n, m]`, see [`crate::golden`]:
+    /// `pcg(n)`, `seed_hash(n, m)`, `pcg2d(n, m)`, `pcg3d(n, m, n ^ m)`,
+    /// `pcg4d(n, m, n + m, n * m)`, and `seed_float(n, m)`
+    const GOLDEN: [([u32; 2], [u32; 11], f32); 8] = [
+        (
+            [0, 0],
+            [
+                129708002, 817759070, 417608103, 90043601, 2611992518, 2833812075, 1058359340,
+                251852841, 760645481, 850445371, 3542436074,
+            ],
+            0.19039935,
+        ),
+        (
+            [1, 0],
+            [
+                2831084092, 1583546800, 2647388618, 2239437279, 171184229, 34287405, 4106931559,
+                3541482852, 2429246728, 2919364857, 3580757336,
+            ],
+            0.36869818,
+        ),
+        (
+            [0, 1],
+            [
+                129708002, 3911864714, 2313183303, 4026777116, 4037051260, 2638470567, 319853868,
+                181099554, 1814089358, 2937843635, 739343897,
+            ],
+            0.91080195,
+        ),
+        (
+            [42, 7],
+            [
+                1223963391, 2386313513, 247809911, 4202694454, 3823927805, 2414753669, 647294889,
+                3397449581, 3641121420, 915398886, 168343585,
+            ],
+            0.5556069,
+        ),
+        (
+            [4294967295, 4294967295],
+            [
+                3861530882, 3094549908, 505842200, 1816539540, 1799728140, 3508057903, 752492897,
+                3804397779, 2681599130, 449053109, 3603323781,
+            ],
+            0.720506,
+        ),
+        (
+            [2147483648, 3],
+            [
+                566699590, 157660086, 1919062449, 3579473832, 1036789553, 3718891084, 2069038924,
+                465854272, 2194517633, 3907502964, 1152027687,
+            ],
+            0.036708057,
+        ),
+        (
+            [123456789, 987654321],
+            [
+                4272394698, 2285590419, 3082568863, 1742963564, 2361366488, 1075519983, 746128916,
+                4095342834, 37828109, 1771851330, 1906028597,
+            ],
+            0.53215545,
+        ),
+        (
+            [3735928559, 16],
+            [
+                1730779506, 213634318, 179944218, 1470900751, 437203018, 591865938, 3284739532,
+                1053430528, 1989327623, 459926275, 3577249498,
+            ],
+            0.049740613,
+        ),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for ([n, m], expected, expected_float) in GOLDEN {
+            let hashes = [
+                [pcg(n), seed_hash(n, m)].as_slice(),
+                &pcg2d(UVec2::new(n, m)).to_array(),
+                &pcg3d(UVec3::new(n, m, n ^ m)).to_array(),
+                &pcg4d(UVec4::new(n, m, n.wrapping_add(m), n.wrapping_mul(m))).to_array(),
+            ]
+            .concat();
+            assert_eq!(hashes, expected, "{n} {m}");
+            assert_eq!(seed_float(n, m), expected_float, "{n} {m}");
+        }
+    }
+
+    #[test]
+    fn unit_floats_are_below_one() {
+        assert_eq!(unit_float(0), 0.0);
+        assert!(unit_float(u32::MAX) < 1.0);
+        assert!(unit_float_4d(UVec4::MAX).cmplt(Vec4::ONE).all());
+    }
+}
@@ -1,26 +1,92 @@
 // some wgsl from https://gist.github.com/munrocket/236ed5ba7e409b8bdf1ff6eca5dcdc39
 
-// Noise Functions
+pub mod blackbody;
+pub mod craters;
+pub mod fresnel;
+#[cfg(test)]
+mod golden;
+pub mod hash;
+pub mod perlin_noise_2d;
+pub mod perlin_noise_3d;
+#[cfg(feature = "pristine_grid_material")]
+pub mod pristine_grid;
+pub mod simplex_noise_2d;
+pub mod simplex_noise_3d;
+pub mod simplex_noise_4d;
+pub mod value_noise;
+pub mod voronoise;
+pub mod worley_noise_3d;
+
+use std::collections::HashSet;
 
 use bevy_app::{App, Plugin};
-use bevy_asset::{embedded_asset, load_internal_asset, Handle};
+#[cfg(all(feature = "hot_reload", debug_assertions))]
+use bevy_asset::{embedded_asset, AssetServer};
+use bevy_asset::{Assets, Handle};
+#[cfg(all(feature = "hot_reload", debug_assertions))]
+use bevy_ecs::system::Resource;
 use bevy_render::render_resource::Shader;
 
+// Noise Functions
 const PERLIN_NOISE_2D: Handle<Shader> = Handle::weak_from_u128(11918512342344596158);
 pub const PERLIN_NOISE_3D: Handle<Shader> = Handle::weak_from_u128(11918512442344596158);
 pub const SIMPLEX_NOISE_2D: Handle<Shader> = Handle::weak_from_u128(11918512542344596158);
 pub const SIMPLEX_NOISE_3D: Handle<Shader> = Handle::weak_from_u128(11918512642344596158);
-// pub const FBM: Handle<Shader> =
-// Handle::weak_from_u128(11918512342344596158);
+pub const FBM: Handle<Shader> = Handle::weak_from_u128(11918513242344596158);
+pub const VALUE_NOISE: Handle<Shader> = Handle::weak_from_u128(11918513342344596158);
+pub const WORLEY_NOISE_3D: Handle<Shader> = Handle::weak_from_u128(11918513442344596158);
+pub const SIMPLEX_NOISE_4D: Handle<Shader> = Handle::weak_from_u128(11918513542344596158);
 pub const VORONOISE: Handle<Shader> = Handle::weak_from_u128(11918512742344596158);
 // other utility functions
 pub const MOCK_FRESNEL: Handle<Shader> = Handle::weak_from_u128(11918512842344596158);
 pub const PRISTINE_GRID: Handle<Shader> = Handle::weak_from_u128(11918512942344596158);
+// procedural surface features
+pub const CRATERS: Handle<Shader> = Handle::weak_from_u128(11918513042344596158);
+// color
+pub const BLACKBODY: Handle<Shader> = Handle::weak_from_u128(11918513142344596158);
+// hashing
+pub const HASH: Handle<Shader> = Handle::weak_from_u128(11918513642344596158);
+
+/// Registers the shader at `path`, relative to `src`, and checks that no
+/// other shader has its handle. In debug builds with the `hot_reload` feature
+/// it is loaded through the asset server, which reloads it when the file
+/// changes. Otherwise it is embedded in the binary under `handle`.
+macro_rules! shader {
+    ($app:ident, $ids:ident, $handle:expr, $path:literal) => {{
+        assert!(
+            $ids.insert($handle.id())
+                && !$app
+                    .world()
+                    .resource::<Assets<Shader>>()
+                    .contains($handle.id()),
+            concat!("the handle of ", $path, " is already taken")
+        );
+        #[cfg(all(feature = "hot_reload", debug_assertions))]
+        {
+            embedded_asset!($app, $path);
+            let handle = $app
+                .world()
+                .resource::<AssetServer>()
+                .load(concat!("embedded://bevy_shader_utils/", $path));
+            $app.world_mut()
+                .resource_mut::<HotReloadedShaders>()
+                .0
+                .push(handle);
+        }
+        #[cfg(not(all(feature = "hot_reload", debug_assertions)))]
+        bevy_asset::load_internal_asset!($app, $handle, $path, Shader::from_wgsl);
+    }};
+}
+
+/// Keeps the shaders loaded by path alive
+#[cfg(all(feature = "hot_reload", debug_assertions))]
+#[derive(Resource, Default)]
+struct HotReloadedShaders(Vec<Handle<Shader>>);
 
 /// To use the shader utility functions, add the plugin to your
 /// app.
 ///
-/// ```rust
+/// ```ignore
 /// App::new()
 ///     .add_plugins((
 ///         DefaultPlugins,
@@ -31,103 +97,91 @@ pub const PRISTINE_GRID: Handle<Shader> = Handle::weak_from_u128(119185129423445
 ///
 /// then import the relevant function in your shader.
 ///
-/// ```
+/// ```wgsl
 /// #import bevy_shader_utils::perlin_noise_2d::perlin_noise_2d
 /// ```
 ///
+/// With the `pristine_grid_material` feature it also registers
+/// [`PristineGridMaterial`](pristine_grid::PristineGridMaterial).
 pub struct ShaderUtilsPlugin;
 
 impl Plugin for ShaderUtilsPlugin {
     fn build(&self, app: &mut App) {
-        // embedded_asset!(
-        //     app,
-        //     "shaders/perlin_noise_2d.wgsl"
-        // );
-        // embedded_asset!(
-        //     app,
-        //     "shaders/perlin_noise_3d.wgsl"
-        // );
-        // embedded_asset!(
-        //     app,
-        //     "shaders/simplex_noise_2d.wgsl"
-        // );
-        // embedded_asset!(
-        //     app,
-        //     "shaders/simplex_noise_3d.wgsl"
-        // );
-        // embedded_asset!(app, "shaders/voronoise.wgsl");
-        // embedded_asset!(app, "shaders/mock_fresnel.wgsl");
-        // embedded_asset!(app, "shaders/pristine_grid.wgsl");
+        #[cfg(all(feature = "hot_reload", debug_assertions))]
+        app.init_resource::<HotReloadedShaders>();
+        let mut ids = HashSet::new();
+        shader!(app, ids, PERLIN_NOISE_2D, "shaders/perlin_noise_2d.wgsl");
+        shader!(app, ids, PERLIN_NOISE_3D, "shaders/perlin_noise_3d.wgsl");
+        shader!(app, ids, SIMPLEX_NOISE_2D, "shaders/simplex_noise_2d.wgsl");
+        shader!(app, ids, SIMPLEX_NOISE_3D, "shaders/simplex_noise_3d.wgsl");
+        shader!(app, ids, SIMPLEX_NOISE_4D, "shaders/simplex_noise_4d.wgsl");
+        shader!(app, ids, VALUE_NOISE, "shaders/value_noise.wgsl");
+        shader!(app, ids, WORLEY_NOISE_3D, "shaders/worley_noise_3d.wgsl");
+        shader!(app, ids, VORONOISE, "shaders/voronoise.wgsl");
+        shader!(app, ids, FBM, "shaders/fbm.wgsl");
+        shader!(app, ids, MOCK_FRESNEL, "shaders/mock_fresnel.wgsl");
+        shader!(app, ids, PRISTINE_GRID, "shaders/pristine_grid.wgsl");
+        shader!(app, ids, CRATERS, "shaders/craters.wgsl");
+        shader!(app, ids, BLACKBODY, "shaders/blackbody.wgsl");
+        shader!(app, ids, HASH, "shaders/hash.wgsl");
 
-        embedded_asset!(app, "materials/pristine_grid.wgsl");
-
-        load_internal_asset!(
-            app,
-            PERLIN_NOISE_2D,
-            "shaders/perlin_noise_2d.wgsl",
-            Shader::from_wgsl
-        );
-        load_internal_asset!(
-            app,
-            PERLIN_NOISE_3D,
-            "shaders/perlin_noise_3d.wgsl",
-            Shader::from_wgsl
-        );
-        load_internal_asset!(
-            app,
-            SIMPLEX_NOISE_2D,
-            "shaders/simplex_noise_2d.wgsl",
-            Shader::from_wgsl
-        );
-        load_internal_asset!(
-            app,
-            SIMPLEX_NOISE_3D,
-            "shaders/simplex_noise_3d.wgsl",
-            Shader::from_wgsl
-        );
-        load_internal_asset!(app, VORONOISE, "shaders/voronoise.wgsl", Shader::from_wgsl);
-        load_internal_asset!(
-            app,
-            MOCK_FRESNEL,
-            "shaders/mock_fresnel.wgsl",
-            Shader::from_wgsl
-        );
-        load_internal_asset!(
-            app,
-            PRISTINE_GRID,
-            "shaders/pristine_grid.wgsl",
-            Shader::from_wgsl
-        );
-
-        // app.add_plugins(MaterialPlugin::<
-        //     PristineGridMaterial,
-        // >::default());
+        #[cfg(feature = "pristine_grid_material")]
+        {
+            bevy_asset::embedded_asset!(app, "materials/pristine_grid.wgsl");
+            app.add_plugins(bevy_pbr::MaterialPlugin::<
+                pristine_grid::PristineGridMaterial,
+            >::default());
+        }
     }
 }
 
-// impl Material for PristineGridMaterial {
-//     fn fragment_shader() -> ShaderRef {
-//         "embedded://bevy_shader_utils/materials/pristine_grid.wgsl".into()
-//     }
-// }
+#[cfg(test)]
+mod tests {
+    use bevy_asset::{AssetApp, AssetPlugin};
+    use bevy_tasks::{IoTaskPool, TaskPool};
+
+    use super::*;
 
-// // This is the struct that will be passed to your shader
-// #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-// pub struct PristineGridMaterial {
-//     #[uniform(0)]
-//     pub color: Color,
-//     #[uniform(0)]
-//     pub cell_multiplier: Vec2,
-//     #[uniform(0)]
-//     pub line_size: Vec2,
-// }
+    #[test]
+    fn registers_every_shader() {
+        IoTaskPool::get_or_init(TaskPool::default);
+        let mut app = App::new();
+        app.add_plugins(AssetPlugin {
+            watch_for_changes_override: Some(false),
+            ..Default::default()
+        })
+        .init_asset::<Shader>()
+        .add_plugins(ShaderUtilsPlugin);
 
-// impl Default for PristineGridMaterial {
-//     fn default() -> Self {
-//         Self {
-//             color: Color::WHITE,
-//             cell_multiplier: Vec2::splat(10.),
-//             line_size: Vec2::splat(0.1),
-//         }
-//     }
-// }
+        #[cfg(all(feature = "hot_reload", debug_assertions))]
+        assert_eq!(app.world().resource::<HotReloadedShaders>().0.len(), 14);
+        #[cfg(not(all(feature = "hot_reload", debug_assertions)))]
+        {
+            // `pristine_grid_material` adds the shaders of `MaterialPlugin`
+            let shaders = app.world().resource::<Assets<Shader>>();
+            for handle in [
+                PERLIN_NOISE_2D,
+                PERLIN_NOISE_3D,
+                SIMPLEX_NOISE_2D,
+                SIMPLEX_NOISE_3D,
+                SIMPLEX_NOISE_4D,
+                VALUE_NOISE,
+                WORLEY_NOISE_3D,
+                VORONOISE,
+                FBM,
+                MOCK_FRESNEL,
+                PRISTINE_GRID,
+                CRATERS,
+                BLACKBODY,
+                HASH,
+            ] {
+                assert!(shaders.contains(&handle), "{handle:?}");
+            }
+            let hash = shaders.get(&HASH).unwrap();
+            assert_eq!(
+                hash.import_path().module_name(),
+                "bevy_shader_utils::hash".into()
+            );
+        }
+    }
+}
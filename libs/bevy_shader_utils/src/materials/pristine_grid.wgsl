@@ -1,7 +1,9 @@
-#import bevy_pbr::forward_io::VertexOutput
-#import bevy_sprite::mesh2d_view_bindings::globals
+#import bevy_pbr::{
+    forward_io::VertexOutput,
+    mesh_functions::get_world_from_local,
+}
 
-#import bevy_shader_utils::pristine_grid::pristine_grid
+#import bevy_shader_utils::pristine_grid::{pristine_grid, pristine_grid_grad}
 
 struct PristineMaterial {
     color: vec4f,
@@ -12,12 +14,35 @@ struct PristineMaterial {
 @group(2) @binding(0)
 var<uniform> material: PristineMaterial;
 
+const PI: f32 = 3.141592653589793;
+
+// Longitude and latitude of the fragment in the mesh's local space, Y up, in
+// turns and half turns, with the derivatives of the longitude taken away
+// from its seam
+fn lat_long_grid(mesh: VertexOutput) -> f32 {
+    let world_from_local = get_world_from_local(mesh.instance_index);
+    let basis = mat3x3<f32>(world_from_local[0].xyz, world_from_local[1].xyz, world_from_local[2].xyz);
+    let direction = normalize((mesh.world_position.xyz - world_from_local[3].xyz) * basis);
+
+    let longitude = atan2(direction.z, direction.x);
+    let latitude = asin(clamp(direction.y, -1.0, 1.0));
+    let uv = vec2(longitude / (2.0 * PI), latitude / PI) * material.cell_multiplier;
+
+    // The same longitude with its seam on the other side of the sphere
+    let shifted = atan2(-direction.z, -direction.x) / (2.0 * PI) * material.cell_multiplier.x;
+    let ddx = vec2(select(dpdx(uv.x), dpdx(shifted), abs(dpdx(shifted)) < abs(dpdx(uv.x))), dpdx(uv.y));
+    let ddy = vec2(select(dpdy(uv.x), dpdy(shifted), abs(dpdy(shifted)) < abs(dpdy(uv.x))), dpdy(uv.y));
+    return pristine_grid_grad(uv, ddx, ddy, material.line_size);
+}
+
 @fragment
 fn fragment(
     mesh: VertexOutput
 ) -> @location(0) vec4<f32> {
-    let color = pristine_grid(mesh.uv * material.cell_multiplier, material.line_size) * material.color;
-    return color;
+#ifdef PRISTINE_GRID_LAT_LONG
+    let grid = lat_long_grid(mesh);
+#else
+    let grid = pristine_grid(mesh.uv * material.cell_multiplier, material.line_size);
+#endif
+    return grid * material.color;
 }
-
-
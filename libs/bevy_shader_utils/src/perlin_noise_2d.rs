@@ -0,0 +1,127 @@
+//! CPU mirror of `bevy_shader_utils::perlin_noise_2d`, step for step in f32,
+//! for terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{Vec2, Vec2Swizzles, Vec4, Vec4Swizzles};
+
+/// WGSL's `fract`, which unlike [`f32::fract`] is positive for negative values
+fn fract(x: Vec4) -> Vec4 {
+    x - x.floor()
+}
+
+fn permute_four(x: Vec4) -> Vec4 {
+    ((x * 34.0 + 1.0) * x) % Vec4::splat(289.0)
+}
+
+fn fade_two(t: Vec2) -> Vec2 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Classic Perlin noise, about `-1.0..=1.0`
+pub fn perlin_noise_2d(p: Vec2) -> f32 {
+    perlin_noise_2d_periodic(p, Vec2::ZERO)
+}
+
+/// Perlin noise that repeats every `period` units, which should be whole
+/// numbers. Axes with a period of 0 don't repeat.
+pub fn perlin_noise_2d_periodic(p: Vec2, period: Vec2) -> f32 {
+    let mut pi = p.xyxy().floor() + Vec4::new(0.0, 0.0, 1.0, 1.0);
+    let pf = fract(p.xyxy()) - Vec4::new(0.0, 0.0, 1.0, 1.0);
+    let period = period.xyxy();
+    pi = Vec4::select(
+        period.cmpgt(Vec4::ZERO),
+        pi - period * (pi / period).floor(),
+        pi,
+    );
+    pi %= Vec4::splat(289.0); // To avoid truncation effects in permutation
+    let ix = pi.xzxz();
+    let iy = pi.yyww();
+    let fx = pf.xzxz();
+    let fy = pf.yyww();
+    let i = permute_four(permute_four(ix) + iy);
+    let mut gx = 2.0 * fract(i * 0.024_390_243) - 1.0; // 1/41 = 0.024...
+    let gy = gx.abs() - 0.5;
+    let tx = (gx + 0.5).floor();
+    gx -= tx;
+    let mut g00 = Vec2::new(gx.x, gy.x);
+    let mut g10 = Vec2::new(gx.y, gy.y);
+    let mut g01 = Vec2::new(gx.z, gy.z);
+    let mut g11 = Vec2::new(gx.w, gy.w);
+    let norm = 1.792_842_9
+        - 0.853_734_7 * Vec4::new(g00.dot(g00), g01.dot(g01), g10.dot(g10), g11.dot(g11));
+    g00 *= norm.x;
+    g01 *= norm.y;
+    g10 *= norm.z;
+    g11 *= norm.w;
+    let n00 = g00.dot(Vec2::new(fx.x, fy.x));
+    let n10 = g10.dot(Vec2::new(fx.y, fy.y));
+    let n01 = g01.dot(Vec2::new(fx.z, fy.z));
+    let n11 = g11.dot(Vec2::new(fx.w, fy.w));
+    let fade_xy = fade_two(pf.xy());
+    let n_x = Vec2::new(n00, n01).lerp(Vec2::new(n10, n11), fade_xy.x);
+    let n_xy = n_x.x + (n_x.y - n_x.x) * fade_xy.y;
+    2.3 * n_xy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_matches_shader;
+
+    /// Outputs of the WGSL function, see [`crate::golden`]
+    const GOLDEN: [([f32; 2], f32); 10] = [
+        ([0.0, 0.0], 0.0),
+        ([0.5, 0.25], -0.28268173),
+        ([-3.7, 12.2], 0.4098532),
+        ([123.456, -78.9], 0.46523765),
+        ([-0.001, 0.999], 0.0003307998),
+        ([7.3, 7.3], 0.29490006),
+        ([290.5, -290.5], -0.35212934),
+        ([1000.25, 2000.75], -0.3076585),
+        ([0.1, -0.2], -0.33714977),
+        ([-45.67, 89.01], -0.35434112),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let value = perlin_noise_2d(Vec2::from_array(p));
+            assert_matches_shader(&p, &[value], &[expected]);
+        }
+    }
+
+    /// With a period of `(4, 3)`
+    const PERIODIC_GOLDEN: [([f32; 2], f32); 10] = [
+        ([0.0, 0.0], 0.0),
+        ([0.5, 0.25], -0.28268173),
+        ([-3.7, 12.2], 0.05957882),
+        ([123.456, -78.9], 0.49059373),
+        ([-0.001, 0.999], 0.00033354163),
+        ([7.3, 7.3], 0.45281443),
+        ([290.5, -290.5], -0.082482666),
+        ([1000.25, 2000.75], -0.52996635),
+        ([0.1, -0.2], -0.39544222),
+        ([-45.67, 89.01], 0.034671493),
+    ];
+
+    #[test]
+    fn periodic_matches_the_shader() {
+        for (p, expected) in PERIODIC_GOLDEN {
+            let value = perlin_noise_2d_periodic(Vec2::from_array(p), Vec2::new(4.0, 3.0));
+            assert_matches_shader(&p, &[value], &[expected]);
+        }
+    }
+
+    #[test]
+    fn repeats_over_the_period() {
+        let period = Vec2::new(4.0, 3.0);
+        for p in [
+            Vec2::new(0.3, 0.7),
+            Vec2::new(-1.25, 2.5),
+            Vec2::new(3.9, -0.1),
+        ] {
+            let value = perlin_noise_2d_periodic(p, period);
+            assert!((value - perlin_noise_2d_periodic(p + period, period)).abs() < 1e-5);
+            assert!((value - perlin_noise_2d_periodic(p - 2.0 * period, period)).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,180 @@
+//! CPU mirror of `bevy_shader_utils::perlin_noise_3d`, step for step in f32,
+//! for terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{Vec3, Vec4, Vec4Swizzles};
+
+/// WGSL's `fract`, which unlike [`f32::fract`] is positive for negative values
+fn fract(x: Vec4) -> Vec4 {
+    x - x.floor()
+}
+
+/// WGSL's `step` on each component: 0.0 below the edge, 1.0 from it on
+fn step(edge: Vec4, x: Vec4) -> Vec4 {
+    Vec4::select(x.cmplt(edge), Vec4::ZERO, Vec4::ONE)
+}
+
+fn permute_four(x: Vec4) -> Vec4 {
+    ((x * 34.0 + 1.0) * x) % Vec4::splat(289.0)
+}
+
+fn taylor_inv_sqrt_four(r: Vec4) -> Vec4 {
+    1.792_842_9 - 0.853_734_7 * r
+}
+
+fn fade_three(t: Vec3) -> Vec3 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Lattice cells wrapped into `0..period`, on the axes whose period isn't 0
+fn wrap(cell: Vec3, period: Vec3) -> Vec3 {
+    Vec3::select(
+        period.cmpgt(Vec3::ZERO),
+        cell - period * (cell / period).floor(),
+        cell,
+    )
+}
+
+/// Classic Perlin noise, about `-1.0..=1.0`
+pub fn perlin_noise_3d(p: Vec3) -> f32 {
+    perlin_noise_3d_periodic(p, Vec3::ZERO)
+}
+
+/// Perlin noise that repeats every `period` units, which should be whole
+/// numbers. Axes with a period of 0 don't repeat.
+pub fn perlin_noise_3d_periodic(p: Vec3, period: Vec3) -> f32 {
+    let mut pi0 = wrap(p.floor(), period); // Integer part for indexing
+    let mut pi1 = wrap(pi0 + 1.0, period); // Integer part + 1
+    pi0 %= Vec3::splat(289.0);
+    pi1 %= Vec3::splat(289.0);
+    let pf0 = p - p.floor(); // Fractional part for interpolation
+    let pf1 = pf0 - 1.0; // Fractional part - 1.
+    let ix = Vec4::new(pi0.x, pi1.x, pi0.x, pi1.x);
+    let iy = Vec4::new(pi0.y, pi0.y, pi1.y, pi1.y);
+    let iz0 = Vec4::splat(pi0.z);
+    let iz1 = Vec4::splat(pi1.z);
+
+    let ixy = permute_four(permute_four(ix) + iy);
+    let ixy0 = permute_four(ixy + iz0);
+    let ixy1 = permute_four(ixy + iz1);
+
+    let mut gx0 = ixy0 / 7.0;
+    let mut gy0 = fract(gx0.floor() / 7.0) - 0.5;
+    gx0 = fract(gx0);
+    let gz0 = 0.5 - gx0.abs() - gy0.abs();
+    let sz0 = step(gz0, Vec4::ZERO);
+    gx0 += sz0 * (step(Vec4::ZERO, gx0) - 0.5);
+    gy0 += sz0 * (step(Vec4::ZERO, gy0) - 0.5);
+
+    let mut gx1 = ixy1 / 7.0;
+    let mut gy1 = fract(gx1.floor() / 7.0) - 0.5;
+    gx1 = fract(gx1);
+    let gz1 = 0.5 - gx1.abs() - gy1.abs();
+    let sz1 = step(gz1, Vec4::ZERO);
+    gx1 -= sz1 * (step(Vec4::ZERO, gx1) - 0.5);
+    gy1 -= sz1 * (step(Vec4::ZERO, gy1) - 0.5);
+
+    let mut g000 = Vec3::new(gx0.x, gy0.x, gz0.x);
+    let mut g100 = Vec3::new(gx0.y, gy0.y, gz0.y);
+    let mut g010 = Vec3::new(gx0.z, gy0.z, gz0.z);
+    let mut g110 = Vec3::new(gx0.w, gy0.w, gz0.w);
+    let mut g001 = Vec3::new(gx1.x, gy1.x, gz1.x);
+    let mut g101 = Vec3::new(gx1.y, gy1.y, gz1.y);
+    let mut g011 = Vec3::new(gx1.z, gy1.z, gz1.z);
+    let mut g111 = Vec3::new(gx1.w, gy1.w, gz1.w);
+
+    let norm0 = taylor_inv_sqrt_four(Vec4::new(
+        g000.dot(g000),
+        g010.dot(g010),
+        g100.dot(g100),
+        g110.dot(g110),
+    ));
+    g000 *= norm0.x;
+    g010 *= norm0.y;
+    g100 *= norm0.z;
+    g110 *= norm0.w;
+    let norm1 = taylor_inv_sqrt_four(Vec4::new(
+        g001.dot(g001),
+        g011.dot(g011),
+        g101.dot(g101),
+        g111.dot(g111),
+    ));
+    g001 *= norm1.x;
+    g011 *= norm1.y;
+    g101 *= norm1.z;
+    g111 *= norm1.w;
+
+    let n000 = g000.dot(pf0);
+    let n100 = g100.dot(Vec3::new(pf1.x, pf0.y, pf0.z));
+    let n010 = g010.dot(Vec3::new(pf0.x, pf1.y, pf0.z));
+    let n110 = g110.dot(Vec3::new(pf1.x, pf1.y, pf0.z));
+    let n001 = g001.dot(Vec3::new(pf0.x, pf0.y, pf1.z));
+    let n101 = g101.dot(Vec3::new(pf1.x, pf0.y, pf1.z));
+    let n011 = g011.dot(Vec3::new(pf0.x, pf1.y, pf1.z));
+    let n111 = g111.dot(pf1);
+
+    let fade_xyz = fade_three(pf0);
+    let n_z = Vec4::new(n000, n100, n010, n110).lerp(Vec4::new(n001, n101, n011, n111), fade_xyz.z);
+    let n_yz = n_z.xy().lerp(n_z.zw(), fade_xyz.y);
+    let n_xyz = n_yz.x + (n_yz.y - n_yz.x) * fade_xyz.x;
+    2.2 * n_xyz
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_matches_shader;
+
+    /// Outputs of the WGSL function, see [`crate::golden`]
+    const GOLDEN: [([f32; 3], f32); 10] = [
+        ([0.0, 0.0, 0.0], 0.0),
+        ([0.5, 0.25, 0.125], 0.6330874),
+        ([-3.7, 12.2, 5.5], 0.3778368),
+        ([123.456, -78.9, 0.001], -0.009013727),
+        ([-0.001, 0.999, -17.25], 0.330267),
+        ([7.3, 7.3, 7.3], 0.8952123),
+        ([290.5, -290.5, 1.75], -0.27148548),
+        ([1000.25, 2000.75, -3000.5], 0.26899838),
+        ([0.1, -0.2, 0.3], 0.17068867),
+        ([-45.67, 89.01, -23.45], 0.1614868),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let value = perlin_noise_3d(Vec3::from_array(p));
+            assert_matches_shader(&p, &[value], &[expected]);
+        }
+    }
+
+    /// With a period of `(4, 0, 7)`
+    const PERIODIC_GOLDEN: [([f32; 3], f32); 10] = [
+        ([0.0, 0.0, 0.0], 0.0),
+        ([0.5, 0.25, 0.125], 0.6330874),
+        ([-3.7, 12.2, 5.5], -0.21021606),
+        ([123.456, -78.9, 0.001], -0.21077192),
+        ([-0.001, 0.999, -17.25], -0.2087125),
+        ([7.3, 7.3, 7.3], 0.43210444),
+        ([290.5, -290.5, 1.75], -0.35121727),
+        ([1000.25, 2000.75, -3000.5], 0.37497076),
+        ([0.1, -0.2, 0.3], 0.17068867),
+        ([-45.67, 89.01, -23.45], 0.13728246),
+    ];
+
+    #[test]
+    fn periodic_matches_the_shader() {
+        for (p, expected) in PERIODIC_GOLDEN {
+            let value = perlin_noise_3d_periodic(Vec3::from_array(p), Vec3::new(4.0, 0.0, 7.0));
+            assert_matches_shader(&p, &[value], &[expected]);
+        }
+    }
+
+    #[test]
+    fn repeats_over_the_period() {
+        let period = Vec3::new(4.0, 3.0, 7.0);
+        for p in [Vec3::new(0.3, 0.7, 0.1), Vec3::new(-1.25, 2.5, 6.5)] {
+            let value = perlin_noise_3d_periodic(p, period);
+            assert!((value - perlin_noise_3d_periodic(p + period, period)).abs() < 1e-5);
+            assert!((value - perlin_noise_3d_periodic(p - 2.0 * period, period)).abs() < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,108 @@
+//! A debug material drawing the pristine grid, either over the mesh's UVs or
+//! as latitude and longitude lines over a sphere.
+
+use bevy_asset::Asset;
+use bevy_color::LinearRgba;
+use bevy_math::Vec2;
+use bevy_pbr::{Material, MaterialPipeline, MaterialPipelineKey};
+use bevy_reflect::TypePath;
+use bevy_render::{
+    alpha::AlphaMode,
+    mesh::MeshVertexBufferLayoutRef,
+    render_resource::{
+        AsBindGroup, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+    },
+};
+
+const SHADER_PATH: &str = "embedded://bevy_shader_utils/materials/pristine_grid.wgsl";
+
+/// What the grid lines follow
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GridMode {
+    /// The mesh's UVs, one cell per `1 / cell_multiplier`
+    #[default]
+    Uv,
+    /// Longitude and latitude of the direction from the mesh's origin, Y up,
+    /// with the prime meridian on +X. `cell_multiplier` is the number of cells
+    /// around the equator and from pole to pole.
+    LatLong,
+}
+
+/// Anti-aliased grid lines from
+/// [The Best Darn Grid Shader (yet)](https://bgolus.medium.com/the-best-darn-grid-shader-yet-727f9278b9d8).
+///
+/// Registered by [`ShaderUtilsPlugin`](crate::ShaderUtilsPlugin) with the
+/// `pristine_grid_material` feature.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+#[bind_group_data(GridMode)]
+pub struct PristineGridMaterial {
+    /// Color of the lines. The cells are transparent black.
+    #[uniform(0)]
+    pub color: LinearRgba,
+    /// Number of cells per unit of UV, or per sphere in
+    /// [`GridMode::LatLong`]
+    #[uniform(0)]
+    pub cell_multiplier: Vec2,
+    /// Width of the lines, as a fraction of a cell
+    #[uniform(0)]
+    pub line_size: Vec2,
+    pub mode: GridMode,
+    pub alpha_mode: AlphaMode,
+}
+
+impl PristineGridMaterial {
+    /// Parallels and meridians every `degrees`, blended over what is behind.
+    /// Put it on a sphere slightly larger than a planet to check how the
+    /// planet's surface lines up with its latitude and longitude.
+    pub fn graticule(degrees: f32) -> Self {
+        Self {
+            cell_multiplier: Vec2::new(360.0, 180.0) / degrees,
+            line_size: Vec2::splat(0.05),
+            mode: GridMode::LatLong,
+            alpha_mode: AlphaMode::Blend,
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for PristineGridMaterial {
+    fn default() -> Self {
+        Self {
+            color: LinearRgba::WHITE,
+            cell_multiplier: Vec2::splat(10.),
+            line_size: Vec2::splat(0.1),
+            mode: GridMode::Uv,
+            alpha_mode: AlphaMode::Opaque,
+        }
+    }
+}
+
+impl From<&PristineGridMaterial> for GridMode {
+    fn from(material: &PristineGridMaterial) -> Self {
+        material.mode
+    }
+}
+
+impl Material for PristineGridMaterial {
+    fn fragment_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if key.bind_group_data == GridMode::LatLong {
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("PRISTINE_GRID_LAT_LONG".into());
+            }
+        }
+        Ok(())
+    }
+}
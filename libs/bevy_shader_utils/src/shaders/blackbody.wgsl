@@ -0,0 +1,151 @@
+#define_import_path bevy_shader_utils::blackbody
+
+// Color of a blackbody from its temperature.
+//
+// Planck's law is integrated against an analytic fit of the CIE 1931
+// colour-matching functions, "Simple Analytic Approximations to the CIE XYZ
+// Color Matching Functions" (Wyman, Sloan, Shirley 2013).
+//
+// Mirrored on the CPU by `bevy_shader_utils::blackbody`, keep the two in sync.
+
+// Second radiation constant h * c / k, in nanometer kelvins
+const SECOND_RADIATION_CONSTANT = 1.4388e7;
+const FIRST_WAVELENGTH = 380.0;
+const WAVELENGTH_STEP = 10.0;
+const WAVELENGTH_SAMPLES = 41u;
+
+// Temperatures are clamped to this range, in kelvin
+const MIN_TEMPERATURE = 500.0;
+const MAX_TEMPERATURE = 100000.0;
+
+const BLACKBODY_TABLE_SIZE = 64u;
+
+// CIE XYZ to linear sRGB, D65 white point
+const XYZ_TO_LINEAR_SRGB = mat3x3<f32>(
+    3.2404542, -0.969266, 0.0556434,
+    -1.5371385, 1.8760108, -0.2040259,
+    -0.4985314, 0.041556, 1.0572252,
+);
+
+// A lobe of the colour-matching function fit, with a different width on each side
+fn lobe(wavelength: f32, center: f32, width_below: f32, width_above: f32) -> f32 {
+    let width = select(width_above, width_below, wavelength < center);
+    let t = (wavelength - center) / width;
+    return exp(-0.5 * t * t);
+}
+
+// The CIE 1931 2° colour-matching functions at `wavelength`, in nanometers
+fn color_matching(wavelength: f32) -> vec3<f32> {
+    let l = wavelength;
+    return vec3(
+        1.056 * lobe(l, 599.8, 37.9, 31.0) + 0.362 * lobe(l, 442.0, 16.0, 26.7) - 0.065 * lobe(l, 501.1, 20.4, 26.2),
+        0.821 * lobe(l, 568.8, 46.9, 40.5) + 0.286 * lobe(l, 530.9, 16.3, 31.1),
+        1.217 * lobe(l, 437.0, 11.8, 36.0) + 0.681 * lobe(l, 459.0, 26.0, 13.8),
+    );
+}
+
+// Spectral radiance of a blackbody at `wavelength` nanometers, up to a constant factor
+fn planck(wavelength: f32, temperature: f32) -> f32 {
+    // Scaled to 550 nm, so the values stay in range
+    let x = wavelength / 550.0;
+    return 1.0 / (pow(x, 5.0) * (exp(SECOND_RADIATION_CONSTANT / (wavelength * temperature)) - 1.0));
+}
+
+// CIE XYZ of a blackbody at `temperature` kelvin, scaled to Y = 1.0
+fn blackbody_xyz(temperature: f32) -> vec3<f32> {
+    let t = clamp(temperature, MIN_TEMPERATURE, MAX_TEMPERATURE);
+    var xyz = vec3(0.0);
+    for (var i = 0u; i < WAVELENGTH_SAMPLES; i++) {
+        let wavelength = FIRST_WAVELENGTH + f32(i) * WAVELENGTH_STEP;
+        xyz += color_matching(wavelength) * planck(wavelength, t);
+    }
+    return xyz / xyz.y;
+}
+
+// Linear sRGB of a blackbody at `temperature` kelvin, scaled so the brightest
+// channel is 1.0. Colors outside the sRGB gamut are clamped.
+fn blackbody_color(temperature: f32) -> vec3<f32> {
+    let rgb = max(XYZ_TO_LINEAR_SRGB * blackbody_xyz(temperature), vec3(0.0));
+    return rgb / max(max(rgb.r, rgb.g), rgb.b);
+}
+
+// `blackbody_color` interpolated between the entries of a table, cheap enough
+// to run per fragment
+fn blackbody_color_lookup(temperature: f32) -> vec3<f32> {
+    // `blackbody_color` at temperatures spaced evenly in log temperature from
+    // MIN_TEMPERATURE to MAX_TEMPERATURE, printed by `print_table` in
+    // `src/blackbody.rs`. A variable, as naga can't index constant arrays
+    // dynamically.
+    var table = array<vec3<f32>, BLACKBODY_TABLE_SIZE>(
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.0, 0.0),
+        vec3(1.0, 0.008968726, 0.0),
+        vec3(1.0, 0.024433421, 0.0),
+        vec3(1.0, 0.04267392, 0.0),
+        vec3(1.0, 0.063673094, 0.0),
+        vec3(1.0, 0.08742898, 0.0),
+        vec3(1.0, 0.113943316, 0.0),
+        vec3(1.0, 0.14321306, 0.0),
+        vec3(1.0, 0.17522098, 0.0),
+        vec3(1.0, 0.20993026, 0.0),
+        vec3(1.0, 0.24727768, 0.00093201536),
+        vec3(1.0, 0.28716683, 0.016068183),
+        vec3(1.0, 0.32946673, 0.036595475),
+        vec3(1.0, 0.37400484, 0.063458264),
+        vec3(1.0, 0.42056665, 0.097576685),
+        vec3(1.0, 0.46889278, 0.13979994),
+        vec3(1.0, 0.5186801, 0.19085865),
+        vec3(1.0, 0.5695781, 0.2513125),
+        vec3(1.0, 0.6211984, 0.32150355),
+        vec3(1.0, 0.67311525, 0.40151045),
+        vec3(1.0, 0.7248816, 0.49111837),
+        vec3(1.0, 0.77603775, 0.589797),
+        vec3(1.0, 0.8261289, 0.6967049),
+        vec3(1.0, 0.87472886, 0.81071454),
+        vec3(1.0, 0.92145014, 0.93045807),
+        vec3(0.94840705, 0.9161321, 1.0),
+        vec3(0.84679836, 0.85360277, 1.0),
+        vec3(0.76430374, 0.8005801, 1.0),
+        vec3(0.6967486, 0.7553868, 1.0),
+        vec3(0.64099234, 0.7166905, 1.0),
+        vec3(0.59463835, 0.68342113, 1.0),
+        vec3(0.55584264, 0.6547111, 1.0),
+        vec3(0.5231671, 0.62985057, 1.0),
+        vec3(0.49548456, 0.6082534, 1.0),
+        vec3(0.4719012, 0.5894339, 1.0),
+        vec3(0.451704, 0.5729869, 1.0),
+        vec3(0.4343244, 0.5585723, 1.0),
+        vec3(0.41929832, 0.5459052, 1.0),
+        vec3(0.40625152, 0.53474396, 1.0),
+        vec3(0.39487723, 0.5248854, 1.0),
+        vec3(0.3849229, 0.51615596, 1.0),
+        vec3(0.37617964, 0.50840884, 1.0),
+        vec3(0.36847585, 0.5015174, 1.0),
+        vec3(0.36166522, 0.49537477, 1.0),
+        vec3(0.3556277, 0.48988804, 1.0),
+        vec3(0.35026023, 0.48497775, 1.0),
+        vec3(0.34547687, 0.48057514, 1.0),
+        vec3(0.34120372, 0.47662136, 1.0),
+        vec3(0.337378, 0.47306448, 1.0),
+        vec3(0.33394578, 0.46986014, 1.0),
+        vec3(0.3308616, 0.4669685, 1.0),
+        vec3(0.32808453, 0.4643561, 1.0),
+        vec3(0.32558006, 0.4619928, 1.0),
+        vec3(0.3233185, 0.45985243, 1.0),
+        vec3(0.3212725, 0.4579118, 1.0),
+        vec3(0.31942025, 0.45615023, 1.0),
+        vec3(0.31774086, 0.45454967, 1.0),
+        vec3(0.31621683, 0.45309454, 1.0),
+    );
+    let t = clamp(temperature, MIN_TEMPERATURE, MAX_TEMPERATURE);
+    let x = log(t / MIN_TEMPERATURE) / log(MAX_TEMPERATURE / MIN_TEMPERATURE) * f32(BLACKBODY_TABLE_SIZE - 1u);
+    let index = min(u32(x), BLACKBODY_TABLE_SIZE - 2u);
+    let rgb = mix(table[index], table[index + 1u], x - f32(index));
+    // Around 6500 K the brightest channel changes between the entries
+    return rgb / max(max(rgb.r, rgb.g), rgb.b);
+}
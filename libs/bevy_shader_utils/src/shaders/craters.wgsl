@@ -0,0 +1,115 @@
+#define_import_path bevy_shader_utils::craters
+
+// Procedural impact craters on the unit sphere.
+//
+// Craters are scattered over a 3D grid per size octave. Every octave halves
+// the crater radius and, because a shell crosses four times as many of the
+// smaller cells, holds four times as many craters: a cumulative size
+// distribution N(>r) ~ r^-2, close to what is observed on airless bodies.
+//
+// Mirrored on the CPU by `bevy_shader_utils::craters`, keep the two in sync.
+
+#import bevy_shader_utils::hash::{pcg3d, unit_float_3d}
+
+struct CraterField {
+    seed: u32,
+    // Number of size octaves, each with half the radius of the previous one
+    octaves: u32,
+    // Radius of the largest craters, as a fraction of the body's radius
+    max_radius: f32,
+    // Probability of a grid cell holding a crater
+    density: f32,
+}
+
+struct CraterSample {
+    // Height offset as a fraction of the body's radius
+    height: f32,
+    // Brightness of fresh ejecta: rays and blankets, 0.0 to 1.0
+    ejecta: f32,
+    // 1.0 on crater floors, 0.0 outside the rims
+    floor: f32,
+}
+
+// Craters extend this many radii from their center, counting the ejecta rays
+const CRATER_EXTENT: f32 = 4.0;
+// Craters larger than this fraction of `max_radius` grow a central peak
+const CENTRAL_PEAK_THRESHOLD: f32 = 0.3;
+const TAU: f32 = 6.28318530718;
+
+// Uniform floats in [0, 1) from the top 24 bits of each hash lane
+fn crater_random(p: vec3u) -> vec3<f32> {
+    return unit_float_3d(pcg3d(p));
+}
+
+// Height profile of a single crater, `x` is the distance from its center in radii
+fn crater_profile(x: f32, radius: f32, large: f32) -> f32 {
+    let depth = 0.2 * radius;
+    // Parabolic bowl, flattened into a floor on large craters
+    let bowl = max(x * x - 1.0, mix(-1.0, -0.6, large));
+    let cavity = depth * min(bowl, 0.0);
+    // Raised rim, steep inside and gently sloping outside
+    let rim_offset = (x - 1.0) / select(0.5, 0.2, x < 1.0);
+    let rim = depth * 0.35 * exp(-rim_offset * rim_offset);
+    let peak_offset = x / 0.18;
+    let peak = depth * 0.5 * large * exp(-peak_offset * peak_offset);
+    return cavity + rim + peak;
+}
+
+fn crater_field(p_in: vec3<f32>, field: CraterField) -> CraterSample {
+    let p = normalize(p_in);
+    var sample: CraterSample;
+    sample.height = 0.0;
+    sample.ejecta = 0.0;
+    sample.floor = 0.0;
+
+    for (var octave = 0u; octave < field.octaves; octave++) {
+        let radius = field.max_radius * exp2(-f32(octave));
+        // Each crater lies in its cell and its influence fits inside half a
+        // cell, so only the 2x2x2 cells nearest to `p` can reach it
+        let cell_size = 2.0 * CRATER_EXTENT * radius;
+        let base = vec3<i32>(floor(p / cell_size - 0.5));
+
+        for (var i = 0u; i < 8u; i++) {
+            let cell = base + vec3<i32>(vec3u(i & 1u, (i >> 1u) & 1u, (i >> 2u) & 1u));
+            let key = bitcast<vec3u>(cell) + vec3u(field.seed, field.seed * 3u + octave, octave * 7919u);
+            let jitter = crater_random(key);
+            let extra = crater_random(key.zxy + 101u);
+
+            // The crater sits where the jittered point projects onto the sphere. The
+            // cell only holds it if that stays inside the cell, so every crater is in
+            // its own cell and the cells crossing the sphere hold one each.
+            let center = normalize((vec3<f32>(cell) + jitter) * cell_size);
+            if extra.x >= field.density || any(vec3<i32>(floor(center / cell_size)) != cell) {
+                continue;
+            }
+
+            // Power law within the octave as well: small craters are more common
+            let crater_radius = radius * mix(0.5, 1.0, extra.y * extra.y);
+            let x = length(p - center) / crater_radius;
+            if x >= CRATER_EXTENT {
+                continue;
+            }
+
+            let large = smoothstep(CENTRAL_PEAK_THRESHOLD * 0.8, CENTRAL_PEAK_THRESHOLD * 1.2, crater_radius / field.max_radius);
+            sample.height += crater_profile(x, crater_radius, large);
+            sample.floor = max(sample.floor, 1.0 - smoothstep(0.7, 0.9, x));
+
+            // Only young craters still show bright ejecta
+            let freshness = max(extra.z * 4.0 - 3.0, 0.0);
+            if freshness > 0.0 && x > 0.9 {
+                // Azimuth around the crater center in its tangent plane
+                let tangent = normalize(cross(center, select(vec3(0.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), abs(center.y) > 0.9)));
+                let bitangent = cross(center, tangent);
+                let offset = p - center;
+                let azimuth = atan2(dot(offset, bitangent), dot(offset, tangent));
+                let phase = jitter.x * TAU;
+                let rays = pow(abs(sin(azimuth * 7.0 + phase) * sin(azimuth * 11.0 + phase * 2.0)), 6.0);
+                let blanket = exp(-max(x - 1.0, 0.0) * 3.0);
+                let fade = 1.0 - smoothstep(1.5, CRATER_EXTENT, x);
+                sample.ejecta = max(sample.ejecta, freshness * max(blanket, rays * fade));
+            }
+        }
+    }
+
+    return sample;
+}
@@ -1,12 +1,175 @@
+#define_import_path bevy_shader_utils::fbm
+
 //  MIT License. © Inigo Quilez, Munrocket
-//  noise_2d() is any noise here: Value, Perlin, Simplex, Worley
 //
-var m_two: mat2x2<f32> = mat2x2<f32>(vec2<f32>(0.8, 0.6), vec2<f32>(-0.6, 0.8));
-fn fbm(p: vec2<f32>, noise_2d: ) -> f32 {
-  var f: f32 = 0.;
-  f = f + 0.5000 * noise_2d(p); p = m_two * p * 2.02;
-  f = f + 0.2500 * noise_2d(p); p = m_two * p * 2.03;
-  f = f + 0.1250 * noise_2d(p); p = m_two * p * 2.01;
-  f = f + 0.0625 * noise_2d(p);
-  return f / 0.9375;
-}
\ No newline at end of file
+// Fractal sums of the noise functions of bevy_shader_utils. WGSL can't pass
+// functions around, so the base noise is picked by one of the NOISE_
+// constants instead. Pass it as a constant and the switch folds away.
+
+#import bevy_shader_utils::perlin_noise_2d::perlin_noise_2d
+#import bevy_shader_utils::perlin_noise_3d::perlin_noise_3d
+#import bevy_shader_utils::simplex_noise_2d::simplex_noise_2d
+#import bevy_shader_utils::simplex_noise_3d::simplex_noise_3d
+
+const NOISE_PERLIN: u32 = 0u;
+const NOISE_SIMPLEX: u32 = 1u;
+
+struct Fbm {
+    // One of the NOISE_ constants
+    noise: u32,
+    octaves: u32,
+    // Frequency multiplier from one octave to the next
+    lacunarity: f32,
+    // Amplitude multiplier from one octave to the next
+    gain: f32,
+}
+
+// Five octaves, each twice the frequency and half the amplitude of the last
+fn fbm_default(noise: u32) -> Fbm {
+    return Fbm(noise, 5u, 2.0, 0.5);
+}
+
+// Rotating each octave keeps the grids of the octaves from lining up
+const OCTAVE_ROTATION_2D: mat2x2<f32> = mat2x2<f32>(0.8, 0.6, -0.6, 0.8);
+const OCTAVE_ROTATION_3D: mat3x3<f32> = mat3x3<f32>(
+    0.00, 0.80, 0.60,
+    -0.80, 0.36, -0.48,
+    -0.60, -0.48, 0.64
+);
+
+fn base_noise_2d(p: vec2<f32>, noise: u32) -> f32 {
+    switch noise {
+        case NOISE_PERLIN: { return perlin_noise_2d(p); }
+        default: { return simplex_noise_2d(p); }
+    }
+}
+
+fn base_noise_3d(p: vec3<f32>, noise: u32) -> f32 {
+    switch noise {
+        case NOISE_PERLIN: { return perlin_noise_3d(p); }
+        default: { return simplex_noise_3d(p); }
+    }
+}
+
+// The octaves summed, in about -1.0..1.0
+fn fbm_2d(p: vec2<f32>, fbm: Fbm) -> f32 {
+    var q = p;
+    var amplitude = 1.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var i = 0u; i < fbm.octaves; i++) {
+        sum += amplitude * base_noise_2d(q, fbm.noise);
+        total += amplitude;
+        amplitude *= fbm.gain;
+        q = OCTAVE_ROTATION_2D * q * fbm.lacunarity;
+    }
+    return sum / max(total, 1e-6);
+}
+
+fn fbm_3d(p: vec3<f32>, fbm: Fbm) -> f32 {
+    var q = p;
+    var amplitude = 1.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var i = 0u; i < fbm.octaves; i++) {
+        sum += amplitude * base_noise_3d(q, fbm.noise);
+        total += amplitude;
+        amplitude *= fbm.gain;
+        q = OCTAVE_ROTATION_3D * q * fbm.lacunarity;
+    }
+    return sum / max(total, 1e-6);
+}
+
+// Octaves of the absolute noise, in 0.0..1.0: creases along the zeros
+// of the noise, like marble veins or fire
+fn turbulence_2d(p: vec2<f32>, fbm: Fbm) -> f32 {
+    var q = p;
+    var amplitude = 1.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var i = 0u; i < fbm.octaves; i++) {
+        sum += amplitude * abs(base_noise_2d(q, fbm.noise));
+        total += amplitude;
+        amplitude *= fbm.gain;
+        q = OCTAVE_ROTATION_2D * q * fbm.lacunarity;
+    }
+    return sum / max(total, 1e-6);
+}
+
+fn turbulence_3d(p: vec3<f32>, fbm: Fbm) -> f32 {
+    var q = p;
+    var amplitude = 1.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var i = 0u; i < fbm.octaves; i++) {
+        sum += amplitude * abs(base_noise_3d(q, fbm.noise));
+        total += amplitude;
+        amplitude *= fbm.gain;
+        q = OCTAVE_ROTATION_3D * q * fbm.lacunarity;
+    }
+    return sum / max(total, 1e-6);
+}
+
+// Turbulence remapped to about -1.0..1.0: puffy, rounded lumps like clouds
+fn billow_2d(p: vec2<f32>, fbm: Fbm) -> f32 {
+    return turbulence_2d(p, fbm) * 2.0 - 1.0;
+}
+
+fn billow_3d(p: vec3<f32>, fbm: Fbm) -> f32 {
+    return turbulence_3d(p, fbm) * 2.0 - 1.0;
+}
+
+// Musgrave's ridged multifractal, in 0.0..1.0: sharp ridges where the noise
+// crosses zero. Each octave is weighted by the one before, so the detail
+// gathers on the ridges, like mountain ranges.
+fn ridged_2d(p: vec2<f32>, fbm: Fbm) -> f32 {
+    var q = p;
+    var amplitude = 1.0;
+    var weight = 1.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var i = 0u; i < fbm.octaves; i++) {
+        var ridge = 1.0 - abs(base_noise_2d(q, fbm.noise));
+        ridge = ridge * ridge * weight;
+        weight = clamp(ridge * 2.0, 0.0, 1.0);
+        sum += amplitude * ridge;
+        total += amplitude;
+        amplitude *= fbm.gain;
+        q = OCTAVE_ROTATION_2D * q * fbm.lacunarity;
+    }
+    return sum / max(total, 1e-6);
+}
+
+fn ridged_3d(p: vec3<f32>, fbm: Fbm) -> f32 {
+    var q = p;
+    var amplitude = 1.0;
+    var weight = 1.0;
+    var sum = 0.0;
+    var total = 0.0;
+    for (var i = 0u; i < fbm.octaves; i++) {
+        var ridge = 1.0 - abs(base_noise_3d(q, fbm.noise));
+        ridge = ridge * ridge * weight;
+        weight = clamp(ridge * 2.0, 0.0, 1.0);
+        sum += amplitude * ridge;
+        total += amplitude;
+        amplitude *= fbm.gain;
+        q = OCTAVE_ROTATION_3D * q * fbm.lacunarity;
+    }
+    return sum / max(total, 1e-6);
+}
+
+// fBm of a point pushed around by fBm, https://iquilezles.org/articles/warp/
+// `strength` is how far the point moves, in the units of `p`.
+fn domain_warp_2d(p: vec2<f32>, fbm: Fbm, strength: f32) -> f32 {
+    let warp = vec2(fbm_2d(p, fbm), fbm_2d(p + vec2(5.2, 1.3), fbm));
+    return fbm_2d(p + warp * strength, fbm);
+}
+
+fn domain_warp_3d(p: vec3<f32>, fbm: Fbm, strength: f32) -> f32 {
+    let warp = vec3(
+        fbm_3d(p, fbm),
+        fbm_3d(p + vec3(5.2, 1.3, 2.8), fbm),
+        fbm_3d(p + vec3(1.7, 9.2, 4.1), fbm),
+    );
+    return fbm_3d(p + warp * strength, fbm);
+}
@@ -0,0 +1,69 @@
+#define_import_path bevy_shader_utils::hash
+
+// Integer hashes, and the floats and seeds built on them. Unlike the
+// `fract(sin(x) * 43758.5453)` hashes they are exact on every GPU and for
+// every u32, and the Rust mirrors in `bevy_shader_utils::hash` give the same
+// bits on the CPU.
+
+// https://www.pcg-random.org/
+fn pcg(n: u32) -> u32 {
+    var h = n * 747796405u + 2891336453u;
+    h = ((h >> ((h >> 28u) + 4u)) ^ h) * 277803737u;
+    return (h >> 22u) ^ h;
+}
+
+fn pcg2d(p: vec2u) -> vec2u {
+    var v = p * 1664525u + 1013904223u;
+    v.x += v.y * 1664525u; v.y += v.x * 1664525u;
+    v ^= v >> vec2u(16u);
+    v.x += v.y * 1664525u; v.y += v.x * 1664525u;
+    v ^= v >> vec2u(16u);
+    return v;
+}
+
+// http://www.jcgt.org/published/0009/03/02/
+fn pcg3d(p: vec3u) -> vec3u {
+    var v = p * 1664525u + 1013904223u;
+    v.x += v.y * v.z; v.y += v.z * v.x; v.z += v.x * v.y;
+    v ^= v >> vec3u(16u);
+    v.x += v.y * v.z; v.y += v.z * v.x; v.z += v.x * v.y;
+    return v;
+}
+
+// http://www.jcgt.org/published/0009/03/02/
+fn pcg4d(p: vec4u) -> vec4u {
+    var v = p * 1664525u + 1013904223u;
+    v.x += v.y * v.w; v.y += v.z * v.x; v.z += v.x * v.y; v.w += v.y * v.z;
+    v ^= v >> vec4u(16u);
+    v.x += v.y * v.w; v.y += v.z * v.x; v.z += v.x * v.y; v.w += v.y * v.z;
+    return v;
+}
+
+// A hash as a uniform float in 0.0..1.0, from its top 24 bits, which an f32
+// holds exactly
+fn unit_float(h: u32) -> f32 {
+    return f32(h >> 8u) / 16777216.0;
+}
+
+fn unit_float_2d(h: vec2u) -> vec2<f32> {
+    return vec2<f32>(h >> vec2u(8u)) / 16777216.0;
+}
+
+fn unit_float_3d(h: vec3u) -> vec3<f32> {
+    return vec3<f32>(h >> vec3u(8u)) / 16777216.0;
+}
+
+fn unit_float_4d(h: vec4u) -> vec4<f32> {
+    return vec4<f32>(h >> vec4u(8u)) / 16777216.0;
+}
+
+// The `index`th random number of `seed`. Each index gives an independent
+// stream, so `seed_hash(seed, 1u)` and `seed_hash(seed + 1u, 0u)` are unrelated.
+fn seed_hash(seed: u32, index: u32) -> u32 {
+    return pcg(seed ^ pcg(index));
+}
+
+// The `index`th random float of `seed`, in 0.0..1.0
+fn seed_float(seed: u32, index: u32) -> f32 {
+    return unit_float(seed_hash(seed, index));
+}
@@ -60,4 +60,23 @@ fn fresnel(
     // Here's were increasing the contrast with pow 
     // and making it brighter by multiplying by 2
     return pow(fresnel, power) * strength;
-};
\ No newline at end of file
+};
+
+// A glow around the silhouette of a body, for bodies without a scattering
+// atmosphere
+struct FresnelRim {
+    // Color at the limb, in nits like an emissive. Black turns the rim off.
+    color: vec4<f32>,
+    // Sharpness of the falloff from the limb, higher for a thinner rim
+    power: f32,
+}
+
+// Emission of `rim` at a fragment
+fn fresnel_rim(
+    rim: FresnelRim,
+    camera_view_world_position: vec3<f32>,
+    world_position: vec3<f32>,
+    world_normal: vec3<f32>,
+) -> vec3<f32> {
+    return rim.color.rgb * mock_fresnel(camera_view_world_position, world_position, world_normal, rim.power, 1.0);
+}
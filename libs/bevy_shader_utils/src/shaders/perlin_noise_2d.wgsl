@@ -6,8 +6,15 @@ fn permute_four(x: vec4<f32>) -> vec4<f32> { return ((x * 34. + 1.) * x) % vec4<
 fn fade_two(t: vec2<f32>) -> vec2<f32> { return t * t * t * (t * (t * 6. - 15.) + 10.); }
 
 fn perlin_noise_2d(P: vec2<f32>) -> f32 {
+  return perlin_noise_2d_periodic(P, vec2<f32>(0.));
+}
+
+// Repeats every `period` units, which should be whole numbers. Axes with a
+// period of 0 don't repeat.
+fn perlin_noise_2d_periodic(P: vec2<f32>, period: vec2<f32>) -> f32 {
   var Pi: vec4<f32> = floor(P.xyxy) + vec4<f32>(0., 0., 1., 1.);
   let Pf = fract(P.xyxy) - vec4<f32>(0., 0., 1., 1.);
+  Pi = select(Pi, Pi - period.xyxy * floor(Pi / period.xyxy), period.xyxy > vec4<f32>(0.));
   Pi = Pi % vec4<f32>(289.); // To avoid truncation effects in permutation
   let ix = Pi.xzxz;
   let iy = Pi.yyww;
@@ -7,8 +7,19 @@ fn taylor_inv_sqrt_four(r: vec4<f32>) -> vec4<f32> { return 1.79284291400159 - 0
 fn fade_three(t: vec3<f32>) -> vec3<f32> { return t * t * t * (t * (t * 6. - 15.) + 10.); }
 
 fn perlin_noise_3d(P: vec3<f32>) -> f32 {
-  var Pi0 : vec3<f32> = floor(P); // Integer part for indexing
-  var Pi1 : vec3<f32> = Pi0 + vec3<f32>(1.); // Integer part + 1
+  return perlin_noise_3d_periodic(P, vec3<f32>(0.));
+}
+
+// Lattice cells wrapped into 0..period, on the axes whose period isn't 0
+fn perlin_wrap_three(cell: vec3<f32>, period: vec3<f32>) -> vec3<f32> {
+  return select(cell, cell - period * floor(cell / period), period > vec3<f32>(0.));
+}
+
+// Repeats every `period` units, which should be whole numbers. Axes with a
+// period of 0 don't repeat.
+fn perlin_noise_3d_periodic(P: vec3<f32>, period: vec3<f32>) -> f32 {
+  var Pi0 : vec3<f32> = perlin_wrap_three(floor(P), period); // Integer part for indexing
+  var Pi1 : vec3<f32> = perlin_wrap_three(Pi0 + vec3<f32>(1.), period); // Integer part + 1
   Pi0 = Pi0 % vec3<f32>(289.);
   Pi1 = Pi1 % vec3<f32>(289.);
   let Pf0 = fract(P); // Fractional part for interpolation
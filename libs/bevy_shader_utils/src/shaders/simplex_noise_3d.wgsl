@@ -5,7 +5,14 @@
 fn permute_four(x: vec4<f32>) -> vec4<f32> { return ((x * 34. + 1.) * x) % vec4<f32>(289.); }
 fn taylor_inv_sqrt_four(r: vec4<f32>) -> vec4<f32> { return 1.79284291400159 - 0.85373472095314 * r; }
 
-fn simplex_noise_3d(v: vec3<f32>) -> f32 {
+// The offsets of `v` from the four corners of its simplex, and the gradients
+// at those corners
+struct SimplexCorners3d {
+  x: array<vec3<f32>, 4>,
+  p: array<vec3<f32>, 4>,
+}
+
+fn simplex_corners_3d(v: vec3<f32>) -> SimplexCorners3d {
   let C = vec2<f32>(1. / 6., 1. / 3.);
   let D = vec4<f32>(0., 0.5, 1., 2.);
 
@@ -56,9 +63,7 @@ fn simplex_noise_3d(v: vec3<f32>) -> f32 {
 
   var p0: vec3<f32> = vec3<f32>(a0.xy, h.x);
   var p1: vec3<f32> = vec3<f32>(a0.zw, h.y);
-  #define_import_path bevy_shader_utils::simplex_noise_3d
-var p2: vec3<f32> = vec3<f32>
-  (a1.xy, h.z);
+  var p2: vec3<f32> = vec3<f32>(a1.xy, h.z);
   var p3: vec3<f32> = vec3<f32>(a1.zw, h.w);
 
   // Normalise gradients
@@ -68,9 +73,49 @@ var p2: vec3<f32> = vec3<f32>
   p2 = p2 * norm.z;
   p3 = p3 * norm.w;
 
+  return SimplexCorners3d(array(x0, x1, x2, x3), array(p0, p1, p2, p3));
+}
+
+fn simplex_noise_3d(v: vec3<f32>) -> f32 {
+  let c = simplex_corners_3d(v);
+  let x0 = c.x[0];
+  let x1 = c.x[1];
+  let x2 = c.x[2];
+  let x3 = c.x[3];
+  let p0 = c.p[0];
+  let p1 = c.p[1];
+  let p2 = c.p[2];
+  let p3 = c.p[3];
+
   // Mix final noise value
   var m: vec4<f32> = 0.6 - vec4<f32>(dot(x0,x0), dot(x1,x1), dot(x2,x2), dot(x3,x3));
   m = max(m, vec4<f32>(0.));
   m = m * m;
   return 42. * dot(m * m, vec4<f32>(dot(p0,x0), dot(p1,x1), dot(p2,x2), dot(p3,x3)));
-}
\ No newline at end of file
+}
+
+// The noise in x and its gradient in yzw, for normals and slopes without
+// sampling the noise four times
+fn simplex_noise_3d_derivatives(v: vec3<f32>) -> vec4<f32> {
+  let c = simplex_corners_3d(v);
+  let x0 = c.x[0];
+  let x1 = c.x[1];
+  let x2 = c.x[2];
+  let x3 = c.x[3];
+  let p0 = c.p[0];
+  let p1 = c.p[1];
+  let p2 = c.p[2];
+  let p3 = c.p[3];
+
+  var m: vec4<f32> = 0.6 - vec4<f32>(dot(x0,x0), dot(x1,x1), dot(x2,x2), dot(x3,x3));
+  m = max(m, vec4<f32>(0.));
+  let m2 = m * m;
+  let m4 = m2 * m2;
+  let pdotx = vec4<f32>(dot(p0,x0), dot(p1,x1), dot(p2,x2), dot(p3,x3));
+
+  // Each corner adds m^4 (p . x), whose gradient is m^4 p - 8 m^3 (p . x) x
+  let temp = m2 * m * pdotx;
+  var gradient = -8. * (temp.x * x0 + temp.y * x1 + temp.z * x2 + temp.w * x3);
+  gradient += m4.x * p0 + m4.y * p1 + m4.z * p2 + m4.w * p3;
+  return 42. * vec4<f32>(dot(m4, pdotx), gradient);
+}
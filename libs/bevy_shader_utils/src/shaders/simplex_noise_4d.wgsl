@@ -0,0 +1,91 @@
+#define_import_path bevy_shader_utils::simplex_noise_4d
+
+//  MIT License. © Ian McEwan, Stefan Gustavson
+//
+// A 3D pattern that moves around a circle in the fourth dimension loops
+// seamlessly: simplex_noise_4d(vec4(p, cos(angle), sin(angle))).
+
+fn mod289_four(x: vec4<f32>) -> vec4<f32> { return x - floor(x * (1. / 289.)) * 289.; }
+fn mod289_one(x: f32) -> f32 { return x - floor(x * (1. / 289.)) * 289.; }
+fn permute_four(x: vec4<f32>) -> vec4<f32> { return mod289_four(((x * 34.) + 1.) * x); }
+fn permute_one(x: f32) -> f32 { return mod289_one(((x * 34.) + 1.) * x); }
+fn taylor_inv_sqrt_four(r: vec4<f32>) -> vec4<f32> { return 1.79284291400159 - 0.85373472095314 * r; }
+fn taylor_inv_sqrt_one(r: f32) -> f32 { return 1.79284291400159 - 0.85373472095314 * r; }
+
+fn grad_four(j: f32, ip: vec4<f32>) -> vec4<f32> {
+    let ones = vec4(1., 1., 1., -1.);
+    var p: vec4<f32>;
+    p = vec4(floor(fract(vec3(j) * ip.xyz) * 7.) * ip.z - 1., 0.);
+    p.w = 1.5 - dot(abs(p.xyz), ones.xyz);
+    let s = select(vec4(0.), vec4(1.), p < vec4(0.));
+    p = vec4(p.xyz + (s.xyz * 2. - 1.) * s.www, p.w);
+    return p;
+}
+
+// Simplex noise in -1.0..1.0
+fn simplex_noise_4d(v: vec4<f32>) -> f32 {
+    let F4 = 0.309016994374947451; // (sqrt(5) - 1) / 4
+    let C = vec4(
+        0.138196601125011, // (5 - sqrt(5))/20  G4
+        0.276393202250021, // 2 * G4
+        0.414589803375032, // 3 * G4
+        -0.447213595499958 // -1 + 4 * G4
+    );
+
+    // First corner
+    var i = floor(v + dot(v, vec4(F4)));
+    let x0 = v - i + dot(i, C.xxxx);
+
+    // Other corners, ranked by the size of the components of x0
+    var i0: vec4<f32>;
+    let is_x = step(x0.yzw, x0.xxx);
+    let is_yz = step(x0.zww, x0.yyz);
+    i0.x = is_x.x + is_x.y + is_x.z;
+    i0 = vec4(i0.x, 1. - is_x);
+    i0.y += is_yz.x + is_yz.y;
+    i0 = vec4(i0.xy, i0.zw + 1. - is_yz.xy);
+    i0.z += is_yz.z;
+    i0.w += 1. - is_yz.z;
+
+    let i3 = clamp(i0, vec4(0.), vec4(1.));
+    let i2 = clamp(i0 - 1., vec4(0.), vec4(1.));
+    let i1 = clamp(i0 - 2., vec4(0.), vec4(1.));
+
+    let x1 = x0 - i1 + C.xxxx;
+    let x2 = x0 - i2 + C.yyyy;
+    let x3 = x0 - i3 + C.zzzz;
+    let x4 = x0 + C.wwww;
+
+    // Permutations
+    i = mod289_four(i);
+    let j0 = permute_one(permute_one(permute_one(permute_one(i.w) + i.z) + i.y) + i.x);
+    let j1 = permute_four(permute_four(permute_four(permute_four(
+        i.w + vec4(i1.w, i2.w, i3.w, 1.))
+        + i.z + vec4(i1.z, i2.z, i3.z, 1.))
+        + i.y + vec4(i1.y, i2.y, i3.y, 1.))
+        + i.x + vec4(i1.x, i2.x, i3.x, 1.));
+
+    // Gradients: 7x7x6 points over a cube, mapped onto a 4-cross polytope
+    let ip = vec4(1. / 294., 1. / 49., 1. / 7., 0.);
+    var p0 = grad_four(j0, ip);
+    var p1 = grad_four(j1.x, ip);
+    var p2 = grad_four(j1.y, ip);
+    var p3 = grad_four(j1.z, ip);
+    var p4 = grad_four(j1.w, ip);
+
+    // Normalise gradients
+    let norm = taylor_inv_sqrt_four(vec4(dot(p0, p0), dot(p1, p1), dot(p2, p2), dot(p3, p3)));
+    p0 *= norm.x;
+    p1 *= norm.y;
+    p2 *= norm.z;
+    p3 *= norm.w;
+    p4 *= taylor_inv_sqrt_one(dot(p4, p4));
+
+    // Mix contributions from the five corners
+    var m0 = max(0.6 - vec3(dot(x0, x0), dot(x1, x1), dot(x2, x2)), vec3(0.));
+    var m1 = max(0.6 - vec2(dot(x3, x3), dot(x4, x4)), vec2(0.));
+    m0 = m0 * m0;
+    m1 = m1 * m1;
+    return 49. * (dot(m0 * m0, vec3(dot(p0, x0), dot(p1, x1), dot(p2, x2)))
+        + dot(m1 * m1, vec2(dot(p3, x3), dot(p4, x4))));
+}
@@ -0,0 +1,73 @@
+#define_import_path bevy_shader_utils::value_noise
+
+// Random values on the integer lattice, smoothly interpolated. Blobbier than
+// Perlin or simplex noise, and cheap.
+
+#import bevy_shader_utils::hash::{pcg, unit_float}
+
+// A hash as a value in -1.0..1.0
+fn value_from_hash(hash: u32) -> f32 {
+    return unit_float(hash) * 2.0 - 1.0;
+}
+
+fn lattice_value_2d(cell: vec2<f32>) -> f32 {
+    let c = bitcast<vec2<u32>>(vec2<i32>(cell));
+    return value_from_hash(pcg(c.x ^ pcg(c.y)));
+}
+
+fn lattice_value_3d(cell: vec3<f32>) -> f32 {
+    let c = bitcast<vec3<u32>>(vec3<i32>(cell));
+    return value_from_hash(pcg(c.x ^ pcg(c.y ^ pcg(c.z))));
+}
+
+// Lattice cells wrapped into 0..period, on the axes whose period isn't 0
+fn value_wrap_2d(cell: vec2<f32>, period: vec2<f32>) -> vec2<f32> {
+    return select(cell, cell - period * floor(cell / period), period > vec2(0.0));
+}
+
+fn value_wrap_3d(cell: vec3<f32>, period: vec3<f32>) -> vec3<f32> {
+    return select(cell, cell - period * floor(cell / period), period > vec3(0.0));
+}
+
+fn value_fade(t: f32) -> f32 {
+    return t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+}
+
+// Value noise in -1.0..1.0 that repeats every `period` units, which should
+// be whole numbers. Axes with a period of 0 don't repeat.
+fn value_noise_2d_periodic(p: vec2<f32>, period: vec2<f32>) -> f32 {
+    let i = floor(p);
+    let f = p - i;
+    let a = lattice_value_2d(value_wrap_2d(i, period));
+    let b = lattice_value_2d(value_wrap_2d(i + vec2(1.0, 0.0), period));
+    let c = lattice_value_2d(value_wrap_2d(i + vec2(0.0, 1.0), period));
+    let d = lattice_value_2d(value_wrap_2d(i + vec2(1.0, 1.0), period));
+    let u = value_fade(f.x);
+    return mix(mix(a, b, u), mix(c, d, u), value_fade(f.y));
+}
+
+fn value_noise_3d_periodic(p: vec3<f32>, period: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = p - i;
+    let u = value_fade(f.x);
+    let v = value_fade(f.y);
+    var layers = vec2(0.0);
+    for (var z = 0; z < 2; z++) {
+        let k = i + vec3(0.0, 0.0, f32(z));
+        let a = lattice_value_3d(value_wrap_3d(k, period));
+        let b = lattice_value_3d(value_wrap_3d(k + vec3(1.0, 0.0, 0.0), period));
+        let c = lattice_value_3d(value_wrap_3d(k + vec3(0.0, 1.0, 0.0), period));
+        let d = lattice_value_3d(value_wrap_3d(k + vec3(1.0, 1.0, 0.0), period));
+        layers[z] = mix(mix(a, b, u), mix(c, d, u), v);
+    }
+    return mix(layers.x, layers.y, value_fade(f.z));
+}
+
+// Value noise in -1.0..1.0
+fn value_noise_2d(p: vec2<f32>) -> f32 {
+    return value_noise_2d_periodic(p, vec2(0.0));
+}
+
+fn value_noise_3d(p: vec3<f32>) -> f32 {
+    return value_noise_3d_periodic(p, vec3(0.0));
+}
@@ -35,12 +35,13 @@
 // Voronoise:    https://www.shadertoy.com/view/Xd23Dh
 #define_import_path bevy_shader_utils::voronoise
 
+#import bevy_shader_utils::hash::{pcg, pcg2d, unit_float, unit_float_2d}
+
+// The jitter and value of a cell, hashed from its integer coordinates
 fn hash_three( p: vec2<f32> ) -> vec3<f32>
 {
-    let q: vec3<f32> = vec3( dot(p,vec2(127.1,311.7)), 
-                   dot(p,vec2(269.5,183.3)), 
-                   dot(p,vec2(419.2,371.9)) );
-    return fract(sin(q)*43758.5453);
+    let h = pcg2d(bitcast<vec2<u32>>(vec2<i32>(p)));
+    return vec3(unit_float_2d(h), unit_float(pcg(h.x ^ h.y)));
 }
 
 fn voronoise( p: vec2<f32>, u: f32, v: f32 ) -> f32
@@ -0,0 +1,113 @@
+#define_import_path bevy_shader_utils::worley_noise_3d
+
+// Cellular noise: one random feature point in each cell of the integer
+// lattice, measured by the distances to the closest ones.
+
+#import bevy_shader_utils::hash::{pcg, unit_float_3d}
+
+struct Worley {
+    // Distance to the closest feature point, F1
+    closest: f32,
+    // Distance to the second closest feature point, F2
+    second_closest: f32,
+    // Distance to the nearest border between the cells of two feature points,
+    // 0.0 on the border. Unlike F2 - F1 it grows evenly away from the border.
+    edge: f32,
+}
+
+// The feature point of a cell, relative to its corner
+fn feature_point(cell: vec3<f32>) -> vec3<f32> {
+    let c = bitcast<vec3<u32>>(vec3<i32>(cell));
+    let x = pcg(c.x ^ pcg(c.y ^ pcg(c.z)));
+    let y = pcg(x);
+    let z = pcg(y);
+    return unit_float_3d(vec3(x, y, z));
+}
+
+// Lattice cells wrapped into 0..period, on the axes whose period isn't 0
+fn worley_wrap(cell: vec3<f32>, period: vec3<f32>) -> vec3<f32> {
+    return select(cell, cell - period * floor(cell / period), period > vec3(0.0));
+}
+
+// Updates F1 and F2 with the feature point of the cell at offset `g`, unless
+// the whole cell is farther than F2
+fn worley_visit(
+    g: vec3<f32>,
+    f: vec3<f32>,
+    cell: vec3<f32>,
+    period: vec3<f32>,
+    f1: ptr<function, f32>,
+    f2: ptr<function, f32>,
+    closest: ptr<function, vec3<f32>>,
+    closest_cell: ptr<function, vec3<f32>>,
+) {
+    let nearest = max(g - f, vec3(0.0)) + max(f - g - 1.0, vec3(0.0));
+    if dot(nearest, nearest) >= *f2 {
+        return;
+    }
+    let r = g + feature_point(worley_wrap(cell + g, period)) - f;
+    let d = dot(r, r);
+    if d < *f1 {
+        *f2 = *f1;
+        *f1 = d;
+        *closest = r;
+        *closest_cell = g;
+    } else if d < *f2 {
+        *f2 = d;
+    }
+}
+
+// Worley noise that repeats every `period` units, which should be whole
+// numbers. Axes with a period of 0 don't repeat.
+fn worley_noise_3d_periodic(p: vec3<f32>, period: vec3<f32>) -> Worley {
+    let cell = floor(p);
+    let f = p - cell;
+
+    // Squared distances, and the closest point relative to `p`
+    var f1 = 8.0;
+    var f2 = 8.0;
+    var closest = vec3(0.0);
+    var closest_cell = vec3(0.0);
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                worley_visit(vec3(f32(x), f32(y), f32(z)), f, cell, period, &f1, &f2, &closest, &closest_cell);
+            }
+        }
+    }
+    // The second closest point can lie two cells away, so the outer shell of
+    // the 5x5x5 block is searched too, skipping the cells that are all
+    // farther than F2 already
+    for (var z = -2; z <= 2; z++) {
+        for (var y = -2; y <= 2; y++) {
+            for (var x = -2; x <= 2; x++) {
+                let g = vec3(f32(x), f32(y), f32(z));
+                if max(abs(g.x), max(abs(g.y), abs(g.z))) > 1.0 {
+                    worley_visit(g, f, cell, period, &f1, &f2, &closest, &closest_cell);
+                }
+            }
+        }
+    }
+
+    // The borders are halfway between the closest point and its neighbours,
+    // https://iquilezles.org/articles/voronoilines/
+    var edge = 8.0;
+    for (var z = -1; z <= 1; z++) {
+        for (var y = -1; y <= 1; y++) {
+            for (var x = -1; x <= 1; x++) {
+                let g = closest_cell + vec3(f32(x), f32(y), f32(z));
+                let r = g + feature_point(worley_wrap(cell + g, period)) - f;
+                let between = r - closest;
+                if dot(between, between) > 1e-6 {
+                    edge = min(edge, dot(0.5 * (closest + r), normalize(between)));
+                }
+            }
+        }
+    }
+
+    return Worley(sqrt(f1), sqrt(f2), edge);
+}
+
+fn worley_noise_3d(p: vec3<f32>) -> Worley {
+    return worley_noise_3d_periodic(p, vec3(0.0));
+}
@@ -0,0 +1,94 @@
+//! CPU mirror of `bevy_shader_utils::simplex_noise_2d`, step for step in f32,
+//! for terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{Vec2, Vec2Swizzles, Vec3, Vec4, Vec4Swizzles};
+
+fn mod289(x: Vec3) -> Vec3 {
+    x - (x * (1.0 / 289.0)).floor() * 289.0
+}
+
+fn permute_three(x: Vec3) -> Vec3 {
+    mod289(((x * 34.0) + 1.0) * x)
+}
+
+/// Simplex noise in `-1.0..=1.0`
+pub fn simplex_noise_2d(v: Vec2) -> f32 {
+    let c = Vec4::new(
+        0.211_324_87,  // (3.0-sqrt(3.0))/6.0
+        0.366_025_42,  // 0.5*(sqrt(3.0)-1.0)
+        -0.577_350_26, // -1.0 + 2.0 * C.x
+        0.024_390_243, // 1.0 / 41.0
+    );
+
+    // First corner
+    let mut i = (v + v.dot(c.yy())).floor();
+    let x0 = v - i + i.dot(c.xx());
+
+    // Other corners
+    let i1 = if x0.x > x0.y {
+        Vec2::new(1.0, 0.0)
+    } else {
+        Vec2::new(0.0, 1.0)
+    };
+
+    let mut x12 = x0.xyxy() + c.xxzz();
+    x12.x -= i1.x;
+    x12.y -= i1.y;
+
+    // Permutations
+    i = i - (i * (1.0 / 289.0)).floor() * 289.0; // Avoid truncation effects in permutation
+
+    let p = permute_three(
+        permute_three(i.y + Vec3::new(0.0, i1.y, 1.0)) + i.x + Vec3::new(0.0, i1.x, 1.0),
+    );
+    let mut m = (0.5 - Vec3::new(x0.dot(x0), x12.xy().dot(x12.xy()), x12.zw().dot(x12.zw())))
+        .max(Vec3::ZERO);
+    m *= m;
+    m *= m;
+
+    // Gradients: 41 points uniformly over a line, mapped onto a diamond.
+    // The ring size 17*17 = 289 is close to a multiple of 41 (41*7 = 287)
+    let x = 2.0 * (p * c.w - (p * c.w).floor()) - 1.0;
+    let h = x.abs() - 0.5;
+    let ox = (x + 0.5).floor();
+    let a0 = x - ox;
+
+    // Normalize gradients implicitly by scaling m
+    m *= 1.792_842_9 - 0.853_734_7 * (a0 * a0 + h * h);
+
+    // Compute final noise value at P
+    let g = Vec3::new(
+        a0.x * x0.x + h.x * x0.y,
+        a0.y * x12.x + h.y * x12.y,
+        a0.z * x12.z + h.z * x12.w,
+    );
+    130.0 * m.dot(g)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_matches_shader;
+
+    /// Outputs of the WGSL function, see [`crate::golden`]
+    const GOLDEN: [([f32; 2], f32); 10] = [
+        ([0.0, 0.0], 0.0),
+        ([0.5, 0.25], -0.2183558),
+        ([-3.7, 12.2], 0.46038908),
+        ([123.456, -78.9], -0.2881643),
+        ([-0.001, 0.999], 0.36473417),
+        ([7.3, 7.3], 0.40862468),
+        ([290.5, -290.5], 0.21203381),
+        ([1000.25, 2000.75], -0.5277699),
+        ([0.1, -0.2], -0.85636234),
+        ([-45.67, 89.01], 0.67738485),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let value = simplex_noise_2d(Vec2::from_array(p));
+            assert_matches_shader(&p, &[value], &[expected]);
+        }
+    }
+}
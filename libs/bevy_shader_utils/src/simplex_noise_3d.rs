@@ -0,0 +1,235 @@
+//! CPU mirror of `bevy_shader_utils::simplex_noise_3d`, step for step in f32,
+//! for terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{Vec2, Vec2Swizzles, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles};
+
+/// WGSL's `step` on each component: 0.0 below the edge, 1.0 from it on
+fn step3(edge: Vec3, x: Vec3) -> Vec3 {
+    Vec3::select(x.cmplt(edge), Vec3::ZERO, Vec3::ONE)
+}
+
+fn step4(edge: Vec4, x: Vec4) -> Vec4 {
+    Vec4::select(x.cmplt(edge), Vec4::ZERO, Vec4::ONE)
+}
+
+fn permute_four(x: Vec4) -> Vec4 {
+    ((x * 34.0 + 1.0) * x) % Vec4::splat(289.0)
+}
+
+fn taylor_inv_sqrt_four(r: Vec4) -> Vec4 {
+    1.792_842_9 - 0.853_734_7 * r
+}
+
+/// The offsets of `v` from the four corners of its simplex, and the
+/// gradients at those corners
+struct SimplexCorners3d {
+    x: [Vec3; 4],
+    p: [Vec3; 4],
+}
+
+fn simplex_corners_3d(v: Vec3) -> SimplexCorners3d {
+    let c = Vec2::new(1.0 / 6.0, 1.0 / 3.0);
+    let d = Vec4::new(0.0, 0.5, 1.0, 2.0);
+
+    // First corner
+    let mut i = (v + v.dot(c.yyy())).floor();
+    let x0 = v - i + i.dot(c.xxx());
+
+    // Other corners
+    let g = step3(x0.yzx(), x0);
+    let l = 1.0 - g;
+    let i1 = g.min(l.zxy());
+    let i2 = g.max(l.zxy());
+
+    let x1 = x0 - i1 + c.xxx();
+    let x2 = x0 - i2 + 2.0 * c.xxx();
+    let x3 = x0 - 1.0 + 3.0 * c.xxx();
+
+    // Permutations
+    i %= Vec3::splat(289.0);
+    let p = permute_four(
+        permute_four(
+            permute_four(i.z + Vec4::new(0.0, i1.z, i2.z, 1.0))
+                + i.y
+                + Vec4::new(0.0, i1.y, i2.y, 1.0),
+        ) + i.x
+            + Vec4::new(0.0, i1.x, i2.x, 1.0),
+    );
+
+    // Gradients (NxN points uniformly over a square, mapped onto an octahedron.)
+    let n_ = 1.0 / 7.0;
+    let ns = n_ * d.wyz() - d.xzx();
+
+    let j = p - 49.0 * (p * ns.z * ns.z).floor();
+
+    let x_ = (j * ns.z).floor();
+    let y_ = (j - 7.0 * x_).floor();
+
+    let x = x_ * ns.x + ns.yyyy();
+    let y = y_ * ns.x + ns.yyyy();
+    let h = 1.0 - x.abs() - y.abs();
+
+    let b0 = Vec4::new(x.x, x.y, y.x, y.y);
+    let b1 = Vec4::new(x.z, x.w, y.z, y.w);
+
+    let s0 = b0.floor() * 2.0 + 1.0;
+    let s1 = b1.floor() * 2.0 + 1.0;
+    let sh = -step4(h, Vec4::ZERO);
+
+    let a0 = b0.xzyw() + s0.xzyw() * sh.xxyy();
+    let a1 = b1.xzyw() + s1.xzyw() * sh.zzww();
+
+    let mut p0 = Vec3::new(a0.x, a0.y, h.x);
+    let mut p1 = Vec3::new(a0.z, a0.w, h.y);
+    let mut p2 = Vec3::new(a1.x, a1.y, h.z);
+    let mut p3 = Vec3::new(a1.z, a1.w, h.w);
+
+    // Normalise gradients
+    let norm = taylor_inv_sqrt_four(Vec4::new(p0.dot(p0), p1.dot(p1), p2.dot(p2), p3.dot(p3)));
+    p0 *= norm.x;
+    p1 *= norm.y;
+    p2 *= norm.z;
+    p3 *= norm.w;
+
+    SimplexCorners3d {
+        x: [x0, x1, x2, x3],
+        p: [p0, p1, p2, p3],
+    }
+}
+
+/// Simplex noise in `-1.0..=1.0`
+pub fn simplex_noise_3d(v: Vec3) -> f32 {
+    let SimplexCorners3d {
+        x: [x0, x1, x2, x3],
+        p: [p0, p1, p2, p3],
+    } = simplex_corners_3d(v);
+
+    // Mix final noise value
+    let mut m = 0.6 - Vec4::new(x0.dot(x0), x1.dot(x1), x2.dot(x2), x3.dot(x3));
+    m = m.max(Vec4::ZERO);
+    m *= m;
+    42.0 * (m * m).dot(Vec4::new(p0.dot(x0), p1.dot(x1), p2.dot(x2), p3.dot(x3)))
+}
+
+/// The noise in x and its gradient in yzw, for normals and slopes without
+/// sampling the noise four times
+pub fn simplex_noise_3d_derivatives(v: Vec3) -> Vec4 {
+    let SimplexCorners3d {
+        x: [x0, x1, x2, x3],
+        p: [p0, p1, p2, p3],
+    } = simplex_corners_3d(v);
+
+    let mut m = 0.6 - Vec4::new(x0.dot(x0), x1.dot(x1), x2.dot(x2), x3.dot(x3));
+    m = m.max(Vec4::ZERO);
+    let m2 = m * m;
+    let m4 = m2 * m2;
+    let pdotx = Vec4::new(p0.dot(x0), p1.dot(x1), p2.dot(x2), p3.dot(x3));
+
+    // Each corner adds m^4 (p . x), whose gradient is m^4 p - 8 m^3 (p . x) x
+    let temp = m2 * m * pdotx;
+    let mut gradient = -8.0 * (temp.x * x0 + temp.y * x1 + temp.z * x2 + temp.w * x3);
+    gradient += m4.x * p0 + m4.y * p1 + m4.z * p2 + m4.w * p3;
+    42.0 * Vec4::new(m4.dot(pdotx), gradient.x, gradient.y, gradient.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::{assert_matches_shader, assert_within};
+
+    /// Outputs of the WGSL function, see [`crate::golden`]
+    const GOLDEN: [([f32; 3], f32); 10] = [
+        ([0.0, 0.0, 0.0], -0.4121988),
+        ([0.5, 0.25, 0.125], 0.12590295),
+        ([-3.7, 12.2, 5.5], 0.39379168),
+        ([123.456, -78.9, 0.001], 0.1746337),
+        ([-0.001, 0.999, -17.25], -0.5771712),
+        ([7.3, 7.3, 7.3], -0.37633923),
+        ([290.5, -290.5, 1.75], -0.59650177),
+        ([1000.25, 2000.75, -3000.5], 0.0018108799),
+        ([0.1, -0.2, 0.3], -0.50290084),
+        ([-45.67, 89.01, -23.45], 0.42555785),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let value = simplex_noise_3d(Vec3::from_array(p));
+            assert_matches_shader(&p, &[value], &[expected]);
+        }
+    }
+
+    /// The noise and its gradient
+    const DERIVATIVES_GOLDEN: [([f32; 3], [f32; 4]); 10] = [
+        (
+            [0.0, 0.0, 0.0],
+            [-0.4121988, 1.7608602, 1.7608602, -7.301715],
+        ),
+        (
+            [0.5, 0.25, 0.125],
+            [0.12590295, 0.069031395, 1.3974171, -0.48458314],
+        ),
+        (
+            [-3.7, 12.2, 5.5],
+            [0.39379168, 0.48774946, -3.169476, -1.3166878],
+        ),
+        (
+            [123.456, -78.9, 0.001],
+            [0.1746337, -0.65641475, 0.3042992, 1.859241],
+        ),
+        (
+            [-0.001, 0.999, -17.25],
+            [-0.5771712, 0.38811564, -0.495874, -1.3335489],
+        ),
+        (
+            [7.3, 7.3, 7.3],
+            [-0.37633923, -0.98152894, 2.2772956, 0.73444057],
+        ),
+        (
+            [290.5, -290.5, 1.75],
+            [-0.59650177, -0.875237, 2.1328616, -0.9466053],
+        ),
+        (
+            [1000.25, 2000.75, -3000.5],
+            [0.0018108799, -3.7820551, 0.21615541, -0.84452593],
+        ),
+        (
+            [0.1, -0.2, 0.3],
+            [-0.50290084, 0.8271169, -2.285831, 2.049521],
+        ),
+        (
+            [-45.67, 89.01, -23.45],
+            [0.42555785, -0.15850168, 3.122189, -1.9337199],
+        ),
+    ];
+
+    #[test]
+    fn derivatives_match_the_shader() {
+        for (p, expected) in DERIVATIVES_GOLDEN {
+            let value = simplex_noise_3d_derivatives(Vec3::from_array(p));
+            // The gradient is about ten times the value
+            assert_within(&p, &value.to_array(), &expected, 1e-5);
+        }
+    }
+
+    #[test]
+    fn derivatives_are_the_slope() {
+        let h = 1e-3;
+        for p in [
+            Vec3::new(0.3, 0.7, 0.1),
+            Vec3::new(-1.25, 2.5, 6.5),
+            Vec3::new(4.2, -3.3, 0.9),
+        ] {
+            let gradient = simplex_noise_3d_derivatives(p).yzw();
+            let slope = Vec3::new(
+                simplex_noise_3d(p + Vec3::X * h) - simplex_noise_3d(p - Vec3::X * h),
+                simplex_noise_3d(p + Vec3::Y * h) - simplex_noise_3d(p - Vec3::Y * h),
+                simplex_noise_3d(p + Vec3::Z * h) - simplex_noise_3d(p - Vec3::Z * h),
+            ) / (2.0 * h);
+            assert!(
+                gradient.abs_diff_eq(slope, 1e-2),
+                "{p}: {gradient} != {slope}"
+            );
+        }
+    }
+}
@@ -0,0 +1,148 @@
+//! CPU mirror of `bevy_shader_utils::simplex_noise_4d`, step for step in f32,
+//! for terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{Vec2, Vec3, Vec4, Vec4Swizzles};
+
+fn mod289_four(x: Vec4) -> Vec4 {
+    x - (x * (1.0 / 289.0)).floor() * 289.0
+}
+
+fn mod289_one(x: f32) -> f32 {
+    x - (x * (1.0 / 289.0)).floor() * 289.0
+}
+
+fn permute_four(x: Vec4) -> Vec4 {
+    mod289_four(((x * 34.0) + 1.0) * x)
+}
+
+fn permute_one(x: f32) -> f32 {
+    mod289_one(((x * 34.0) + 1.0) * x)
+}
+
+fn taylor_inv_sqrt_four(r: Vec4) -> Vec4 {
+    1.792_842_9 - 0.853_734_7 * r
+}
+
+fn taylor_inv_sqrt_one(r: f32) -> f32 {
+    1.792_842_9 - 0.853_734_7 * r
+}
+
+/// WGSL's `step` on each component: 0.0 below the edge, 1.0 from it on
+fn step(edge: Vec3, x: Vec3) -> Vec3 {
+    Vec3::select(x.cmplt(edge), Vec3::ZERO, Vec3::ONE)
+}
+
+fn grad_four(j: f32, ip: Vec4) -> Vec4 {
+    let ones = Vec4::new(1.0, 1.0, 1.0, -1.0);
+    let xyz = Vec3::splat(j) * ip.xyz();
+    let xyz = ((xyz - xyz.floor()) * 7.0).floor() * ip.z - 1.0;
+    let p = xyz.extend(1.5 - xyz.abs().dot(ones.xyz()));
+    let s = Vec4::select(p.cmplt(Vec4::ZERO), Vec4::ONE, Vec4::ZERO);
+    (p.xyz() + (s.xyz() * 2.0 - 1.0) * s.w).extend(p.w)
+}
+
+/// Simplex noise in `-1.0..=1.0`. A 3D pattern that moves around a circle in
+/// the fourth dimension loops seamlessly.
+pub fn simplex_noise_4d(v: Vec4) -> f32 {
+    let f4 = 0.309_017; // (sqrt(5) - 1) / 4
+    let c = Vec4::new(
+        0.138_196_6,  // (5 - sqrt(5))/20  G4
+        0.276_393_2,  // 2 * G4
+        0.414_589_8,  // 3 * G4
+        -0.447_213_6, // -1 + 4 * G4
+    );
+
+    // First corner
+    let mut i = (v + v.dot(Vec4::splat(f4))).floor();
+    let x0 = v - i + i.dot(c.xxxx());
+
+    // Other corners, ranked by the size of the components of x0
+    let is_x = step(x0.yzw(), x0.xxx());
+    let is_yz = step(x0.zww(), x0.yyz());
+    let mut i0 = Vec4::new(
+        is_x.x + is_x.y + is_x.z,
+        1.0 - is_x.x,
+        1.0 - is_x.y,
+        1.0 - is_x.z,
+    );
+    i0.y += is_yz.x + is_yz.y;
+    i0.z += 1.0 - is_yz.x;
+    i0.w += 1.0 - is_yz.y;
+    i0.z += is_yz.z;
+    i0.w += 1.0 - is_yz.z;
+
+    let i3 = i0.clamp(Vec4::ZERO, Vec4::ONE);
+    let i2 = (i0 - 1.0).clamp(Vec4::ZERO, Vec4::ONE);
+    let i1 = (i0 - 2.0).clamp(Vec4::ZERO, Vec4::ONE);
+
+    let x1 = x0 - i1 + c.xxxx();
+    let x2 = x0 - i2 + c.yyyy();
+    let x3 = x0 - i3 + c.zzzz();
+    let x4 = x0 + c.wwww();
+
+    // Permutations
+    i = mod289_four(i);
+    let j0 = permute_one(permute_one(permute_one(permute_one(i.w) + i.z) + i.y) + i.x);
+    let j1 = permute_four(
+        permute_four(
+            permute_four(
+                permute_four(i.w + Vec4::new(i1.w, i2.w, i3.w, 1.0))
+                    + i.z
+                    + Vec4::new(i1.z, i2.z, i3.z, 1.0),
+            ) + i.y
+                + Vec4::new(i1.y, i2.y, i3.y, 1.0),
+        ) + i.x
+            + Vec4::new(i1.x, i2.x, i3.x, 1.0),
+    );
+
+    // Gradients: 7x7x6 points over a cube, mapped onto a 4-cross polytope
+    let ip = Vec4::new(1.0 / 294.0, 1.0 / 49.0, 1.0 / 7.0, 0.0);
+    let mut p0 = grad_four(j0, ip);
+    let mut p1 = grad_four(j1.x, ip);
+    let mut p2 = grad_four(j1.y, ip);
+    let mut p3 = grad_four(j1.z, ip);
+    let mut p4 = grad_four(j1.w, ip);
+
+    // Normalise gradients
+    let norm = taylor_inv_sqrt_four(Vec4::new(p0.dot(p0), p1.dot(p1), p2.dot(p2), p3.dot(p3)));
+    p0 *= norm.x;
+    p1 *= norm.y;
+    p2 *= norm.z;
+    p3 *= norm.w;
+    p4 *= taylor_inv_sqrt_one(p4.dot(p4));
+
+    // Mix contributions from the five corners
+    let mut m0 = (0.6 - Vec3::new(x0.dot(x0), x1.dot(x1), x2.dot(x2))).max(Vec3::ZERO);
+    let mut m1 = (0.6 - Vec2::new(x3.dot(x3), x4.dot(x4))).max(Vec2::ZERO);
+    m0 = m0 * m0;
+    m1 = m1 * m1;
+    49.0 * ((m0 * m0).dot(Vec3::new(p0.dot(x0), p1.dot(x1), p2.dot(x2)))
+        + (m1 * m1).dot(Vec2::new(p3.dot(x3), p4.dot(x4))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_matches_shader;
+
+    /// Outputs of the WGSL function, see [`crate::golden`]
+    const GOLDEN: [([f32; 4], f32); 9] = [
+        ([0.0, 0.0, 0.0, 0.0], 0.0),
+        ([0.5, 0.25, 0.125, 0.0625], 0.03204725),
+        ([-3.7, 12.2, 5.5, -1.25], 0.17034462),
+        ([123.456, -78.9, 0.001, 42.0], -0.11515738),
+        ([-0.001, 0.999, -17.25, 3.3], -0.50769615),
+        ([7.3, 7.3, 7.3, 7.3], 0.23021017),
+        ([290.5, -290.5, 1.75, -0.5], -0.44143483),
+        ([0.1, -0.2, 0.3, -0.4], -0.09107662),
+        ([-45.67, 89.01, -23.45, 11.11], 0.15398514),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let value = simplex_noise_4d(Vec4::from_array(p));
+            assert_matches_shader(&p, &[value], &[expected]);
+        }
+    }
+}
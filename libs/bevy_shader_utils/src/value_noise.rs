@@ -0,0 +1,165 @@
+//! CPU mirror of `bevy_shader_utils::value_noise`, step for step in f32, for
+//! terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{IVec2, IVec3, Vec2, Vec3};
+
+use crate::hash::{pcg, unit_float};
+
+/// A hash as a value in `-1.0..1.0`
+fn value_from_hash(hash: u32) -> f32 {
+    unit_float(hash) * 2.0 - 1.0
+}
+
+fn lattice_value_2d(cell: Vec2) -> f32 {
+    let c = IVec2::new(cell.x as i32, cell.y as i32).as_uvec2();
+    value_from_hash(pcg(c.x ^ pcg(c.y)))
+}
+
+fn lattice_value_3d(cell: Vec3) -> f32 {
+    let c = IVec3::new(cell.x as i32, cell.y as i32, cell.z as i32).as_uvec3();
+    value_from_hash(pcg(c.x ^ pcg(c.y ^ pcg(c.z))))
+}
+
+/// Lattice cells wrapped into `0..period`, on the axes whose period isn't 0
+fn value_wrap_2d(cell: Vec2, period: Vec2) -> Vec2 {
+    Vec2::select(
+        period.cmpgt(Vec2::ZERO),
+        cell - period * (cell / period).floor(),
+        cell,
+    )
+}
+
+fn value_wrap_3d(cell: Vec3, period: Vec3) -> Vec3 {
+    Vec3::select(
+        period.cmpgt(Vec3::ZERO),
+        cell - period * (cell / period).floor(),
+        cell,
+    )
+}
+
+fn value_fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn mix(a: f32, b: f32, t: f32) -> f32 {
+    a * (1.0 - t) + b * t
+}
+
+/// Value noise in `-1.0..1.0` that repeats every `period` units, which
+/// should be whole numbers. Axes with a period of 0 don't repeat.
+pub fn value_noise_2d_periodic(p: Vec2, period: Vec2) -> f32 {
+    let i = p.floor();
+    let f = p - i;
+    let a = lattice_value_2d(value_wrap_2d(i, period));
+    let b = lattice_value_2d(value_wrap_2d(i + Vec2::new(1.0, 0.0), period));
+    let c = lattice_value_2d(value_wrap_2d(i + Vec2::new(0.0, 1.0), period));
+    let d = lattice_value_2d(value_wrap_2d(i + Vec2::new(1.0, 1.0), period));
+    let u = value_fade(f.x);
+    mix(mix(a, b, u), mix(c, d, u), value_fade(f.y))
+}
+
+/// Value noise in `-1.0..1.0` that repeats every `period` units, which
+/// should be whole numbers. Axes with a period of 0 don't repeat.
+pub fn value_noise_3d_periodic(p: Vec3, period: Vec3) -> f32 {
+    let i = p.floor();
+    let f = p - i;
+    let u = value_fade(f.x);
+    let v = value_fade(f.y);
+    let mut layers = [0.0; 2];
+    for (z, layer) in layers.iter_mut().enumerate() {
+        let k = i + Vec3::new(0.0, 0.0, z as f32);
+        let a = lattice_value_3d(value_wrap_3d(k, period));
+        let b = lattice_value_3d(value_wrap_3d(k + Vec3::new(1.0, 0.0, 0.0), period));
+        let c = lattice_value_3d(value_wrap_3d(k + Vec3::new(0.0, 1.0, 0.0), period));
+        let d = lattice_value_3d(value_wrap_3d(k + Vec3::new(1.0, 1.0, 0.0), period));
+        *layer = mix(mix(a, b, u), mix(c, d, u), v);
+    }
+    mix(layers[0], layers[1], value_fade(f.z))
+}
+
+/// Value noise in `-1.0..1.0`
+pub fn value_noise_2d(p: Vec2) -> f32 {
+    value_noise_2d_periodic(p, Vec2::ZERO)
+}
+
+/// Value noise in `-1.0..1.0`
+pub fn value_noise_3d(p: Vec3) -> f32 {
+    value_noise_3d_periodic(p, Vec3::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_matches_shader;
+
+    /// Outputs of the WGSL functions, see [`crate::golden`]: in 2D, in 2D with
+    /// a period of `(4, 3)`, in 3D and in 3D with a period of `(4, 0, 7)`
+    const GOLDEN: [([f32; 3], [f32; 4]); 10] = [
+        (
+            [0.0, 0.0, 0.0],
+            [-0.6192013, -0.6192013, -0.0010466576, -0.0010466576],
+        ),
+        (
+            [0.5, 0.25, 0.125],
+            [-0.33269346, -0.33269346, 0.31401485, 0.31401485],
+        ),
+        (
+            [-3.7, 12.2, 5.5],
+            [-0.6447821, -0.48506695, -0.03630346, 0.24542823],
+        ),
+        (
+            [123.456, -78.9, 0.001],
+            [-0.43363827, -0.28537998, -0.41709346, -0.010362224],
+        ),
+        (
+            [-0.001, 0.999, -17.25],
+            [0.8216025, 0.82160187, 0.1646865, 0.7507631],
+        ),
+        (
+            [7.3, 7.3, 7.3],
+            [0.45753598, 0.07584318, -0.3025184, -0.4451939],
+        ),
+        (
+            [290.5, -290.5, 1.75],
+            [0.18360871, 0.3319274, 0.38356832, 0.11625162],
+        ),
+        (
+            [1000.25, 2000.75, -3000.5],
+            [-0.2129814, -0.52649134, 0.3688995, -0.31563115],
+        ),
+        (
+            [0.1, -0.2, 0.3],
+            [-0.58112895, -0.5831341, -0.12933674, -0.12933674],
+        ),
+        (
+            [-45.67, 89.01, -23.45],
+            [-0.5531296, 0.24291664, 0.28086618, 0.19052257],
+        ),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let p = Vec3::from_array(p);
+            let values = [
+                value_noise_2d(p.truncate()),
+                value_noise_2d_periodic(p.truncate(), Vec2::new(4.0, 3.0)),
+                value_noise_3d(p),
+                value_noise_3d_periodic(p, Vec3::new(4.0, 0.0, 7.0)),
+            ];
+            assert_matches_shader(&p.to_array(), &values, &expected);
+        }
+    }
+
+    #[test]
+    fn repeats_over_the_period() {
+        let period = Vec3::new(4.0, 3.0, 7.0);
+        for p in [Vec3::new(0.3, 0.7, 0.1), Vec3::new(-1.25, 2.5, 6.5)] {
+            let value = value_noise_3d_periodic(p, period);
+            assert!((value - value_noise_3d_periodic(p + period, period)).abs() < 1e-5);
+            let value = value_noise_2d_periodic(p.truncate(), period.truncate());
+            let moved = p.truncate() - 3.0 * period.truncate();
+            assert!((value - value_noise_2d_periodic(moved, period.truncate())).abs() < 1e-5);
+        }
+    }
+}
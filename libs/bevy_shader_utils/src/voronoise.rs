@@ -0,0 +1,69 @@
+//! CPU mirror of `bevy_shader_utils::voronoise`, step for step in f32, for
+//! terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{IVec2, Vec2, Vec3};
+
+use crate::hash::{pcg, pcg2d, unit_float, unit_float_2d};
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// The jitter and value of a cell, hashed from its integer coordinates
+fn hash_three(p: Vec2) -> Vec3 {
+    let h = pcg2d(IVec2::new(p.x as i32, p.y as i32).as_uvec2());
+    unit_float_2d(h).extend(unit_float(pcg(h.x ^ h.y)))
+}
+
+/// Inigo Quilez's voronoise: `u` blends from a regular grid to jittered
+/// cells, `v` from hard cell edges to smooth noise
+pub fn voronoise(p: Vec2, u: f32, v: f32) -> f32 {
+    let k = 1.0 + 63.0 * (1.0 - v).powf(6.0);
+
+    let i = p.floor();
+    let f = p - i;
+
+    let mut a = Vec2::ZERO;
+    for y in -2..=2 {
+        for x in -2..=2 {
+            let g = Vec2::new(x as f32, y as f32);
+            let o = hash_three(i + g) * Vec3::new(u, u, 1.0);
+            let d = g - f + o.truncate();
+            let w = (1.0 - smoothstep(0.0, 1.414, d.length())).powf(k);
+            a += Vec2::new(o.z * w, w);
+        }
+    }
+
+    a.x / a.y
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_matches_shader;
+
+    /// Outputs of the WGSL function with `(u, v)` at `(1, 1)`, `(0, 0)` and
+    /// `(0.5, 0.5)`, see [`crate::golden`]
+    const GOLDEN: [([f32; 2], [f32; 3]); 10] = [
+        ([0.0, 0.0], [0.6656242, 0.8102801, 0.7111292]),
+        ([0.5, 0.25], [0.6851554, 0.44522452, 0.6555724]),
+        ([-3.7, 12.2], [0.33444142, 0.21152723, 0.24410082]),
+        ([123.456, -78.9], [0.60218346, 0.91108835, 0.7722024]),
+        ([-0.001, 0.999], [0.61388546, 0.91886437, 0.8283243]),
+        ([7.3, 7.3], [0.59699607, 0.60709864, 0.62481064]),
+        ([290.5, -290.5], [0.6445387, 0.7434555, 0.9166963]),
+        ([1000.25, 2000.75], [0.46671146, 0.6864121, 0.5231356]),
+        ([0.1, -0.2], [0.72450215, 0.81028, 0.80322033]),
+        ([-45.67, 89.01], [0.4351491, 0.38165164, 0.39186996]),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let p = Vec2::from_array(p);
+            let values = [(1.0, 1.0), (0.0, 0.0), (0.5, 0.5)].map(|(u, v)| voronoise(p, u, v));
+            assert_matches_shader(&p.to_array(), &values, &expected);
+        }
+    }
+}
@@ -0,0 +1,249 @@
+//! CPU mirror of `bevy_shader_utils::worley_noise_3d`, step for step in f32,
+//! for terrain and gameplay code that needs the values the shaders see.
+
+use bevy_math::{IVec3, UVec3, Vec3};
+
+use crate::hash::{pcg, unit_float_3d};
+
+/// Distances from a point to the feature points of the cells around it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Worley {
+    /// Distance to the closest feature point, F1
+    pub closest: f32,
+    /// Distance to the second closest feature point, F2
+    pub second_closest: f32,
+    /// Distance to the nearest border between the cells of two feature
+    /// points, 0.0 on the border. Unlike F2 - F1 it grows evenly away from
+    /// the border.
+    pub edge: f32,
+}
+
+/// The feature point of a cell, relative to its corner
+fn feature_point(cell: Vec3) -> Vec3 {
+    let c = IVec3::new(cell.x as i32, cell.y as i32, cell.z as i32).as_uvec3();
+    let x = pcg(c.x ^ pcg(c.y ^ pcg(c.z)));
+    let y = pcg(x);
+    let z = pcg(y);
+    unit_float_3d(UVec3::new(x, y, z))
+}
+
+/// Lattice cells wrapped into `0..period`, on the axes whose period isn't 0
+fn worley_wrap(cell: Vec3, period: Vec3) -> Vec3 {
+    Vec3::select(
+        period.cmpgt(Vec3::ZERO),
+        cell - period * (cell / period).floor(),
+        cell,
+    )
+}
+
+/// The 27 cells around and including a cell, as offsets
+fn neighbours() -> impl Iterator<Item = Vec3> {
+    (-1..=1).flat_map(|z| {
+        (-1..=1).flat_map(move |y| (-1..=1).map(move |x| Vec3::new(x as f32, y as f32, z as f32)))
+    })
+}
+
+/// Worley noise that repeats every `period` units, which should be whole
+/// numbers. Axes with a period of 0 don't repeat.
+pub fn worley_noise_3d_periodic(p: Vec3, period: Vec3) -> Worley {
+    let cell = p.floor();
+    let f = p - cell;
+
+    // Squared distances, and the closest point relative to `p`
+    let mut f1 = 8.0;
+    let mut f2 = 8.0;
+    let mut closest = Vec3::ZERO;
+    let mut closest_cell = Vec3::ZERO;
+    // The second closest point can lie two cells away, so the outer shell of
+    // the 5x5x5 block is searched too, skipping the cells that are all
+    // farther than F2 already
+    let shell = (-2..=2)
+        .flat_map(|z| {
+            (-2..=2)
+                .flat_map(move |y| (-2..=2).map(move |x| Vec3::new(x as f32, y as f32, z as f32)))
+        })
+        .filter(|g| g.abs().max_element() > 1.0);
+    for g in neighbours().chain(shell) {
+        let nearest = (g - f).max(Vec3::ZERO) + (f - g - 1.0).max(Vec3::ZERO);
+        if nearest.dot(nearest) >= f2 {
+            continue;
+        }
+        let r = g + feature_point(worley_wrap(cell + g, period)) - f;
+        let d = r.dot(r);
+        if d < f1 {
+            f2 = f1;
+            f1 = d;
+            closest = r;
+            closest_cell = g;
+        } else if d < f2 {
+            f2 = d;
+        }
+    }
+
+    // The borders are halfway between the closest point and its neighbours,
+    // https://iquilezles.org/articles/voronoilines/
+    let mut edge: f32 = 8.0;
+    for g in neighbours() {
+        let g = closest_cell + g;
+        let r = g + feature_point(worley_wrap(cell + g, period)) - f;
+        let between = r - closest;
+        if between.dot(between) > 1e-6 {
+            edge = edge.min((0.5 * (closest + r)).dot(between.normalize()));
+        }
+    }
+
+    Worley {
+        closest: f1.sqrt(),
+        second_closest: f2.sqrt(),
+        edge,
+    }
+}
+
+/// Worley noise, see [`Worley`]
+pub fn worley_noise_3d(p: Vec3) -> Worley {
+    worley_noise_3d_periodic(p, Vec3::ZERO)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::golden::assert_matches_shader;
+
+    /// Outputs of the WGSL function, see [`crate::golden`]: F1, F2, the edge
+    /// distance, and the edge distance with a period of `(4, 0, 7)`
+    const GOLDEN: [([f32; 3], [f32; 4]); 11] = [
+        (
+            [0.0, 0.0, 0.0],
+            [0.47012812, 0.77435815, 0.16267425, 0.058789916],
+        ),
+        (
+            [0.5, 0.25, 0.125],
+            [0.31450918, 0.9586092, 0.37269565, 0.3774811],
+        ),
+        (
+            [-3.7, 12.2, 5.5],
+            [0.50827855, 0.66683936, 0.10045315, 0.05646555],
+        ),
+        (
+            [123.456, -78.9, 0.001],
+            [0.79106045, 0.8203559, 0.025200471, 0.29981962],
+        ),
+        (
+            [-0.001, 0.999, -17.25],
+            [0.57972723, 0.7188223, 0.09304351, 0.087390035],
+        ),
+        ([7.3, 7.3, 7.3], [0.1567234, 0.723984, 0.3712106, 0.1644645]),
+        (
+            [290.5, -290.5, 1.75],
+            [0.3182125, 0.88811255, 0.380827, 0.04096984],
+        ),
+        (
+            [1000.25, 2000.75, -3000.5],
+            [0.22602186, 0.6487278, 0.25974166, 0.035438955],
+        ),
+        (
+            [0.1, -0.2, 0.3],
+            [0.77644026, 0.7837757, 0.0040815137, 0.018572379],
+        ),
+        (
+            [-45.67, 89.01, -23.45],
+            [0.5223947, 0.76336545, 0.16342524, 0.27128848],
+        ),
+        (
+            [14.84375, -15.0, -19.375],
+            [0.74485683, 1.0261731, 0.18091144, 0.07148401],
+        ),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for (p, expected) in GOLDEN {
+            let p = Vec3::from_array(p);
+            let worley = worley_noise_3d(p);
+            let values = [
+                worley.closest,
+                worley.second_closest,
+                worley.edge,
+                worley_noise_3d_periodic(p, Vec3::new(4.0, 0.0, 7.0)).edge,
+            ];
+            assert_matches_shader(&p.to_array(), &values, &expected);
+        }
+    }
+
+    /// Points spread through a 40 unit box around the origin
+    fn points(count: u32) -> impl Iterator<Item = Vec3> {
+        (0..count).map(|i| {
+            let i = i as f32;
+            Vec3::new(
+                (i * 0.618_034).fract(),
+                (i * 0.754_877_7).fract(),
+                (i * 0.569_840_3).fract(),
+            ) * 40.0
+                - 20.0
+        })
+    }
+
+    #[test]
+    fn matches_a_brute_force_search() {
+        for p in points(50_000) {
+            let cell = p.floor();
+            let mut distances: Vec<f32> = (-2..=2)
+                .flat_map(|z| {
+                    (-2..=2).flat_map(move |y| {
+                        (-2..=2).map(move |x| Vec3::new(x as f32, y as f32, z as f32))
+                    })
+                })
+                .map(|g| (g + feature_point(cell + g) - (p - cell)).length())
+                .collect();
+            distances.sort_by(f32::total_cmp);
+            let worley = worley_noise_3d(p);
+            assert!(
+                (worley.closest - distances[0]).abs() < 1e-5
+                    && (worley.second_closest - distances[1]).abs() < 1e-5,
+                "at {p}: {worley:?}, brute force {:?}",
+                &distances[..2]
+            );
+        }
+    }
+
+    #[test]
+    fn repeats_over_the_period() {
+        let period = Vec3::new(4.0, 0.0, 7.0);
+        for p in points(2_000) {
+            let worley = worley_noise_3d_periodic(p, period);
+            for shift in [
+                Vec3::new(4.0, 0.0, 0.0),
+                Vec3::new(-8.0, 0.0, 7.0),
+                Vec3::new(0.0, 0.0, -21.0),
+            ] {
+                let shifted = worley_noise_3d_periodic(p + shift, period);
+                assert!(
+                    (worley.closest - shifted.closest).abs() < 1e-4
+                        && (worley.second_closest - shifted.second_closest).abs() < 1e-4
+                        && (worley.edge - shifted.edge).abs() < 1e-4,
+                    "at {p} + {shift}: {worley:?} != {shifted:?}"
+                );
+            }
+        }
+        // The axis with a period of 0 doesn't repeat
+        let p = Vec3::new(0.3, 0.7, 0.1);
+        assert_ne!(
+            worley_noise_3d_periodic(p, period),
+            worley_noise_3d_periodic(p + Vec3::new(0.0, 4.0, 0.0), period)
+        );
+    }
+
+    #[test]
+    fn edge_is_at_least_half_of_f2_minus_f1() {
+        for p in [
+            Vec3::new(0.3, 0.7, 0.1),
+            Vec3::new(-1.25, 2.5, 6.5),
+            Vec3::new(4.2, -3.3, 0.9),
+        ] {
+            let worley = worley_noise_3d(p);
+            assert!(worley.closest <= worley.second_closest);
+            // Every border is at least that far, by the triangle inequality
+            assert!(worley.edge >= (worley.second_closest - worley.closest) / 2.0 - 1e-5);
+        }
+    }
+}
@@ -0,0 +1,692 @@
+//! CPU reference of the atmosphere lookup tables baked by `shaders/atmosphere_luts.wgsl`.
+//!
+//! The tables follow "A Scalable and Production Ready Sky and Atmosphere
+//! Rendering Technique" (Hillaire 2020), with the transmittance parameterization
+//! of Bruneton and Neyret. Everything here mirrors `shaders/atmosphere.wgsl`
+//! step for step, so the baked textures can be checked without a GPU.
+
+use std::f32::consts::PI;
+
+use bevy::math::{UVec2, Vec2, Vec3};
+
+pub use uniform::{Atmosphere, SkyView};
+
+/// Size of the transmittance LUT: cosine of the view zenith by altitude
+pub const TRANSMITTANCE_LUT_SIZE: UVec2 = UVec2::new(256, 64);
+/// Size of the multiple scattering LUT: cosine of the sun zenith by altitude
+pub const MULTISCATTERING_LUT_SIZE: UVec2 = UVec2::new(32, 32);
+/// Size of the sky-view LUT: azimuth from the sun by view zenith
+pub const SKY_VIEW_LUT_SIZE: UVec2 = UVec2::new(192, 108);
+/// Size of the sky irradiance LUT: cosine of the sun zenith by altitude
+pub const SKY_IRRADIANCE_LUT_SIZE: UVec2 = UVec2::new(32, 16);
+
+const TRANSMITTANCE_STEPS: u32 = 40;
+const MULTISCATTERING_STEPS: u32 = 20;
+/// Square root of the number of directions integrated per multiple scattering texel
+const MULTISCATTERING_SQRT_DIRECTIONS: u32 = 8;
+const SCATTERING_STEPS: u32 = 32;
+/// Square root of the number of directions integrated per sky irradiance texel
+const SKY_IRRADIANCE_SQRT_DIRECTIONS: u32 = 8;
+
+// Scopes the derive's lint allow, see `uniform` in `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy::{math::Vec3, render::render_resource::ShaderType};
+
+    /// Parameters of a Rayleigh + Mie atmosphere, matching `Atmosphere` in
+    /// `shaders/atmosphere.wgsl`. Lengths are in world units.
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct Atmosphere {
+        pub planet_radius: f32,
+        pub atmosphere_radius: f32,
+        /// Rayleigh scattering coefficients at the surface, per unit length
+        pub rayleigh_scattering: Vec3,
+        /// Altitude over which the Rayleigh density falls by a factor e
+        pub rayleigh_scale_height: f32,
+        /// Mie scattering coefficient at the surface, per unit length
+        pub mie_scattering: f32,
+        /// Mie extinction coefficient at the surface: scattering plus absorption
+        pub mie_extinction: f32,
+        pub mie_scale_height: f32,
+        /// Asymmetry of the Mie phase function, towards forward scattering when positive
+        pub mie_anisotropy: f32,
+    }
+
+    /// Where the sky-view LUT is seen from, matching `SkyView` in `shaders/atmosphere.wgsl`
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct SkyView {
+        /// Distance of the camera from the planet's center, inside the atmosphere
+        pub camera_radius: f32,
+        /// Cosine of the sun's zenith angle seen from the camera
+        pub sun_cos_zenith: f32,
+    }
+}
+
+/// A baked lookup table, row-major from the top left.
+///
+/// [`Lut::sample`] filters like a linearly sampled, edge-clamped texture, so
+/// the CPU tables can be chained the same way as the GPU ones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    pub size: UVec2,
+    pub data: Vec<Vec3>,
+}
+
+impl Lut {
+    /// Evaluates `texel` at the UV of the center of each texel
+    pub fn bake(size: UVec2, texel: impl Fn(Vec2) -> Vec3) -> Self {
+        let data = (0..size.y)
+            .flat_map(|y| (0..size.x).map(move |x| UVec2::new(x, y)))
+            .map(|texel_position| texel((texel_position.as_vec2() + 0.5) / size.as_vec2()))
+            .collect();
+        Self { size, data }
+    }
+
+    /// Bilinear sample, clamped to the edge texels
+    pub fn sample(&self, uv: Vec2) -> Vec3 {
+        let position = uv * self.size.as_vec2() - 0.5;
+        let base = position.floor();
+        let fraction = position - base;
+        let max = self.size.as_ivec2() - 1;
+        let texel = |offset_x: i32, offset_y: i32| {
+            let x = (base.x as i32 + offset_x).clamp(0, max.x);
+            let y = (base.y as i32 + offset_y).clamp(0, max.y);
+            self.data[(y * self.size.x as i32 + x) as usize]
+        };
+        let top = texel(0, 0).lerp(texel(1, 0), fraction.x);
+        let bottom = texel(0, 1).lerp(texel(1, 1), fraction.x);
+        top.lerp(bottom, fraction.y)
+    }
+}
+
+/// Scattering and extinction at a point in the atmosphere
+struct Medium {
+    rayleigh_scattering: Vec3,
+    mie_scattering: f32,
+    extinction: Vec3,
+}
+
+impl Medium {
+    fn scattering(&self) -> Vec3 {
+        self.rayleigh_scattering + self.mie_scattering
+    }
+}
+
+/// Cornette-Shanks phase function, `c` is the cosine of the scattering angle
+pub fn phase_mie(g: f32, c: f32) -> f32 {
+    let gg = g * g;
+    let a = (1.0 - gg) * (1.0 + c * c);
+    let mut b = 1.0 + gg - 2.0 * g * c;
+    b *= b.sqrt();
+    b *= 2.0 + gg;
+    (3.0 / 8.0 / PI) * a / b
+}
+
+pub fn phase_rayleigh(c: f32) -> f32 {
+    (3.0 / 16.0 / PI) * (1.0 + c * c)
+}
+
+/// Distance from a point at `r` from the center to the sphere of `radius`,
+/// along a ray whose zenith angle has the cosine `mu`. Assumes the point is inside.
+fn distance_to_sphere(r: f32, mu: f32, radius: f32) -> f32 {
+    let discriminant = r * r * (mu * mu - 1.0) + radius * radius;
+    -r * mu + discriminant.max(0.0).sqrt()
+}
+
+/// Distance from the center after travelling `t` from a point at `r`, at a zenith cosine `mu`
+fn radius_along_ray(r: f32, mu: f32, t: f32) -> f32 {
+    (r * r + 2.0 * r * mu * t + t * t).max(0.0).sqrt()
+}
+
+/// Keeps `a` away from zero, so it can be divided by
+fn non_zero(a: Vec3) -> Vec3 {
+    a.max(Vec3::splat(1e-9))
+}
+
+impl Default for Atmosphere {
+    /// Earth's atmosphere around a planet of unit radius
+    fn default() -> Self {
+        Self::earth_like(1.0, 1.0)
+    }
+}
+
+impl Atmosphere {
+    /// Earth's atmosphere around a planet of `planet_radius`, made `exaggeration`
+    /// times thicker so it reads from orbit. The scattering coefficients are
+    /// divided by the same factor, so the sky is as dense as on Earth.
+    pub fn earth_like(planet_radius: f32, exaggeration: f32) -> Self {
+        // World units per kilometer of Earth (radius 6360 km)
+        let scale = planet_radius / 6360.0 * exaggeration;
+        Self {
+            planet_radius,
+            atmosphere_radius: planet_radius + 60.0 * scale,
+            rayleigh_scattering: Vec3::new(5.802e-3, 13.558e-3, 33.1e-3) / scale,
+            rayleigh_scale_height: 8.0 * scale,
+            mie_scattering: 3.996e-3 / scale,
+            mie_extinction: 4.44e-3 / scale,
+            mie_scale_height: 1.2 * scale,
+            mie_anisotropy: 0.8,
+        }
+    }
+
+    fn medium(&self, r: f32) -> Medium {
+        let altitude = (r - self.planet_radius).max(0.0);
+        let rayleigh = (-altitude / self.rayleigh_scale_height).exp();
+        let mie = (-altitude / self.mie_scale_height).exp();
+        Medium {
+            rayleigh_scattering: self.rayleigh_scattering * rayleigh,
+            mie_scattering: self.mie_scattering * mie,
+            extinction: self.rayleigh_scattering * rayleigh + self.mie_extinction * mie,
+        }
+    }
+
+    /// Whether a ray from `r` at the zenith cosine `mu` hits the planet
+    pub fn ray_hits_ground(&self, r: f32, mu: f32) -> bool {
+        mu < 0.0 && r * r * (mu * mu - 1.0) + self.planet_radius * self.planet_radius >= 0.0
+    }
+
+    /// UV in the transmittance LUT of the ray from `r` at the zenith cosine `mu`
+    pub fn transmittance_lut_uv(&self, r: f32, mu: f32) -> Vec2 {
+        let bottom = self.planet_radius;
+        let top = self.atmosphere_radius;
+        // Distance to the horizon from the top of the atmosphere, and from `r`
+        let h = (top * top - bottom * bottom).sqrt();
+        let rho = (r * r - bottom * bottom).max(0.0).sqrt();
+        let d = distance_to_sphere(r, mu, top);
+        let d_min = top - r;
+        let d_max = rho + h;
+        Vec2::new((d - d_min) / (d_max - d_min), rho / h)
+    }
+
+    /// Inverse of [`Atmosphere::transmittance_lut_uv`], returns `(r, mu)`
+    pub fn transmittance_lut_r_mu(&self, uv: Vec2) -> (f32, f32) {
+        let bottom = self.planet_radius;
+        let top = self.atmosphere_radius;
+        let h = (top * top - bottom * bottom).sqrt();
+        let rho = h * uv.y;
+        let r = (rho * rho + bottom * bottom).sqrt();
+        let d_min = top - r;
+        let d_max = rho + h;
+        let d = d_min + uv.x * (d_max - d_min);
+        let mu = if d == 0.0 {
+            1.0
+        } else {
+            (h * h - rho * rho - d * d) / (2.0 * r * d)
+        };
+        (r, mu.clamp(-1.0, 1.0))
+    }
+
+    /// Transmittance from `r` to the top of the atmosphere at the zenith cosine `mu`,
+    /// ignoring the ground
+    pub fn transmittance_to_top(&self, r: f32, mu: f32) -> Vec3 {
+        let step = distance_to_sphere(r, mu, self.atmosphere_radius) / TRANSMITTANCE_STEPS as f32;
+        let optical_depth: Vec3 = (0..TRANSMITTANCE_STEPS)
+            .map(|i| {
+                let t = (i as f32 + 0.5) * step;
+                self.medium(radius_along_ray(r, mu, t)).extinction
+            })
+            .sum();
+        (-optical_depth * step).exp()
+    }
+
+    /// Bakes the transmittance LUT, see [`Atmosphere::transmittance_lut_uv`]
+    pub fn bake_transmittance_lut(&self, size: UVec2) -> Lut {
+        Lut::bake(size, |uv| {
+            let (r, mu) = self.transmittance_lut_r_mu(uv);
+            self.transmittance_to_top(r, mu)
+        })
+    }
+
+    /// Transmittance of the sunlight reaching `r` at the sun zenith cosine `mu`,
+    /// zero in the planet's shadow
+    fn sun_transmittance(&self, transmittance_lut: &Lut, r: f32, mu: f32) -> Vec3 {
+        if self.ray_hits_ground(r, mu) {
+            return Vec3::ZERO;
+        }
+        transmittance_lut.sample(self.transmittance_lut_uv(r, mu))
+    }
+
+    /// UV in the multiple scattering LUT for a point at `r` with the sun at the zenith cosine `mu`
+    pub fn multiscattering_lut_uv(&self, r: f32, mu: f32) -> Vec2 {
+        let altitude = (r - self.planet_radius) / (self.atmosphere_radius - self.planet_radius);
+        Vec2::new(mu * 0.5 + 0.5, altitude.clamp(0.0, 1.0))
+    }
+
+    /// Bakes the multiple scattering LUT: the isotropic light scattered more
+    /// than once, per unit of scattering coefficient and of sun illuminance
+    pub fn bake_multiscattering_lut(&self, transmittance_lut: &Lut, size: UVec2) -> Lut {
+        Lut::bake(size, |uv| {
+            let r = self.planet_radius + uv.y * (self.atmosphere_radius - self.planet_radius);
+            let sun_mu = uv.x * 2.0 - 1.0;
+            self.multiscattering(transmittance_lut, r, sun_mu)
+        })
+    }
+
+    fn multiscattering(&self, transmittance_lut: &Lut, r: f32, sun_mu: f32) -> Vec3 {
+        let position = Vec3::new(0.0, r, 0.0);
+        let to_sun = Vec3::new((1.0 - sun_mu * sun_mu).max(0.0).sqrt(), sun_mu, 0.0);
+        let isotropic_phase = 1.0 / (4.0 * PI);
+        let mut second_order = Vec3::ZERO;
+        let mut transfer = Vec3::ZERO;
+
+        // Uniformly distributed directions over the sphere
+        for i in 0..MULTISCATTERING_SQRT_DIRECTIONS {
+            for j in 0..MULTISCATTERING_SQRT_DIRECTIONS {
+                let theta = 2.0 * PI * (i as f32 + 0.5) / MULTISCATTERING_SQRT_DIRECTIONS as f32;
+                let cos_phi = 1.0 - 2.0 * (j as f32 + 0.5) / MULTISCATTERING_SQRT_DIRECTIONS as f32;
+                let sin_phi = (1.0 - cos_phi * cos_phi).sqrt();
+                let direction = Vec3::new(theta.cos() * sin_phi, cos_phi, theta.sin() * sin_phi);
+
+                let mu = direction.y;
+                let t_max = if self.ray_hits_ground(r, mu) {
+                    -r * mu
+                        - (r * r * (mu * mu - 1.0) + self.planet_radius * self.planet_radius).sqrt()
+                } else {
+                    distance_to_sphere(r, mu, self.atmosphere_radius)
+                };
+                let step = t_max / MULTISCATTERING_STEPS as f32;
+
+                let mut throughput = Vec3::ONE;
+                for k in 0..MULTISCATTERING_STEPS {
+                    let point = position + direction * (k as f32 + 0.5) * step;
+                    let point_r = point.length();
+                    let medium = self.medium(point_r);
+                    let scattering = medium.scattering();
+                    let extinction = non_zero(medium.extinction);
+                    let step_transmittance = (-medium.extinction * step).exp();
+
+                    let point_sun_mu = point.dot(to_sun) / point_r;
+                    let sun = self.sun_transmittance(transmittance_lut, point_r, point_sun_mu);
+                    let source = scattering * sun * isotropic_phase;
+                    // Analytic integration of the source over the step
+                    second_order +=
+                        throughput * (source - source * step_transmittance) / extinction;
+                    transfer +=
+                        throughput * (scattering - scattering * step_transmittance) / extinction;
+                    throughput *= step_transmittance;
+                }
+            }
+        }
+
+        let directions = (MULTISCATTERING_SQRT_DIRECTIONS * MULTISCATTERING_SQRT_DIRECTIONS) as f32;
+        second_order /= directions;
+        transfer /= directions;
+        // Sum of the geometric series of ever higher scattering orders
+        second_order / (Vec3::ONE - transfer)
+    }
+
+    /// Single and multiple scattering along a ray from `origin` in the unit
+    /// `direction`, over the distance `t_max`, for a sun of unit illuminance.
+    /// Returns the scattered radiance and the transmittance along the ray.
+    pub fn integrate_scattering(
+        &self,
+        transmittance_lut: &Lut,
+        multiscattering_lut: &Lut,
+        origin: Vec3,
+        direction: Vec3,
+        t_max: f32,
+        to_sun: Vec3,
+    ) -> (Vec3, Vec3) {
+        let c = direction.dot(to_sun);
+        let phase_r = phase_rayleigh(c);
+        let phase_m = phase_mie(self.mie_anisotropy, c);
+        let step = t_max / SCATTERING_STEPS as f32;
+
+        let mut radiance = Vec3::ZERO;
+        let mut throughput = Vec3::ONE;
+        for i in 0..SCATTERING_STEPS {
+            let point = origin + direction * (i as f32 + 0.5) * step;
+            let r = point.length();
+            let medium = self.medium(r);
+            let step_transmittance = (-medium.extinction * step).exp();
+
+            let sun_mu = point.dot(to_sun) / r;
+            let sun = self.sun_transmittance(transmittance_lut, r, sun_mu);
+            let multiscattering =
+                multiscattering_lut.sample(self.multiscattering_lut_uv(r, sun_mu));
+            let source = (medium.rayleigh_scattering * phase_r + medium.mie_scattering * phase_m)
+                * sun
+                + medium.scattering() * multiscattering;
+            radiance +=
+                throughput * (source - source * step_transmittance) / non_zero(medium.extinction);
+            throughput *= step_transmittance;
+        }
+        (radiance, throughput)
+    }
+
+    /// Bakes the sky irradiance LUT: the light from the sky on a surface facing
+    /// the zenith, over pi, for a sun of unit illuminance. Indexed like the
+    /// multiple scattering LUT, with [`Atmosphere::multiscattering_lut_uv`].
+    pub fn bake_sky_irradiance_lut(
+        &self,
+        transmittance_lut: &Lut,
+        multiscattering_lut: &Lut,
+        size: UVec2,
+    ) -> Lut {
+        Lut::bake(size, |uv| {
+            let r = self.planet_radius + uv.y * (self.atmosphere_radius - self.planet_radius);
+            let sun_mu = uv.x * 2.0 - 1.0;
+            self.sky_irradiance(transmittance_lut, multiscattering_lut, r, sun_mu)
+        })
+    }
+
+    fn sky_irradiance(
+        &self,
+        transmittance_lut: &Lut,
+        multiscattering_lut: &Lut,
+        r: f32,
+        sun_mu: f32,
+    ) -> Vec3 {
+        let position = Vec3::new(0.0, r, 0.0);
+        let to_sun = Vec3::new((1.0 - sun_mu * sun_mu).max(0.0).sqrt(), sun_mu, 0.0);
+        let mut irradiance = Vec3::ZERO;
+
+        // Uniformly distributed directions over the upper hemisphere, which never meet the ground
+        for i in 0..SKY_IRRADIANCE_SQRT_DIRECTIONS {
+            for j in 0..SKY_IRRADIANCE_SQRT_DIRECTIONS {
+                let theta = 2.0 * PI * (i as f32 + 0.5) / SKY_IRRADIANCE_SQRT_DIRECTIONS as f32;
+                let cos_phi = (j as f32 + 0.5) / SKY_IRRADIANCE_SQRT_DIRECTIONS as f32;
+                let sin_phi = (1.0 - cos_phi * cos_phi).sqrt();
+                let direction = Vec3::new(theta.cos() * sin_phi, cos_phi, theta.sin() * sin_phi);
+                let t_max = distance_to_sphere(r, cos_phi, self.atmosphere_radius);
+                let (radiance, _) = self.integrate_scattering(
+                    transmittance_lut,
+                    multiscattering_lut,
+                    position,
+                    direction,
+                    t_max,
+                    to_sun,
+                );
+                irradiance += radiance * cos_phi;
+            }
+        }
+
+        // Each direction covers 2 pi / n steradians, and the irradiance is divided by pi
+        let directions = (SKY_IRRADIANCE_SQRT_DIRECTIONS * SKY_IRRADIANCE_SQRT_DIRECTIONS) as f32;
+        irradiance * 2.0 / directions
+    }
+
+    /// Zenith angle of the horizon and the angle between the horizon and the nadir at `r`
+    fn horizon_angles(&self, r: f32) -> (f32, f32) {
+        let horizon = (r * r - self.planet_radius * self.planet_radius)
+            .max(0.0)
+            .sqrt();
+        let beta = (horizon / r).clamp(-1.0, 1.0).acos();
+        (PI - beta, beta)
+    }
+
+    /// View zenith angle and azimuth from the sun of a texel in the sky-view LUT.
+    /// Half the LUT height covers the sky and half the ground, squeezed towards the horizon.
+    pub fn sky_view_lut_angles(&self, r: f32, uv: Vec2) -> (f32, f32) {
+        let (zenith_horizon_angle, beta) = self.horizon_angles(r);
+        let view_zenith = if uv.y < 0.5 {
+            let coord = 1.0 - 2.0 * uv.y;
+            zenith_horizon_angle * (1.0 - coord * coord)
+        } else {
+            let coord = uv.y * 2.0 - 1.0;
+            zenith_horizon_angle + beta * coord * coord
+        };
+        // More resolution around the sun
+        (view_zenith, PI * uv.x * uv.x)
+    }
+
+    /// Inverse of [`Atmosphere::sky_view_lut_angles`]
+    pub fn sky_view_lut_uv(&self, r: f32, view_zenith: f32, azimuth: f32) -> Vec2 {
+        let (zenith_horizon_angle, beta) = self.horizon_angles(r);
+        let v = if view_zenith < zenith_horizon_angle {
+            let coord = (1.0 - view_zenith / zenith_horizon_angle).max(0.0).sqrt();
+            (1.0 - coord) * 0.5
+        } else {
+            let coord = ((view_zenith - zenith_horizon_angle) / beta)
+                .max(0.0)
+                .sqrt();
+            coord * 0.5 + 0.5
+        };
+        Vec2::new((azimuth.abs() / PI).min(1.0).sqrt(), v)
+    }
+
+    /// Keeps the sky-view camera inside the atmosphere, where the LUT is defined
+    pub fn sky_view_radius(&self, camera_radius: f32) -> f32 {
+        let thickness = self.atmosphere_radius - self.planet_radius;
+        camera_radius.clamp(
+            self.planet_radius + thickness * 1e-3,
+            self.atmosphere_radius - thickness * 1e-3,
+        )
+    }
+
+    /// Bakes the sky-view LUT: the sky radiance around a camera for a sun of unit illuminance
+    pub fn bake_sky_view_lut(
+        &self,
+        transmittance_lut: &Lut,
+        multiscattering_lut: &Lut,
+        sky_view: SkyView,
+        size: UVec2,
+    ) -> Lut {
+        let r = self.sky_view_radius(sky_view.camera_radius);
+        let sun_mu = sky_view.sun_cos_zenith;
+        let to_sun = Vec3::new((1.0 - sun_mu * sun_mu).max(0.0).sqrt(), sun_mu, 0.0);
+        Lut::bake(size, |uv| {
+            let (view_zenith, azimuth) = self.sky_view_lut_angles(r, uv);
+            let direction = Vec3::new(
+                view_zenith.sin() * azimuth.cos(),
+                view_zenith.cos(),
+                view_zenith.sin() * azimuth.sin(),
+            );
+            let mu = direction.y;
+            let t_max = if self.ray_hits_ground(r, mu) {
+                -r * mu
+                    - (r * r * (mu * mu - 1.0) + self.planet_radius * self.planet_radius)
+                        .max(0.0)
+                        .sqrt()
+            } else {
+                distance_to_sphere(r, mu, self.atmosphere_radius)
+            };
+            self.integrate_scattering(
+                transmittance_lut,
+                multiscattering_lut,
+                Vec3::new(0.0, r, 0.0),
+                direction,
+                t_max,
+                to_sun,
+            )
+            .0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn earth() -> Atmosphere {
+        Atmosphere::earth_like(150.0, 6.0)
+    }
+
+    #[test]
+    fn transmittance_uv_round_trips() {
+        let atmosphere = earth();
+        for &uv in &[
+            Vec2::new(0.1, 0.2),
+            Vec2::new(0.5, 0.5),
+            Vec2::new(0.9, 0.7),
+            Vec2::new(0.3, 0.95),
+        ] {
+            let (r, mu) = atmosphere.transmittance_lut_r_mu(uv);
+            let round_trip = atmosphere.transmittance_lut_uv(r, mu);
+            assert!(
+                (round_trip - uv).length() < 1e-3,
+                "{uv} -> ({r}, {mu}) -> {round_trip}"
+            );
+        }
+    }
+
+    #[test]
+    fn transmittance_is_one_at_the_top() {
+        let atmosphere = earth();
+        let transmittance = atmosphere.transmittance_to_top(atmosphere.atmosphere_radius, 1.0);
+        assert!((transmittance - Vec3::ONE).abs().max_element() < 1e-6);
+    }
+
+    #[test]
+    fn vertical_transmittance_matches_the_analytic_optical_depth() {
+        let atmosphere = earth();
+        let thickness = atmosphere.atmosphere_radius - atmosphere.planet_radius;
+        // Integral of an exponential density from the ground to the top
+        let column = |scale_height: f32| scale_height * (1.0 - (-thickness / scale_height).exp());
+        let optical_depth = atmosphere.rayleigh_scattering
+            * column(atmosphere.rayleigh_scale_height)
+            + atmosphere.mie_extinction * column(atmosphere.mie_scale_height);
+
+        let transmittance = atmosphere.transmittance_to_top(atmosphere.planet_radius, 1.0);
+        let measured = -Vec3::new(
+            transmittance.x.ln(),
+            transmittance.y.ln(),
+            transmittance.z.ln(),
+        );
+        assert!(
+            ((measured - optical_depth) / optical_depth)
+                .abs()
+                .max_element()
+                < 1e-2,
+            "{measured} != {optical_depth}"
+        );
+    }
+
+    #[test]
+    fn transmittance_falls_towards_the_horizon_and_reddens() {
+        let atmosphere = earth();
+        let r = atmosphere.planet_radius;
+        let zenith = atmosphere.transmittance_to_top(r, 1.0);
+        let horizon = atmosphere.transmittance_to_top(r, 0.05);
+        assert!(horizon.cmplt(zenith).all());
+        // Rayleigh scattering takes out blue first
+        assert!(horizon.x > horizon.y && horizon.y > horizon.z);
+    }
+
+    #[test]
+    fn transmittance_lut_matches_direct_evaluation() {
+        let atmosphere = earth();
+        let lut = atmosphere.bake_transmittance_lut(TRANSMITTANCE_LUT_SIZE);
+        for &(r_fraction, mu) in &[(0.1, 0.8), (0.5, 0.3), (0.0, 0.6), (0.9, -0.2)] {
+            let r = atmosphere.planet_radius
+                + r_fraction * (atmosphere.atmosphere_radius - atmosphere.planet_radius);
+            let sampled = lut.sample(atmosphere.transmittance_lut_uv(r, mu));
+            let direct = atmosphere.transmittance_to_top(r, mu);
+            assert!(
+                (sampled - direct).abs().max_element() < 1e-2,
+                "{sampled} != {direct}"
+            );
+        }
+    }
+
+    #[test]
+    fn lut_samples_texel_centers_exactly() {
+        let lut = Lut::bake(UVec2::new(4, 3), |uv| uv.extend(0.0));
+        let uv = Vec2::new(2.5 / 4.0, 1.5 / 3.0);
+        assert_eq!(lut.sample(uv), uv.extend(0.0));
+        // Clamped past the edges
+        assert_eq!(
+            lut.sample(Vec2::new(-1.0, 2.0)),
+            Vec3::new(0.125, 2.5 / 3.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn multiscattering_vanishes_without_scattering() {
+        let atmosphere = Atmosphere {
+            rayleigh_scattering: Vec3::ZERO,
+            mie_scattering: 0.0,
+            mie_extinction: 0.0,
+            ..earth()
+        };
+        let size = UVec2::new(8, 8);
+        let transmittance = atmosphere.bake_transmittance_lut(size);
+        let multiscattering = atmosphere.bake_multiscattering_lut(&transmittance, size);
+        assert!(multiscattering
+            .data
+            .iter()
+            .all(|&texel| texel == Vec3::ZERO));
+    }
+
+    #[test]
+    fn multiscattering_grows_with_the_sun() {
+        let atmosphere = earth();
+        let transmittance = atmosphere.bake_transmittance_lut(UVec2::new(64, 16));
+        let multiscattering =
+            atmosphere.bake_multiscattering_lut(&transmittance, UVec2::new(16, 8));
+        assert!(multiscattering
+            .data
+            .iter()
+            .all(|texel| texel.is_finite() && texel.min_element() >= 0.0));
+
+        let ground = atmosphere.planet_radius;
+        let night = multiscattering.sample(atmosphere.multiscattering_lut_uv(ground, -0.9));
+        let noon = multiscattering.sample(atmosphere.multiscattering_lut_uv(ground, 1.0));
+        assert!(noon.cmpgt(night).all(), "{noon} <= {night}");
+    }
+
+    #[test]
+    fn sky_view_uv_round_trips() {
+        let atmosphere = earth();
+        let r = atmosphere.planet_radius + 0.5;
+        for &uv in &[
+            Vec2::new(0.2, 0.1),
+            Vec2::new(0.7, 0.45),
+            Vec2::new(0.5, 0.8),
+        ] {
+            let (view_zenith, azimuth) = atmosphere.sky_view_lut_angles(r, uv);
+            let round_trip = atmosphere.sky_view_lut_uv(r, view_zenith, azimuth);
+            assert!((round_trip - uv).length() < 1e-4, "{uv} -> {round_trip}");
+        }
+    }
+
+    #[test]
+    fn noon_sky_is_blue_and_brightest_around_the_sun() {
+        let atmosphere = earth();
+        let transmittance = atmosphere.bake_transmittance_lut(UVec2::new(64, 16));
+        let multiscattering =
+            atmosphere.bake_multiscattering_lut(&transmittance, UVec2::new(16, 16));
+        let r = atmosphere.planet_radius + 0.1;
+        let sky_view = SkyView {
+            camera_radius: r,
+            sun_cos_zenith: 0.5,
+        };
+        let sky = atmosphere.bake_sky_view_lut(
+            &transmittance,
+            &multiscattering,
+            sky_view,
+            UVec2::new(32, 32),
+        );
+
+        let zenith = sky.sample(atmosphere.sky_view_lut_uv(r, 0.1, 0.0));
+        assert!(zenith.z > zenith.y && zenith.y > zenith.x, "{zenith}");
+
+        let sun_zenith = 0.5f32.acos();
+        let towards_sun = sky.sample(atmosphere.sky_view_lut_uv(r, sun_zenith, 0.0));
+        let away_from_sun = sky.sample(atmosphere.sky_view_lut_uv(r, sun_zenith, PI));
+        assert!(towards_sun.length() > away_from_sun.length());
+    }
+
+    #[test]
+    fn sky_irradiance_follows_the_sun() {
+        let atmosphere = earth();
+        let transmittance = atmosphere.bake_transmittance_lut(UVec2::new(64, 16));
+        let multiscattering =
+            atmosphere.bake_multiscattering_lut(&transmittance, UVec2::new(16, 8));
+        let irradiance =
+            atmosphere.bake_sky_irradiance_lut(&transmittance, &multiscattering, UVec2::new(16, 4));
+        assert!(irradiance
+            .data
+            .iter()
+            .all(|texel| texel.is_finite() && texel.min_element() >= 0.0));
+
+        let ground = atmosphere.planet_radius;
+        let night = irradiance.sample(atmosphere.multiscattering_lut_uv(ground, -0.9));
+        let sunset = irradiance.sample(atmosphere.multiscattering_lut_uv(ground, 0.05));
+        let noon = irradiance.sample(atmosphere.multiscattering_lut_uv(ground, 1.0));
+        assert!(noon.cmpgt(sunset).all() && sunset.cmpgt(night).all());
+        // Blue sky, and much dimmer than the sun itself
+        assert!(noon.z > noon.x, "{noon}");
+        assert!(noon.max_element() < 1.0, "{noon}");
+    }
+}
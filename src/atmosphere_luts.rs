@@ -0,0 +1,449 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{AssetId, AssetServer, Assets, Handle},
+    core_pipeline::core_3d::Camera3d,
+    math::{UVec2, Vec3},
+    pbr::DirectionalLight,
+    prelude::{
+        Entity, FromWorld, GlobalTransform, Image, IntoSystemConfigs, Local, Query, Res, ResMut,
+        Resource, With, World,
+    },
+    render::{
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_resource::{
+            binding_types::{sampler, texture_2d, texture_storage_2d, uniform_buffer},
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            CachedComputePipelineId, CommandEncoder, CommandEncoderDescriptor,
+            ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Extent3d,
+            PipelineCache, SamplerBindingType, ShaderStages, StorageTextureAccess,
+            TextureDimension, TextureFormat, TextureSampleType, TextureUsages, UniformBuffer,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::{GpuImage, ImageSampler},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    transform::TransformSystem,
+    utils::HashMap,
+};
+use planet_material::atmosphere::{Atmosphere, SkyView};
+
+use crate::atmosphere_pass::AtmosphereBody;
+
+const ATMOSPHERE_LUTS_SHADER_ASSET_PATH: &str = "shaders/atmosphere_luts.wgsl";
+const WORKGROUP_SIZE: u32 = 8;
+
+/// Bakes the lookup tables of every [`AtmosphereBody`] on the GPU.
+///
+/// The transmittance, multiple scattering and sky irradiance tables are
+/// rebaked when the atmosphere parameters change. The sky view also depends on where the
+/// camera and the sun are, and is rebaked as they move while the camera is
+/// inside the atmosphere.
+pub(crate) struct AtmosphereLutPlugin;
+
+impl Plugin for AtmosphereLutPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AtmosphereLutBakes>().add_systems(
+            PostUpdate,
+            queue_atmosphere_luts.after(TransformSystem::TransformPropagate),
+        );
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<PendingAtmosphereLutBakes>()
+            .add_systems(ExtractSchedule, extract_atmosphere_lut_bakes)
+            .add_systems(
+                Render,
+                bake_atmosphere_luts.in_set(RenderSet::PrepareBindGroups),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<AtmosphereLutPipelines>();
+    }
+}
+
+/// A blank lookup table that the compute shaders can write and the atmosphere pass can sample
+pub(crate) fn lut_image(size: UVec2, images: &mut Assets<Image>) -> Handle<Image> {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0; 8],
+        TextureFormat::Rgba16Float,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING | TextureUsages::COPY_DST;
+    image.sampler = ImageSampler::linear();
+    images.add(image)
+}
+
+#[derive(Clone)]
+struct AtmosphereLutBake {
+    atmosphere: Atmosphere,
+    sky_view: SkyView,
+    /// The transmittance, multiple scattering and sky irradiance tables are up to date
+    sky_view_only: bool,
+    transmittance_lut: AssetId<Image>,
+    multiscattering_lut: AssetId<Image>,
+    sky_irradiance_lut: AssetId<Image>,
+    sky_view_lut: AssetId<Image>,
+}
+
+/// The bakes queued this frame, extracted to the render world
+#[derive(Resource, Default)]
+struct AtmosphereLutBakes(Vec<AtmosphereLutBake>);
+
+/// The bakes waiting in the render world for their pipelines and textures
+#[derive(Resource, Default)]
+struct PendingAtmosphereLutBakes(Vec<AtmosphereLutBake>);
+
+fn queue_atmosphere_luts(
+    mut bakes: ResMut<AtmosphereLutBakes>,
+    mut baked: Local<HashMap<Entity, (Atmosphere, SkyView)>>,
+    atmospheres: Query<(Entity, &GlobalTransform, &AtmosphereBody)>,
+    cameras: Query<&GlobalTransform, With<Camera3d>>,
+    lights: Query<&GlobalTransform, With<DirectionalLight>>,
+) {
+    bakes.0.clear();
+    let camera = cameras.iter().next().map(GlobalTransform::translation);
+    let sun_direction = lights
+        .iter()
+        .next()
+        .map_or(Vec3::Y, |transform| transform.back().as_vec3());
+    baked.retain(|entity, _| atmospheres.contains(*entity));
+
+    for (entity, transform, body) in &atmospheres {
+        let atmosphere = body.atmosphere;
+
+        // Seen from the camera, in the planet's frame
+        let camera_position = camera
+            .map(|camera| transform.affine().inverse().transform_point3(camera))
+            .unwrap_or_default();
+        let camera_radius = camera_position.length();
+        let up = (camera.unwrap_or_default() - transform.translation()).normalize_or_zero();
+        let sky_view = SkyView {
+            camera_radius: atmosphere.sky_view_radius(camera_radius),
+            sun_cos_zenith: up.dot(sun_direction),
+        };
+
+        let sky_view_only = match baked.get(&entity) {
+            Some((baked_atmosphere, _)) if *baked_atmosphere != atmosphere => false,
+            None => false,
+            Some((_, baked_sky_view)) => {
+                // Outside the atmosphere the sky view is never read
+                let thickness = atmosphere.atmosphere_radius - atmosphere.planet_radius;
+                let moved = (baked_sky_view.camera_radius - sky_view.camera_radius).abs()
+                    > thickness * 1e-3
+                    || (baked_sky_view.sun_cos_zenith - sky_view.sun_cos_zenith).abs() > 1e-3;
+                if camera_radius > atmosphere.atmosphere_radius || !moved {
+                    continue;
+                }
+                true
+            }
+        };
+
+        baked.insert(entity, (atmosphere, sky_view));
+        bakes.0.push(AtmosphereLutBake {
+            atmosphere,
+            sky_view,
+            sky_view_only,
+            transmittance_lut: body.transmittance_lut.id(),
+            multiscattering_lut: body.multiscattering_lut.id(),
+            sky_irradiance_lut: body.sky_irradiance_lut.id(),
+            sky_view_lut: body.sky_view_lut.id(),
+        });
+    }
+}
+
+fn extract_atmosphere_lut_bakes(
+    mut pending: ResMut<PendingAtmosphereLutBakes>,
+    bakes: Extract<Res<AtmosphereLutBakes>>,
+) {
+    for bake in &bakes.0 {
+        // A newer bake of the same tables replaces one that is still waiting
+        match pending
+            .0
+            .iter_mut()
+            .find(|pending| pending.sky_view_lut == bake.sky_view_lut)
+        {
+            Some(pending) => {
+                let sky_view_only = pending.sky_view_only && bake.sky_view_only;
+                *pending = AtmosphereLutBake {
+                    sky_view_only,
+                    ..bake.clone()
+                };
+            }
+            None => pending.0.push(bake.clone()),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct AtmosphereLutPipelines {
+    transmittance_layout: BindGroupLayout,
+    multiscattering_layout: BindGroupLayout,
+    sky_irradiance_layout: BindGroupLayout,
+    sky_view_layout: BindGroupLayout,
+    transmittance: CachedComputePipelineId,
+    multiscattering: CachedComputePipelineId,
+    sky_irradiance: CachedComputePipelineId,
+    sky_view: CachedComputePipelineId,
+}
+
+impl FromWorld for AtmosphereLutPipelines {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let output =
+            texture_storage_2d(TextureFormat::Rgba16Float, StorageTextureAccess::WriteOnly);
+        let lut = texture_2d(TextureSampleType::Float { filterable: true });
+        let lut_sampler = sampler(SamplerBindingType::Filtering);
+
+        // The bindings match `atmosphere_luts.wgsl`, each pass uses a subset
+        let transmittance_layout = render_device.create_bind_group_layout(
+            "atmosphere_transmittance_lut_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                ((0, uniform_buffer::<Atmosphere>(false)), (5, output)),
+            ),
+        );
+        let multiscattering_layout = render_device.create_bind_group_layout(
+            "atmosphere_multiscattering_lut_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                (
+                    (0, uniform_buffer::<Atmosphere>(false)),
+                    (2, lut),
+                    (4, lut_sampler),
+                    (5, output),
+                ),
+            ),
+        );
+        let sky_irradiance_layout = render_device.create_bind_group_layout(
+            "atmosphere_sky_irradiance_lut_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                (
+                    (0, uniform_buffer::<Atmosphere>(false)),
+                    (2, lut),
+                    (3, lut),
+                    (4, lut_sampler),
+                    (5, output),
+                ),
+            ),
+        );
+        let sky_view_layout = render_device.create_bind_group_layout(
+            "atmosphere_sky_view_lut_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                (
+                    (0, uniform_buffer::<Atmosphere>(false)),
+                    (1, uniform_buffer::<SkyView>(false)),
+                    (2, lut),
+                    (3, lut),
+                    (4, lut_sampler),
+                    (5, output),
+                ),
+            ),
+        );
+
+        let shader = world
+            .resource::<AssetServer>()
+            .load(ATMOSPHERE_LUTS_SHADER_ASSET_PATH);
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |label: &'static str, layout: &BindGroupLayout, entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(label.into()),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs: Vec::new(),
+                entry_point: entry_point.into(),
+            })
+        };
+        let transmittance = queue(
+            "atmosphere_transmittance_lut_pipeline",
+            &transmittance_layout,
+            "bake_transmittance",
+        );
+        let multiscattering = queue(
+            "atmosphere_multiscattering_lut_pipeline",
+            &multiscattering_layout,
+            "bake_multiscattering",
+        );
+        let sky_irradiance = queue(
+            "atmosphere_sky_irradiance_lut_pipeline",
+            &sky_irradiance_layout,
+            "bake_sky_irradiance",
+        );
+        let sky_view = queue(
+            "atmosphere_sky_view_lut_pipeline",
+            &sky_view_layout,
+            "bake_sky_view",
+        );
+
+        Self {
+            transmittance_layout,
+            multiscattering_layout,
+            sky_irradiance_layout,
+            sky_view_layout,
+            transmittance,
+            multiscattering,
+            sky_irradiance,
+            sky_view,
+        }
+    }
+}
+
+fn dispatch(
+    encoder: &mut CommandEncoder,
+    pipeline: &ComputePipeline,
+    bind_group: &BindGroup,
+    output: &GpuImage,
+) {
+    let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+        label: Some("atmosphere_lut_pass"),
+        timestamp_writes: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.dispatch_workgroups(
+        output.size.x.div_ceil(WORKGROUP_SIZE),
+        output.size.y.div_ceil(WORKGROUP_SIZE),
+        1,
+    );
+}
+
+fn bake_atmosphere_luts(
+    mut pending: ResMut<PendingAtmosphereLutBakes>,
+    pipelines: Res<AtmosphereLutPipelines>,
+    pipeline_cache: Res<PipelineCache>,
+    images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    if pending.0.is_empty() {
+        return;
+    }
+    // Retried next frame while the shader is still loading
+    let (
+        Some(transmittance_pipeline),
+        Some(multiscattering_pipeline),
+        Some(sky_irradiance_pipeline),
+        Some(sky_view_pipeline),
+    ) = (
+        pipeline_cache.get_compute_pipeline(pipelines.transmittance),
+        pipeline_cache.get_compute_pipeline(pipelines.multiscattering),
+        pipeline_cache.get_compute_pipeline(pipelines.sky_irradiance),
+        pipeline_cache.get_compute_pipeline(pipelines.sky_view),
+    )
+    else {
+        return;
+    };
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("atmosphere_luts"),
+    });
+    pending.0.retain(|bake| {
+        let (
+            Some(transmittance_lut),
+            Some(multiscattering_lut),
+            Some(sky_irradiance_lut),
+            Some(sky_view_lut),
+        ) = (
+            images.get(bake.transmittance_lut),
+            images.get(bake.multiscattering_lut),
+            images.get(bake.sky_irradiance_lut),
+            images.get(bake.sky_view_lut),
+        )
+        else {
+            return true;
+        };
+
+        let mut atmosphere_buffer = UniformBuffer::from(bake.atmosphere);
+        atmosphere_buffer.write_buffer(&render_device, &render_queue);
+        let mut sky_view_buffer = UniformBuffer::from(bake.sky_view);
+        sky_view_buffer.write_buffer(&render_device, &render_queue);
+        let (Some(atmosphere), Some(sky_view)) =
+            (atmosphere_buffer.binding(), sky_view_buffer.binding())
+        else {
+            return true;
+        };
+
+        if !bake.sky_view_only {
+            let bind_group = render_device.create_bind_group(
+                "atmosphere_transmittance_lut_bind_group",
+                &pipelines.transmittance_layout,
+                &BindGroupEntries::with_indices((
+                    (0, atmosphere.clone()),
+                    (5, &transmittance_lut.texture_view),
+                )),
+            );
+            dispatch(
+                &mut encoder,
+                transmittance_pipeline,
+                &bind_group,
+                transmittance_lut,
+            );
+
+            let bind_group = render_device.create_bind_group(
+                "atmosphere_multiscattering_lut_bind_group",
+                &pipelines.multiscattering_layout,
+                &BindGroupEntries::with_indices((
+                    (0, atmosphere.clone()),
+                    (2, &transmittance_lut.texture_view),
+                    (4, &transmittance_lut.sampler),
+                    (5, &multiscattering_lut.texture_view),
+                )),
+            );
+            dispatch(
+                &mut encoder,
+                multiscattering_pipeline,
+                &bind_group,
+                multiscattering_lut,
+            );
+
+            let bind_group = render_device.create_bind_group(
+                "atmosphere_sky_irradiance_lut_bind_group",
+                &pipelines.sky_irradiance_layout,
+                &BindGroupEntries::with_indices((
+                    (0, atmosphere.clone()),
+                    (2, &transmittance_lut.texture_view),
+                    (3, &multiscattering_lut.texture_view),
+                    (4, &transmittance_lut.sampler),
+                    (5, &sky_irradiance_lut.texture_view),
+                )),
+            );
+            dispatch(
+                &mut encoder,
+                sky_irradiance_pipeline,
+                &bind_group,
+                sky_irradiance_lut,
+            );
+        }
+
+        let bind_group = render_device.create_bind_group(
+            "atmosphere_sky_view_lut_bind_group",
+            &pipelines.sky_view_layout,
+            &BindGroupEntries::with_indices((
+                (0, atmosphere),
+                (1, sky_view),
+                (2, &transmittance_lut.texture_view),
+                (3, &multiscattering_lut.texture_view),
+                (4, &transmittance_lut.sampler),
+                (5, &sky_view_lut.texture_view),
+            )),
+        );
+        dispatch(&mut encoder, sky_view_pipeline, &bind_group, sky_view_lut);
+        false
+    });
+    render_queue.submit([encoder.finish()]);
+}
@@ -0,0 +1,456 @@
+use bevy::{
+    app::{App, Plugin},
+    asset::{AssetId, AssetServer, Assets, Handle},
+    core_pipeline::{
+        core_3d::graph::{Core3d, Node3d},
+        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
+        prepass::{DepthPrepass, ViewPrepassTextures},
+    },
+    ecs::query::QueryItem,
+    pbr::DirectionalLight,
+    prelude::{
+        Commands, Component, Entity, FromWorld, GlobalTransform, Image, IntoSystemConfigs, Query,
+        Res, ResMut, Resource, With, World,
+    },
+    render::{
+        render_asset::RenderAssets,
+        render_graph::{
+            NodeRunError, RenderGraphApp, RenderGraphContext, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            binding_types::{
+                sampler, texture_2d, texture_depth_2d, texture_depth_2d_multisampled,
+                uniform_buffer,
+            },
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            CachedRenderPipelineId, ColorTargetState, ColorWrites, FragmentState, MultisampleState,
+            Operations, PipelineCache, PrimitiveState, RenderPassColorAttachment,
+            RenderPassDescriptor, RenderPipelineDescriptor, SamplerBindingType, Shader,
+            ShaderStages, SpecializedRenderPipeline, SpecializedRenderPipelines, TextureFormat,
+            TextureSampleType, UniformBuffer,
+        },
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        texture::{BevyDefault, GpuImage},
+        view::{ExtractedView, Msaa, ViewTarget, ViewUniform, ViewUniformOffset, ViewUniforms},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+};
+use planet_material::atmosphere::{
+    Atmosphere, MULTISCATTERING_LUT_SIZE, SKY_IRRADIANCE_LUT_SIZE, SKY_VIEW_LUT_SIZE,
+    TRANSMITTANCE_LUT_SIZE,
+};
+
+use crate::atmosphere_luts::{lut_image, AtmosphereLutPlugin};
+
+use uniform::AtmosphereBodyUniform;
+
+const ATMOSPHERE_PASS_SHADER_ASSET_PATH: &str = "shaders/atmosphere_pass.wgsl";
+
+/// Draws the atmosphere of every [`AtmosphereBody`] in a fullscreen pass
+/// after the main pass.
+///
+/// Only cameras with a [`DepthPrepass`] draw atmospheres: the prepass depth
+/// bounds the rays, so terrain and other bodies get the right amount of haze
+/// in front of them, from space down to the ground.
+pub(crate) struct AtmospherePlugin;
+
+impl Plugin for AtmospherePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(AtmosphereLutPlugin);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedRenderPipelines<AtmospherePassPipeline>>()
+            .add_systems(ExtractSchedule, extract_atmosphere_bodies)
+            .add_systems(
+                Render,
+                (
+                    prepare_atmosphere_pipelines.in_set(RenderSet::Prepare),
+                    prepare_atmosphere_bind_groups.in_set(RenderSet::PrepareBindGroups),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<AtmosphereNode>>(Core3d, AtmosphereLabel)
+            .add_render_graph_edges(
+                Core3d,
+                (Node3d::EndMainPass, AtmosphereLabel, Node3d::Bloom),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<AtmospherePassPipeline>();
+    }
+}
+
+/// An atmosphere around the entity's origin, which should be the planet's
+/// center. Lengths are in world units, the entity should not be scaled.
+///
+/// The lookup tables are baked by the [`AtmosphereLutPlugin`] and lit by the
+/// first [`DirectionalLight`].
+#[derive(Component, Debug, Clone)]
+pub(crate) struct AtmosphereBody {
+    pub(crate) atmosphere: Atmosphere,
+    pub(crate) transmittance_lut: Handle<Image>,
+    pub(crate) multiscattering_lut: Handle<Image>,
+    /// Lights the ground under the atmosphere, sampled by the planet material
+    pub(crate) sky_irradiance_lut: Handle<Image>,
+    pub(crate) sky_view_lut: Handle<Image>,
+}
+
+impl AtmosphereBody {
+    /// An atmosphere with blank lookup tables, to be baked
+    pub(crate) fn new(atmosphere: Atmosphere, images: &mut Assets<Image>) -> Self {
+        Self {
+            atmosphere,
+            transmittance_lut: lut_image(TRANSMITTANCE_LUT_SIZE, images),
+            multiscattering_lut: lut_image(MULTISCATTERING_LUT_SIZE, images),
+            sky_irradiance_lut: lut_image(SKY_IRRADIANCE_LUT_SIZE, images),
+            sky_view_lut: lut_image(SKY_VIEW_LUT_SIZE, images),
+        }
+    }
+}
+
+// Scopes the derive's lint allow, see `uniform` in `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy::{math::Vec3, render::render_resource::ShaderType};
+
+    /// Where an atmosphere is and how it is lit, matches `Body` in `atmosphere_pass.wgsl`
+    #[derive(ShaderType, Clone, Copy)]
+    pub(super) struct AtmosphereBodyUniform {
+        pub(super) center: Vec3,
+        pub(super) sun_illuminance: f32,
+        pub(super) sun_direction: Vec3,
+    }
+}
+
+struct ExtractedAtmosphere {
+    atmosphere: Atmosphere,
+    body: AtmosphereBodyUniform,
+    transmittance_lut: AssetId<Image>,
+    multiscattering_lut: AssetId<Image>,
+    sky_view_lut: AssetId<Image>,
+}
+
+#[derive(Resource, Default)]
+struct ExtractedAtmospheres(Vec<ExtractedAtmosphere>);
+
+fn extract_atmosphere_bodies(
+    mut commands: Commands,
+    bodies: Extract<Query<(&GlobalTransform, &AtmosphereBody)>>,
+    lights: Extract<Query<(&GlobalTransform, &DirectionalLight)>>,
+) {
+    // Unlit without a sun
+    let Some((sun_transform, sun)) = lights.iter().next() else {
+        commands.insert_resource(ExtractedAtmospheres::default());
+        return;
+    };
+    let sun_direction = sun_transform.back().as_vec3();
+
+    let atmospheres = bodies
+        .iter()
+        .map(|(transform, body)| ExtractedAtmosphere {
+            atmosphere: body.atmosphere,
+            body: AtmosphereBodyUniform {
+                center: transform.translation(),
+                sun_illuminance: sun.illuminance,
+                sun_direction,
+            },
+            transmittance_lut: body.transmittance_lut.id(),
+            multiscattering_lut: body.multiscattering_lut.id(),
+            sky_view_lut: body.sky_view_lut.id(),
+        })
+        .collect();
+    commands.insert_resource(ExtractedAtmospheres(atmospheres));
+}
+
+#[derive(Resource)]
+struct AtmospherePassPipeline {
+    view_layout: BindGroupLayout,
+    view_layout_multisampled: BindGroupLayout,
+    body_layout: BindGroupLayout,
+    scene_layout: BindGroupLayout,
+    shader: Handle<Shader>,
+}
+
+impl FromWorld for AtmospherePassPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let view_layout = render_device.create_bind_group_layout(
+            "atmosphere_pass_view_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (uniform_buffer::<ViewUniform>(true), texture_depth_2d()),
+            ),
+        );
+        let view_layout_multisampled = render_device.create_bind_group_layout(
+            "atmosphere_pass_view_layout_multisampled",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    uniform_buffer::<ViewUniform>(true),
+                    texture_depth_2d_multisampled(),
+                ),
+            ),
+        );
+        let lut = texture_2d(TextureSampleType::Float { filterable: true });
+        let body_layout = render_device.create_bind_group_layout(
+            "atmosphere_pass_body_layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    uniform_buffer::<Atmosphere>(false),
+                    uniform_buffer::<AtmosphereBodyUniform>(false),
+                    lut,
+                    sampler(SamplerBindingType::Filtering),
+                    lut,
+                    lut,
+                ),
+            ),
+        );
+        let scene_layout = render_device.create_bind_group_layout(
+            "atmosphere_pass_scene_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::FRAGMENT,
+                texture_2d(TextureSampleType::Float { filterable: false }),
+            ),
+        );
+
+        Self {
+            view_layout,
+            view_layout_multisampled,
+            body_layout,
+            scene_layout,
+            shader: world
+                .resource::<AssetServer>()
+                .load(ATMOSPHERE_PASS_SHADER_ASSET_PATH),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct AtmospherePassPipelineKey {
+    hdr: bool,
+    samples: u32,
+}
+
+impl SpecializedRenderPipeline for AtmospherePassPipeline {
+    type Key = AtmospherePassPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        let mut shader_defs = Vec::new();
+        let view_layout = if key.samples > 1 {
+            shader_defs.push("MULTISAMPLED".into());
+            &self.view_layout_multisampled
+        } else {
+            &self.view_layout
+        };
+        let format = if key.hdr {
+            ViewTarget::TEXTURE_FORMAT_HDR
+        } else {
+            TextureFormat::bevy_default()
+        };
+
+        RenderPipelineDescriptor {
+            label: Some("atmosphere_pass_pipeline".into()),
+            layout: vec![
+                view_layout.clone(),
+                self.body_layout.clone(),
+                self.scene_layout.clone(),
+            ],
+            push_constant_ranges: Vec::new(),
+            vertex: fullscreen_shader_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs,
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            // Writes the resolved main texture, only the depth is multisampled
+            multisample: MultisampleState::default(),
+        }
+    }
+}
+
+#[derive(Component)]
+struct ViewAtmospherePipeline(CachedRenderPipelineId);
+
+fn prepare_atmosphere_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<AtmospherePassPipeline>>,
+    pipeline: Res<AtmospherePassPipeline>,
+    msaa: Res<Msaa>,
+    views: Query<(Entity, &ExtractedView), With<DepthPrepass>>,
+) {
+    for (entity, view) in &views {
+        let id = pipelines.specialize(
+            &pipeline_cache,
+            &pipeline,
+            AtmospherePassPipelineKey {
+                hdr: view.hdr,
+                samples: msaa.samples(),
+            },
+        );
+        commands.entity(entity).insert(ViewAtmospherePipeline(id));
+    }
+}
+
+/// The bind groups of a view and of its atmospheres, farthest first
+#[derive(Component)]
+struct ViewAtmosphereBindGroups {
+    view: BindGroup,
+    bodies: Vec<BindGroup>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_atmosphere_bind_groups(
+    mut commands: Commands,
+    atmospheres: Res<ExtractedAtmospheres>,
+    pipeline: Res<AtmospherePassPipeline>,
+    view_uniforms: Res<ViewUniforms>,
+    images: Res<RenderAssets<GpuImage>>,
+    msaa: Res<Msaa>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    views: Query<(Entity, &ExtractedView, &ViewPrepassTextures), With<DepthPrepass>>,
+) {
+    let Some(view_uniforms) = view_uniforms.uniforms.binding() else {
+        return;
+    };
+
+    let mut bodies = Vec::new();
+    for extracted in &atmospheres.0 {
+        // Skipped until the lookup tables are uploaded
+        let (Some(transmittance_lut), Some(multiscattering_lut), Some(sky_view_lut)) = (
+            images.get(extracted.transmittance_lut),
+            images.get(extracted.multiscattering_lut),
+            images.get(extracted.sky_view_lut),
+        ) else {
+            continue;
+        };
+
+        let mut atmosphere = UniformBuffer::from(extracted.atmosphere);
+        atmosphere.write_buffer(&render_device, &render_queue);
+        let mut body = UniformBuffer::from(extracted.body);
+        body.write_buffer(&render_device, &render_queue);
+        let (Some(atmosphere), Some(body)) = (atmosphere.binding(), body.binding()) else {
+            continue;
+        };
+
+        let bind_group = render_device.create_bind_group(
+            "atmosphere_pass_body_bind_group",
+            &pipeline.body_layout,
+            &BindGroupEntries::sequential((
+                atmosphere,
+                body,
+                &transmittance_lut.texture_view,
+                &transmittance_lut.sampler,
+                &multiscattering_lut.texture_view,
+                &sky_view_lut.texture_view,
+            )),
+        );
+        bodies.push((extracted.body.center, bind_group));
+    }
+
+    let view_layout = if msaa.samples() > 1 {
+        &pipeline.view_layout_multisampled
+    } else {
+        &pipeline.view_layout
+    };
+    for (entity, view, prepass_textures) in &views {
+        let Some(depth) = prepass_textures.depth_view() else {
+            continue;
+        };
+        let view_bind_group = render_device.create_bind_group(
+            "atmosphere_pass_view_bind_group",
+            view_layout,
+            &BindGroupEntries::sequential((view_uniforms.clone(), depth)),
+        );
+
+        // Nearer atmospheres are blended over farther ones
+        let camera = view.world_from_view.translation();
+        let mut sorted: Vec<_> = bodies.iter().collect();
+        sorted.sort_by(|(a, _), (b, _)| camera.distance(*b).total_cmp(&camera.distance(*a)));
+
+        commands.entity(entity).insert(ViewAtmosphereBindGroups {
+            view: view_bind_group,
+            bodies: sorted
+                .into_iter()
+                .map(|(_, bind_group)| bind_group.clone())
+                .collect(),
+        });
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct AtmosphereLabel;
+
+#[derive(Default)]
+struct AtmosphereNode;
+
+impl ViewNode for AtmosphereNode {
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static ViewUniformOffset,
+        &'static ViewAtmospherePipeline,
+        &'static ViewAtmosphereBindGroups,
+    );
+
+    fn run<'w>(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (view_target, view_uniform_offset, pipeline, bind_groups): QueryItem<'w, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        if bind_groups.bodies.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline.0)
+        else {
+            return Ok(());
+        };
+
+        let atmosphere_pipeline = world.resource::<AtmospherePassPipeline>();
+        // Each atmosphere reads the scene with the ones behind it and writes
+        // it back with its own on top
+        for body in &bind_groups.bodies {
+            let post_process = view_target.post_process_write();
+            let scene_bind_group = render_context.render_device().create_bind_group(
+                "atmosphere_pass_scene_bind_group",
+                &atmosphere_pipeline.scene_layout,
+                &BindGroupEntries::single(post_process.source),
+            );
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some("atmosphere_pass"),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: post_process.destination,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_render_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_groups.view, &[view_uniform_offset.offset]);
+            render_pass.set_bind_group(1, body, &[]);
+            render_pass.set_bind_group(2, &scene_bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        Ok(())
+    }
+}
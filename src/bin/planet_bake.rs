@@ -0,0 +1,128 @@
+//! Bakes the surface maps of a planet on the CPU, without a window or a GPU.
+//! Planets with a climate get every map, lava and rocky planets their height
+//! and normal maps, and gas giants their albedo.
+//!
+//! ```text
+//! planet-bake --seed 1234 --size 4096 --out dir/ [--format png|png16|exr|ktx2] [--kind ocean]
+//! ```
+
+use std::{path::PathBuf, process::ExitCode, time::Instant};
+
+use planet_material::{
+    export::ExportFormat,
+    gas_giant::GasGiant,
+    planet_kind::PlanetKind,
+    planet_maps::{write_maps, PlanetMaps},
+    terrain::{spherical_cuboid_uv, terrain_elevation},
+};
+
+/// Radius of the planet in the app, which sets the scale of the terrain noise
+const PLANET_RADIUS: f32 = 150.0;
+/// Height of an elevation of 1.0 in the normal map, as a fraction of the radius
+const RELIEF: f32 = 0.02;
+
+const USAGE: &str = "usage: planet-bake --seed <u32> --size <width> --out <dir> \
+                     [--format png|png16|exr|ktx2] [--kind <kind>]";
+
+struct Args {
+    seed: u32,
+    size: u32,
+    out: PathBuf,
+    format: ExportFormat,
+    kind: Option<PlanetKind>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut seed = None;
+    let mut size = 2048;
+    let mut out = None;
+    let mut format = ExportFormat::Png;
+    let mut kind = None;
+
+    let mut args = std::env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or(format!("{flag} needs a value"));
+        match flag.as_str() {
+            "--seed" => seed = Some(value()?.parse().map_err(|_| "--seed takes a u32")?),
+            "--size" => {
+                size = value()?
+                    .parse()
+                    .map_err(|_| "--size takes a width in texels")?
+            }
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--format" => {
+                let name = value()?;
+                format =
+                    ExportFormat::from_name(&name).ok_or(format!("unknown format {name:?}"))?;
+            }
+            "--kind" => {
+                let name = value()?;
+                kind = Some(PlanetKind::from_name(&name).ok_or(format!("unknown kind {name:?}"))?);
+            }
+            _ => return Err(format!("unknown argument {flag:?}")),
+        }
+    }
+
+    Ok(Args {
+        seed: seed.ok_or("--seed is required")?,
+        size,
+        out: out.ok_or("--out is required")?,
+        format,
+        kind,
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(error) => {
+            eprintln!("{error}\n{USAGE}");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let kind = args.kind.unwrap_or(PlanetKind::from_seed(args.seed));
+    let start = Instant::now();
+    let (width, height, written) = if kind == PlanetKind::GasGiant {
+        // No solid surface, only the cloud tops
+        let albedo = GasGiant::from_seed(args.seed).albedo_map(args.size);
+        let (width, height) = (albedo.width, albedo.height);
+        (
+            width,
+            height,
+            write_maps(&args.out, args.format, [("albedo", albedo)]),
+        )
+    } else {
+        let maps = PlanetMaps::bake(kind.climate(), args.size, |direction| {
+            terrain_elevation(
+                direction * PLANET_RADIUS,
+                spherical_cuboid_uv(direction),
+                args.seed,
+            )
+        });
+        // The lava and rocky surfaces are only shaded on the GPU, so those
+        // kinds get their relief without the climate maps
+        let written = if kind.has_climate() {
+            maps.export(&args.out, args.format, RELIEF)
+        } else {
+            write_maps(&args.out, args.format, maps.relief_maps(RELIEF))
+        };
+        (maps.width, maps.height, written)
+    };
+    if let Err(error) = written {
+        eprintln!(
+            "failed to write the maps to {}: {error}",
+            args.out.display()
+        );
+        return ExitCode::FAILURE;
+    }
+    println!(
+        "baked the {kind:?} planet {} at {}x{} into {} in {:.1?}",
+        args.seed,
+        width,
+        height,
+        args.out.display(),
+        start.elapsed()
+    );
+    ExitCode::SUCCESS
+}
@@ -1,14 +1,4 @@
-use bevy::prelude::Entity;
-
-struct Sun {
+pub(crate) struct Sun {
     pub seed: u64,
     pub temperature: f64,
 }
-
-struct Planet {
-    pub seed: u64,
-}
-
-struct Moon {
-    pub seed: u64,
-}
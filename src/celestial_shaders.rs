@@ -1,15 +1,44 @@
 use bevy::{
     app::{App, Plugin},
-    asset::Asset,
-    color::{Color, LinearRgba},
-    pbr::{ExtendedMaterial, Material, MaterialExtension, MaterialPlugin, StandardMaterial},
+    asset::{Asset, Assets, Handle},
+    pbr::{
+        ExtendedMaterial, Material, MaterialExtension, MaterialExtensionKey,
+        MaterialExtensionPipeline, MaterialPlugin, StandardMaterial,
+    },
+    prelude::{AlphaMode, Image},
     reflect::TypePath,
-    render::render_resource::{AsBindGroup, ShaderRef},
+    render::{
+        mesh::MeshVertexBufferLayoutRef,
+        render_asset::RenderAssets,
+        render_resource::{
+            AsBindGroup, AsBindGroupShaderType, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError,
+        },
+        texture::GpuImage,
+    },
+};
+use bevy_shader_utils::{blackbody::blackbody_color, craters::CraterField, fresnel::FresnelRim};
+use planet_material::{
+    atmosphere::Atmosphere,
+    climate::ClimateModel,
+    clouds::CloudLayer,
+    gas_giant::GasGiant,
+    planet_kind::PlanetKind,
+    sea_distance::SeaDistance,
+    terrain::{spherical_cuboid_uv, terrain_elevation},
+};
+
+use crate::{
+    atmosphere_pass::{AtmosphereBody, AtmospherePlugin},
+    skybox_bake::SkyboxBakePlugin,
 };
 
 const PLANET_SHADER_ASSET_PATH: &str = "shaders/planet_shader.wgsl";
-const ATMOSPHERE_SHADER_ASSET_PATH: &str = "shaders/atmosphere_shader.wgsl";
-const SKYBOX_SHADER_ASSET_PATH: &str = "shaders/skybox.wgsl";
+const GAS_GIANT_SHADER_ASSET_PATH: &str = "shaders/gas_giant_material.wgsl";
+const MOON_SHADER_ASSET_PATH: &str = "shaders/moon_shader.wgsl";
+const CLOUD_SHADER_ASSET_PATH: &str = "shaders/cloud_material.wgsl";
+const STAR_SHADER_ASSET_PATH: &str = "shaders/star_material.wgsl";
+const STAR_CORONA_SHADER_ASSET_PATH: &str = "shaders/star_corona.wgsl";
 
 pub struct CelestialShadersPlugin;
 
@@ -17,25 +46,125 @@ impl Plugin for CelestialShadersPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins((
             MaterialPlugin::<ExtendedMaterial<StandardMaterial, PlanetMaterial>>::default(),
-            MaterialPlugin::<ExtendedMaterial<StandardMaterial, AtmosphereMaterial>>::default(),
-            MaterialPlugin::<SkyboxMaterial>::default(),
+            MaterialPlugin::<ExtendedMaterial<StandardMaterial, GasGiantMaterial>>::default(),
+            MaterialPlugin::<ExtendedMaterial<StandardMaterial, MoonMaterial>>::default(),
+            MaterialPlugin::<ExtendedMaterial<StandardMaterial, CloudMaterial>>::default(),
+            MaterialPlugin::<ExtendedMaterial<StandardMaterial, StarMaterial>>::default(),
+            MaterialPlugin::<StarCoronaMaterial>::default(),
+            AtmospherePlugin,
+            SkyboxBakePlugin,
         ));
     }
 }
 
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
+#[bind_group_data(PlanetMaterialKey)]
 pub struct PlanetMaterial {
     // planet_radius: f32,
 
     // #[uniform(101)]
     #[uniform(100)]
     pub planet_seed: u32,
+    #[uniform(101)]
+    pub climate: ClimateModel,
+    /// Casts the shadows of the matching [`CloudMaterial`] onto the surface
+    #[uniform(102)]
+    pub clouds: CloudLayer,
+    /// Tints the sunlight and lights the ground with the sky, see
+    /// [`PlanetMaterial::with_atmosphere`]
+    #[uniform(103)]
+    pub atmosphere: Atmosphere,
+    #[texture(104)]
+    #[sampler(105)]
+    pub transmittance_lut: Option<Handle<Image>>,
+    #[texture(106)]
+    pub sky_irradiance_lut: Option<Handle<Image>>,
+    /// Glow around the limb, see [`PlanetMaterial::with_rim`]
+    #[uniform(107)]
+    pub rim: FresnelRim,
+    /// Dries out the climate inland, see [`PlanetMaterial::with_sea_distance`]
+    #[texture(108)]
+    #[sampler(109)]
+    pub sea_distance: Option<Handle<Image>>,
+    /// The bands and storms of [`PlanetKind::GasGiant`] planets
+    #[uniform(110)]
+    pub gas_giant: GasGiant,
+    /// Selects the shading path, see [`PlanetKind::shader_def`]
+    pub kind: PlanetKind,
     // #[texture(1)]
     // #[sampler(2)]
     // color_texture: Option<Handle<Image>>,
     // alpha_mode: AlphaMode,
 }
 
+impl PlanetMaterial {
+    /// A planet of the kind picked by the seed, with that kind's climate
+    pub fn from_seed(seed: u32) -> Self {
+        let kind = PlanetKind::from_seed(seed);
+        Self {
+            planet_seed: seed,
+            climate: kind.climate(),
+            clouds: kind.clouds(seed),
+            atmosphere: Atmosphere::default(),
+            transmittance_lut: None,
+            sky_irradiance_lut: None,
+            rim: FresnelRim::NONE,
+            sea_distance: None,
+            gas_giant: GasGiant::from_seed(seed),
+            kind,
+        }
+    }
+
+    /// Bakes the distance from the sea of the terrain the shader draws on a
+    /// planet of `radius`. Kinds without seas are left as they are.
+    pub fn with_sea_distance(self, radius: f32, images: &mut Assets<Image>) -> Self {
+        if !self.kind.has_climate() {
+            return self;
+        }
+        let seed = self.planet_seed;
+        let field = SeaDistance::bake(self.climate.sea_level, SeaDistance::WIDTH, |direction| {
+            terrain_elevation(direction * radius, spherical_cuboid_uv(direction), seed)
+        });
+        Self {
+            sea_distance: Some(images.add(field.image())),
+            ..self
+        }
+    }
+
+    /// Adds a glow around the limb, a cheap stand-in for
+    /// [`with_atmosphere`](Self::with_atmosphere) on distant planets
+    pub fn with_rim(self, rim: FresnelRim) -> Self {
+        Self { rim, ..self }
+    }
+
+    /// Lights the surface through the atmosphere of the planet's [`AtmosphereBody`]
+    pub(crate) fn with_atmosphere(self, body: &AtmosphereBody) -> Self {
+        Self {
+            atmosphere: body.atmosphere,
+            transmittance_lut: Some(body.transmittance_lut.clone()),
+            sky_irradiance_lut: Some(body.sky_irradiance_lut.clone()),
+            ..self
+        }
+    }
+}
+
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct PlanetMaterialKey {
+    kind: PlanetKind,
+    atmosphere: bool,
+    rim: bool,
+}
+
+impl From<&PlanetMaterial> for PlanetMaterialKey {
+    fn from(material: &PlanetMaterial) -> Self {
+        Self {
+            kind: material.kind,
+            atmosphere: material.transmittance_lut.is_some(),
+            rim: material.rim.is_visible(),
+        }
+    }
+}
+
 /// The Material trait is very configurable, but comes with sensible defaults for all methods.
 /// You only need to implement functions for features that need non-default behavior. See the Material api docs for details!
 impl MaterialExtension for PlanetMaterial {
@@ -46,38 +175,317 @@ impl MaterialExtension for PlanetMaterial {
     fn deferred_fragment_shader() -> ShaderRef {
         PLANET_SHADER_ASSET_PATH.into()
     }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment
+                .shader_defs
+                .push(key.bind_group_data.kind.shader_def().into());
+            if key.bind_group_data.atmosphere {
+                fragment.shader_defs.push("PLANET_ATMOSPHERE".into());
+            }
+            if key.bind_group_data.rim {
+                fragment.shader_defs.push("FRESNEL_RIM".into());
+            }
+        }
+        Ok(())
+    }
 }
 
+/// A gas giant with animated latitude bands and vortex storms.
+///
+/// Shading happens on the unit sphere in the body's local space, so any sphere
+/// mesh works, including [`spherical_cuboid`](crate::geometry::spherical_cuboid).
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct GasGiantMaterial {
+    #[uniform(100)]
+    pub gas_giant: GasGiant,
+}
+
+impl MaterialExtension for GasGiantMaterial {
+    fn fragment_shader() -> ShaderRef {
+        GAS_GIANT_SHADER_ASSET_PATH.into()
+    }
+}
+
+/// Whether a material draws its [`FresnelRim`]. Without one the rim is left
+/// out of the shader instead of evaluated to black.
+#[derive(Eq, PartialEq, Hash, Clone)]
+pub struct RimKey {
+    rim: bool,
+}
+
+impl RimKey {
+    fn specialize(&self, descriptor: &mut RenderPipelineDescriptor) {
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            if self.rim {
+                fragment.shader_defs.push("FRESNEL_RIM".into());
+            }
+        }
+    }
+}
+
+/// An airless body covered in impact craters.
+///
+/// The shader only colors the surface: floors, plains and fresh ejecta rays.
+/// The relief comes from displacing the mesh with the same [`CraterField`],
+/// see [`displace_sphere`](crate::geometry::displace_sphere).
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+#[bind_group_data(RimKey)]
+pub struct MoonMaterial {
+    #[uniform(100)]
+    pub craters: CraterField,
+    /// Glow around the limb, off by default
+    #[uniform(101)]
+    pub rim: FresnelRim,
+}
+
+impl From<&MoonMaterial> for RimKey {
+    fn from(material: &MoonMaterial) -> Self {
+        Self {
+            rim: material.rim.is_visible(),
+        }
+    }
+}
+
+impl MaterialExtension for MoonMaterial {
+    fn fragment_shader() -> ShaderRef {
+        MOON_SHADER_ASSET_PATH.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        key.bind_group_data.specialize(descriptor);
+        Ok(())
+    }
+}
+
+/// A cloud layer, drawn on a shell slightly larger than the planet.
+///
+/// The shell should be a child of the planet, so it follows the planet's
+/// rotation, scaled by `1.0 + altitude`. The base material should use
+/// [`AlphaMode::Blend`](bevy::prelude::AlphaMode::Blend), its base color tints the clouds.
+#[derive(Asset, TypePath, AsBindGroup, Debug, Clone, Default)]
+pub struct CloudMaterial {
+    #[uniform(100)]
+    pub layer: CloudLayer,
+}
+
+impl MaterialExtension for CloudMaterial {
+    fn fragment_shader() -> ShaderRef {
+        CLOUD_SHADER_ASSET_PATH.into()
+    }
+}
+
+// Scopes the derive's lint allow, see `uniform` in `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy::{math::Vec3, render::render_resource::ShaderType};
+
+    /// [`StarMaterial`](super::StarMaterial) as `Star` in `star.wgsl`, with
+    /// the blackbody colors worked out once instead of per fragment
+    #[derive(ShaderType, Debug, Clone, Copy)]
+    pub struct StarUniform {
+        pub color: Vec3,
+        pub luminance: f32,
+        pub spot_color: Vec3,
+        pub seed: u32,
+        pub limb_darkening: f32,
+        pub granulation_scale: f32,
+        pub granulation_contrast: f32,
+        pub spot_coverage: f32,
+        pub rotation_speed: f32,
+    }
+
+    /// [`StarCoronaMaterial`](super::StarCoronaMaterial) as `Corona` in
+    /// `star_corona.wgsl`
+    #[derive(ShaderType, Debug, Clone, Copy)]
+    pub struct StarCoronaUniform {
+        pub color: Vec3,
+        pub luminance: f32,
+        pub star_radius: f32,
+        pub extent: f32,
+        pub seed: u32,
+    }
+}
+
+pub use uniform::{StarCoronaUniform, StarUniform};
+
+/// The photosphere of a star, colored by its blackbody temperature, with limb
+/// darkening, boiling granulation and sunspots.
+///
+/// The star is not lit, it only emits. The emission is in nits like the
+/// standard material's emissive, so it blooms on HDR cameras.
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-pub(crate) struct AtmosphereMaterial {
-    pub(crate) planet_radius: f32,
-    pub(crate) atmosphere_radius: f32,
-    pub(crate) atmosphere_color: Color,
-    pub(crate) atmosphere_density: f32,
+#[uniform(100, StarUniform)]
+#[bind_group_data(RimKey)]
+pub struct StarMaterial {
+    /// Effective temperature, in kelvin
+    pub temperature: f32,
+    /// Luminance at the center of the disc, in nits
+    pub luminance: f32,
+    pub seed: u32,
+    /// Linear limb darkening coefficient, 0.0 for a uniform disc
+    pub limb_darkening: f32,
+    /// Number of granules across the equator
+    pub granulation_scale: f32,
+    /// Brightness variation between the granule centers and the lanes
+    pub granulation_contrast: f32,
+    /// Fraction of the active latitudes covered by spots
+    pub spot_coverage: f32,
+    /// Rotation of the surface, in radians per second
+    pub rotation_speed: f32,
+    /// Glow around the limb, off by default
+    #[uniform(101)]
+    pub rim: FresnelRim,
+}
+
+impl StarMaterial {
+    /// Temperature of the sun, in kelvin
+    pub const SUN_TEMPERATURE: f32 = 5772.0;
+
+    /// A star of the spectral class given by its temperature.
+    ///
+    /// Cool stars have deep convection zones: large, contrasted granules,
+    /// strong limb darkening and many spots. Hot stars are smoother and
+    /// evenly bright up to the limb.
+    pub fn from_temperature(temperature: f32, seed: u32) -> Self {
+        // 0.0 for an M dwarf at 3000 K, 1.0 for an O star at 30000 K
+        let heat = (temperature / 3000.0).log10().clamp(0.0, 1.0);
+        Self {
+            temperature,
+            seed,
+            limb_darkening: 0.8 - 0.5 * heat,
+            granulation_scale: 60.0 + 140.0 * heat,
+            granulation_contrast: 0.35 * (1.0 - heat),
+            spot_coverage: 0.5 * (1.0 - heat).powi(2),
+            ..Default::default()
+        }
+    }
+}
+
+impl Default for StarMaterial {
+    /// The sun
+    fn default() -> Self {
+        Self {
+            temperature: Self::SUN_TEMPERATURE,
+            luminance: 20_000.0,
+            seed: 0,
+            limb_darkening: 0.6,
+            granulation_scale: 120.0,
+            granulation_contrast: 0.2,
+            spot_coverage: 0.2,
+            rotation_speed: 0.01,
+            rim: FresnelRim::NONE,
+        }
+    }
+}
+
+impl AsBindGroupShaderType<StarUniform> for StarMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<GpuImage>) -> StarUniform {
+        StarUniform {
+            color: blackbody_color(self.temperature),
+            luminance: self.luminance,
+            // Spots are cooler, so redder
+            spot_color: blackbody_color(self.temperature * 0.7),
+            seed: self.seed,
+            limb_darkening: self.limb_darkening,
+            granulation_scale: self.granulation_scale,
+            granulation_contrast: self.granulation_contrast,
+            spot_coverage: self.spot_coverage,
+            rotation_speed: self.rotation_speed,
+        }
+    }
+}
+
+impl From<&StarMaterial> for RimKey {
+    fn from(material: &StarMaterial) -> Self {
+        Self {
+            rim: material.rim.is_visible(),
+        }
+    }
 }
 
-impl MaterialExtension for AtmosphereMaterial {
+impl MaterialExtension for StarMaterial {
     fn fragment_shader() -> ShaderRef {
-        ATMOSPHERE_SHADER_ASSET_PATH.into()
+        STAR_SHADER_ASSET_PATH.into()
     }
 
-    fn deferred_fragment_shader() -> ShaderRef {
-        ATMOSPHERE_SHADER_ASSET_PATH.into()
+    fn specialize(
+        _pipeline: &MaterialExtensionPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        key: MaterialExtensionKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        key.bind_group_data.specialize(descriptor);
+        Ok(())
     }
 }
 
+/// The corona and glare around a star, on a quad that faces the camera.
+///
+/// The quad should be a [`Rectangle`](bevy::math::primitives::Rectangle) of
+/// unit size, a child of the star with
+/// [`NoFrustumCulling`](bevy::render::view::NoFrustumCulling), since the
+/// vertex shader scales it to `extent` star radii.
 #[derive(Asset, TypePath, AsBindGroup, Debug, Clone)]
-pub(crate) struct SkyboxMaterial {
-    #[uniform(100)]
+#[uniform(100, StarCoronaUniform)]
+pub struct StarCoronaMaterial {
+    /// Effective temperature of the star, in kelvin
+    pub temperature: f32,
+    /// Luminance of the corona at the limb, in nits
+    pub luminance: f32,
+    /// Radius of the star, in world units
+    pub star_radius: f32,
+    /// Half the size of the quad, in star radii
+    pub extent: f32,
     pub seed: u32,
 }
 
-impl Material for SkyboxMaterial {
+impl StarCoronaMaterial {
+    /// A corona matching the star
+    pub fn for_star(star: &StarMaterial, star_radius: f32) -> Self {
+        Self {
+            temperature: star.temperature,
+            luminance: star.luminance * 0.25,
+            star_radius,
+            extent: 4.0,
+            seed: star.seed,
+        }
+    }
+}
+
+impl AsBindGroupShaderType<StarCoronaUniform> for StarCoronaMaterial {
+    fn as_bind_group_shader_type(&self, _images: &RenderAssets<GpuImage>) -> StarCoronaUniform {
+        StarCoronaUniform {
+            color: blackbody_color(self.temperature),
+            luminance: self.luminance,
+            star_radius: self.star_radius,
+            extent: self.extent,
+            seed: self.seed,
+        }
+    }
+}
+
+impl Material for StarCoronaMaterial {
+    fn vertex_shader() -> ShaderRef {
+        STAR_CORONA_SHADER_ASSET_PATH.into()
+    }
+
     fn fragment_shader() -> ShaderRef {
-        SKYBOX_SHADER_ASSET_PATH.into()
+        STAR_CORONA_SHADER_ASSET_PATH.into()
     }
 
-    fn alpha_mode(&self) -> bevy::prelude::AlphaMode {
-        bevy::prelude::AlphaMode::Opaque
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Add
     }
 }
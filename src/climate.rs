@@ -0,0 +1,424 @@
+use std::f32::consts::FRAC_PI_2;
+
+use bevy::math::Vec3;
+
+use crate::sea_distance::SeaDistance;
+
+pub use uniform::ClimateModel;
+
+// `#[derive(ShaderType)]` emits a `check` function per field that nothing
+// calls, which trips `dead_code`. Lints on derive output follow the enclosing
+// module, so each uniform in the workspace lives in a `uniform` module of its
+// own that scopes the allow, and is re-exported from its parent. The other
+// uniforms point here.
+#[allow(dead_code)]
+mod uniform {
+    use bevy::render::render_resource::ShaderType;
+
+    /// Parameters of the planet climate model.
+    ///
+    /// Mirrors `ClimateModel` in `shaders/climate.wgsl`, so the CPU and the shader
+    /// classify the same point into the same biome.
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct ClimateModel {
+        /// Sea level temperature at the equator in °C
+        pub equator_temperature: f32,
+        /// Sea level temperature at the poles in °C
+        pub pole_temperature: f32,
+        /// Temperature drop in °C between sea level and the summit level
+        pub lapse_rate: f32,
+        /// Normalized elevation of the shoreline
+        pub sea_level: f32,
+        /// Normalized elevation treated as the top of the mountains
+        pub summit_level: f32,
+        /// Annual precipitation in cm on a warm coast under a wet wind band
+        pub max_precipitation: f32,
+        /// How quickly moisture falls off moving inland, per radian of
+        /// distance from the sea, see [`SeaDistance`](crate::sea_distance::SeaDistance)
+        pub coastal_falloff: f32,
+    }
+}
+
+impl Default for ClimateModel {
+    /// An earth-like climate
+    fn default() -> Self {
+        Self {
+            equator_temperature: 28.0,
+            pole_temperature: -28.0,
+            lapse_rate: 40.0,
+            sea_level: 0.15,
+            summit_level: 0.5,
+            max_precipitation: 420.0,
+            coastal_falloff: 5.0,
+        }
+    }
+}
+
+/// Biomes of the Whittaker diagram, plus ocean and permanent ice.
+///
+/// The discriminants match the `BIOME_*` constants in `shaders/climate.wgsl`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u32)]
+pub enum Biome {
+    Ocean = 0,
+    Ice = 1,
+    Tundra = 2,
+    BorealForest = 3,
+    ColdDesert = 4,
+    TemperateGrassland = 5,
+    Woodland = 6,
+    TemperateForest = 7,
+    TemperateRainforest = 8,
+    Desert = 9,
+    Savanna = 10,
+    TropicalSeasonalForest = 11,
+    TropicalRainforest = 12,
+}
+
+/// The climate at a single point on the planet surface
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClimateSample {
+    /// Mean annual temperature in °C
+    pub temperature: f32,
+    /// Annual precipitation in cm
+    pub precipitation: f32,
+    pub biome: Biome,
+}
+
+impl ClimateModel {
+    /// Height above the shoreline, normalized so the summit level is 1.0
+    pub fn land_height(&self, elevation: f32) -> f32 {
+        ((elevation - self.sea_level) / (self.summit_level - self.sea_level)).clamp(0.0, 1.0)
+    }
+
+    /// Mean annual temperature in °C. Latitude is in radians.
+    pub fn temperature(&self, latitude: f32, elevation: f32) -> f32 {
+        let insolation = latitude.cos().powi(2);
+        let sea_level_temperature =
+            self.pole_temperature + (self.equator_temperature - self.pole_temperature) * insolation;
+        sea_level_temperature - self.lapse_rate * self.land_height(elevation)
+    }
+
+    /// Annual precipitation in cm. Latitude and the distance from the sea,
+    /// see [`SeaDistance`], are in radians.
+    pub fn precipitation(&self, latitude: f32, sea_distance: f32, temperature: f32) -> f32 {
+        // Three circulation cells per hemisphere: rising air (wet) at the
+        // equator and at 60°, sinking air (dry) at 30° and at the poles.
+        let wind_bands = 0.5 + 0.5 * (latitude * 6.0).cos();
+        // The air dries out as it moves inland
+        let coastal = (-sea_distance * self.coastal_falloff).exp();
+        // Cold air holds less water
+        let capacity = ((temperature + 15.0) / 45.0).clamp(0.05, 1.0);
+        self.max_precipitation * (0.15 + 0.85 * wind_bands) * coastal * capacity
+    }
+
+    /// Classifies the climate at a latitude (in radians), normalized elevation
+    /// and distance from the sea (in radians)
+    pub fn sample(&self, latitude: f32, elevation: f32, sea_distance: f32) -> ClimateSample {
+        let temperature = self.temperature(latitude, elevation);
+        let precipitation = self.precipitation(latitude, sea_distance, temperature);
+        let biome = if elevation < self.sea_level {
+            Biome::Ocean
+        } else {
+            Biome::classify(temperature, precipitation)
+        };
+
+        ClimateSample {
+            temperature,
+            precipitation,
+            biome,
+        }
+    }
+
+    /// Classifies the climate at a latitude and longitude in radians.
+    ///
+    /// `elevation` returns the normalized terrain elevation for a direction in
+    /// planet-local space, and `sea_distance` is baked from the same terrain.
+    pub fn sample_at(
+        &self,
+        latitude: f32,
+        longitude: f32,
+        elevation: impl Fn(Vec3) -> f32,
+        sea_distance: &SeaDistance,
+    ) -> ClimateSample {
+        let latitude = latitude.clamp(-FRAC_PI_2, FRAC_PI_2);
+        let direction = lat_long_to_direction(latitude, longitude);
+        self.sample(latitude, elevation(direction), sea_distance.at(direction))
+    }
+}
+
+impl Biome {
+    /// Whittaker classification from mean annual temperature (°C) and precipitation (cm)
+    pub fn classify(temperature: f32, precipitation: f32) -> Self {
+        if temperature < -12.0 {
+            Biome::Ice
+        } else if temperature < -5.0 {
+            Biome::Tundra
+        } else if temperature < 3.0 {
+            if precipitation < 30.0 {
+                Biome::Tundra
+            } else {
+                Biome::BorealForest
+            }
+        } else if temperature < 20.0 {
+            if precipitation < 25.0 {
+                Biome::ColdDesert
+            } else if precipitation < 80.0 {
+                Biome::TemperateGrassland
+            } else if precipitation < 120.0 {
+                Biome::Woodland
+            } else if precipitation < 220.0 {
+                Biome::TemperateForest
+            } else {
+                Biome::TemperateRainforest
+            }
+        } else if precipitation < 50.0 {
+            Biome::Desert
+        } else if precipitation < 150.0 {
+            Biome::Savanna
+        } else if precipitation < 250.0 {
+            Biome::TropicalSeasonalForest
+        } else {
+            Biome::TropicalRainforest
+        }
+    }
+
+    /// Ground color of the biome, linear. Mirrors `biome_color` in `shaders/climate.wgsl`.
+    pub fn color(self) -> Vec3 {
+        match self {
+            Biome::Ocean => Vec3::new(0.254, 0.647, 0.705),
+            Biome::Ice => Vec3::new(0.92, 0.94, 0.96),
+            Biome::Tundra => Vec3::new(0.608, 0.620, 0.529),
+            Biome::BorealForest => Vec3::new(0.306, 0.447, 0.329),
+            Biome::ColdDesert => Vec3::new(0.741, 0.690, 0.584),
+            Biome::TemperateGrassland => Vec3::new(0.745, 0.773, 0.447),
+            Biome::Woodland => Vec3::new(0.588, 0.655, 0.400),
+            Biome::TemperateForest => Vec3::new(0.361, 0.600, 0.329),
+            Biome::TemperateRainforest => Vec3::new(0.227, 0.490, 0.333),
+            Biome::Desert => Vec3::new(0.886, 0.800, 0.576),
+            Biome::Savanna => Vec3::new(0.780, 0.737, 0.420),
+            Biome::TropicalSeasonalForest => Vec3::new(0.463, 0.639, 0.302),
+            Biome::TropicalRainforest => Vec3::new(0.200, 0.541, 0.255),
+        }
+    }
+}
+
+/// Converts a latitude and longitude in radians to a direction in planet-local space, Y up
+pub fn lat_long_to_direction(latitude: f32, longitude: f32) -> Vec3 {
+    Vec3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.sin(),
+        latitude.cos() * longitude.sin(),
+    )
+}
+
+/// Converts a direction in planet-local space, Y up, to latitude and longitude in radians
+pub fn direction_to_lat_long(direction: Vec3) -> (f32, f32) {
+    let direction = direction.normalize();
+    (
+        direction.y.clamp(-1.0, 1.0).asin(),
+        direction.z.atan2(direction.x),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_6;
+
+    use super::*;
+
+    const BIOMES: [Biome; 13] = [
+        Biome::Ocean,
+        Biome::Ice,
+        Biome::Tundra,
+        Biome::BorealForest,
+        Biome::ColdDesert,
+        Biome::TemperateGrassland,
+        Biome::Woodland,
+        Biome::TemperateForest,
+        Biome::TemperateRainforest,
+        Biome::Desert,
+        Biome::Savanna,
+        Biome::TropicalSeasonalForest,
+        Biome::TropicalRainforest,
+    ];
+
+    #[test]
+    fn temperature_falls_towards_the_poles_and_the_summits() {
+        let model = ClimateModel::default();
+        let shore = model.sea_level;
+        assert_eq!(model.temperature(0.0, shore), model.equator_temperature);
+        assert!((model.temperature(FRAC_PI_2, shore) - model.pole_temperature).abs() < 1e-4);
+        assert!((model.temperature(-FRAC_PI_2, shore) - model.pole_temperature).abs() < 1e-4);
+        assert_eq!(
+            model.temperature(0.0, model.summit_level),
+            model.equator_temperature - model.lapse_rate
+        );
+        // The sea floor is as warm as the shore, the peaks above the summit
+        // level as cold as the summit
+        assert_eq!(model.temperature(0.3, 0.0), model.temperature(0.3, shore));
+        assert_eq!(
+            model.temperature(0.3, 1.0),
+            model.temperature(0.3, model.summit_level)
+        );
+    }
+
+    #[test]
+    fn precipitation_follows_the_wind_bands_and_dries_inland() {
+        let model = ClimateModel::default();
+        let rain = |latitude: f32, sea_distance: f32| {
+            let temperature = model.temperature(latitude, model.sea_level);
+            model.precipitation(latitude, sea_distance, temperature)
+        };
+        // Wet at the equator and at 60°, dry at 30° and at the poles
+        let bands = |latitude: f32| model.precipitation(latitude, 0.0, 20.0);
+        assert!(bands(0.0) > bands(FRAC_PI_6));
+        assert!(bands(2.0 * FRAC_PI_6) > bands(FRAC_PI_6));
+        assert!(bands(2.0 * FRAC_PI_6) > bands(FRAC_PI_2));
+        assert!(rain(0.0, 0.0) > rain(FRAC_PI_6, 0.0));
+        assert!(rain(0.0, 0.0) <= model.max_precipitation);
+        assert!(rain(0.0, 0.3) < rain(0.0, 0.0));
+        // Cold air holds less water
+        assert!(model.precipitation(0.0, 0.0, -20.0) < model.precipitation(0.0, 0.0, 20.0));
+    }
+
+    #[test]
+    fn samples_earth_like_climate_zones() {
+        let model = ClimateModel::default();
+        assert_eq!(
+            model.sample(0.5, model.sea_level - 0.01, 0.0).biome,
+            Biome::Ocean
+        );
+        assert_eq!(
+            model.sample(0.0, model.sea_level, 0.0).biome,
+            Biome::TropicalRainforest
+        );
+        assert_eq!(
+            model.sample(FRAC_PI_2, model.sea_level, 0.0).biome,
+            Biome::Ice
+        );
+        // Snow caps on the equatorial summits
+        assert_eq!(
+            model.sample(0.0, model.summit_level, 0.0).biome,
+            Biome::Tundra
+        );
+        // Deserts deep inland
+        assert_eq!(model.sample(0.0, 0.2, 1.0).biome, Biome::Desert);
+    }
+
+    #[test]
+    fn classifies_the_whittaker_diagram() {
+        assert_eq!(Biome::classify(-20.0, 100.0), Biome::Ice);
+        assert_eq!(Biome::classify(-8.0, 100.0), Biome::Tundra);
+        assert_eq!(Biome::classify(0.0, 10.0), Biome::Tundra);
+        assert_eq!(Biome::classify(0.0, 100.0), Biome::BorealForest);
+        assert_eq!(Biome::classify(10.0, 10.0), Biome::ColdDesert);
+        assert_eq!(Biome::classify(10.0, 50.0), Biome::TemperateGrassland);
+        assert_eq!(Biome::classify(10.0, 100.0), Biome::Woodland);
+        assert_eq!(Biome::classify(10.0, 150.0), Biome::TemperateForest);
+        assert_eq!(Biome::classify(10.0, 300.0), Biome::TemperateRainforest);
+        assert_eq!(Biome::classify(25.0, 10.0), Biome::Desert);
+        assert_eq!(Biome::classify(25.0, 100.0), Biome::Savanna);
+        assert_eq!(Biome::classify(25.0, 200.0), Biome::TropicalSeasonalForest);
+        assert_eq!(Biome::classify(25.0, 300.0), Biome::TropicalRainforest);
+    }
+
+    /// Outputs of `sample_climate` with the default model: the temperature,
+    /// the precipitation and the biome at a latitude, elevation and distance
+    /// from the sea. Printed by `tests/golden.rs`, like the other tables.
+    const GOLDEN: [([f32; 3], [f32; 2], u32); 12] = [
+        ([0.0, 0.15, 0.0], [28.0, 401.33334], 12),
+        ([0.5, 0.14, 0.0], [15.128464, 43.375843], 0),
+        ([1.56, 0.15, 0.0], [-27.993473, 3.1687193], 1),
+        ([-1.57, 0.4, 0.2], [-56.571396, 1.1588578], 1),
+        ([0.0, 0.45, 0.05], [-6.285713, 63.342472], 2),
+        ([0.3, 0.2, 0.4], [17.395113, 19.577354], 4),
+        ([0.4, 0.2, 1.0], [13.793501, 0.47370642], 4),
+        ([-0.9, 0.25, 0.1], [-17.79023, 10.75963], 1),
+        ([0.6, 0.3, 0.8], [-6.9968395, 0.26524565], 2),
+        ([1.1, 0.18, 0.05], [-19.906603, 16.008894], 1),
+        ([-0.2, 0.35, 1.2], [2.9325657, 0.30244115], 2),
+        ([0.8, 0.9, 3.0], [-40.81759, 3.9326565e-6], 1),
+    ];
+
+    /// Outputs of `classify_biome` at a temperature and precipitation
+    const CLASSIFY_GOLDEN: [([f32; 2], u32); 13] = [
+        ([-20.0, 100.0], 1),
+        ([-8.0, 100.0], 2),
+        ([0.0, 10.0], 2),
+        ([0.0, 100.0], 3),
+        ([10.0, 10.0], 4),
+        ([10.0, 50.0], 5),
+        ([10.0, 100.0], 6),
+        ([10.0, 150.0], 7),
+        ([10.0, 300.0], 8),
+        ([25.0, 10.0], 9),
+        ([25.0, 100.0], 10),
+        ([25.0, 200.0], 11),
+        ([25.0, 300.0], 12),
+    ];
+
+    /// Outputs of `biome_color` for each biome
+    const COLOR_GOLDEN: [(u32, [f32; 3]); 13] = [
+        (0, [0.254, 0.647, 0.705]),
+        (1, [0.92, 0.94, 0.96]),
+        (2, [0.608, 0.62, 0.529]),
+        (3, [0.306, 0.447, 0.329]),
+        (4, [0.741, 0.69, 0.584]),
+        (5, [0.745, 0.773, 0.447]),
+        (6, [0.588, 0.655, 0.4]),
+        (7, [0.361, 0.6, 0.329]),
+        (8, [0.227, 0.49, 0.333]),
+        (9, [0.886, 0.8, 0.576]),
+        (10, [0.78, 0.737, 0.42]),
+        (11, [0.463, 0.639, 0.302]),
+        (12, [0.2, 0.541, 0.255]),
+    ];
+
+    fn assert_matches_shader(values: [f32; 2], expected: [f32; 2]) {
+        for (value, expected) in values.into_iter().zip(expected) {
+            assert!(
+                (value - expected).abs() <= 1e-5 * (1.0 + expected.abs()),
+                "{values:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn samples_match_the_shader() {
+        let model = ClimateModel::default();
+        for ([latitude, elevation, sea_distance], expected, biome) in GOLDEN {
+            let sample = model.sample(latitude, elevation, sea_distance);
+            assert_matches_shader([sample.temperature, sample.precipitation], expected);
+            assert_eq!(
+                sample.biome as u32, biome,
+                "{latitude} {elevation} {sea_distance}"
+            );
+        }
+    }
+
+    #[test]
+    fn biomes_match_the_shader() {
+        for ([temperature, precipitation], biome) in CLASSIFY_GOLDEN {
+            assert_eq!(
+                Biome::classify(temperature, precipitation) as u32,
+                biome,
+                "{temperature} {precipitation}"
+            );
+        }
+        for (index, color) in COLOR_GOLDEN {
+            let biome = BIOMES[index as usize];
+            assert_eq!(biome as u32, index);
+            assert_eq!(biome.color().to_array(), color, "{biome:?}");
+        }
+    }
+
+    #[test]
+    fn lat_long_round_trips() {
+        for (latitude, longitude) in [(0.0, 0.0), (0.5, -2.0), (-1.2, 3.0), (1.5, 0.7)] {
+            let (lat, long) = direction_to_lat_long(lat_long_to_direction(latitude, longitude));
+            assert!((lat - latitude).abs() < 1e-5, "{latitude} {longitude}");
+            assert!((long - longitude).abs() < 1e-5, "{latitude} {longitude}");
+        }
+    }
+}
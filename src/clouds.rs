@@ -0,0 +1,51 @@
+pub use uniform::CloudLayer;
+
+// Scopes the derive's lint allow, see `uniform` in `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy::render::render_resource::ShaderType;
+
+    /// Parameters of a planet's cloud layer.
+    ///
+    /// Mirrors `CloudLayer` in `shaders/clouds.wgsl`. The same parameters drive the
+    /// cloud shell and the cloud shadows drawn by the planet shader, so both agree.
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct CloudLayer {
+        pub seed: u32,
+        /// Fraction of the sky covered by clouds, 0.0 to 1.0
+        pub coverage: f32,
+        /// Height of the cloud shell above the surface, as a fraction of the planet's radius
+        pub altitude: f32,
+        /// Number of wind band pairs from pole to pole, like the gas giant jets
+        pub band_count: f32,
+        /// Speed of the zonal winds, in radians per second at the fastest jet
+        pub wind_speed: f32,
+        /// Rotation of the whole layer relative to the surface, in radians per second
+        pub drift_speed: f32,
+        /// How much sunlight a full cover blocks from the surface, 0.0 to 1.0
+        pub shadow_strength: f32,
+    }
+}
+
+impl CloudLayer {
+    /// No clouds at all
+    pub const NONE: Self = Self {
+        seed: 0,
+        coverage: 0.0,
+        altitude: 0.03,
+        band_count: 3.0,
+        wind_speed: 0.02,
+        drift_speed: 0.005,
+        shadow_strength: 0.6,
+    };
+}
+
+impl Default for CloudLayer {
+    /// Earth-like clouds: half the sky covered, three wind cells per hemisphere
+    fn default() -> Self {
+        Self {
+            coverage: 0.5,
+            ..Self::NONE
+        }
+    }
+}
@@ -0,0 +1,479 @@
+//! Writes generated textures to disk, for offline renders and other engines.
+//!
+//! PNG and EXR files hold one image, so the faces of a cubemap are stacked
+//! top to bottom. KTX2 files hold the faces as a real cubemap.
+
+use std::{fmt, fs, io, path::Path};
+
+use bevy::color::Srgba;
+use half::f16;
+use image::{DynamicImage, ImageBuffer, ImageError, Pixel};
+
+/// File formats textures can be written to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// 8 bits per channel
+    Png,
+    /// 16 bits per channel
+    Png16,
+    /// 32-bit float channels, always linear
+    Exr,
+    /// Uncompressed, 8-bit sRGB colors, 16-bit data and half float light
+    Ktx2,
+}
+
+impl ExportFormat {
+    pub const ALL: [ExportFormat; 4] = [
+        ExportFormat::Png,
+        ExportFormat::Png16,
+        ExportFormat::Exr,
+        ExportFormat::Ktx2,
+    ];
+
+    /// Parses a format name, as given on the command line
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" | "png8" => Some(ExportFormat::Png),
+            "png16" => Some(ExportFormat::Png16),
+            "exr" => Some(ExportFormat::Exr),
+            "ktx2" => Some(ExportFormat::Ktx2),
+            _ => None,
+        }
+    }
+
+    /// File extension, without the dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Png | ExportFormat::Png16 => "png",
+            ExportFormat::Exr => "exr",
+            ExportFormat::Ktx2 => "ktx2",
+        }
+    }
+}
+
+/// What the values of a texture mean, which picks how they are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TexelEncoding {
+    /// Linear colors in `0.0..=1.0`, stored with the sRGB transfer function
+    Srgb,
+    /// Data such as heights and normals in `0.0..=1.0`, stored as is
+    Linear,
+    /// Linear light, unbounded. Clipped to white in PNGs.
+    Hdr,
+}
+
+/// A texture to write, as linear `f32` values
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExportTexture {
+    pub width: u32,
+    /// Height of one face
+    pub height: u32,
+    /// 1 for a flat texture, 6 for a cubemap with the faces in wgpu's order
+    pub faces: u32,
+    /// 1 for grayscale, 3 for RGB, 4 for RGBA
+    pub channels: u32,
+    pub encoding: TexelEncoding,
+    /// `channels` values per texel, face by face, row by row from the top left
+    pub data: Vec<f32>,
+}
+
+/// Why a texture could not be written
+#[derive(Debug)]
+pub enum ExportError {
+    Io(io::Error),
+    Image(ImageError),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExportError::Io(error) => write!(f, "could not write the texture: {error}"),
+            ExportError::Image(error) => write!(f, "could not encode the texture: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ExportError::Io(error) => Some(error),
+            ExportError::Image(error) => Some(error),
+        }
+    }
+}
+
+impl From<io::Error> for ExportError {
+    fn from(error: io::Error) -> Self {
+        ExportError::Io(error)
+    }
+}
+
+impl From<ImageError> for ExportError {
+    fn from(error: ImageError) -> Self {
+        ExportError::Image(error)
+    }
+}
+
+impl ExportTexture {
+    /// A flat texture from `channels` values per texel
+    pub fn new(
+        width: u32,
+        height: u32,
+        channels: u32,
+        encoding: TexelEncoding,
+        data: Vec<f32>,
+    ) -> Self {
+        assert_eq!(data.len(), (width * height * channels) as usize);
+        Self {
+            width,
+            height,
+            faces: 1,
+            channels,
+            encoding,
+            data,
+        }
+    }
+
+    /// A cubemap of linear light, as made by `SkyboxGenerator::generate_radiance`
+    pub fn cubemap(size: u32, radiance: &[bevy::math::Vec3]) -> Self {
+        assert_eq!(radiance.len(), (6 * size * size) as usize);
+        Self {
+            width: size,
+            height: size,
+            faces: 6,
+            channels: 3,
+            encoding: TexelEncoding::Hdr,
+            data: radiance.iter().flat_map(|texel| texel.to_array()).collect(),
+        }
+    }
+
+    /// Value of a channel as stored in integer formats, in `0.0..=1.0`
+    fn encode(&self, value: f32, channel: u32) -> f32 {
+        let value = value.clamp(0.0, 1.0);
+        match self.encoding {
+            // Alpha is never gamma encoded
+            TexelEncoding::Srgb | TexelEncoding::Hdr if channel < 3 => {
+                Srgba::gamma_function_inverse(value)
+            }
+            _ => value,
+        }
+    }
+
+    fn encoded<T>(&self, scale: f32, convert: impl Fn(f32) -> T) -> Vec<T> {
+        self.data
+            .iter()
+            .enumerate()
+            .map(|(i, &value)| {
+                let channel = i as u32 % self.channels;
+                convert((self.encode(value, channel) * scale).round())
+            })
+            .collect()
+    }
+
+    /// The texture as one image, with the faces stacked top to bottom
+    fn to_image(&self, format: ExportFormat) -> DynamicImage {
+        let (width, height) = (self.width, self.height * self.faces);
+        match (format, self.channels) {
+            (ExportFormat::Png, 1) => {
+                DynamicImage::ImageLuma8(buffer(width, height, self.encoded(255.0, |v| v as u8)))
+            }
+            (ExportFormat::Png, 3) => {
+                DynamicImage::ImageRgb8(buffer(width, height, self.encoded(255.0, |v| v as u8)))
+            }
+            (ExportFormat::Png, _) => {
+                DynamicImage::ImageRgba8(buffer(width, height, self.encoded(255.0, |v| v as u8)))
+            }
+            (ExportFormat::Png16, 1) => DynamicImage::ImageLuma16(buffer(
+                width,
+                height,
+                self.encoded(65535.0, |v| v as u16),
+            )),
+            (ExportFormat::Png16, 3) => {
+                DynamicImage::ImageRgb16(buffer(width, height, self.encoded(65535.0, |v| v as u16)))
+            }
+            (ExportFormat::Png16, _) => DynamicImage::ImageRgba16(buffer(
+                width,
+                height,
+                self.encoded(65535.0, |v| v as u16),
+            )),
+            // OpenEXR has no grayscale in `image`, gray is stored as RGB
+            (_, 1) => DynamicImage::ImageRgb32F(buffer(
+                width,
+                height,
+                self.data.iter().flat_map(|&value| [value; 3]).collect(),
+            )),
+            (_, 3) => DynamicImage::ImageRgb32F(buffer(width, height, self.data.clone())),
+            (_, _) => DynamicImage::ImageRgba32F(buffer(width, height, self.data.clone())),
+        }
+    }
+
+    /// Writes the texture to `path` in `format`, whatever the extension of the path
+    pub fn write(&self, path: impl AsRef<Path>, format: ExportFormat) -> Result<(), ExportError> {
+        let path = path.as_ref();
+        match format {
+            ExportFormat::Png | ExportFormat::Png16 => {
+                self.to_image(format)
+                    .save_with_format(path, image::ImageFormat::Png)?;
+            }
+            ExportFormat::Exr => {
+                self.to_image(format)
+                    .save_with_format(path, image::ImageFormat::OpenExr)?;
+            }
+            ExportFormat::Ktx2 => fs::write(path, self.to_ktx2())?,
+        }
+        Ok(())
+    }
+
+    /// The texture as a KTX2 file: one mip level, uncompressed
+    pub fn to_ktx2(&self) -> Vec<u8> {
+        // GPUs rarely sample three channel formats, RGB gains an opaque alpha
+        let channels = if self.channels == 1 { 1 } else { 4 };
+        let texels = self.data.chunks_exact(self.channels as usize);
+        let padded = texels.flat_map(|texel| {
+            let mut rgba = [0.0, 0.0, 0.0, 1.0];
+            rgba[..texel.len()].copy_from_slice(texel);
+            rgba.into_iter().take(channels)
+        });
+
+        let ktx = Ktx2Format::new(self.encoding, channels);
+        let level: Vec<u8> = match self.encoding {
+            TexelEncoding::Srgb => padded
+                .enumerate()
+                .map(|(i, value)| {
+                    (self.encode(value, i as u32 % channels as u32) * 255.0).round() as u8
+                })
+                .collect(),
+            TexelEncoding::Linear => padded
+                .flat_map(|value| {
+                    (((value.clamp(0.0, 1.0)) * 65535.0).round() as u16).to_le_bytes()
+                })
+                .collect(),
+            TexelEncoding::Hdr => padded
+                .flat_map(|value| f16::from_f32(value).to_le_bytes())
+                .collect(),
+        };
+        ktx.file(self.width, self.height, self.faces, &level)
+    }
+}
+
+fn buffer<P: Pixel>(
+    width: u32,
+    height: u32,
+    data: Vec<P::Subpixel>,
+) -> ImageBuffer<P, Vec<P::Subpixel>> {
+    ImageBuffer::from_raw(width, height, data).expect("texel data matches the size")
+}
+
+/// Vulkan format of the texels of a KTX2 file, and its data format descriptor
+struct Ktx2Format {
+    vk_format: u32,
+    /// Bytes per channel
+    type_size: u32,
+    channels: usize,
+    transfer_function: u8,
+    /// Channel type qualifiers, and lowest and highest values of the samples
+    qualifiers: u8,
+    lower: u32,
+    upper: u32,
+}
+
+impl Ktx2Format {
+    /// Transfer functions of the data format descriptor
+    const LINEAR: u8 = 1;
+    const SRGB: u8 = 2;
+
+    fn new(encoding: TexelEncoding, channels: usize) -> Self {
+        match (encoding, channels) {
+            (TexelEncoding::Srgb, 1) => Self::integer(15, 1, channels, Self::SRGB),
+            (TexelEncoding::Srgb, _) => Self::integer(43, 1, channels, Self::SRGB),
+            (TexelEncoding::Linear, 1) => Self::integer(70, 2, channels, Self::LINEAR),
+            (TexelEncoding::Linear, _) => Self::integer(91, 2, channels, Self::LINEAR),
+            (TexelEncoding::Hdr, _) => Self {
+                vk_format: if channels == 1 { 76 } else { 97 },
+                type_size: 2,
+                channels,
+                transfer_function: Self::LINEAR,
+                // Float and signed, normalized to -1.0..=1.0
+                qualifiers: 0x80 | 0x40,
+                lower: (-1.0f32).to_bits(),
+                upper: 1.0f32.to_bits(),
+            },
+        }
+    }
+
+    fn integer(vk_format: u32, type_size: u32, channels: usize, transfer_function: u8) -> Self {
+        Self {
+            vk_format,
+            type_size,
+            channels,
+            transfer_function,
+            qualifiers: 0,
+            lower: 0,
+            upper: (1u64 << (type_size * 8)) as u32 - 1,
+        }
+    }
+
+    /// The basic data format descriptor, see the Khronos Data Format Specification
+    fn descriptor(&self) -> Vec<u8> {
+        let block_size = 24 + 16 * self.channels as u32;
+        let mut dfd = Vec::new();
+        dfd.extend((4 + block_size).to_le_bytes());
+        // Khronos vendor, basic descriptor type, version 1.3
+        dfd.extend(0u32.to_le_bytes());
+        dfd.extend(2u16.to_le_bytes());
+        dfd.extend((block_size as u16).to_le_bytes());
+        // RGBSDA color model, BT.709 primaries, straight alpha
+        dfd.extend([1, 1, self.transfer_function, 0]);
+        dfd.extend([0; 4]);
+        let texel_size = self.type_size as usize * self.channels;
+        dfd.extend([texel_size as u8, 0, 0, 0, 0, 0, 0, 0]);
+        for channel in 0..self.channels {
+            let bits = self.type_size * 8;
+            // Red, green, blue and alpha, which is never gamma encoded
+            let (id, linear) = match channel {
+                3 if self.transfer_function == Self::SRGB => (15, 0x10),
+                3 => (15, 0),
+                channel => (channel as u8, 0),
+            };
+            dfd.extend(((channel as u32 * bits) as u16).to_le_bytes());
+            dfd.extend([(bits - 1) as u8, id | self.qualifiers | linear]);
+            dfd.extend([0; 4]);
+            dfd.extend(self.lower.to_le_bytes());
+            dfd.extend(self.upper.to_le_bytes());
+        }
+        dfd
+    }
+
+    fn file(&self, width: u32, height: u32, faces: u32, level: &[u8]) -> Vec<u8> {
+        const IDENTIFIER: [u8; 12] = [
+            0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+        ];
+        // Identifier, header, index and the level index of one level
+        const DFD_OFFSET: usize = 12 + 9 * 4 + 4 * 4 + 2 * 8 + 3 * 8;
+
+        let dfd = self.descriptor();
+        // Levels start at a multiple of the texel size and of 4
+        let texel_size = self.type_size as usize * self.channels;
+        let alignment = if texel_size.is_multiple_of(4) { texel_size } else { 4 };
+        let level_offset = (DFD_OFFSET + dfd.len()).next_multiple_of(alignment);
+
+        let mut file = Vec::with_capacity(level_offset + level.len());
+        file.extend(IDENTIFIER);
+        for value in [
+            self.vk_format,
+            self.type_size,
+            width,
+            height,
+            // Depth and array layers, 0 for a plain texture
+            0,
+            0,
+            faces,
+            // One mip level, no supercompression
+            1,
+            0,
+        ] {
+            file.extend(value.to_le_bytes());
+        }
+        for value in [DFD_OFFSET as u32, dfd.len() as u32, 0, 0] {
+            file.extend(value.to_le_bytes());
+        }
+        for value in [
+            0u64,
+            0,
+            level_offset as u64,
+            level.len() as u64,
+            level.len() as u64,
+        ] {
+            file.extend(value.to_le_bytes());
+        }
+        file.extend(dfd);
+        file.resize(level_offset, 0);
+        file.extend(level);
+        file
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gradient(channels: u32, encoding: TexelEncoding) -> ExportTexture {
+        let data = (0..4 * 2 * channels).map(|i| i as f32 / 31.0).collect();
+        ExportTexture::new(4, 2, channels, encoding, data)
+    }
+
+    #[test]
+    fn ktx2_files_parse() {
+        for (channels, encoding, format, type_size) in [
+            (3, TexelEncoding::Srgb, ktx2::Format::R8G8B8A8_SRGB, 1),
+            (1, TexelEncoding::Linear, ktx2::Format::R16_UNORM, 2),
+            (4, TexelEncoding::Hdr, ktx2::Format::R16G16B16A16_SFLOAT, 2),
+        ] {
+            let bytes = gradient(channels, encoding).to_ktx2();
+            let reader = ktx2::Reader::new(&bytes).unwrap();
+            let header = reader.header();
+            assert_eq!(header.format, Some(format));
+            assert_eq!(header.type_size, type_size);
+            assert_eq!((header.pixel_width, header.pixel_height), (4, 2));
+            assert_eq!(header.face_count, 1);
+
+            let texel_size = type_size * if channels == 1 { 1 } else { 4 };
+            let levels: Vec<_> = reader.levels().collect();
+            assert_eq!(levels.len(), 1);
+            assert_eq!(levels[0].len(), (4 * 2 * texel_size) as usize);
+
+            let dfd = reader.data_format_descriptors().next().unwrap();
+            let basic = ktx2::BasicDataFormatDescriptor::parse(dfd.data).unwrap();
+            assert_eq!(basic.bytes_planes[0], texel_size);
+            assert_eq!(
+                basic.sample_information().count(),
+                if channels == 1 { 1 } else { 4 }
+            );
+        }
+    }
+
+    #[test]
+    fn ktx2_cubemaps_have_six_faces() {
+        let radiance = vec![bevy::math::Vec3::splat(2.0); 6 * 8 * 8];
+        let bytes = ExportTexture::cubemap(8, &radiance).to_ktx2();
+        let reader = ktx2::Reader::new(&bytes).unwrap();
+        assert_eq!(reader.header().face_count, 6);
+        let level = reader.levels().next().unwrap();
+        assert_eq!(level.len(), 6 * 8 * 8 * 8);
+        // Light brighter than white is kept
+        assert_eq!(f16::from_le_bytes([level[0], level[1]]).to_f32(), 2.0);
+    }
+
+    #[test]
+    fn pngs_round_trip() {
+        let dir = std::env::temp_dir().join("planet_material_export_test");
+        fs::create_dir_all(&dir).unwrap();
+        let texture = gradient(1, TexelEncoding::Linear);
+        for format in [ExportFormat::Png, ExportFormat::Png16] {
+            let path = dir.join(format!("{format:?}.png"));
+            texture.write(&path, format).unwrap();
+            let image = image::open(&path).unwrap();
+            let max = if format == ExportFormat::Png {
+                255.0
+            } else {
+                65535.0
+            };
+            let values: Vec<f32> = match image {
+                DynamicImage::ImageLuma8(image) => image
+                    .into_raw()
+                    .into_iter()
+                    .map(|v| v as f32 / max)
+                    .collect(),
+                DynamicImage::ImageLuma16(image) => image
+                    .into_raw()
+                    .into_iter()
+                    .map(|v| v as f32 / max)
+                    .collect(),
+                image => panic!("{format:?} was read back as {:?}", image.color()),
+            };
+            for (value, expected) in values.into_iter().zip(&texture.data) {
+                assert!((value - expected).abs() <= 0.5 / max, "{value} {expected}");
+            }
+        }
+    }
+}
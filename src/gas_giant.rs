@@ -0,0 +1,310 @@
+//! The banded atmosphere of gas giants, and a CPU mirror of its colors in
+//! `shaders/gas_giant.wgsl` for baking their maps.
+
+use std::f32::consts::{PI, TAU};
+
+use bevy::{
+    color::LinearRgba,
+    math::Vec3,
+    tasks::{ComputeTaskPool, ParallelSliceMut, TaskPool},
+};
+use bevy_shader_utils::{
+    hash::{seed_float, seed_hash},
+    simplex_noise_3d::simplex_noise_3d,
+};
+
+use crate::{
+    climate::lat_long_to_direction,
+    export::{ExportTexture, TexelEncoding},
+    planet_maps::texel_lat_long,
+};
+
+pub use uniform::GasGiant;
+
+// Scopes the derive's lint allow, see `uniform` in `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy::{color::LinearRgba, render::render_resource::ShaderType};
+
+    /// Parameters of a gas giant's banded atmosphere.
+    ///
+    /// Mirrors `GasGiant` in `shaders/gas_giant.wgsl`. Gas giant planets and
+    /// the standalone gas giant material both shade from these values, so the
+    /// shaders never derive them from the seed themselves.
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct GasGiant {
+        /// Color of the light bands
+        pub zone_color: LinearRgba,
+        /// Color of the dark bands
+        pub belt_color: LinearRgba,
+        /// Color of the turbulent streaks along the band edges
+        pub accent_color: LinearRgba,
+        /// Color of the storm cores
+        pub storm_color: LinearRgba,
+        pub seed: u32,
+        /// Number of vortex storms, up to [`GasGiant::MAX_STORMS`]
+        pub storm_count: u32,
+        /// Number of light/dark band pairs from pole to pole
+        pub band_count: f32,
+        /// Strength of the turbulence along the band edges
+        pub turbulence: f32,
+        /// Speed of the zonal winds, in radians per second at the fastest jet
+        pub wind_speed: f32,
+    }
+}
+
+impl GasGiant {
+    /// Matches `MAX_STORMS` in `shaders/gas_giant.wgsl`
+    pub const MAX_STORMS: u32 = 8;
+
+    /// A gas giant with seeded band colors, band count and number of storms
+    pub fn from_seed(seed: u32) -> Self {
+        let tint = |index: u32, base: Vec3| {
+            let color = Vec3::new(
+                seed_float(seed, index),
+                seed_float(seed, index + 1),
+                seed_float(seed, index + 2),
+            ) * 0.3
+                + base;
+            LinearRgba::rgb(color.x, color.y, color.z)
+        };
+
+        Self {
+            zone_color: tint(61, Vec3::new(0.65, 0.55, 0.4)),
+            belt_color: tint(64, Vec3::new(0.35, 0.25, 0.15)),
+            storm_color: tint(67, Vec3::new(0.6, 0.25, 0.15)),
+            seed,
+            storm_count: (seed_float(seed, 70) * (Self::MAX_STORMS / 2 + 1) as f32) as u32,
+            band_count: 6.0 + 8.0 * seed_float(seed, 60),
+            ..Default::default()
+        }
+    }
+
+    /// Color of the cloud tops towards `direction` in the planet's frame,
+    /// `time` seconds in. Mirrors `gas_giant_color`.
+    pub fn color(&self, direction: Vec3, time: f32) -> Vec3 {
+        let mut p = direction.normalize();
+        // Small enough for the noise offsets to stay precise
+        let seed = seed_float(self.seed, 100) * 1000.0;
+        let mut storm_mask = 0.0f32;
+
+        // Vortices twist the sampling position around their centers
+        for index in 0..self.storm_count.min(Self::MAX_STORMS) {
+            let (center, signed_radius) = self.storm(index, time);
+            let radius = signed_radius.abs();
+            let distance = p.dot(center).clamp(-1.0, 1.0).acos();
+            let falloff = (1.0 - distance / (radius * 1.6)).max(0.0);
+            let angle = sign(signed_radius) * (falloff * falloff * 6.0 + falloff * time * 0.3);
+            p = rotate_around(p, center, angle);
+            storm_mask = storm_mask.max(smoothstep(radius, radius * 0.6, distance));
+        }
+
+        // Flow noise along the zonal winds, cross-faded between two phases
+        let latitude = p.y.clamp(-1.0, 1.0).asin();
+        let flow = zonal_wind(latitude, self.band_count) * self.wind_speed;
+        let cycle = 20.0;
+        let phase_a = fract(time / cycle);
+        let phase_b = fract(time / cycle + 0.5);
+        let turbulence_a = band_turbulence(rotate_around(p, Vec3::Y, flow * phase_a * cycle), seed);
+        let turbulence_b = band_turbulence(
+            rotate_around(p, Vec3::Y, flow * phase_b * cycle),
+            seed + 101.0,
+        );
+        let turbulence = turbulence_a + (turbulence_b - turbulence_a) * (1.0 - 2.0 * phase_a).abs();
+
+        let bands =
+            0.5 + 0.5 * ((p.y + turbulence * self.turbulence * 0.1) * self.band_count * PI).sin();
+        let rgb = |color: LinearRgba| Vec3::new(color.red, color.green, color.blue);
+        let mut color = rgb(self.belt_color).lerp(rgb(self.zone_color), bands);
+        color = color.lerp(
+            rgb(self.accent_color),
+            smoothstep(0.35, 0.8, turbulence) * self.turbulence,
+        );
+        color.lerp(rgb(self.storm_color), storm_mask)
+    }
+
+    /// Center and signed radius of storm `index`, the sign giving its spin
+    fn storm(&self, index: u32, time: f32) -> (Vec3, f32) {
+        let seed = seed_hash(self.seed, index);
+        // Big storms sit in the bands between the jets
+        let latitude = ((seed_float(seed, 1) * self.band_count * 0.8).floor() + 0.5)
+            / self.band_count
+            * PI
+            * 0.5
+            * sign(seed_float(seed, 2) - 0.5);
+        // and drift with the wind at their latitude
+        let longitude = seed_float(seed, 3) * TAU
+            + zonal_wind(latitude, self.band_count) * self.wind_speed * time;
+        let radius = 0.04 + (0.16 - 0.04) * seed_float(seed, 4).powf(3.0);
+        let spin = sign(seed_float(seed, 5) - 0.5);
+        (lat_long_to_direction(latitude, longitude), radius * spin)
+    }
+
+    /// Base color at `time` zero, on a latitude/longitude grid with the
+    /// layout of [`PlanetMaps`](crate::planet_maps::PlanetMaps)
+    pub fn albedo_map(&self, width: u32) -> ExportTexture {
+        let height = (width / 2).max(1);
+        let mut data = vec![0.0; (width * height * 3) as usize];
+        let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        data.par_chunk_map_mut(task_pool, (width.max(1) * 3) as usize, |y, row| {
+            for (x, texel) in row.chunks_exact_mut(3).enumerate() {
+                let (latitude, longitude) = texel_lat_long(x as u32, y as u32, width, height);
+                let color = self.color(lat_long_to_direction(latitude, longitude), 0.0);
+                texel.copy_from_slice(&color.to_array());
+            }
+        });
+        ExportTexture::new(width, height, 3, TexelEncoding::Srgb, data)
+    }
+}
+
+impl Default for GasGiant {
+    /// A Jupiter-like gas giant with a single great storm
+    fn default() -> Self {
+        Self {
+            zone_color: LinearRgba::rgb(0.82, 0.72, 0.55),
+            belt_color: LinearRgba::rgb(0.45, 0.30, 0.18),
+            accent_color: LinearRgba::rgb(0.95, 0.92, 0.85),
+            storm_color: LinearRgba::rgb(0.70, 0.28, 0.14),
+            seed: 0,
+            storm_count: 1,
+            band_count: 10.0,
+            turbulence: 1.0,
+            wind_speed: 0.02,
+        }
+    }
+}
+
+/// Rotates `p` around the unit `axis` by `angle` radians
+fn rotate_around(p: Vec3, axis: Vec3, angle: f32) -> Vec3 {
+    p * angle.cos() + axis.cross(p) * angle.sin() + axis * axis.dot(p) * (1.0 - angle.cos())
+}
+
+/// Zonal wind at a latitude, alternating east and west between neighbouring bands
+fn zonal_wind(latitude: f32, band_count: f32) -> f32 {
+    (latitude * band_count * 2.0).sin() * latitude.cos()
+}
+
+/// Turbulent streaks, stretched along the bands
+fn band_turbulence(p: Vec3, seed: f32) -> f32 {
+    let stretch = Vec3::new(2.0, 10.0, 2.0);
+    // Domain warp so the streaks curl instead of running straight
+    let warp = Vec3::new(
+        simplex_noise_3d(p * stretch + seed * 0.013),
+        simplex_noise_3d(p * stretch + 17.0 + seed * 0.007),
+        simplex_noise_3d(p * stretch + 31.0 + seed * 0.011),
+    ) * 0.15;
+    let q = p + warp;
+    simplex_noise_3d(q * stretch * 1.5 + seed * 0.01) * 0.6
+        + simplex_noise_3d(q * stretch * 4.0) * 0.3
+        + simplex_noise_3d(q * stretch * 9.0) * 0.1
+}
+
+/// WGSL's `sign`, which is 0.0 at 0.0
+fn sign(x: f32) -> f32 {
+    if x > 0.0 {
+        1.0
+    } else if x < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+/// WGSL's `fract`, which rounds towards negative infinity
+fn fract(x: f32) -> f32 {
+    x - x.floor()
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_stay_in_range() {
+        for seed in 0..1000 {
+            let gas_giant = GasGiant::from_seed(seed);
+            assert!(gas_giant.storm_count <= GasGiant::MAX_STORMS / 2);
+            assert!((6.0..=14.0).contains(&gas_giant.band_count));
+            // Light zones over dark belts
+            let luminance = |color: LinearRgba| color.red + color.green + color.blue;
+            assert!(luminance(gas_giant.zone_color) > luminance(gas_giant.belt_color));
+        }
+        assert_ne!(GasGiant::from_seed(1), GasGiant::from_seed(2));
+    }
+
+    /// Outputs of `gas_giant_color` at a direction and time, for the default
+    /// gas giant and the one from seed 7. Printed by `tests/golden.rs`, like
+    /// the other tables.
+    const GOLDEN: [([f32; 4], [f32; 6]); 8] = [
+        (
+            [1.0, 0.0, 0.0, 0.0],
+            [
+                0.52069914, 0.38025308, 0.25069916, 0.48921677, 0.36519822, 0.34546733,
+            ],
+        ),
+        (
+            [0.3, 0.5, -0.81, 0.0],
+            [
+                0.6514686, 0.5286941, 0.3814686, 0.7729231, 0.621119, 0.60674846,
+            ],
+        ),
+        (
+            [-0.6, -0.2, 0.77, 12.5],
+            [
+                0.72989833, 0.6177225, 0.45989835, 0.39307037, 0.27846816, 0.2569207,
+            ],
+        ),
+        (
+            [0.05, 0.99, 0.1, 37.0],
+            [
+                0.7451402, 0.6350241, 0.47514024, 0.7718339, 0.62013644, 0.6057453,
+            ],
+        ),
+        (
+            [-0.7, 0.1, -0.7, 100.0],
+            [
+                0.7750047, 0.6689243, 0.5050047, 0.43859395, 0.3195333, 0.29884595,
+            ],
+        ),
+        (
+            [0.45, -0.55, 0.7, 3.25],
+            [
+                0.78487957, 0.6801336, 0.5148796, 0.38941166, 0.2751678, 0.2535512,
+            ],
+        ),
+        (
+            [0.1, -0.98, 0.15, 0.0],
+            [
+                0.79044527, 0.68645144, 0.5204453, 0.4818961, 0.3585945, 0.33872533,
+            ],
+        ),
+        (
+            [-0.2, 0.35, 0.91, 61.0],
+            [
+                0.4551367, 0.30583087, 0.1851367, 0.70299816, 0.5611422, 0.54413664,
+            ],
+        ),
+    ];
+
+    #[test]
+    fn colors_match_the_shader() {
+        let gas_giants = [GasGiant::default(), GasGiant::from_seed(7)];
+        for ([x, y, z, time], expected) in GOLDEN {
+            let found =
+                gas_giants.map(|gas_giant| gas_giant.color(Vec3::new(x, y, z), time).to_array());
+            // The twisting storms and warped noise amplify the rounding of
+            // the GPU's transcendentals
+            for (value, expected) in found.into_iter().flatten().zip(expected) {
+                assert!(
+                    (value - expected).abs() <= 2e-3,
+                    "{found:?} != {expected:?} at {x} {y} {z} {time}"
+                );
+            }
+        }
+    }
+}
@@ -1,3 +1,5 @@
+//! A module for generating various geometric shapes.
+
 use bevy::{
     math::Vec3,
     render::{
@@ -6,8 +8,6 @@ use bevy::{
     },
 };
 
-/// A module for generating various geometric shapes.
-
 /// Creates a spherical cuboid mesh with the given radius and subdivisions.
 pub(crate) fn spherical_cuboid(
     radius: f32,
@@ -103,3 +103,39 @@ pub(crate) fn spherical_cuboid(
 
     mesh
 }
+
+/// Displaces the vertices of a sphere mesh centered on the origin along their
+/// direction from the center, by `height(direction) * radius`.
+///
+/// Normals are recomputed from the height function rather than from the
+/// triangles, so they stay continuous across the faces of a [`spherical_cuboid`].
+/// They always point outwards, inverted meshes are not supported.
+pub(crate) fn displace_sphere(mesh: &mut Mesh, radius: f32, height: impl Fn(Vec3) -> f32) {
+    // Step for the finite differences, about a tenth of a degree
+    const EPSILON: f32 = 0.002;
+    let surface = |direction: Vec3| direction * radius * (1.0 + height(direction));
+
+    let Some(positions) = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|positions| positions.as_float3())
+    else {
+        return;
+    };
+    let (positions, normals): (Vec<[f32; 3]>, Vec<[f32; 3]>) = positions
+        .iter()
+        .map(|&position| {
+            let direction = Vec3::from(position).normalize();
+            let tangent = direction.any_orthonormal_vector();
+            let bitangent = direction.cross(tangent);
+            let du = surface((direction + tangent * EPSILON).normalize())
+                - surface((direction - tangent * EPSILON).normalize());
+            let dv = surface((direction + bitangent * EPSILON).normalize())
+                - surface((direction - bitangent * EPSILON).normalize());
+            let normal = du.cross(dv).normalize();
+            (surface(direction).to_array(), normal.to_array())
+        })
+        .unzip();
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+}
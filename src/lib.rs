@@ -0,0 +1,13 @@
+//! CPU side of the planet generation, shared by the app and by gameplay code.
+
+pub mod atmosphere;
+pub mod climate;
+pub mod clouds;
+pub mod export;
+pub mod gas_giant;
+pub mod planet_kind;
+pub mod planet_maps;
+pub mod sea_distance;
+pub mod sky;
+pub mod skybox;
+pub mod terrain;
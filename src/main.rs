@@ -1,42 +1,64 @@
 use std::f32::consts::PI;
 
+use atmosphere_pass::AtmosphereBody;
 use bevy::{
     app::{App, Startup, Update},
-    asset::{AssetServer, Assets, Handle},
-    color::{
-        palettes::tailwind::{ORANGE_300, YELLOW_500, ZINC_300},
-        Color,
-    },
+    asset::{Assets, Handle},
+    color::{palettes::tailwind::ZINC_300, Color, LinearRgba},
+    core_pipeline::{bloom::BloomSettings, prepass::DepthPrepass},
     input::ButtonInput,
-    math::{Quat, Vec3},
+    math::{primitives::Rectangle, Quat, Vec3},
     pbr::{
         light_consts,
         wireframe::{Wireframe, WireframeColor},
-        AmbientLight, CascadeShadowConfigBuilder, DirectionalLight, DirectionalLightBundle,
-        ExtendedMaterial, MaterialMeshBundle, PbrBundle, StandardMaterial,
+        AmbientLight, DirectionalLight, DirectionalLightBundle, ExtendedMaterial,
+        MaterialMeshBundle, NotShadowCaster, StandardMaterial,
+    },
+    prelude::{
+        AlphaMode, BuildChildren, Camera3dBundle, Changed, Children, Commands, Component,
+        IntoSystemConfigs, KeyCode, Query, Res, ResMut, With,
+    },
+    render::{
+        camera::Camera,
+        mesh::Mesh,
+        texture::Image,
+        view::{NoFrustumCulling, Visibility},
     },
-    prelude::{Camera3dBundle, Commands, KeyCode, Query, Res, ResMut, Resource},
-    render::{mesh::Mesh, texture::Image},
     time::Time,
     transform::components::Transform,
     utils::default,
     DefaultPlugins,
 };
+use bevy_shader_utils::{
+    blackbody::blackbody_color,
+    craters::{crater_field, CraterField},
+    fresnel::FresnelRim,
+    pristine_grid::PristineGridMaterial,
+};
+use celestial_data::Sun;
 use celestial_shaders::{
-    AtmosphereMaterial, CelestialShadersPlugin, PlanetMaterial, SkyboxMaterial,
+    CelestialShadersPlugin, CloudMaterial, GasGiantMaterial, MoonMaterial, PlanetMaterial,
+    StarCoronaMaterial, StarMaterial,
 };
-use geometry::spherical_cuboid;
+use geometry::{displace_sphere, spherical_cuboid};
 use orbits::{OrbitalBody, OrbitalNode, OrbitalPlugin};
 
 use pcg_planet::PcgPlanetPlugin;
+use planet_material::{
+    atmosphere::Atmosphere, clouds::CloudLayer, gas_giant::GasGiant, planet_kind::PlanetKind,
+    sky::Sky,
+};
 use rand::Rng;
+use skybox_bake::SkyboxBake;
 
+mod atmosphere_luts;
+mod atmosphere_pass;
 mod celestial_data;
 mod celestial_shaders;
 mod geometry;
 mod orbits;
 mod pcg_planet;
-mod skybox;
+mod skybox_bake;
 
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
 use bevy_shader_utils::ShaderUtilsPlugin;
@@ -52,37 +74,58 @@ fn main() {
             OrbitalPlugin,
         ))
         .add_systems(Startup, setup)
-        .add_systems(Update, (orbit_sun, create_new_seed))
+        .add_systems(
+            Update,
+            (
+                orbit_sun,
+                (create_new_seed, sync_clouds).chain(),
+                toggle_graticule,
+            ),
+        )
         .run();
 }
 
 /// set up a simple 3D scene
+#[allow(clippy::too_many_arguments)]
 fn setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     mut planet_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, PlanetMaterial>>>,
-    mut atmo_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, AtmosphereMaterial>>>,
-    mut skybox_mats: ResMut<Assets<SkyboxMaterial>>,
-    asset_server: Res<AssetServer>,
+    mut gas_giant_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, GasGiantMaterial>>>,
+    mut moon_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, MoonMaterial>>>,
+    mut cloud_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, CloudMaterial>>>,
+    mut star_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, StarMaterial>>>,
+    mut corona_mats: ResMut<Assets<StarCoronaMaterial>>,
+    mut grid_mats: ResMut<Assets<PristineGridMaterial>>,
+    mut images: ResMut<Assets<Image>>,
 ) {
     let mut rng = rand::thread_rng();
 
     const SUN_RADIUS: f32 = 500.0;
     const PLANET_RADIUS: f32 = 150.0;
     const MOON_RADIUS: f32 = 50.0;
+    const GAS_GIANT_RADIUS: f32 = 350.0;
+    const OUTER_PLANET_RADIUS: f32 = 100.0;
 
     const PLANET_ORBIT_RADIUS: f32 = 1800.0;
     const MOON_ORBIT_RADIUS: f32 = 300.0;
+    const GAS_GIANT_ORBIT_RADIUS: f32 = 3200.0;
+    const OUTER_PLANET_ORBIT_RADIUS: f32 = 4400.0;
 
     // Sun
+    let sun = Sun {
+        seed: rng.gen(),
+        temperature: StarMaterial::SUN_TEMPERATURE as f64,
+    };
+    let star = StarMaterial::from_temperature(sun.temperature as f32, sun.seed as u32);
+    let corona = StarCoronaMaterial::for_star(&star, SUN_RADIUS);
     let sun_entity = commands
         .spawn((
-            PbrBundle {
+            MaterialMeshBundle {
                 mesh: meshes.add(spherical_cuboid(SUN_RADIUS, 16, false, true)),
-                material: materials.add(StandardMaterial {
-                    base_color: ORANGE_300.into(),
-                    ..Default::default()
+                material: star_mats.add(ExtendedMaterial {
+                    base: StandardMaterial::default(),
+                    extension: star,
                 }),
                 transform: Transform::from_xyz(0.0, 0.0, 0.0),
                 ..default()
@@ -93,14 +136,28 @@ fn setup(
                 radius: SUN_RADIUS,
                 angular_momentum: 10_000_000.0,
             },
-            Wireframe,
-            WireframeColor {
-                color: YELLOW_500.into(),
-            },
+            // The sun is the light, it does not shadow itself
+            NotShadowCaster,
         ))
         .id();
+    commands
+        .spawn((
+            MaterialMeshBundle {
+                mesh: meshes.add(Rectangle::new(1.0, 1.0)),
+                material: corona_mats.add(corona),
+                ..default()
+            },
+            NoFrustumCulling,
+            NotShadowCaster,
+        ))
+        .set_parent(sun_entity);
 
     // Create planet
+    let atmosphere = AtmosphereBody::new(Atmosphere::earth_like(PLANET_RADIUS, 6.0), &mut images);
+    let planet_material = PlanetMaterial::from_seed(rng.gen())
+        .with_sea_distance(PLANET_RADIUS, &mut images)
+        .with_atmosphere(&atmosphere);
+    let clouds = Clouds(planet_material.clouds);
     let planet_entity = commands
         .spawn((
             MaterialMeshBundle {
@@ -111,13 +168,13 @@ fn setup(
                         base_color: Color::srgb(0.0, 0.0, 1.0),
                         ..Default::default()
                     },
-                    extension: PlanetMaterial {
-                        // planet_radius: 180.0,
-                        planet_seed: rng.gen(),
-                    },
+                    extension: planet_material,
                 }),
                 ..default()
             },
+            atmosphere,
+            clouds,
+            MainPlanet,
             OrbitalBody {
                 mass: 10.0,
                 radius: PLANET_RADIUS,
@@ -131,86 +188,161 @@ fn setup(
             Wireframe,
         ))
         .id();
-    // // Create atmosphere
-    // commands.spawn(MaterialMeshBundle {
-    //     mesh: meshes.add(Sphere {
-    //         radius: 200.0,
-    //     }.mesh()),
-    //     transform: Transform::from_xyz(0.0, 0.0, 0.0),
-    //     material: atmo_mats.add(ExtendedMaterial {
-    //         base: StandardMaterial {
-    //             base_color: Color::rgb(1.0, 1.0, 1.0),
-    //             alpha_mode: AlphaMode::Blend,
-    //             ..Default::default()
-    //         },
-    //         extension: AtmosphereMaterial {
-    //             // planet_radius: 180.0,
-    //             planet_radius: 180.0,
-    //             atmosphere_radius: 200.0,
-    //             atmosphere_color: Color::rgba(0.0, 0.0, 1.0, 0.1),
-    //             atmosphere_density: 0.1,
-    //         },
-    //     }),
-    //     ..default()
-    // });
 
-    // Moon
-    let moon_entity = commands
+    // Clouds, on a shell that turns with the planet. `sync_clouds` fills in
+    // the layer and the altitude.
+    commands
         .spawn((
-            PbrBundle {
-                mesh: meshes.add(spherical_cuboid(MOON_RADIUS, 16, false, true)),
-                material: materials.add(StandardMaterial {
-                    base_color: ZINC_300.into(),
-                    ..Default::default()
+            MaterialMeshBundle {
+                mesh: meshes.add(spherical_cuboid(PLANET_RADIUS, 16, false, true)),
+                material: cloud_mats.add(ExtendedMaterial {
+                    base: StandardMaterial {
+                        base_color: Color::WHITE,
+                        alpha_mode: AlphaMode::Blend,
+                        ..Default::default()
+                    },
+                    extension: CloudMaterial::default(),
                 }),
-                transform: Transform::from_xyz(PLANET_ORBIT_RADIUS + MOON_ORBIT_RADIUS, 0.0, 0.0),
                 ..default()
             },
-            OrbitalNode::Intermediate {
-                radius: MOON_ORBIT_RADIUS,
-                parent_node: planet_entity,
-                orbital_period: 10.0,
-            },
-            OrbitalBody {
-                mass: 1.0,
-                radius: MOON_RADIUS,
-                angular_momentum: 10.0,
-            },
-            Wireframe,
-            WireframeColor {
-                color: ZINC_300.into(),
+            // The planet shader draws the cloud shadows itself
+            NotShadowCaster,
+        ))
+        .set_parent(planet_entity);
+
+    // Latitude and longitude lines, shown with G
+    commands
+        .spawn((
+            MaterialMeshBundle {
+                mesh: meshes.add(spherical_cuboid(PLANET_RADIUS, 16, false, true)),
+                transform: Transform::from_scale(Vec3::splat(1.001)),
+                material: grid_mats.add(PristineGridMaterial::graticule(15.0)),
+                visibility: Visibility::Hidden,
+                ..default()
             },
+            Graticule,
+            NotShadowCaster,
         ))
-        .id();
+        .set_parent(planet_entity);
 
-    // Skybox
+    // Moon
+    let craters = CraterField {
+        seed: rng.gen(),
+        ..default()
+    };
+    let mut moon_mesh = spherical_cuboid(MOON_RADIUS, 64, false, true);
+    displace_sphere(&mut moon_mesh, MOON_RADIUS, |direction| {
+        crater_field(direction, &craters).height
+    });
     commands.spawn((
         MaterialMeshBundle {
-            mesh: meshes.add(spherical_cuboid(35000.0, 32, true, true)),
-            material: skybox_mats.add(SkyboxMaterial {
-                seed: rng.gen::<u32>(),
+            mesh: meshes.add(moon_mesh),
+            material: moon_mats.add(ExtendedMaterial {
+                base: StandardMaterial::default(),
+                extension: MoonMaterial {
+                    craters,
+                    ..default()
+                },
             }),
+            transform: Transform::from_xyz(PLANET_ORBIT_RADIUS + MOON_ORBIT_RADIUS, 0.0, 0.0),
             ..default()
         },
+        OrbitalNode::Intermediate {
+            radius: MOON_ORBIT_RADIUS,
+            parent_node: planet_entity,
+            orbital_period: 10.0,
+        },
+        OrbitalBody {
+            mass: 1.0,
+            radius: MOON_RADIUS,
+            angular_momentum: 10.0,
+        },
         Wireframe,
+        WireframeColor {
+            color: ZINC_300.into(),
+        },
     ));
 
-    // let skybox_texture = skybox::generate_skybox(256, 256);
-    // let texture_handle = asset_server.add(skybox_texture);
+    // Gas giant
+    commands.spawn((
+        MaterialMeshBundle {
+            mesh: meshes.add(spherical_cuboid(GAS_GIANT_RADIUS, 32, false, true)),
+            transform: Transform::from_xyz(GAS_GIANT_ORBIT_RADIUS, 0.0, 0.0),
+            material: gas_giant_mats.add(ExtendedMaterial {
+                base: StandardMaterial::default(),
+                extension: GasGiantMaterial {
+                    gas_giant: GasGiant::from_seed(rng.gen()),
+                },
+            }),
+            ..default()
+        },
+        OrbitalBody {
+            mass: 300.0,
+            radius: GAS_GIANT_RADIUS,
+            angular_momentum: 500_000.0,
+        },
+        OrbitalNode::Intermediate {
+            radius: GAS_GIANT_ORBIT_RADIUS,
+            parent_node: sun_entity,
+            orbital_period: 120.0,
+        },
+    ));
+
+    // Outer planet, too far out to need the atmosphere's scattering: a rim
+    // stands in for it
+    commands.spawn((
+        MaterialMeshBundle {
+            mesh: meshes.add(spherical_cuboid(OUTER_PLANET_RADIUS, 16, false, true)),
+            transform: Transform::from_xyz(OUTER_PLANET_ORBIT_RADIUS, 0.0, 0.0),
+            material: planet_mats.add(ExtendedMaterial {
+                base: StandardMaterial::default(),
+                extension: PlanetMaterial {
+                    // No cloud shell, so no cloud shadows
+                    clouds: CloudLayer::NONE,
+                    ..PlanetMaterial::from_seed(rng.gen())
+                        .with_sea_distance(OUTER_PLANET_RADIUS, &mut images)
+                }
+                .with_rim(FresnelRim::new(LinearRgba::rgb(45.0, 75.0, 150.0), 3.0)),
+            }),
+            ..default()
+        },
+        OrbitalBody {
+            mass: 5.0,
+            radius: OUTER_PLANET_RADIUS,
+            angular_momentum: 500.0,
+        },
+        OrbitalNode::Intermediate {
+            radius: OUTER_PLANET_ORBIT_RADIUS,
+            parent_node: sun_entity,
+            orbital_period: 200.0,
+        },
+    ));
+
+    // Skybox, baked once and shown behind every camera
+    commands.insert_resource(SkyboxBake::new(
+        Sky::from_seed(rng.gen()),
+        1024,
+        true,
+        &mut images,
+    ));
 
     let camera_spawn = Vec3::new(0.5, 0.5, 0.5) * 5500.0;
 
     // camera
     commands.spawn((
         Camera3dBundle {
+            camera: Camera {
+                hdr: true,
+                ..default()
+            },
             transform: Transform::from_translation(camera_spawn).looking_at(Vec3::ZERO, Vec3::Y),
             ..default()
         },
+        // The sun's emission is bright enough to bloom
+        BloomSettings::NATURAL,
         PanOrbitCamera::default(),
-        // Skybox {
-        //     image: texture_handle.clone(),
-        //     brightness: 1000.0,
-        // },
+        // Bounds the atmosphere rays, see `AtmospherePlugin`
+        DepthPrepass,
     ));
 
     // ambient light
@@ -220,8 +352,11 @@ fn setup(
     });
 
     // directional 'sun' light
+    let sunlight = blackbody_color(sun.temperature as f32);
     commands.spawn(DirectionalLightBundle {
         directional_light: DirectionalLight {
+            // Sunlight has the color of the star's surface
+            color: Color::linear_rgb(sunlight.x, sunlight.y, sunlight.z),
             illuminance: light_consts::lux::OVERCAST_DAY,
             shadows_enabled: true,
             ..default()
@@ -244,29 +379,87 @@ fn setup(
     });
 }
 
-#[derive(Resource)]
-struct Cubemap {
-    is_loaded: bool,
-    image_handle: Handle<Image>,
+/// A shell of latitude and longitude lines over a planet
+#[derive(Component)]
+struct Graticule;
+
+fn toggle_graticule(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut graticules: Query<&mut Visibility, With<Graticule>>,
+) {
+    if keys.just_pressed(KeyCode::KeyG) {
+        for mut visibility in graticules.iter_mut() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Inherited,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
 }
 
 fn orbit_sun(time: Res<Time>, mut sun_query: Query<(&mut Transform, &DirectionalLight)>) {
     for (mut transform, _) in sun_query.iter_mut() {
-        transform.rotation = Quat::from_rotation_y(time.elapsed_seconds() as f32 * 0.1);
+        transform.rotation = Quat::from_rotation_y(time.elapsed_seconds() * 0.1);
+    }
+}
+
+type PlanetMaterialHandle = Handle<ExtendedMaterial<StandardMaterial, PlanetMaterial>>;
+type CloudMaterialHandle = Handle<ExtendedMaterial<StandardMaterial, CloudMaterial>>;
+
+/// The planet that Space gives a new surface
+#[derive(Component)]
+struct MainPlanet;
+
+/// The cloud layer of a planet. [`sync_clouds`] copies it into the planet's
+/// material, for the cloud shadows, and into the cloud shell among its children.
+#[derive(Component)]
+struct Clouds(CloudLayer);
+
+fn sync_clouds(
+    planets: Query<(&Clouds, &PlanetMaterialHandle, &Children), Changed<Clouds>>,
+    mut shells: Query<(&CloudMaterialHandle, &mut Transform)>,
+    mut planet_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, PlanetMaterial>>>,
+    mut cloud_mats: ResMut<Assets<ExtendedMaterial<StandardMaterial, CloudMaterial>>>,
+) {
+    for (Clouds(layer), planet_material, children) in &planets {
+        if let Some(material) = planet_mats.get_mut(planet_material) {
+            material.extension.clouds = *layer;
+        }
+        let mut shells = shells.iter_many_mut(children);
+        while let Some((cloud_material, mut transform)) = shells.fetch_next() {
+            if let Some(material) = cloud_mats.get_mut(cloud_material) {
+                material.extension.layer = *layer;
+            }
+            transform.scale = Vec3::splat(1.0 + layer.altitude);
+        }
     }
 }
 
 fn create_new_seed(
     keys: Res<ButtonInput<KeyCode>>,
     mut materials: ResMut<Assets<ExtendedMaterial<StandardMaterial, PlanetMaterial>>>,
+    mut images: ResMut<Assets<Image>>,
+    mut planets: Query<(&PlanetMaterialHandle, &OrbitalBody, &mut Clouds), With<MainPlanet>>,
 ) {
     // When the user presses space, we want to create a new seed
     if keys.just_pressed(KeyCode::Space) {
         let seed: u32 = rand::thread_rng().gen();
-        materials.iter_mut().for_each(|(_handle, material)| {
-            material.extension.planet_seed = seed;
-        });
-        println!("New Seed: {}", seed);
+        for (handle, body, mut clouds) in &mut planets {
+            let planet =
+                PlanetMaterial::from_seed(seed).with_sea_distance(body.radius, &mut images);
+            if let Some(material) = materials.get_mut(handle) {
+                // Same planet, new surface: keep its atmosphere and rim
+                material.extension = PlanetMaterial {
+                    atmosphere: material.extension.atmosphere,
+                    transmittance_lut: material.extension.transmittance_lut.clone(),
+                    sky_irradiance_lut: material.extension.sky_irradiance_lut.clone(),
+                    rim: material.extension.rim,
+                    ..planet
+                };
+            }
+            clouds.0 = planet.clouds;
+        }
+        println!("New Seed: {} ({:?})", seed, PlanetKind::from_seed(seed));
     }
     // query the planet material, then set a new seed
 }
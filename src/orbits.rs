@@ -67,7 +67,7 @@ fn update_orbital_nodes(mut q: Query<(&mut Transform, &OrbitalNode, Entity)>, ti
         .map(|(transform, _, entity)| (entity, *transform))
         .collect();
 
-    for (mut transform, node, entity) in q.iter_mut() {
+    for (mut transform, node, _) in q.iter_mut() {
         match node {
             OrbitalNode::Root => {}
             OrbitalNode::Intermediate {
@@ -81,7 +81,7 @@ fn update_orbital_nodes(mut q: Query<(&mut Transform, &OrbitalNode, Entity)>, ti
                 let omega = 2.0 * PI / orbital_period;
 
                 // Calculate the angular displacement of the node
-                let theta = omega * time.elapsed_seconds() as f32;
+                let theta = omega * time.elapsed_seconds();
 
                 // Assuming the orbit lies in the XZ plane and rotates around the Y axis
                 // Calculate the new position using quaternion rotation
@@ -100,7 +100,7 @@ fn update_orbital_bodies(mut q: Query<(&mut Transform, &OrbitalBody)>, time: Res
     for (mut transform, body) in q.iter_mut() {
         let angular_velocity = body.get_angular_velocity();
         transform.rotate(Quat::from_rotation_y(
-            angular_velocity as f32 * time.delta_seconds() as f32,
+            angular_velocity * time.delta_seconds(),
         ));
     }
 }
@@ -125,7 +125,7 @@ fn draw_orbit_gizmos(mut gizmos: Gizmos, q: Query<(&Transform, &OrbitalNode, Ent
                 gizmos.circle(
                     parent_transform.translation,
                     parent_transform.up(),
-                    *radius as f32,
+                    *radius,
                     Color::WHITE,
                 );
             }
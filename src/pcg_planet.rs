@@ -1,3 +1,6 @@
+// The LOD quadtree is unfinished, its systems aren't registered yet
+#![allow(dead_code)]
+
 use bevy::{
     app::{App, Plugin},
     asset::Assets,
@@ -53,7 +56,7 @@ fn spawn_planet(
     //     Wireframe
     // ));
     const LOD_LEVELS: u32 = 4;
-    for i in 0..LOD_LEVELS {}
+    for _ in 0..LOD_LEVELS {}
     let quad = Quad {
         rect: Rect::from_center_size(Vec2::ZERO, Vec2::splat(RADIUS)),
         transform: Transform::from_translation(Vec3::ZERO),
@@ -61,12 +64,12 @@ fn spawn_planet(
     let children = calculate_lod_quads(&quad);
 
     for n in children {
-        commands.spawn((n.clone(), n.transform.clone()));
+        commands.spawn((n.clone(), n.transform));
         commands.spawn((
             PbrBundle {
                 mesh: meshes.add(n.build()),
                 material: materials.add(Color::srgba(0.0, 0.0, 0.0, 0.0)),
-                transform: n.transform.clone(),
+                transform: n.transform,
                 ..Default::default()
             },
             Wireframe,
@@ -78,7 +81,7 @@ fn spawn_planet(
         PbrBundle {
             mesh: meshes.add(quad.build()),
             material: materials.add(Color::srgba(0.0, 0.0, 0.0, 0.0)),
-            transform: quad.transform.clone(),
+            transform: quad.transform,
             ..Default::default()
         },
         Wireframe,
@@ -113,11 +116,11 @@ fn hierarcical_lod(
     camera: Query<(&Camera, &GlobalTransform)>,
     quads: Query<(&Quad, &GlobalTransform)>,
 ) {
-    let (player_camera, player_camera_transform) = camera.get_single().unwrap();
+    let (_, player_camera_transform) = camera.get_single().unwrap();
 
     // Query all LodQuads
     // For each quad, check its visible size to the camera
-    for (quad, transform) in quads.iter() {
+    for (_quad, transform) in quads.iter() {
         // Calculate the distance from the camera to the quad
         let distance = (transform.translation() - player_camera_transform.translation()).length();
         println!("Distance: {}", distance);
@@ -0,0 +1,212 @@
+use bevy_shader_utils::hash::seed_hash;
+
+use crate::{climate::ClimateModel, clouds::CloudLayer};
+
+/// The archetype of a planet, selecting its shading path and climate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum PlanetKind {
+    /// Earth-like, with oceans, continents and biomes
+    #[default]
+    Terrestrial,
+    /// Almost entirely covered by water, with scattered islands
+    Ocean,
+    /// Hot and dry, with dune fields
+    Desert,
+    /// Frozen over, with crevassed ice sheets
+    Ice,
+    /// A dark basalt crust with glowing cracks and lakes of molten rock
+    Lava,
+    /// Barren and airless, like a moon
+    Rocky,
+    /// Banded clouds and storms, without a solid surface
+    GasGiant,
+}
+
+impl PlanetKind {
+    pub const ALL: [PlanetKind; 7] = [
+        PlanetKind::Terrestrial,
+        PlanetKind::Ocean,
+        PlanetKind::Desert,
+        PlanetKind::Ice,
+        PlanetKind::Lava,
+        PlanetKind::Rocky,
+        PlanetKind::GasGiant,
+    ];
+
+    /// Picks a kind from a planet seed. Terrestrial planets are the most common.
+    pub fn from_seed(seed: u32) -> Self {
+        // Weights of the kinds in `ALL`
+        const WEIGHTS: [u32; 7] = [4, 2, 2, 2, 1, 2, 3];
+        let total: u32 = WEIGHTS.iter().sum();
+
+        // The shaders draw the seed's other values from indices 1 and up
+        let mut roll = seed_hash(seed, 0) % total;
+
+        for (kind, weight) in Self::ALL.into_iter().zip(WEIGHTS) {
+            if roll < weight {
+                return kind;
+            }
+            roll -= weight;
+        }
+        unreachable!()
+    }
+
+    /// Parses a kind name, as given on the command line, e.g. `gas_giant`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_uppercase().replace('-', "_");
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.shader_def().strip_prefix("PLANET_KIND_") == Some(name.as_str()))
+    }
+
+    /// The shader def that selects this kind's path in `planet_shader.wgsl`
+    pub fn shader_def(self) -> &'static str {
+        match self {
+            PlanetKind::Terrestrial => "PLANET_KIND_TERRESTRIAL",
+            PlanetKind::Ocean => "PLANET_KIND_OCEAN",
+            PlanetKind::Desert => "PLANET_KIND_DESERT",
+            PlanetKind::Ice => "PLANET_KIND_ICE",
+            PlanetKind::Lava => "PLANET_KIND_LAVA",
+            PlanetKind::Rocky => "PLANET_KIND_ROCKY",
+            PlanetKind::GasGiant => "PLANET_KIND_GAS_GIANT",
+        }
+    }
+
+    /// Whether the kind has seas and biomes, shaded by `terrestrial_surface`
+    /// in `planet_shader.wgsl` from its [`climate`](Self::climate)
+    pub fn has_climate(self) -> bool {
+        matches!(
+            self,
+            PlanetKind::Terrestrial | PlanetKind::Ocean | PlanetKind::Desert | PlanetKind::Ice
+        )
+    }
+
+    /// The climate that goes with this kind
+    pub fn climate(self) -> ClimateModel {
+        let earth = ClimateModel::default();
+        match self {
+            PlanetKind::Terrestrial
+            | PlanetKind::Lava
+            | PlanetKind::Rocky
+            | PlanetKind::GasGiant => earth,
+            PlanetKind::Ocean => ClimateModel {
+                sea_level: 0.3,
+                max_precipitation: 500.0,
+                ..earth
+            },
+            PlanetKind::Desert => ClimateModel {
+                equator_temperature: 42.0,
+                pole_temperature: 5.0,
+                sea_level: 0.02,
+                max_precipitation: 60.0,
+                coastal_falloff: 12.0,
+                ..earth
+            },
+            PlanetKind::Ice => ClimateModel {
+                equator_temperature: -8.0,
+                pole_temperature: -55.0,
+                max_precipitation: 80.0,
+                ..earth
+            },
+        }
+    }
+
+    /// The cloud layer of this kind of planet, seeded by `seed`
+    pub fn clouds(self, seed: u32) -> CloudLayer {
+        let earth = CloudLayer {
+            seed,
+            ..Default::default()
+        };
+        match self {
+            PlanetKind::Terrestrial => earth,
+            PlanetKind::Ocean => CloudLayer {
+                coverage: 0.65,
+                ..earth
+            },
+            PlanetKind::Desert => CloudLayer {
+                coverage: 0.15,
+                altitude: 0.05,
+                ..earth
+            },
+            PlanetKind::Ice => CloudLayer {
+                coverage: 0.3,
+                wind_speed: 0.03,
+                ..earth
+            },
+            // Airless, or with the clouds as the visible surface
+            PlanetKind::Lava | PlanetKind::Rocky | PlanetKind::GasGiant => CloudLayer {
+                seed,
+                ..CloudLayer::NONE
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::climate::Biome;
+
+    use super::*;
+
+    #[test]
+    fn seeds_pick_kinds_by_weight() {
+        const SEEDS: u32 = 160_000;
+        let mut counts = [0u32; 7];
+        for seed in 0..SEEDS {
+            let kind = PlanetKind::from_seed(seed);
+            counts[PlanetKind::ALL.iter().position(|k| *k == kind).unwrap()] += 1;
+        }
+        // Terrestrial 4, ocean 2, desert 2, ice 2, lava 1, rocky 2, gas giant 3
+        for (count, weight) in counts.into_iter().zip([4, 2, 2, 2, 1, 2, 3]) {
+            let expected = SEEDS / 16 * weight;
+            assert!(count.abs_diff(expected) < expected / 20, "{counts:?}");
+        }
+    }
+
+    #[test]
+    fn neighbouring_seeds_pick_different_kinds() {
+        for start in (0..1000).step_by(8) {
+            let first = PlanetKind::from_seed(start);
+            assert!(
+                (start..start + 8).any(|seed| PlanetKind::from_seed(seed) != first),
+                "{start}"
+            );
+        }
+        assert_eq!(PlanetKind::from_seed(42), PlanetKind::from_seed(42));
+    }
+
+    #[test]
+    fn names_round_trip() {
+        for kind in PlanetKind::ALL {
+            let name = kind
+                .shader_def()
+                .strip_prefix("PLANET_KIND_")
+                .unwrap()
+                .to_ascii_lowercase();
+            assert_eq!(PlanetKind::from_name(&name), Some(kind));
+            assert_eq!(PlanetKind::from_name(&name.replace('_', "-")), Some(kind));
+            assert_eq!(
+                PlanetKind::from_name(&name.to_ascii_uppercase()),
+                Some(kind)
+            );
+        }
+        assert_eq!(PlanetKind::from_name("gas giant"), None);
+        assert_eq!(PlanetKind::from_name(""), None);
+        assert_eq!(PlanetKind::from_name("kind_terrestrial"), None);
+    }
+
+    #[test]
+    fn climates_match_the_kinds() {
+        let earth = PlanetKind::Terrestrial.climate();
+        assert!(PlanetKind::Ocean.climate().sea_level > earth.sea_level);
+        // Ice sheets everywhere but a band of tundra at the equator
+        let ice = PlanetKind::Ice.climate();
+        assert_eq!(ice.sample(0.0, ice.sea_level, 0.0).biome, Biome::Tundra);
+        assert_eq!(ice.sample(0.8, ice.sea_level, 0.0).biome, Biome::Ice);
+        let desert = PlanetKind::Desert.climate();
+        assert_eq!(desert.sample(0.5, 0.1, 0.0).biome, Biome::Desert);
+        assert!(PlanetKind::ALL
+            .into_iter()
+            .all(|kind| kind.climate().sea_level < kind.climate().summit_level));
+    }
+}
@@ -0,0 +1,302 @@
+//! Equirectangular maps of a planet's surface, baked on the CPU for export.
+
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    fs,
+    path::Path,
+};
+
+use bevy::{
+    math::Vec3,
+    tasks::{ComputeTaskPool, ParallelSliceMut, TaskPool},
+};
+
+use crate::{
+    climate::{lat_long_to_direction, Biome, ClimateModel, ClimateSample},
+    export::{ExportError, ExportFormat, ExportTexture, TexelEncoding},
+    sea_distance::SeaDistance,
+};
+
+// Mirrors the palette of `shaders/terrain.wgsl`
+const COLOR_WATER_DEEP_DEEP_OCEAN: Vec3 = Vec3::new(0.16, 0.50, 0.61);
+const COLOR_WATER_DEEP_OCEAN: Vec3 = Vec3::new(0.235, 0.592, 0.666);
+const COLOR_WATER_OCEAN: Vec3 = Vec3::new(0.254, 0.647, 0.705);
+const COLOR_WATER_SHALLOW: Vec3 = Vec3::new(0.360, 0.682, 0.725);
+const COLOR_HIGHLAND: Vec3 = Vec3::new(0.650, 0.568, 0.462);
+const COLOR_MOUNTAIN: Vec3 = Vec3::new(0.486, 0.411, 0.352);
+const COLOR_MOUNTAIN_PEAKS: Vec3 = Vec3::new(0.8, 0.8, 0.8);
+
+/// The surface of a planet sampled on a latitude/longitude grid, twice as
+/// wide as it is high. Row 0 is the north pole, column 0 is longitude -180°.
+#[derive(Debug, Clone)]
+pub struct PlanetMaps {
+    pub width: u32,
+    pub height: u32,
+    pub climate: ClimateModel,
+    /// Normalized terrain elevation of each texel, row by row
+    pub elevation: Vec<f32>,
+    pub samples: Vec<ClimateSample>,
+}
+
+impl PlanetMaps {
+    /// Samples `elevation`, which takes a direction in planet-local space,
+    /// at the center of each texel of a `width` wide map
+    pub fn bake(climate: ClimateModel, width: u32, elevation: impl Fn(Vec3) -> f32 + Sync) -> Self {
+        let height = (width / 2).max(1);
+        let mut texels = vec![0.0; (width * height) as usize];
+        let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        texels.par_chunk_map_mut(task_pool, width.max(1) as usize, |y, row| {
+            for (x, texel) in row.iter_mut().enumerate() {
+                let (latitude, longitude) = texel_lat_long(x as u32, y as u32, width, height);
+                *texel = elevation(lat_long_to_direction(latitude, longitude));
+            }
+        });
+
+        // The sea distance field has the layout of the maps
+        let sea: Vec<bool> = texels.iter().map(|&e| e < climate.sea_level).collect();
+        let sea_distance = SeaDistance::from_sea(width, height, &sea);
+        let samples = texels
+            .iter()
+            .zip(&sea_distance.distance)
+            .enumerate()
+            .map(|(index, (&elevation, &sea_distance))| {
+                let y = index as u32 / width.max(1);
+                let (latitude, _) = texel_lat_long(0, y, width, height);
+                climate.sample(latitude, elevation, sea_distance)
+            })
+            .collect();
+
+        Self {
+            width,
+            height,
+            climate,
+            elevation: texels,
+            samples,
+        }
+    }
+
+    /// Normalized elevation, in `0.0..=1.0`
+    pub fn height_map(&self) -> ExportTexture {
+        let data = self.elevation.iter().map(|e| e.clamp(0.0, 1.0)).collect();
+        ExportTexture::new(self.width, self.height, 1, TexelEncoding::Linear, data)
+    }
+
+    /// Tangent space normals, x east and y north, remapped to `0.0..=1.0`.
+    /// `relief` is the height of an elevation of 1.0, as a fraction of the radius.
+    pub fn normal_map(&self, relief: f32) -> ExportTexture {
+        let (width, height) = (self.width as i64, self.height as i64);
+        let elevation = |x: i64, y: i64| {
+            // Longitude wraps around, latitude stops at the poles
+            let x = x.rem_euclid(width);
+            let y = y.clamp(0, height - 1);
+            self.elevation[(y * width + x) as usize] * relief
+        };
+        let latitude_step = PI / height as f32;
+        let longitude_step = TAU / width as f32;
+
+        let data = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let (latitude, _) = texel_lat_long(x as u32, y as u32, self.width, self.height);
+                // Distances between neighbouring texels on the unit sphere
+                let east = 2.0 * longitude_step * latitude.cos().max(latitude_step);
+                let north = 2.0 * latitude_step;
+                let slope_east = (elevation(x + 1, y) - elevation(x - 1, y)) / east;
+                let slope_north = (elevation(x, y - 1) - elevation(x, y + 1)) / north;
+                let normal = Vec3::new(-slope_east, -slope_north, 1.0).normalize();
+                (normal * 0.5 + 0.5).to_array()
+            })
+            .collect();
+        ExportTexture::new(self.width, self.height, 3, TexelEncoding::Linear, data)
+    }
+
+    /// Base color, the way `terrestrial_surface` in `shaders/planet_shader.wgsl`
+    /// paints it with `terrestrial_color`. The dunes of desert worlds and the
+    /// crevasses of ice worlds are left out.
+    pub fn albedo_map(&self) -> ExportTexture {
+        let data = self
+            .elevation
+            .iter()
+            .zip(&self.samples)
+            .flat_map(|(&elevation, sample)| {
+                terrestrial_color(
+                    &self.climate,
+                    elevation,
+                    sample.temperature,
+                    sample.biome.color(),
+                )
+                .to_array()
+            })
+            .collect();
+        ExportTexture::new(self.width, self.height, 3, TexelEncoding::Srgb, data)
+    }
+
+    /// Perceptual roughness, the way `terrestrial_surface` sets it: glossy
+    /// water, matte sea ice and land
+    pub fn roughness_map(&self) -> ExportTexture {
+        let data = self
+            .elevation
+            .iter()
+            .zip(&self.samples)
+            .map(|(&elevation, sample)| {
+                terrestrial_roughness(&self.climate, elevation, sample.temperature)
+            })
+            .collect();
+        ExportTexture::new(self.width, self.height, 1, TexelEncoding::Linear, data)
+    }
+
+    /// Each texel in the color of its biome, see [`Biome::color`]
+    pub fn biome_map(&self) -> ExportTexture {
+        let data = self
+            .samples
+            .iter()
+            .flat_map(|sample| sample.biome.color().to_array())
+            .collect();
+        ExportTexture::new(self.width, self.height, 3, TexelEncoding::Srgb, data)
+    }
+
+    /// The `height` and `normal` maps, which every solid planet has
+    pub fn relief_maps(&self, relief: f32) -> [(&'static str, ExportTexture); 2] {
+        [
+            ("height", self.height_map()),
+            ("normal", self.normal_map(relief)),
+        ]
+    }
+
+    /// The `albedo`, `roughness` and `biome` maps of the kinds with seas and
+    /// biomes, see [`PlanetKind::has_climate`](crate::planet_kind::PlanetKind::has_climate)
+    pub fn surface_maps(&self) -> [(&'static str, ExportTexture); 3] {
+        [
+            ("albedo", self.albedo_map()),
+            ("roughness", self.roughness_map()),
+            ("biome", self.biome_map()),
+        ]
+    }
+
+    /// Writes `height`, `normal`, `albedo`, `roughness` and `biome` maps into `dir`, in `format`
+    pub fn export(
+        &self,
+        dir: impl AsRef<Path>,
+        format: ExportFormat,
+        relief: f32,
+    ) -> Result<(), ExportError> {
+        write_maps(
+            dir,
+            format,
+            self.relief_maps(relief)
+                .into_iter()
+                .chain(self.surface_maps()),
+        )
+    }
+}
+
+/// Writes each of `maps` into `dir` under its name, in `format`
+pub fn write_maps(
+    dir: impl AsRef<Path>,
+    format: ExportFormat,
+    maps: impl IntoIterator<Item = (&'static str, ExportTexture)>,
+) -> Result<(), ExportError> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    for (name, map) in maps {
+        map.write(dir.join(name).with_extension(format.extension()), format)?;
+    }
+    Ok(())
+}
+
+/// Latitude and longitude in radians at the center of a texel
+pub(crate) fn texel_lat_long(x: u32, y: u32, width: u32, height: u32) -> (f32, f32) {
+    let latitude = FRAC_PI_2 - (y as f32 + 0.5) / height as f32 * PI;
+    let longitude = (x as f32 + 0.5) / width as f32 * TAU - PI;
+    (latitude, longitude)
+}
+
+fn step(edge: f32, x: f32) -> f32 {
+    if x < edge {
+        0.0
+    } else {
+        1.0
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// How much of the sea is frozen over at a temperature in °C, in `0.0..=1.0`
+fn sea_ice(temperature: f32) -> f32 {
+    smoothstep(-8.0, -14.0, temperature)
+}
+
+/// Mirrors `terrestrial_color`: the sea by depth and frozen over where it is
+/// cold, and the `ground` of the land under highlands, mountains and peaks
+fn terrestrial_color(
+    climate: &ClimateModel,
+    elevation: f32,
+    temperature: f32,
+    ground: Vec3,
+) -> Vec3 {
+    let water_area = 1.0 - step(climate.sea_level, elevation);
+    let water_elevation = elevation * water_area / climate.sea_level;
+
+    let mut water =
+        COLOR_WATER_DEEP_DEEP_OCEAN.lerp(COLOR_WATER_DEEP_OCEAN, step(0.3, water_elevation));
+    water = water.lerp(COLOR_WATER_OCEAN, step(0.4, water_elevation));
+    water = water.lerp(COLOR_WATER_SHALLOW, step(0.8, water_elevation));
+    water = water.lerp(Biome::Ice.color(), sea_ice(temperature)) * water_area;
+
+    let land_area = 1.0 - water_area;
+    let land_elevation =
+        (elevation * land_area - climate.sea_level) / (climate.summit_level - climate.sea_level);
+    let mut land = ground;
+    land = land.lerp(COLOR_HIGHLAND, step(0.68, land_elevation));
+    land = land.lerp(COLOR_MOUNTAIN, step(0.85, land_elevation));
+    land = land.lerp(COLOR_MOUNTAIN_PEAKS, step(0.91, land_elevation));
+
+    water.lerp(land, land_area)
+}
+
+/// Mirrors `terrestrial_roughness`: glossy open water, matte sea ice and land
+fn terrestrial_roughness(climate: &ClimateModel, elevation: f32, temperature: f32) -> f32 {
+    let land_area = step(climate.sea_level, elevation);
+    let water = 0.15 + (0.6 - 0.15) * sea_ice(temperature);
+    water + (0.9 - water) * land_area
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Outputs of `terrestrial_color` and `terrestrial_roughness` with the
+    /// default climate model, at an elevation, temperature and ground color.
+    /// Printed by `tests/golden.rs`, like the other tables.
+    const GOLDEN: [([f32; 5], [f32; 4]); 10] = [
+        ([0.02, 20.0, 0.2, 0.541, 0.255], [0.16, 0.5, 0.61, 0.15]),
+        ([0.05, 20.0, 0.2, 0.541, 0.255], [0.235, 0.592, 0.666, 0.15]),
+        ([0.08, 4.0, 0.2, 0.541, 0.255], [0.254, 0.647, 0.705, 0.15]),
+        ([0.13, -11.0, 0.2, 0.541, 0.255], [0.64, 0.811, 0.8425, 0.375]),
+        ([0.14, -20.0, 0.2, 0.541, 0.255], [0.92, 0.94, 0.96, 0.6]),
+        ([0.15, 28.0, 0.886, 0.8, 0.576], [0.886, 0.8, 0.576, 0.9]),
+        ([0.2, 15.0, 0.361, 0.6, 0.329], [0.361, 0.6, 0.329, 0.9]),
+        ([0.42, 5.0, 0.306, 0.447, 0.329], [0.65, 0.568, 0.462, 0.9]),
+        ([0.46, -5.0, 0.608, 0.62, 0.529], [0.486, 0.411, 0.352, 0.9]),
+        ([0.6, -20.0, 0.92, 0.94, 0.96], [0.8, 0.8, 0.8, 0.9]),
+    ];
+
+    #[test]
+    fn colors_match_the_shader() {
+        let climate = ClimateModel::default();
+        for ([elevation, temperature, r, g, b], expected) in GOLDEN {
+            let color = terrestrial_color(&climate, elevation, temperature, Vec3::new(r, g, b));
+            let roughness = terrestrial_roughness(&climate, elevation, temperature);
+            let found = color.extend(roughness).to_array();
+            for (value, expected) in found.into_iter().zip(expected) {
+                assert!(
+                    (value - expected).abs() <= 1e-5,
+                    "{found:?} != {expected:?} at {elevation} {temperature}"
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,217 @@
+//! Distance inland from the sea, which sets how much rain reaches a coast's
+//! hinterland in the climate model.
+
+use std::{cmp::Reverse, collections::BinaryHeap, f32::consts::PI};
+
+use bevy::{
+    math::Vec3,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::{Image, ImageAddressMode, ImageSampler, ImageSamplerDescriptor},
+    },
+    tasks::{ComputeTaskPool, ParallelSliceMut, TaskPool},
+};
+use half::f16;
+
+use crate::{
+    climate::{direction_to_lat_long, lat_long_to_direction},
+    planet_maps::texel_lat_long,
+};
+
+/// The great circle distance, in radians, from the points of a latitude and
+/// longitude grid to the nearest point of the grid below sea level.
+///
+/// The grid has the layout of [`PlanetMaps`](crate::planet_maps::PlanetMaps).
+/// It is coarse: the distance runs from texel center to texel center over
+/// the 8 neighbours of each texel, which overestimates it by up to 8%.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeaDistance {
+    pub width: u32,
+    pub height: u32,
+    /// Distance of each texel, row by row. `PI` on a planet without sea.
+    pub distance: Vec<f32>,
+}
+
+impl SeaDistance {
+    /// Width of the grids the app bakes for its planets
+    pub const WIDTH: u32 = 256;
+
+    /// Samples `elevation`, which takes a direction in planet-local space, at
+    /// the center of each texel of a `width` wide grid, and measures the
+    /// distance to the texels below `sea_level`
+    pub fn bake(sea_level: f32, width: u32, elevation: impl Fn(Vec3) -> f32 + Sync) -> Self {
+        let height = (width / 2).max(1);
+        let mut sea = vec![false; (width * height) as usize];
+        let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        sea.par_chunk_map_mut(task_pool, width.max(1) as usize, |y, row| {
+            for (x, texel) in row.iter_mut().enumerate() {
+                let (latitude, longitude) = texel_lat_long(x as u32, y as u32, width, height);
+                *texel = elevation(lat_long_to_direction(latitude, longitude)) < sea_level;
+            }
+        });
+        Self::from_sea(width, height, &sea)
+    }
+
+    /// The distance field of a grid where `sea` is set on the texels below sea level
+    pub fn from_sea(width: u32, height: u32, sea: &[bool]) -> Self {
+        assert_eq!(sea.len(), (width * height) as usize);
+        let directions: Vec<Vec3> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (latitude, longitude) = texel_lat_long(x, y, width, height);
+                lat_long_to_direction(latitude, longitude)
+            })
+            .collect();
+
+        // Dijkstra from every sea texel at once. Positive floats sort like
+        // their bits.
+        let mut distance = vec![f32::INFINITY; sea.len()];
+        let mut queue = BinaryHeap::new();
+        for (index, _) in sea.iter().enumerate().filter(|(_, &sea)| sea) {
+            distance[index] = 0.0;
+            queue.push(Reverse((0.0f32.to_bits(), index)));
+        }
+        while let Some(Reverse((bits, index))) = queue.pop() {
+            let d = f32::from_bits(bits);
+            if d > distance[index] {
+                continue;
+            }
+            let (x, y) = ((index as u32 % width) as i64, (index as u32 / width) as i64);
+            for (dx, dy) in (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))) {
+                // Longitude wraps around, latitude stops at the poles
+                let ny = y + dy;
+                if (dx, dy) == (0, 0) || ny < 0 || ny >= height as i64 {
+                    continue;
+                }
+                let nx = (x + dx).rem_euclid(width as i64);
+                let neighbour = (ny * width as i64 + nx) as usize;
+                let step = directions[index]
+                    .dot(directions[neighbour])
+                    .clamp(-1.0, 1.0)
+                    .acos();
+                if d + step < distance[neighbour] {
+                    distance[neighbour] = d + step;
+                    queue.push(Reverse(((d + step).to_bits(), neighbour)));
+                }
+            }
+        }
+
+        // No point on a sphere is farther than PI from any other
+        for d in &mut distance {
+            *d = d.min(PI);
+        }
+        Self {
+            width,
+            height,
+            distance,
+        }
+    }
+
+    /// The distance towards the unit `direction`, filtered between the four
+    /// closest texels like the GPU samples [`SeaDistance::image`]
+    pub fn at(&self, direction: Vec3) -> f32 {
+        let (latitude, longitude) = direction_to_lat_long(direction);
+        let x = (longitude / (2.0 * PI) + 0.5) * self.width as f32 - 0.5;
+        let y = (0.5 - latitude / PI) * self.height as f32 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let texel = |x: f32, y: f32| {
+            let x = (x as i64).rem_euclid(self.width as i64);
+            let y = (y as i64).clamp(0, self.height as i64 - 1);
+            self.distance[(y * self.width as i64 + x) as usize]
+        };
+        let top = texel(x0, y0) + (texel(x0 + 1.0, y0) - texel(x0, y0)) * tx;
+        let bottom = texel(x0, y0 + 1.0) + (texel(x0 + 1.0, y0 + 1.0) - texel(x0, y0 + 1.0)) * tx;
+        top + (bottom - top) * ty
+    }
+
+    /// The field as a texture for `sea_distance_map` in `shaders/planet_shader.wgsl`,
+    /// repeating around the longitudes and clamped at the poles
+    pub fn image(&self) -> Image {
+        let data = self
+            .distance
+            .iter()
+            .flat_map(|&d| f16::from_f32(d).to_le_bytes())
+            .collect();
+        let mut image = Image::new(
+            Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::R16Float,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        image.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+            address_mode_u: ImageAddressMode::Repeat,
+            ..ImageSamplerDescriptor::linear()
+        });
+        image
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_away_from_the_coast() {
+        // Sea south of the equator, land to the north
+        let field = SeaDistance::bake(
+            0.5,
+            64,
+            |direction| {
+                if direction.y < 0.0 {
+                    0.0
+                } else {
+                    1.0
+                }
+            },
+        );
+        let at_latitude = |latitude: f32| field.at(lat_long_to_direction(latitude, 0.3));
+        assert_eq!(at_latitude(-0.5), 0.0);
+        // A texel center to texel center measure, so within a texel or two
+        let texel = PI / field.height as f32;
+        for latitude in [0.3, 0.8, 1.2] {
+            let d = at_latitude(latitude);
+            assert!((d - latitude).abs() < 2.0 * texel, "{latitude}: {d}");
+        }
+        assert!(at_latitude(0.8) > at_latitude(0.3));
+    }
+
+    #[test]
+    fn wraps_around_the_longitudes() {
+        // A sea around longitude 180°, land everywhere else
+        let field = SeaDistance::bake(
+            0.5,
+            64,
+            |direction| {
+                if direction.x < -0.9 {
+                    0.0
+                } else {
+                    1.0
+                }
+            },
+        );
+        let east = field.at(lat_long_to_direction(0.0, 2.5));
+        let west = field.at(lat_long_to_direction(0.0, -2.5));
+        assert!(east > 0.0 && (east - west).abs() < 1e-3, "{east} {west}");
+    }
+
+    #[test]
+    fn is_capped_without_sea() {
+        let field = SeaDistance::bake(0.0, 16, |_| 1.0);
+        assert!(field.distance.iter().all(|&d| d == PI));
+    }
+
+    #[test]
+    fn image_holds_the_field() {
+        let field = SeaDistance::bake(0.5, 16, |direction| direction.x);
+        let image = field.image();
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R16Float);
+        assert_eq!(image.data.len(), field.distance.len() * 2);
+    }
+}
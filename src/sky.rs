@@ -0,0 +1,40 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+pub use uniform::Sky;
+
+// Scopes the derive's lint allow, see `uniform` in `src/climate.rs`
+#[allow(dead_code)]
+mod uniform {
+    use bevy::render::render_resource::ShaderType;
+
+    /// Parameters of the procedural sky: a starfield with a galactic band and nebulae.
+    ///
+    /// Mirrors `Sky` in `shaders/skybox.wgsl`.
+    #[derive(ShaderType, Debug, Clone, Copy, PartialEq)]
+    pub struct Sky {
+        /// Picks the stars, their colors and magnitudes, and the galactic plane
+        pub seed: u32,
+        /// Opacity of the nebulae, 0.0 for none
+        pub nebula_density: f32,
+        /// Size of the nebula features, larger is bigger
+        pub nebula_scale: f32,
+        /// Colors of the nebulae, out of [`Sky::NEBULA_PALETTES`]
+        pub nebula_palette: u32,
+    }
+}
+
+impl Sky {
+    /// Number of palettes in `skybox.wgsl`, larger indices wrap around
+    pub const NEBULA_PALETTES: u32 = 4;
+
+    /// A sky with seeded nebula density, scale and palette
+    pub fn from_seed(seed: u32) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed as u64);
+        Self {
+            seed,
+            nebula_density: rng.gen_range(0.4..1.0),
+            nebula_scale: rng.gen_range(0.6..1.6),
+            nebula_palette: rng.gen_range(0..Self::NEBULA_PALETTES),
+        }
+    }
+}
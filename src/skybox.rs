@@ -1,5 +1,11 @@
+//! CPU generation of skybox cubemaps, for tools and exports that run without
+//! a GPU. The app bakes its sky on the GPU, see `skybox_bake.rs`.
+
+use std::{collections::HashMap, f32::consts::PI, path::Path};
+
 use bevy::{
-    math::Vec3,
+    color::{ColorToPacked, LinearRgba, Srgba},
+    math::{IVec3, UVec3, Vec3},
     prelude::default,
     render::{
         render_asset::RenderAssetUsages,
@@ -8,129 +14,468 @@ use bevy::{
         },
         texture::Image,
     },
+    tasks::{ComputeTaskPool, ParallelSliceMut, TaskPool},
+};
+use bevy_shader_utils::{
+    hash::{pcg, pcg3d, unit_float, unit_float_3d},
+    perlin_noise_3d::perlin_noise_3d,
+    simplex_noise_3d::simplex_noise_3d,
 };
-use noise::{core::simplex, NoiseFn, Perlin, Simplex};
-
-const SEED: u32 = 8564132;
-const SCALE: f32 = 10.0;
-
-/// Generates a skybox image
-pub(crate) fn generate_skybox(width: u32, height: u32) -> Image {
-    println!("Generating skybox");
-    let perlin = Perlin::new(SEED);
-    let simplex = Simplex::new(SEED);
-
-    let mut texture_data = Vec::new();
-
-    // Generate texture data for each face of the cubemap
-    for face in 0..6 {
-        // let face_color = match face {
-        //     0 => (255, 0, 0), // r
-        //     1 => (0, 255, 0), // g
-        //     2 => (0, 0, 255), // b
-        //     3 => (255, 255, 0), // y
-        //     4 => (0, 255, 255), // c
-        //     5 => (255, 0, 255), // m
-        //     _ => (0, 0, 0),
-        // };
-        for y in 0..height {
-            for x in 0..width {
-                let u = x as f32 / width as f32;
-                let v = y as f32 / height as f32;
-
-                // This is the position on the face of an unit sphere inscribed in the cube
-                let point = face_uv_to_direction(face, u, v) * SCALE;
-                let point_array = [point.x as f64, point.y as f64, point.z as f64];
-
-                // Stars Layer
-                let star_intensity = if rand::random::<f32>() < 0.001 {
-                    // Adjust probability as needed
-                    rand::random::<f32>() * 0.5 + 0.5 // Brightness of the star
-                } else {
-                    0.0
-                };
-                let stars_color = [
-                    simplex.get(point_array),
-                    simplex.get([
-                        point.x as f64 + 0.5,
-                        point.y as f64 + 0.5,
-                        point.z as f64 + 0.5,
-                    ]),
-                    simplex.get([
-                        point.x as f64 + 0.25,
-                        point.y as f64 + 0.25,
-                        point.z as f64 + 0.25,
-                    ]),
-                ];
-
-                // Nebulas Layer
-                let nebula_intensity = perlin.get([
-                    point.x as f64 * 0.5,
-                    point.y as f64 * 0.5,
-                    point.z as f64 * 0.5,
-                ]) as f32;
-                let nebula_color = (nebula_intensity * 0.8 + 0.2) * (1.0 - star_intensity); // Reduce nebula visibility where stars are present
-
-                // Galaxy Fog Layer
-                let fog_intensity = 0.1; // Constant value for subtle effect, adjust as needed
-
-                // Combine Layers
-                let combined_intensity = star_intensity + nebula_color + fog_intensity;
-                // let noise_value = perlin.get([direction.x as f64, direction.y as f64, direction.z as f64]) as f32 + 0.5;
-                let base_color = [
-                    (stars_color[0] as f32 * 255 as f32) as u8,
-                    (stars_color[1] as f32 * 255 as f32) as u8,
-                    (stars_color[2] as f32 * 255 as f32) as u8,
-                    255,
-                ]; //[255, 255, 255];
-                let final_color = [
-                    (base_color[0] as f32 * combined_intensity) as u8,
-                    (base_color[1] as f32 * combined_intensity) as u8,
-                    (base_color[2] as f32 * combined_intensity) as u8,
-                    255,
-                ];
-                texture_data.extend_from_slice(&base_color);
+
+use crate::export::{ExportError, ExportFormat, ExportTexture};
+
+/// Lattice cells across the radius of the sky. A star takes its direction
+/// from the cell that holds it, so cubemaps of every size show the same stars.
+pub const STAR_CELLS: f32 = 100.0;
+
+/// A layer of the generated sky. Layers add up, in linear color.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SkyLayer {
+    /// Point stars in a fraction `density` of the cells of a lattice of
+    /// directions, see [`STAR_CELLS`], up to `brightness` and tinted by a
+    /// slowly varying color
+    Stars { density: f32, brightness: f32 },
+    /// Clouds of Perlin noise, `scale` features across a radian
+    Nebula {
+        color: Vec3,
+        scale: f32,
+        intensity: f32,
+    },
+    /// A constant glow, the light of the stars too faint to see
+    Fog { color: Vec3 },
+}
+
+/// Generates skybox cubemaps from a seed and a stack of [`SkyLayer`]s.
+///
+/// The same seed, layers and size always give the same cubemap. Faces are in
+/// wgpu's order, +X, -X, +Y, -Y, +Z, -Z, and oriented the way Bevy's
+/// `Skybox` samples them, see [`face_uv_to_direction`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SkyboxGenerator {
+    pub seed: u32,
+    pub layers: Vec<SkyLayer>,
+}
+
+impl Default for SkyboxGenerator {
+    /// Sparse white stars over a faint purple nebula
+    fn default() -> Self {
+        Self {
+            seed: 8564132,
+            layers: vec![
+                SkyLayer::Fog {
+                    color: Vec3::splat(0.02),
+                },
+                SkyLayer::Nebula {
+                    color: Vec3::new(0.35, 0.2, 0.5),
+                    scale: 5.0,
+                    intensity: 0.3,
+                },
+                SkyLayer::Stars {
+                    density: 0.1,
+                    brightness: 1.0,
+                },
+            ],
+        }
+    }
+}
+
+impl SkyboxGenerator {
+    /// Linear radiance of every texel of a cubemap with `size` texels along
+    /// the faces: face by face, each row by row from the top left corner
+    pub fn generate_radiance(&self, size: u32) -> Vec<Vec3> {
+        let offset = noise_offset(self.seed);
+        let stars = StarField::new(self, offset, size);
+        let size = size as usize;
+        let mut texels = vec![Vec3::ZERO; 6 * size * size];
+        if size == 0 {
+            return texels;
+        }
+
+        let task_pool = ComputeTaskPool::get_or_init(TaskPool::default);
+        texels.par_chunk_map_mut(task_pool, size, |row, texels| {
+            let face = row / size;
+            let y = row % size;
+            for (x, texel) in texels.iter_mut().enumerate() {
+                let u = (x as f32 + 0.5) / size as f32;
+                let v = (y as f32 + 0.5) / size as f32;
+                let dir = face_uv_to_direction(face, u, v);
+                *texel = self.radiance(offset, dir) + stars.radiance(dir, texel_angle(size, u, v));
             }
+        });
+        texels
+    }
+
+    /// Radiance of the layers other than stars along the unit direction
+    /// `dir`, with the noises moved by `offset`
+    fn radiance(&self, offset: Vec3, dir: Vec3) -> Vec3 {
+        let mut radiance = Vec3::ZERO;
+        for layer in &self.layers {
+            radiance += match *layer {
+                SkyLayer::Stars { .. } => Vec3::ZERO,
+                SkyLayer::Nebula {
+                    color,
+                    scale,
+                    intensity,
+                } => {
+                    let cloud = perlin_noise_3d(dir * scale + offset);
+                    color * ((cloud * 0.8 + 0.2) * intensity).max(0.0)
+                }
+                SkyLayer::Fog { color } => color,
+            };
         }
+        radiance
     }
 
-    let mut result = Image::new_fill(
-        Extent3d {
-            width,
-            height: height * 6,
-            depth_or_array_layers: 1,
-        },
-        TextureDimension::D2,
-        &texture_data,
-        TextureFormat::Rgba8UnormSrgb,
-        RenderAssetUsages::RENDER_WORLD,
-    );
+    /// A cubemap with `size` texels along the faces, ready for Bevy's `Skybox`
+    pub fn generate(&self, size: u32) -> Image {
+        let data = self
+            .generate_radiance(size)
+            .into_iter()
+            .flat_map(|radiance| {
+                // Brighter than white clips
+                let linear = radiance.clamp(Vec3::ZERO, Vec3::ONE);
+                Srgba::from(LinearRgba::rgb(linear.x, linear.y, linear.z)).to_u8_array()
+            })
+            .collect();
 
-    result.reinterpret_stacked_2d_as_array(result.height() / result.width());
-    result.texture_view_descriptor = Some(TextureViewDescriptor {
-        dimension: Some(TextureViewDimension::Cube),
-        ..default()
-    });
+        let mut image = Image::new(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 6,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::RENDER_WORLD,
+        );
+        image.texture_view_descriptor = Some(TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::Cube),
+            ..default()
+        });
+        image
+    }
 
-    println!("Skybox generated");
+    /// Writes a cubemap with `size` texels along the faces to `path`, in `format`
+    pub fn export(
+        &self,
+        size: u32,
+        path: impl AsRef<Path>,
+        format: ExportFormat,
+    ) -> Result<(), ExportError> {
+        ExportTexture::cubemap(size, &self.generate_radiance(size)).write(path, format)
+    }
+}
 
-    result
+/// Generates the default sky, see [`SkyboxGenerator`]
+pub fn generate_skybox(size: u32) -> Image {
+    SkyboxGenerator::default().generate(size)
 }
 
-// Helper function to convert cubemap face pixel coordinates to a direction vector
-fn face_uv_to_direction(face: usize, u: f32, v: f32) -> Vec3 {
-    let x = (u - 0.5) * 2.0;
-    let y = (v - 0.5) * 2.0;
-    let z = 1.0;
+/// The world direction Bevy's `Skybox` shows at `u, v` on `face`, with `u`
+/// to the right and `v` down from the top left corner of the face.
+///
+/// Faces are in wgpu's order, and Bevy looks cubemaps up with z flipped, so
+/// the +Z face shows the sky towards -Z.
+pub fn face_uv_to_direction(face: usize, u: f32, v: f32) -> Vec3 {
+    let s = u * 2.0 - 1.0;
+    let t = v * 2.0 - 1.0;
 
     match face {
-        0 => Vec3::new(z, y, x),   // Positive X
-        1 => Vec3::new(-z, y, -x), // Negative X
-        2 => Vec3::new(x, -z, -y), // Negative Y
-        3 => Vec3::new(x, z, y),   // Positive Y
-        4 => Vec3::new(x, y, -z),  // Positive Z
-        5 => Vec3::new(-x, y, z),  // Negative Z
-        _ => Vec3::new(0.0, 0.0, 0.0),
+        0 => Vec3::new(1.0, -t, s),   // Positive X
+        1 => Vec3::new(-1.0, -t, -s), // Negative X
+        2 => Vec3::new(s, 1.0, -t),   // Positive Y
+        3 => Vec3::new(s, -1.0, t),   // Negative Y
+        4 => Vec3::new(s, -t, -1.0),  // Positive Z, looked up from -Z
+        5 => Vec3::new(-s, -t, 1.0),  // Negative Z, looked up from +Z
+        _ => panic!("a cubemap has no face {face}"),
     }
     .normalize()
 }
+
+/// The stars of every [`SkyLayer::Stars`], bucketed by direction so each texel
+/// only visits the stars close enough to reach it
+struct StarField {
+    /// Buckets across the radius of the sky, so a bucket is at least twice as
+    /// wide as the farthest a star reaches
+    buckets: f32,
+    /// Direction and color of the stars, by the bucket of their direction
+    stars: HashMap<IVec3, Vec<(Vec3, Vec3)>>,
+}
+
+impl StarField {
+    /// The stars of `generator`, for a cubemap with `size` texels along the faces
+    fn new(generator: &SkyboxGenerator, offset: Vec3, size: u32) -> Self {
+        // A star reaches 4 sigma, 2.4 times the widest texel angle, 2 / size
+        let buckets = size as f32 / 10.0;
+        let mut stars: HashMap<IVec3, Vec<(Vec3, Vec3)>> = HashMap::new();
+        for (i, layer) in generator.layers.iter().enumerate() {
+            let SkyLayer::Stars {
+                density,
+                brightness,
+            } = *layer
+            else {
+                continue;
+            };
+            for cell in sphere_cells() {
+                let traits = cell_random(cell, 2 * i as u32, generator.seed);
+                if traits.x >= density {
+                    continue;
+                }
+                let position = cell_random(cell, 2 * i as u32 + 1, generator.seed);
+                let dir = (cell.as_vec3() + position).normalize();
+                // Simplex noise is in -1.0..1.0, remapped to a color
+                let p = dir * 10.0 + offset;
+                let tint = Vec3::new(
+                    simplex_noise_3d(p),
+                    simplex_noise_3d(p + 0.5),
+                    simplex_noise_3d(p + 0.25),
+                ) * 0.5
+                    + 0.5;
+                let color = tint.clamp(Vec3::ZERO, Vec3::ONE) * (traits.y * 0.5 + 0.5) * brightness;
+                let bucket = (dir * buckets).floor().as_ivec3();
+                stars.entry(bucket).or_default().push((dir, color));
+            }
+        }
+        Self { buckets, stars }
+    }
+
+    /// Radiance of the stars around the unit direction `dir`, through a texel
+    /// `pixel` radians wide. Each star spreads over the texels around it as a
+    /// Gaussian a little narrower than a texel, keeping its total light.
+    fn radiance(&self, dir: Vec3, pixel: f32) -> Vec3 {
+        let sigma = pixel * 0.6;
+        // The 2x2x2 buckets closest to `dir` hold every star within half a
+        // bucket of it
+        let base = (dir * self.buckets - 0.5).floor().as_ivec3();
+        let mut radiance = Vec3::ZERO;
+        for i in 0..8 {
+            let bucket = base + IVec3::new(i & 1, (i >> 1) & 1, (i >> 2) & 1);
+            for &(star, color) in self.stars.get(&bucket).into_iter().flatten() {
+                let angle = dir.cross(star).length();
+                if dir.dot(star) < 0.0 || angle > sigma * 4.0 {
+                    continue;
+                }
+                let splat = (-0.5 * angle * angle / (sigma * sigma)).exp() * pixel * pixel
+                    / (2.0 * PI * sigma * sigma);
+                radiance += color * splat;
+            }
+        }
+        radiance
+    }
+}
+
+/// The lattice cells at most half a cell from the sphere of radius
+/// [`STAR_CELLS`], which hold the stars
+fn sphere_cells() -> impl Iterator<Item = IVec3> {
+    let radius = STAR_CELLS;
+    let reach = radius as i32 + 1;
+    (-reach..reach).flat_map(move |x| {
+        (-reach..reach).flat_map(move |y| {
+            // Cell centers are at `cell + 0.5`, so no center lies on z = 0 and
+            // the runs above and below it never share a cell
+            let ring = (x as f32 + 0.5).powi(2) + (y as f32 + 0.5).powi(2);
+            let outer = ((radius + 0.5).powi(2) - ring).max(0.0).sqrt();
+            let inner = ((radius - 0.5).powi(2) - ring).max(0.0).sqrt();
+            let run = |from: f32, to: f32| (from - 0.5).ceil() as i32..=(to - 0.5).floor() as i32;
+            run(-outer, -inner)
+                .chain(run(inner, outer))
+                .map(move |z| IVec3::new(x, y, z))
+        })
+    })
+}
+
+/// Three random floats in `0.0..1.0` for a lattice cell, one set per `salt`
+fn cell_random(cell: IVec3, salt: u32, seed: u32) -> Vec3 {
+    let key = pcg(seed ^ pcg(salt));
+    let key = UVec3::new(key, key.wrapping_mul(3), key.wrapping_mul(7));
+    unit_float_3d(pcg3d(cell.as_uvec3() ^ key))
+}
+
+/// The angle across the texel at `u, v` of a face with `size` texels, the
+/// square root of its solid angle
+fn texel_angle(size: usize, u: f32, v: f32) -> f32 {
+    let s = u * 2.0 - 1.0;
+    let t = v * 2.0 - 1.0;
+    2.0 / size as f32 / (1.0 + s * s + t * t).powf(0.75)
+}
+
+/// Where the seed moves the noises, which take no seed of their own.
+/// They repeat every 289 units.
+fn noise_offset(seed: u32) -> Vec3 {
+    let hash = pcg(seed);
+    Vec3::new(
+        unit_float(hash),
+        unit_float(pcg(hash)),
+        unit_float(pcg(pcg(hash))),
+    ) * 289.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The face and texture coordinates a cubemap lookup in `dir` samples,
+    /// straight from the cube map selection table of the Vulkan and WebGPU specs
+    fn wgpu_lookup(dir: Vec3) -> (usize, f32, f32) {
+        let a = dir.abs();
+        let (face, sc, tc, ma) = if a.x >= a.y && a.x >= a.z {
+            if dir.x > 0.0 {
+                (0, -dir.z, -dir.y, a.x)
+            } else {
+                (1, dir.z, -dir.y, a.x)
+            }
+        } else if a.y >= a.z {
+            if dir.y > 0.0 {
+                (2, dir.x, dir.z, a.y)
+            } else {
+                (3, dir.x, -dir.z, a.y)
+            }
+        } else if dir.z > 0.0 {
+            (4, dir.x, -dir.y, a.z)
+        } else {
+            (5, -dir.x, -dir.y, a.z)
+        };
+        (face, (sc / ma + 1.0) * 0.5, (tc / ma + 1.0) * 0.5)
+    }
+
+    /// What Bevy's skybox shader samples when looking towards `dir`
+    fn bevy_skybox_lookup(dir: Vec3) -> (usize, f32, f32) {
+        wgpu_lookup(dir * Vec3::new(1.0, 1.0, -1.0))
+    }
+
+    #[test]
+    fn faces_round_trip_through_bevy_lookups() {
+        for face in 0..6 {
+            for (u, v) in [(0.5, 0.5), (0.1, 0.2), (0.9, 0.3), (0.25, 0.8), (0.7, 0.95)] {
+                let (lookup_face, lookup_u, lookup_v) =
+                    bevy_skybox_lookup(face_uv_to_direction(face, u, v));
+                assert_eq!(lookup_face, face, "face {face} at ({u}, {v})");
+                assert!(
+                    (lookup_u - u).abs() < 1e-5 && (lookup_v - v).abs() < 1e-5,
+                    "face {face} at ({u}, {v}) is looked up at ({lookup_u}, {lookup_v})"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn face_centers_follow_bevy_convention() {
+        let centers = [Vec3::X, -Vec3::X, Vec3::Y, -Vec3::Y, -Vec3::Z, Vec3::Z];
+        for (face, center) in centers.into_iter().enumerate() {
+            let dir = face_uv_to_direction(face, 0.5, 0.5);
+            assert!(dir.abs_diff_eq(center, 1e-6), "face {face}: {dir}");
+        }
+    }
+
+    #[test]
+    fn side_faces_are_upright() {
+        // Seen from inside, u goes right and v goes down, with +Y up
+        for face in [0, 1, 4, 5] {
+            let forward = face_uv_to_direction(face, 0.5, 0.5);
+            let right = forward.cross(Vec3::Y);
+            let du = face_uv_to_direction(face, 0.6, 0.5) - forward;
+            let dv = face_uv_to_direction(face, 0.5, 0.6) - forward;
+            assert!(du.dot(right) > 0.0, "face {face}: u goes {du}");
+            assert!(dv.dot(Vec3::Y) < 0.0, "face {face}: v goes {dv}");
+        }
+    }
+
+    #[test]
+    fn faces_meet_at_the_edges() {
+        // The right edge of +X meets the left edge of -Z, which shows +Z
+        for v in [0.1, 0.5, 0.9] {
+            let a = face_uv_to_direction(0, 1.0, v);
+            let b = face_uv_to_direction(5, 0.0, v);
+            assert!(a.abs_diff_eq(b, 1e-6), "{a} and {b}");
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        let generator = SkyboxGenerator::default();
+        let a = generator.generate_radiance(16);
+        assert_eq!(a, generator.generate_radiance(16));
+        assert_ne!(
+            a,
+            SkyboxGenerator {
+                seed: 1,
+                ..default()
+            }
+            .generate_radiance(16)
+        );
+        assert!(a
+            .iter()
+            .all(|radiance| radiance.is_finite() && radiance.min_element() >= 0.0));
+    }
+
+    #[test]
+    fn layers_add_up() {
+        let fog = SkyboxGenerator {
+            seed: 0,
+            layers: vec![SkyLayer::Fog {
+                color: Vec3::splat(0.1),
+            }],
+        };
+        assert!(fog
+            .generate_radiance(4)
+            .iter()
+            .all(|&radiance| radiance == Vec3::splat(0.1)));
+        assert!(SkyboxGenerator {
+            seed: 0,
+            layers: Vec::new(),
+        }
+        .generate_radiance(4)
+        .iter()
+        .all(|&radiance| radiance == Vec3::ZERO));
+    }
+
+    #[test]
+    fn star_cells_cover_the_sphere_once() {
+        let cells: Vec<IVec3> = sphere_cells().collect();
+        let unique: std::collections::HashSet<IVec3> = cells.iter().copied().collect();
+        assert_eq!(unique.len(), cells.len());
+        for cell in &cells {
+            let radius = (cell.as_vec3() + 0.5).length();
+            assert!((radius - STAR_CELLS).abs() <= 0.5, "{cell}");
+        }
+        // A shell one cell thick holds about its area in cells
+        let area = 4.0 * PI * STAR_CELLS * STAR_CELLS;
+        assert!(
+            (cells.len() as f32 / area - 1.0).abs() < 0.05,
+            "{}",
+            cells.len()
+        );
+    }
+
+    #[test]
+    fn stars_are_the_same_at_every_size() {
+        let generator = SkyboxGenerator {
+            seed: 3,
+            layers: vec![SkyLayer::Stars {
+                density: 0.1,
+                brightness: 1.0,
+            }],
+        };
+        // Each star shares its light out between the texels it covers, so
+        // the texels add up to the same total however small they get
+        let total_light = |size: u32| -> f32 {
+            generator
+                .generate_radiance(size)
+                .iter()
+                .map(|radiance| radiance.element_sum())
+                .sum()
+        };
+        let small = total_light(48);
+        let large = total_light(96);
+        assert!(small > 0.0);
+        assert!((large / small - 1.0).abs() < 0.05, "{small} and {large}");
+    }
+
+    #[test]
+    fn image_is_a_cubemap() {
+        let image = generate_skybox(8);
+        assert_eq!(image.texture_descriptor.size.depth_or_array_layers, 6);
+        assert_eq!(image.data.len(), 8 * 8 * 6 * 4);
+    }
+}
@@ -0,0 +1,441 @@
+use bevy::{
+    app::{App, Plugin, PostUpdate},
+    asset::{AssetId, AssetServer, Assets, Handle},
+    core_pipeline::{core_3d::Camera3d, Skybox},
+    ecs::system::Commands,
+    math::UVec2,
+    pbr::environment_map::EnvironmentMapLight,
+    prelude::{
+        DetectChanges, Entity, FromWorld, Image, IntoSystemConfigs, Query, Res, ResMut, Resource,
+        With, World,
+    },
+    render::{
+        render_asset::{RenderAssetUsages, RenderAssets},
+        render_resource::{
+            binding_types::{
+                sampler, texture_2d_array, texture_cube, texture_storage_2d_array, uniform_buffer,
+            },
+            BindGroup, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+            CachedComputePipelineId, CommandEncoder, CommandEncoderDescriptor,
+            ComputePassDescriptor, ComputePipeline, ComputePipelineDescriptor, Extent3d,
+            PipelineCache, SamplerBindingType, ShaderStages, StorageTextureAccess,
+            TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+            TextureViewDescriptor, TextureViewDimension, UniformBuffer,
+        },
+        renderer::{RenderDevice, RenderQueue},
+        texture::{GpuImage, ImageSampler},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+};
+use planet_material::sky::Sky;
+
+const SKYBOX_BAKE_SHADER_ASSET_PATH: &str = "shaders/skybox_bake.wgsl";
+const WORKGROUP_SIZE: u32 = 8;
+/// Size of the faces of the diffuse map, the irradiance varies slowly
+const DIFFUSE_MAP_SIZE: u32 = 32;
+/// Size of the faces of the specular map's sharpest mip
+const SPECULAR_MAP_SIZE: u32 = 128;
+/// Mips of the specular map, down to a single texel. Bevy picks the mip from
+/// the perceptual roughness, from 0.0 on the first to 1.0 on the last.
+const SPECULAR_MAP_MIPS: u32 = SPECULAR_MAP_SIZE.ilog2() + 1;
+
+/// Bakes the procedural sky of the [`SkyboxBake`] into cubemaps on the GPU,
+/// and shows it behind every 3D camera.
+pub(crate) struct SkyboxBakePlugin;
+
+impl Plugin for SkyboxBakePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(PostUpdate, attach_skybox);
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<PendingSkyboxBake>()
+            .add_systems(ExtractSchedule, extract_skybox_bake)
+            .add_systems(Render, bake_skybox.in_set(RenderSet::PrepareBindGroups));
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app.init_resource::<SkyboxBakePipelines>();
+    }
+}
+
+/// The sky drawn behind the scene, baked into a cubemap and attached as a
+/// [`Skybox`] to every 3D camera. Changing it rebakes the cubemaps.
+///
+/// With an environment map the sky also lights the scene, through an
+/// [`EnvironmentMapLight`] baked from the skybox: the irradiance for the
+/// diffuse light, and the reflections blurred by roughness for the specular.
+/// Both read the skybox's mips, blurred enough for the spacing of their
+/// samples that the stars don't alias into speckles.
+#[derive(Resource, Debug, Clone)]
+pub(crate) struct SkyboxBake {
+    pub(crate) sky: Sky,
+    /// Luminance of a sky radiance of 1.0, in nits
+    pub(crate) brightness: f32,
+    pub(crate) cubemap: Handle<Image>,
+    pub(crate) environment_map: Option<EnvironmentMaps>,
+}
+
+/// The cubemaps of the [`EnvironmentMapLight`]
+#[derive(Debug, Clone)]
+pub(crate) struct EnvironmentMaps {
+    pub(crate) diffuse_map: Handle<Image>,
+    pub(crate) specular_map: Handle<Image>,
+}
+
+impl SkyboxBake {
+    /// Blank cubemaps with `size` texels along the faces of the skybox, filled by the next bake.
+    /// The skybox has mips down to a single texel.
+    pub(crate) fn new(
+        sky: Sky,
+        size: u32,
+        environment_map: bool,
+        images: &mut Assets<Image>,
+    ) -> Self {
+        Self {
+            sky,
+            // Sky radiances are about 1.0 at the default exposure
+            brightness: 1000.0,
+            cubemap: cube_image(size, size.max(1).ilog2() + 1, images),
+            environment_map: environment_map.then(|| EnvironmentMaps {
+                diffuse_map: cube_image(DIFFUSE_MAP_SIZE, 1, images),
+                specular_map: cube_image(SPECULAR_MAP_SIZE, SPECULAR_MAP_MIPS, images),
+            }),
+        }
+    }
+}
+
+/// A blank cubemap with `mips` levels that the compute shaders can write,
+/// face by face
+fn cube_image(size: u32, mips: u32, images: &mut Assets<Image>) -> Handle<Image> {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 6,
+        },
+        TextureDimension::D2,
+        &[0; 8],
+        TextureFormat::Rgba16Float,
+        RenderAssetUsages::RENDER_WORLD,
+    );
+    // The texture is uploaded with data for every mip
+    let texels: u32 = (0..mips).map(|mip| (size >> mip).max(1).pow(2) * 6).sum();
+    image.data.resize(texels as usize * 8, 0);
+    image.texture_descriptor.mip_level_count = mips;
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::STORAGE_BINDING | TextureUsages::COPY_DST;
+    image.texture_view_descriptor = Some(TextureViewDescriptor {
+        dimension: Some(TextureViewDimension::Cube),
+        ..Default::default()
+    });
+    image.sampler = ImageSampler::linear();
+    images.add(image)
+}
+
+fn attach_skybox(
+    mut commands: Commands,
+    bake: Option<Res<SkyboxBake>>,
+    cameras: Query<(Entity, Option<&Skybox>), With<Camera3d>>,
+) {
+    let Some(bake) = bake else {
+        return;
+    };
+    for (entity, skybox) in &cameras {
+        if skybox.is_some() && !bake.is_changed() {
+            continue;
+        }
+        let mut camera = commands.entity(entity);
+        camera.insert(Skybox {
+            image: bake.cubemap.clone(),
+            brightness: bake.brightness,
+        });
+        if let Some(environment_map) = &bake.environment_map {
+            camera.insert(EnvironmentMapLight {
+                diffuse_map: environment_map.diffuse_map.clone(),
+                specular_map: environment_map.specular_map.clone(),
+                intensity: bake.brightness,
+            });
+        }
+    }
+}
+
+#[derive(Clone)]
+struct SkyboxBakeJob {
+    sky: Sky,
+    cubemap: AssetId<Image>,
+    environment_map: Option<(AssetId<Image>, AssetId<Image>)>,
+}
+
+/// The bake waiting in the render world for its pipelines and textures
+#[derive(Resource, Default)]
+struct PendingSkyboxBake(Option<SkyboxBakeJob>);
+
+fn extract_skybox_bake(
+    mut pending: ResMut<PendingSkyboxBake>,
+    bake: Extract<Option<Res<SkyboxBake>>>,
+) {
+    // A newer bake replaces one that is still waiting
+    if let Some(bake) = bake.as_ref().filter(|bake| bake.is_changed()) {
+        pending.0 = Some(SkyboxBakeJob {
+            sky: bake.sky,
+            cubemap: bake.cubemap.id(),
+            environment_map: bake
+                .environment_map
+                .as_ref()
+                .map(|maps| (maps.diffuse_map.id(), maps.specular_map.id())),
+        });
+    }
+}
+
+#[derive(Resource)]
+struct SkyboxBakePipelines {
+    skybox_layout: BindGroupLayout,
+    downsample_layout: BindGroupLayout,
+    diffuse_layout: BindGroupLayout,
+    specular_layout: BindGroupLayout,
+    skybox: CachedComputePipelineId,
+    downsample: CachedComputePipelineId,
+    diffuse: CachedComputePipelineId,
+    specular: CachedComputePipelineId,
+}
+
+impl FromWorld for SkyboxBakePipelines {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+        let output =
+            texture_storage_2d_array(TextureFormat::Rgba16Float, StorageTextureAccess::WriteOnly);
+
+        // The bindings match `skybox_bake.wgsl`, each pass uses a subset
+        let skybox_layout = render_device.create_bind_group_layout(
+            "skybox_bake_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                ((0, uniform_buffer::<Sky>(false)), (3, output)),
+            ),
+        );
+        let downsample_layout = render_device.create_bind_group_layout(
+            "skybox_downsample_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                (
+                    (3, output),
+                    (
+                        5,
+                        texture_2d_array(TextureSampleType::Float { filterable: false }),
+                    ),
+                ),
+            ),
+        );
+        let skybox_texture = texture_cube(TextureSampleType::Float { filterable: true });
+        let skybox_sampler = sampler(SamplerBindingType::Filtering);
+        let diffuse_layout = render_device.create_bind_group_layout(
+            "skybox_diffuse_bake_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                ((1, skybox_texture), (2, skybox_sampler), (3, output)),
+            ),
+        );
+        let specular_layout = render_device.create_bind_group_layout(
+            "skybox_specular_bake_layout",
+            &BindGroupLayoutEntries::with_indices(
+                ShaderStages::COMPUTE,
+                (
+                    (1, skybox_texture),
+                    (2, skybox_sampler),
+                    (3, output),
+                    (4, uniform_buffer::<f32>(false)),
+                ),
+            ),
+        );
+
+        let shader = world
+            .resource::<AssetServer>()
+            .load(SKYBOX_BAKE_SHADER_ASSET_PATH);
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let queue = |label: &'static str, layout: &BindGroupLayout, entry_point: &'static str| {
+            pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+                label: Some(label.into()),
+                layout: vec![layout.clone()],
+                push_constant_ranges: Vec::new(),
+                shader: shader.clone(),
+                shader_defs: Vec::new(),
+                entry_point: entry_point.into(),
+            })
+        };
+        let skybox = queue("skybox_bake_pipeline", &skybox_layout, "bake_skybox");
+        let downsample = queue(
+            "skybox_downsample_pipeline",
+            &downsample_layout,
+            "downsample_skybox",
+        );
+        let diffuse = queue(
+            "skybox_diffuse_bake_pipeline",
+            &diffuse_layout,
+            "bake_diffuse",
+        );
+        let specular = queue(
+            "skybox_specular_bake_pipeline",
+            &specular_layout,
+            "bake_specular",
+        );
+
+        Self {
+            skybox_layout,
+            downsample_layout,
+            diffuse_layout,
+            specular_layout,
+            skybox,
+            downsample,
+            diffuse,
+            specular,
+        }
+    }
+}
+
+/// The faces of a mip of a cubemap as an array, for the compute shaders to write
+fn face_array_view(image: &GpuImage, mip: u32) -> TextureView {
+    image.texture.create_view(&TextureViewDescriptor {
+        label: Some("skybox_bake_faces"),
+        dimension: Some(TextureViewDimension::D2Array),
+        base_mip_level: mip,
+        mip_level_count: Some(1),
+        ..Default::default()
+    })
+}
+
+/// Runs `pipeline` over the six faces of an output of `size` texels
+fn dispatch(
+    encoder: &mut CommandEncoder,
+    pipeline: &ComputePipeline,
+    bind_group: &BindGroup,
+    size: UVec2,
+) {
+    let mut pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+        label: Some("skybox_bake_pass"),
+        timestamp_writes: None,
+    });
+    pass.set_pipeline(pipeline);
+    pass.set_bind_group(0, bind_group, &[]);
+    pass.dispatch_workgroups(
+        size.x.div_ceil(WORKGROUP_SIZE),
+        size.y.div_ceil(WORKGROUP_SIZE),
+        6,
+    );
+}
+
+fn bake_skybox(
+    mut pending: ResMut<PendingSkyboxBake>,
+    pipelines: Res<SkyboxBakePipelines>,
+    pipeline_cache: Res<PipelineCache>,
+    images: Res<RenderAssets<GpuImage>>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+) {
+    let Some(bake) = &pending.0 else {
+        return;
+    };
+    // Retried next frame while the shader or the textures are still loading
+    let (Some(skybox_pipeline), Some(downsample_pipeline)) = (
+        pipeline_cache.get_compute_pipeline(pipelines.skybox),
+        pipeline_cache.get_compute_pipeline(pipelines.downsample),
+    ) else {
+        return;
+    };
+    let (Some(diffuse_pipeline), Some(specular_pipeline)) = (
+        pipeline_cache.get_compute_pipeline(pipelines.diffuse),
+        pipeline_cache.get_compute_pipeline(pipelines.specular),
+    ) else {
+        return;
+    };
+    let Some(cubemap) = images.get(bake.cubemap) else {
+        return;
+    };
+    let environment_map = match bake.environment_map {
+        Some((diffuse_id, specular_id)) => {
+            match (images.get(diffuse_id), images.get(specular_id)) {
+                (Some(diffuse_map), Some(specular_map)) => Some((diffuse_map, specular_map)),
+                _ => return,
+            }
+        }
+        None => None,
+    };
+
+    let mut sky_buffer = UniformBuffer::from(bake.sky);
+    sky_buffer.write_buffer(&render_device, &render_queue);
+    let Some(sky) = sky_buffer.binding() else {
+        return;
+    };
+
+    let mut encoder = render_device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("skybox_bake"),
+    });
+    let bind_group = render_device.create_bind_group(
+        "skybox_bake_bind_group",
+        &pipelines.skybox_layout,
+        &BindGroupEntries::with_indices(((0, sky), (3, &face_array_view(cubemap, 0)))),
+    );
+    dispatch(&mut encoder, skybox_pipeline, &bind_group, cubemap.size);
+
+    // Each mip averages the one above it
+    for mip in 1..cubemap.mip_level_count {
+        let bind_group = render_device.create_bind_group(
+            "skybox_downsample_bind_group",
+            &pipelines.downsample_layout,
+            &BindGroupEntries::with_indices((
+                (3, &face_array_view(cubemap, mip)),
+                (5, &face_array_view(cubemap, mip - 1)),
+            )),
+        );
+        let size = (cubemap.size >> mip).max(UVec2::ONE);
+        dispatch(&mut encoder, downsample_pipeline, &bind_group, size);
+    }
+
+    if let Some((diffuse_map, specular_map)) = environment_map {
+        let bind_group = render_device.create_bind_group(
+            "skybox_diffuse_bake_bind_group",
+            &pipelines.diffuse_layout,
+            &BindGroupEntries::with_indices((
+                (1, &cubemap.texture_view),
+                (2, &cubemap.sampler),
+                (3, &face_array_view(diffuse_map, 0)),
+            )),
+        );
+        dispatch(
+            &mut encoder,
+            diffuse_pipeline,
+            &bind_group,
+            diffuse_map.size,
+        );
+
+        // Each mip is blurred for its own roughness, from the skybox
+        let mips = specular_map.mip_level_count;
+        for mip in 0..mips {
+            let perceptual_roughness = mip as f32 / (mips - 1).max(1) as f32;
+            let mut roughness_buffer = UniformBuffer::from(perceptual_roughness);
+            roughness_buffer.write_buffer(&render_device, &render_queue);
+            let Some(roughness) = roughness_buffer.binding() else {
+                return;
+            };
+            let bind_group = render_device.create_bind_group(
+                "skybox_specular_bake_bind_group",
+                &pipelines.specular_layout,
+                &BindGroupEntries::with_indices((
+                    (1, &cubemap.texture_view),
+                    (2, &cubemap.sampler),
+                    (3, &face_array_view(specular_map, mip)),
+                    (4, roughness),
+                )),
+            );
+            let size = (specular_map.size >> mip).max(UVec2::ONE);
+            dispatch(&mut encoder, specular_pipeline, &bind_group, size);
+        }
+    }
+    render_queue.submit([encoder.finish()]);
+    pending.0 = None;
+}
@@ -0,0 +1,129 @@
+//! CPU mirror of the terrain of solid planets in `shaders/terrain.wgsl`,
+//! for baking their maps without a GPU.
+
+use bevy::math::{Vec2, Vec3};
+use bevy_shader_utils::{
+    hash::seed_float, simplex_noise_3d::simplex_noise_3d, voronoise::voronoise,
+};
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// Normalized elevation at `local_position`, in the planet's frame and in world
+/// units, where the planet mesh has the texture coordinates `uv`
+pub fn terrain_elevation(local_position: Vec3, uv: Vec2, seed: u32) -> f32 {
+    let octave = |k: u32, scale: (f32, f32), offset: (f32, f32)| {
+        let r = seed_float(seed, k);
+        simplex_noise_3d(local_position * lerp(scale.0, scale.1, r) + lerp(offset.0, offset.1, r))
+    };
+    let oct_01_a = octave(1, (0.003, 0.01), (1.0, 2.0));
+    let oct_01_b = octave(2, (0.003, 0.01), (1.0, 2.0));
+    let oct_02_a = octave(3, (0.014, 0.025), (0.5, 5.0));
+    let oct_02_b = octave(4, (0.014, 0.025), (0.5, 5.0));
+    let oct_03_a = octave(5, (0.08, 0.10), (0.5, 7.0));
+    let oct_03_b = octave(6, (0.08, 0.10), (0.5, 7.0));
+    let oct_04_a = octave(7, (1.5, 0.3), (0.5, 7.0));
+    let oct_04_b = octave(8, (1.5, 0.3), (0.5, 7.0));
+
+    let cells = |kx: u32, ky: u32, x: (f32, f32), y: (f32, f32)| {
+        let scale = Vec2::new(
+            lerp(x.0, x.1, seed_float(seed, kx)),
+            lerp(y.0, y.1, seed_float(seed, ky)),
+        );
+        voronoise(uv * scale, 1.0, 1.0)
+    };
+    let voronoi_01 = cells(9, 10, (5.0, 20.0), (5.0, 20.0));
+    let voronoi_02 = cells(11, 12, (1.0, 3.0), (1.0, 3.0));
+    let voronoi_03 = cells(13, 14, (0.1, 0.3), (0.1, 0.3));
+    let voronoi_04 = cells(15, 16, (0.01, 0.03), (0.01, 0.3));
+
+    let oct_01 = oct_01_a + (oct_01_b * 0.5) * voronoi_01 + 0.4;
+    let oct_02 = oct_02_a + (oct_02_b * 0.5) * voronoi_02 + 0.2;
+    let oct_03 = oct_03_a + (oct_03_b * 0.5) * voronoi_03 + 0.1;
+    let oct_04 = oct_04_a + (oct_04_b * 0.5) * voronoi_04;
+
+    ((oct_01 * 1.0 + oct_02 * 0.5 + oct_03 * 0.15 + oct_04 * 0.02) / 4.0).clamp(0.0, 1.0)
+}
+
+/// The texture coordinates that the inflated `spherical_cuboid` mesh of the app
+/// has in `direction`. Each face of the cube spans `0.0..=1.0` on its own.
+pub fn spherical_cuboid_uv(direction: Vec3) -> Vec2 {
+    let abs = direction.abs();
+    let major = abs.max_element();
+    // The faces sit on the opposite side of the axis they are named after
+    let (u, v) = if major == abs.x {
+        if direction.x < 0.0 {
+            (Vec3::Y, Vec3::Z)
+        } else {
+            (Vec3::Z, Vec3::Y)
+        }
+    } else if major == abs.y {
+        if direction.y < 0.0 {
+            (Vec3::Z, Vec3::X)
+        } else {
+            (Vec3::X, Vec3::Z)
+        }
+    } else if direction.z < 0.0 {
+        (Vec3::X, Vec3::Y)
+    } else {
+        (Vec3::Y, Vec3::X)
+    };
+    Vec2::new(direction.dot(u), direction.dot(v)) / major * 0.5 + 0.5
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_the_vertices_of_the_mesh() {
+        // The vertex positions and UVs of `spherical_cuboid` in `geometry.rs`
+        let subdivisions = 8;
+        let faces = [
+            (Vec3::X, Vec3::Y, Vec3::Z),
+            (-Vec3::X, Vec3::Z, Vec3::Y),
+            (Vec3::Y, Vec3::Z, Vec3::X),
+            (-Vec3::Y, Vec3::X, Vec3::Z),
+            (Vec3::Z, Vec3::X, Vec3::Y),
+            (-Vec3::Z, Vec3::Y, Vec3::X),
+        ];
+        for (dir, u, v) in faces {
+            // Skip the edges, which the neighbouring faces share with other UVs
+            for i in 1..subdivisions {
+                for j in 1..subdivisions {
+                    let position = (dir + u + v) * -0.5
+                        + (i as f32 * u + j as f32 * v) / subdivisions as f32;
+                    let uv = Vec2::new(i as f32, j as f32) / subdivisions as f32;
+                    let found = spherical_cuboid_uv(position.normalize());
+                    assert!(found.abs_diff_eq(uv, 1e-5), "{dir} {uv} {found}");
+                }
+            }
+        }
+    }
+
+    /// Outputs of `terrain_elevation` at a position, mesh UV and seed.
+    /// Printed by `tests/golden.rs`, like the other tables.
+    const GOLDEN: [([f32; 6], f32); 9] = [
+        ([150.0, 0.0, 0.0, 0.5, 0.5, 1.0], 0.34488505),
+        ([106.07, 0.0, 106.07, 0.5, 0.5, 7.0], 0.0),
+        ([-51.3, 140.9, 0.0, 0.1, 0.9, 42.0], 0.29135022),
+        ([12.5, -30.25, 145.0, 0.73, 0.31, 123456.0], 0.048449796),
+        ([-149.9, 2.0, -3.0, 0.99, 0.01, 3.0], 0.25572056),
+        ([60.0, 60.0, -120.0, 0.4, 0.6, 9999.0], 0.13933888),
+        ([0.0, 0.0, 100.0, 0.5, 0.5, 1.0], 0.20275417),
+        ([70.7, -70.7, 0.0, 0.0, 1.0, 2024.0], 0.06053571),
+        ([-95.0, -95.0, 65.0, 0.33, 0.66, 77.0], 0.3232818),
+    ];
+
+    #[test]
+    fn matches_the_shader() {
+        for ([x, y, z, u, v, seed], expected) in GOLDEN {
+            let elevation = terrain_elevation(Vec3::new(x, y, z), Vec2::new(u, v), seed as u32);
+            assert!(
+                (elevation - expected).abs() <= 1e-5,
+                "{elevation} != {expected} at {x} {y} {z}"
+            );
+        }
+    }
+}
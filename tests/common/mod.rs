@@ -0,0 +1,176 @@
+//! The shader modules the tests compose shaders against: those of
+//! `bevy_shader_utils`, of the assets and of Bevy itself. Imports are resolved
+//! by naga_oil, the way Bevy's shader cache does it.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use naga::valid::{Capabilities, ShaderStages};
+use naga_oil::compose::{
+    get_preprocessor_data, ComposableModuleDescriptor, Composer, NagaModuleDescriptor,
+    ShaderDefValue, ShaderLanguage, ShaderType,
+};
+
+pub const MANIFEST_DIR: &str = env!("CARGO_MANIFEST_DIR");
+
+/// Bevy crates whose shader modules our shaders import
+const BEVY_SHADER_CRATES: [&str; 4] = [
+    "bevy_core_pipeline",
+    "bevy_pbr",
+    "bevy_render",
+    "bevy_sprite",
+];
+
+/// A shader that can be imported by others
+struct ShaderModule {
+    name: String,
+    path: PathBuf,
+    source: String,
+    imports: Vec<String>,
+}
+
+pub struct ShaderLibrary {
+    modules: HashMap<String, ShaderModule>,
+}
+
+impl ShaderLibrary {
+    pub fn new() -> Self {
+        let mut library = Self {
+            modules: HashMap::new(),
+        };
+        for dir in bevy_shader_dirs() {
+            library.add_dir(&dir, None);
+        }
+        library.add_dir(
+            &Path::new(MANIFEST_DIR).join("libs/bevy_shader_utils/src"),
+            None,
+        );
+        let assets = Path::new(MANIFEST_DIR).join("assets");
+        library.add_dir(&assets, Some(&assets));
+        library
+    }
+
+    /// Adds the modules under `dir`. Shaders without a `#define_import_path`
+    /// are only importable as assets, by their quoted path in `asset_root`.
+    fn add_dir(&mut self, dir: &Path, asset_root: Option<&Path>) {
+        for path in wgsl_files(dir) {
+            let source = fs::read_to_string(&path).unwrap();
+            let (name, imports, _) = get_preprocessor_data(&source);
+            let name = match (name, asset_root) {
+                (Some(name), _) => name,
+                (None, Some(root)) => format!("\"{}\"", path.strip_prefix(root).unwrap().display()),
+                (None, None) => continue,
+            };
+            let imports = imports.into_iter().map(|import| import.import).collect();
+            self.modules.insert(
+                name.clone(),
+                ShaderModule {
+                    name,
+                    path,
+                    source,
+                    imports,
+                },
+            );
+        }
+    }
+
+    /// Adds `name` and everything it imports to `composer`
+    fn compose(
+        &self,
+        composer: &mut Composer,
+        name: &str,
+        shader_defs: &HashMap<String, ShaderDefValue>,
+    ) -> Result<(), String> {
+        if composer.contains_module(name) {
+            return Ok(());
+        }
+        let module = self
+            .modules
+            .get(name)
+            .ok_or_else(|| format!("unknown module {name}"))?;
+        for import in &module.imports {
+            self.compose(composer, import, shader_defs)?;
+        }
+        composer
+            .add_composable_module(ComposableModuleDescriptor {
+                source: &module.source,
+                file_path: &module.path.display().to_string(),
+                language: ShaderLanguage::Wgsl,
+                as_name: Some(module.name.clone()),
+                shader_defs: shader_defs.clone(),
+                ..Default::default()
+            })
+            .map(|_| ())
+            .map_err(|error| error.emit_to_string(composer))
+    }
+
+    /// Composes `source`, with the modules it imports
+    pub fn make_module(
+        &self,
+        source: &str,
+        file_path: &str,
+        shader_defs: HashMap<String, ShaderDefValue>,
+    ) -> Result<naga::Module, String> {
+        let (_, imports, _) = get_preprocessor_data(source);
+        let mut composer =
+            Composer::default().with_capabilities(Capabilities::all(), ShaderStages::all());
+        for import in imports {
+            self.compose(&mut composer, &import.import, &shader_defs)?;
+        }
+        composer
+            .make_naga_module(NagaModuleDescriptor {
+                source,
+                file_path,
+                shader_type: ShaderType::Wgsl,
+                shader_defs,
+                ..Default::default()
+            })
+            .map_err(|error| error.emit_to_string(&composer))
+    }
+}
+
+/// The `src` directories of [`BEVY_SHADER_CRATES`], wherever cargo keeps them
+fn bevy_shader_dirs() -> Vec<PathBuf> {
+    let cargo = std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output = Command::new(cargo)
+        .args(["metadata", "--format-version", "1"])
+        .current_dir(MANIFEST_DIR)
+        .output()
+        .expect("cargo metadata");
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let metadata: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    let dirs: Vec<PathBuf> = metadata["packages"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|package| BEVY_SHADER_CRATES.contains(&package["name"].as_str().unwrap()))
+        .map(|package| Path::new(package["manifest_path"].as_str().unwrap()).with_file_name("src"))
+        .collect();
+    assert_eq!(dirs.len(), BEVY_SHADER_CRATES.len(), "{dirs:?}");
+    dirs
+}
+
+pub fn wgsl_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            files.extend(wgsl_files(&path));
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "wgsl")
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    files
+}
@@ -0,0 +1,405 @@
+//! Outputs of the app's WGSL functions, that their CPU mirrors in `src` are
+//! tested against, like the golden tables of `bevy_shader_utils`.
+//!
+//! `print_golden_tables` runs the shaders through wgpu on the first adapter
+//! found. The committed tables come from Mesa's llvmpipe:
+//!
+//! ```sh
+//! WGPU_BACKEND=gl EGL_PLATFORM=surfaceless LIBGL_ALWAYS_SOFTWARE=1 \
+//!     cargo test --test golden -- --ignored --nocapture
+//! ```
+
+mod common;
+
+use std::{borrow::Cow, collections::HashMap};
+
+use bevy::color::LinearRgba;
+use common::ShaderLibrary;
+use planet_material::{climate::ClimateModel, gas_giant::GasGiant};
+use wgpu::util::DeviceExt;
+
+enum Input {
+    /// Up to 8 floats per row, as `p: vec4<f32>` and then `q: vec4<f32>`
+    Floats(Vec<Vec<f32>>),
+    /// As `n: u32`
+    Integers(Vec<u32>),
+}
+
+fn floats<const N: usize>(rows: &[[f32; N]]) -> Input {
+    Input::Floats(rows.iter().map(|row| row.to_vec()).collect())
+}
+
+/// A WGSL expression of the input
+enum Output {
+    Float(String),
+    Integer(String),
+}
+
+struct Table {
+    name: &'static str,
+    /// Imports and declarations the outputs use
+    header: String,
+    input: Input,
+    outputs: Vec<Output>,
+}
+
+/// `ClimateModel` of `shaders/climate.wgsl` holding `model`
+fn climate_model(model: &ClimateModel) -> String {
+    let ClimateModel {
+        equator_temperature,
+        pole_temperature,
+        lapse_rate,
+        sea_level,
+        summit_level,
+        max_precipitation,
+        coastal_falloff,
+    } = model;
+    format!(
+        "ClimateModel({equator_temperature:?}, {pole_temperature:?}, {lapse_rate:?}, \
+         {sea_level:?}, {summit_level:?}, {max_precipitation:?}, {coastal_falloff:?})"
+    )
+}
+
+/// `GasGiant` of `shaders/gas_giant.wgsl` holding `gas_giant`
+fn gas_giant(gas_giant: &GasGiant) -> String {
+    let color = |color: LinearRgba| {
+        let LinearRgba {
+            red,
+            green,
+            blue,
+            alpha,
+        } = color;
+        format!("vec4({red:?}, {green:?}, {blue:?}, {alpha:?})")
+    };
+    format!(
+        "GasGiant({}, {}, {}, {}, {}u, {}u, {:?}, {:?}, {:?})",
+        color(gas_giant.zone_color),
+        color(gas_giant.belt_color),
+        color(gas_giant.accent_color),
+        color(gas_giant.storm_color),
+        gas_giant.seed,
+        gas_giant.storm_count,
+        gas_giant.band_count,
+        gas_giant.turbulence,
+        gas_giant.wind_speed,
+    )
+}
+
+fn tables() -> Vec<Table> {
+    let model = climate_model(&ClimateModel::default());
+    vec![
+        Table {
+            name: "climate::GOLDEN",
+            header: format!(
+                "#import \"shaders/climate.wgsl\"::{{ClimateModel, sample_climate}}\n\
+                 const model = {model};"
+            ),
+            // Latitude, elevation and distance from the sea
+            input: floats(&[
+                [0.0, 0.15, 0.0],
+                [0.5, 0.14, 0.0],
+                [1.56, 0.15, 0.0],
+                [-1.57, 0.4, 0.2],
+                [0.0, 0.45, 0.05],
+                [0.3, 0.2, 0.4],
+                [0.4, 0.2, 1.0],
+                [-0.9, 0.25, 0.1],
+                [0.6, 0.3, 0.8],
+                [1.1, 0.18, 0.05],
+                [-0.2, 0.35, 1.2],
+                [0.8, 0.9, 3.0],
+            ]),
+            outputs: vec![
+                Output::Float("sample_climate(model, p.x, p.y, p.z).temperature".to_string()),
+                Output::Float("sample_climate(model, p.x, p.y, p.z).precipitation".to_string()),
+                Output::Integer("sample_climate(model, p.x, p.y, p.z).biome".to_string()),
+            ],
+        },
+        Table {
+            name: "climate::CLASSIFY_GOLDEN",
+            header: "#import \"shaders/climate.wgsl\"::classify_biome".to_string(),
+            // Temperature and precipitation, on each biome of the Whittaker
+            // diagram
+            input: floats(&[
+                [-20.0, 100.0],
+                [-8.0, 100.0],
+                [0.0, 10.0],
+                [0.0, 100.0],
+                [10.0, 10.0],
+                [10.0, 50.0],
+                [10.0, 100.0],
+                [10.0, 150.0],
+                [10.0, 300.0],
+                [25.0, 10.0],
+                [25.0, 100.0],
+                [25.0, 200.0],
+                [25.0, 300.0],
+            ]),
+            outputs: vec![Output::Integer("classify_biome(p.x, p.y)".to_string())],
+        },
+        Table {
+            name: "climate::COLOR_GOLDEN",
+            header: "#import \"shaders/climate.wgsl\"::biome_color".to_string(),
+            input: Input::Integers((0..13).collect()),
+            outputs: ["x", "y", "z"]
+                .map(|channel| Output::Float(format!("biome_color(n).{channel}")))
+                .into_iter()
+                .collect(),
+        },
+        Table {
+            name: "terrain::GOLDEN",
+            header: "#import \"shaders/terrain.wgsl\"::terrain_elevation".to_string(),
+            // Position on the planet in world units, mesh UV and seed
+            input: floats(&[
+                [150.0, 0.0, 0.0, 0.5, 0.5, 1.0],
+                [106.07, 0.0, 106.07, 0.5, 0.5, 7.0],
+                [-51.3, 140.9, 0.0, 0.1, 0.9, 42.0],
+                [12.5, -30.25, 145.0, 0.73, 0.31, 123456.0],
+                [-149.9, 2.0, -3.0, 0.99, 0.01, 3.0],
+                [60.0, 60.0, -120.0, 0.4, 0.6, 9999.0],
+                [0.0, 0.0, 100.0, 0.5, 0.5, 1.0],
+                [70.7, -70.7, 0.0, 0.0, 1.0, 2024.0],
+                [-95.0, -95.0, 65.0, 0.33, 0.66, 77.0],
+            ]),
+            outputs: vec![Output::Float(
+                "terrain_elevation(p.xyz, vec2(p.w, q.x), u32(q.y))".to_string(),
+            )],
+        },
+        Table {
+            name: "planet_maps::GOLDEN",
+            header: format!(
+                "#import \"shaders/climate.wgsl\"::ClimateModel\n\
+                 #import \"shaders/terrain.wgsl\"::{{terrestrial_color, terrestrial_roughness}}\n\
+                 const model = {model};"
+            ),
+            // Elevation, temperature and ground color, over the depths of the
+            // sea, sea ice and the heights of the land
+            input: floats(&[
+                [0.02, 20.0, 0.2, 0.541, 0.255],
+                [0.05, 20.0, 0.2, 0.541, 0.255],
+                [0.08, 4.0, 0.2, 0.541, 0.255],
+                [0.13, -11.0, 0.2, 0.541, 0.255],
+                [0.14, -20.0, 0.2, 0.541, 0.255],
+                [0.15, 28.0, 0.886, 0.8, 0.576],
+                [0.2, 15.0, 0.361, 0.6, 0.329],
+                [0.42, 5.0, 0.306, 0.447, 0.329],
+                [0.46, -5.0, 0.608, 0.62, 0.529],
+                [0.6, -20.0, 0.92, 0.94, 0.96],
+            ]),
+            outputs: vec![
+                Output::Float("terrestrial_color(model, p.x, p.y, vec3(p.zw, q.x)).x".to_string()),
+                Output::Float("terrestrial_color(model, p.x, p.y, vec3(p.zw, q.x)).y".to_string()),
+                Output::Float("terrestrial_color(model, p.x, p.y, vec3(p.zw, q.x)).z".to_string()),
+                Output::Float("terrestrial_roughness(model, p.x, p.y)".to_string()),
+            ],
+        },
+        Table {
+            name: "gas_giant::GOLDEN",
+            header: format!(
+                "#import \"shaders/gas_giant.wgsl\"::{{GasGiant, gas_giant_color}}\n\
+                 const jupiter = {};\n\
+                 const seeded = {};",
+                gas_giant(&GasGiant::default()),
+                gas_giant(&GasGiant::from_seed(GAS_GIANT_SEED)),
+            ),
+            // Direction and time, for the default gas giant and a seeded one
+            input: floats(&[
+                [1.0, 0.0, 0.0, 0.0],
+                [0.3, 0.5, -0.81, 0.0],
+                [-0.6, -0.2, 0.77, 12.5],
+                [0.05, 0.99, 0.1, 37.0],
+                [-0.7, 0.1, -0.7, 100.0],
+                [0.45, -0.55, 0.7, 3.25],
+                [0.1, -0.98, 0.15, 0.0],
+                [-0.2, 0.35, 0.91, 61.0],
+            ]),
+            outputs: ["jupiter", "seeded"]
+                .into_iter()
+                .flat_map(|params| {
+                    ["x", "y", "z"].map(|channel| {
+                        Output::Float(format!("gas_giant_color(p.xyz, p.w, {params}).{channel}"))
+                    })
+                })
+                .collect(),
+        },
+    ]
+}
+
+/// The seed of the seeded gas giant in `gas_giant::GOLDEN`
+const GAS_GIANT_SEED: u32 = 7;
+
+/// A compute shader writing the outputs of `table` for each input, as bits
+fn golden_shader(table: &Table) -> String {
+    let mut shader = format!(
+        "{}
+
+@group(0) @binding(0) var<storage, read> inputs: array<array<vec4<u32>, 2>>;
+@group(0) @binding(1) var<storage, read_write> outputs: array<u32>;
+
+@compute @workgroup_size(1)
+fn main(@builtin(global_invocation_id) id: vec3<u32>) {{
+    let n = inputs[id.x][0].x;
+    let p = bitcast<vec4<f32>>(inputs[id.x][0]);
+    let q = bitcast<vec4<f32>>(inputs[id.x][1]);
+",
+        table.header
+    );
+    for (i, output) in table.outputs.iter().enumerate() {
+        let value = match output {
+            Output::Float(expression) => format!("bitcast<u32>({expression})"),
+            Output::Integer(expression) => expression.clone(),
+        };
+        let index = format!("id.x * {}u + {i}u", table.outputs.len());
+        shader += &format!("    outputs[{index}] = {value};\n");
+    }
+    shader + "}\n"
+}
+
+/// Runs the shader of `table` over its inputs, and returns the bits of the
+/// outputs of each
+fn run(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    library: &ShaderLibrary,
+    table: &Table,
+) -> Vec<Vec<u32>> {
+    let inputs: Vec<[u32; 8]> = match &table.input {
+        Input::Floats(rows) => rows
+            .iter()
+            .map(|row| {
+                let mut bits = [0; 8];
+                for (bits, x) in bits.iter_mut().zip(row) {
+                    *bits = x.to_bits();
+                }
+                bits
+            })
+            .collect(),
+        Input::Integers(integers) => integers.iter().map(|&n| [n, 0, 0, 0, 0, 0, 0, 0]).collect(),
+    };
+    let module = library
+        .make_module(&golden_shader(table), "golden.wgsl", HashMap::new())
+        .unwrap_or_else(|error| panic!("{}: {error}", table.name));
+    let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(table.name),
+        source: wgpu::ShaderSource::Naga(Cow::Owned(module)),
+    });
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some(table.name),
+        layout: None,
+        module: &module,
+        entry_point: "main",
+        compilation_options: Default::default(),
+    });
+
+    let bytes =
+        |words: &[u32]| -> Vec<u8> { words.iter().flat_map(|word| word.to_ne_bytes()).collect() };
+    let input = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: None,
+        contents: &bytes(inputs.as_flattened()),
+        usage: wgpu::BufferUsages::STORAGE,
+    });
+    let size = (inputs.len() * table.outputs.len() * 4) as u64;
+    let output = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let readback = device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: None,
+        layout: &pipeline.get_bind_group_layout(0),
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: input.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: output.as_entire_binding(),
+            },
+        ],
+    });
+
+    let mut encoder = device.create_command_encoder(&Default::default());
+    {
+        let mut pass = encoder.begin_compute_pass(&Default::default());
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_workgroups(inputs.len() as u32, 1, 1);
+    }
+    encoder.copy_buffer_to_buffer(&output, 0, &readback, 0, size);
+    queue.submit([encoder.finish()]);
+    readback
+        .slice(..)
+        .map_async(wgpu::MapMode::Read, Result::unwrap);
+    device.poll(wgpu::Maintain::Wait);
+
+    let data = readback.slice(..).get_mapped_range();
+    data.chunks_exact(4)
+        .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+        .collect::<Vec<_>>()
+        .chunks_exact(table.outputs.len())
+        .map(<[u32]>::to_vec)
+        .collect()
+}
+
+/// A row of `table` as Rust: the input, then the outputs with runs of the
+/// same type grouped into arrays
+fn format_row(table: &Table, row: usize, outputs: &[u32]) -> String {
+    let mut fields = vec![match &table.input {
+        Input::Floats(rows) => format!("{:?}", rows[row]),
+        Input::Integers(integers) => integers[row].to_string(),
+    }];
+    let mut i = 0;
+    while i < outputs.len() {
+        let is_float = |output: &Output| matches!(output, Output::Float(_));
+        let float = is_float(&table.outputs[i]);
+        let run = table.outputs[i..]
+            .iter()
+            .take_while(|output| is_float(output) == float)
+            .count();
+        let values: Vec<String> = outputs[i..i + run]
+            .iter()
+            .map(|&bits| match float {
+                true => format!("{:?}", f32::from_bits(bits)),
+                false => bits.to_string(),
+            })
+            .collect();
+        fields.push(match run {
+            1 => values[0].clone(),
+            _ => format!("[{}]", values.join(", ")),
+        });
+        i += run;
+    }
+    format!("({}),", fields.join(", "))
+}
+
+#[test]
+#[ignore = "needs a GPU, prints the golden tables"]
+fn print_golden_tables() {
+    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+        backends: wgpu::util::backend_bits_from_env().unwrap_or_default(),
+        ..Default::default()
+    });
+    let adapter = bevy::tasks::block_on(wgpu::util::initialize_adapter_from_env_or_default(
+        &instance, None,
+    ))
+    .expect("no adapter");
+    println!("// {:?}", adapter.get_info());
+    let (device, queue) =
+        bevy::tasks::block_on(adapter.request_device(&Default::default(), None)).unwrap();
+
+    let library = ShaderLibrary::new();
+    for table in tables() {
+        println!("\n// {}", table.name);
+        for (row, outputs) in run(&device, &queue, &library, &table).iter().enumerate() {
+            println!("{}", format_row(&table, row, outputs));
+        }
+    }
+}
@@ -0,0 +1,123 @@
+//! Parses and validates every shader with naga, without a GPU. Imports are
+//! resolved by naga_oil, the way Bevy's shader cache does it, against the
+//! shader modules of `bevy_shader_utils`, of the assets and of Bevy itself.
+
+mod common;
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use common::{wgsl_files, ShaderLibrary, MANIFEST_DIR};
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use naga_oil::compose::ShaderDefValue;
+use planet_material::planet_kind::PlanetKind;
+
+/// The defs Bevy's mesh pipeline and shader cache set for a regular mesh
+const PIPELINE_DEFS: [(&str, ShaderDefValue); 9] = [
+    ("MESH_PIPELINE", ShaderDefValue::Bool(true)),
+    ("VERTEX_OUTPUT_INSTANCE_INDEX", ShaderDefValue::Bool(true)),
+    ("VERTEX_POSITIONS", ShaderDefValue::Bool(true)),
+    ("VERTEX_NORMALS", ShaderDefValue::Bool(true)),
+    ("VERTEX_UVS", ShaderDefValue::Bool(true)),
+    ("VERTEX_UVS_A", ShaderDefValue::Bool(true)),
+    ("MAX_DIRECTIONAL_LIGHTS", ShaderDefValue::UInt(10)),
+    ("MAX_CASCADES_PER_LIGHT", ShaderDefValue::UInt(4)),
+    ("AVAILABLE_STORAGE_BUFFER_BINDINGS", ShaderDefValue::UInt(8)),
+];
+
+/// The defs Bevy's prepass pipeline adds for the deferred G-buffer pass
+const DEFERRED_DEFS: [&str; 5] = [
+    "PREPASS_PIPELINE",
+    "PREPASS_FRAGMENT",
+    "DEFERRED_PREPASS",
+    "NORMAL_PREPASS_OR_DEFERRED_PREPASS",
+    "MOTION_VECTOR_PREPASS_OR_DEFERRED_PREPASS",
+];
+
+/// Composes and validates the shader at `path` with `defs` on top of the
+/// pipeline's own
+fn validate(library: &ShaderLibrary, path: &Path, defs: &[&str]) -> Result<(), String> {
+    let mut shader_defs: HashMap<String, ShaderDefValue> = PIPELINE_DEFS
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value))
+        .collect();
+    shader_defs.extend(
+        defs.iter()
+            .map(|def| (def.to_string(), ShaderDefValue::Bool(true))),
+    );
+
+    let source = fs::read_to_string(path).unwrap();
+    let module = library.make_module(&source, &path.display().to_string(), shader_defs)?;
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map(|_| ())
+        .map_err(|error| format!("{error:?}"))
+}
+
+/// The sets of shader defs each shader is specialized with by the app
+fn permutations(path: &Path) -> Vec<Vec<&'static str>> {
+    match path.file_name().unwrap().to_str().unwrap() {
+        // Forward and deferred, for every kind, with and without atmosphere
+        // and rim
+        "planet_shader.wgsl" => PlanetKind::ALL
+            .into_iter()
+            .flat_map(|kind| {
+                [
+                    vec![],
+                    vec!["PLANET_ATMOSPHERE"],
+                    vec!["FRESNEL_RIM"],
+                    vec!["PLANET_ATMOSPHERE", "FRESNEL_RIM"],
+                ]
+                .into_iter()
+                .flat_map(|surroundings| {
+                    [vec![], DEFERRED_DEFS.to_vec()].map(|pass| (surroundings.clone(), pass))
+                })
+                .map(move |(surroundings, pass)| {
+                    [vec![kind.shader_def()], surroundings, pass].concat()
+                })
+            })
+            .collect(),
+        "atmosphere_pass.wgsl" => vec![vec![], vec!["MULTISAMPLED"]],
+        // UV and lat/long grids
+        "pristine_grid.wgsl" => vec![vec![], vec!["PRISTINE_GRID_LAT_LONG"]],
+        // With and without rim
+        "moon_shader.wgsl" | "star_material.wgsl" => vec![vec![], vec!["FRESNEL_RIM"]],
+        _ => vec![vec![]],
+    }
+}
+
+/// Validates every permutation of every shader under `dir`, reporting all
+/// failures at once
+fn validate_dir(dir: &str) {
+    let library = ShaderLibrary::new();
+    let mut failures = Vec::new();
+    let mut validated = HashSet::new();
+    for path in wgsl_files(&Path::new(MANIFEST_DIR).join(dir)) {
+        for defs in permutations(&path) {
+            if let Err(error) = validate(&library, &path, &defs) {
+                failures.push(format!("{} {defs:?}:\n{error}", path.display()));
+            }
+            validated.insert(path.clone());
+        }
+    }
+    assert!(!validated.is_empty(), "no shaders in {dir}");
+    assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn shader_utils_shaders_are_valid() {
+    validate_dir("libs/bevy_shader_utils/src/shaders");
+}
+
+#[test]
+fn shader_utils_materials_are_valid() {
+    validate_dir("libs/bevy_shader_utils/src/materials");
+}
+
+#[test]
+fn asset_shaders_are_valid() {
+    validate_dir("assets/shaders");
+}